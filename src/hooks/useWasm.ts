@@ -49,6 +49,13 @@ export interface ConversionStats {
   output_bytes: number
   filters_active: boolean
   merged: boolean
+  sentiment_by_sender?: Record<string, number>
+  language_distribution?: Record<string, number>
+  senders?: string[]
+  date_span?: { first: string | null; last: string | null }
+  redactions?: Record<string, number>
+  truncated: boolean
+  warnings: string[]
 }
 
 export interface ConversionReport {
@@ -79,13 +86,14 @@ interface UseWasmResult {
 
 // Human-readable error messages
 const ERROR_MESSAGES: Record<string, string> = {
-  'Unknown source': 'Unknown source. Supported: Telegram, WhatsApp, Instagram, Discord',
+  'Unknown source':
+    'Unknown source. Supported: Telegram, WhatsApp, Instagram, Discord, Viber, Skype, LINE, KakaoTalk, Hangouts, Teams, generic CSV',
   'Unknown format': 'Unknown format. Supported: CSV, JSON, JSONL',
   'Failed to parse': "Failed to parse file. Make sure it's an export from a supported messenger",
   'Invalid JSON': 'Invalid JSON. Check file integrity',
   'Invalid date': 'Invalid date. Use YYYY-MM-DD or pick a date from the calendar',
   'Invalid options JSON': 'Invalid conversion options',
-  'Empty input': 'File is empty or contains no messages',
+  NOT_TEXT: "File doesn't look like text. Pick the exported .json or .txt file",
   WASM: 'Failed to load converter. Try refreshing the page',
   network: 'Network error. Check your internet connection',
   default: 'An error occurred during conversion',
@@ -138,6 +146,7 @@ function fallbackReport(input: string, output: string): ConversionReport {
       output_bytes: new Blob([output]).size,
       filters_active: false,
       merged: false,
+      warnings: [],
     },
   }
 }
@@ -55,6 +55,7 @@ export function convert_with_report(
       output_bytes: new Blob(['Sender;Content\n']).size,
       filters_active: false,
       merged: true,
+      warnings: [],
     },
   })
 }
@@ -79,11 +80,105 @@ export function supported_sources() {
   ])
 }
 
+export function supported_platforms() {
+  return JSON.stringify([
+    {
+      id: 'telegram',
+      aliases: ['tg'],
+      display_name: 'Telegram',
+      input_kind: 'json',
+      default_extension: 'json',
+      supports_replies: true,
+      supports_timestamps: true,
+      supports_ids: true,
+      supports_edits: true,
+      supports_pins: true,
+      local_time: false,
+      max_message_length: 4096,
+    },
+    {
+      id: 'whatsapp',
+      aliases: ['wa'],
+      display_name: 'WhatsApp',
+      input_kind: 'text',
+      default_extension: 'txt',
+      supports_replies: false,
+      supports_timestamps: true,
+      supports_ids: false,
+      supports_edits: false,
+      supports_pins: false,
+      local_time: true,
+      max_message_length: null,
+    },
+    {
+      id: 'instagram',
+      aliases: ['ig'],
+      display_name: 'Instagram',
+      input_kind: 'json',
+      default_extension: 'json',
+      supports_replies: false,
+      supports_timestamps: true,
+      supports_ids: false,
+      supports_edits: false,
+      supports_pins: false,
+      local_time: false,
+      max_message_length: null,
+    },
+    {
+      id: 'discord',
+      aliases: ['dc'],
+      display_name: 'Discord',
+      input_kind: 'json',
+      default_extension: 'json',
+      supports_replies: true,
+      supports_timestamps: true,
+      supports_ids: true,
+      supports_edits: true,
+      supports_pins: false,
+      local_time: false,
+      max_message_length: 2000,
+    },
+  ])
+}
+
 export function supported_formats() {
   return JSON.stringify([
-    { id: 'csv', label: 'CSV', extension: 'csv', mime_type: 'text/csv' },
-    { id: 'json', label: 'JSON', extension: 'json', mime_type: 'application/json' },
-    { id: 'jsonl', label: 'JSONL', extension: 'jsonl', mime_type: 'application/x-ndjson' },
+    {
+      id: 'csv',
+      aliases: [],
+      display_name: 'CSV',
+      binary: false,
+      mime_type: 'text/csv',
+      extension: 'csv',
+      compact: false,
+    },
+    {
+      id: 'json',
+      aliases: [],
+      display_name: 'JSON',
+      binary: false,
+      mime_type: 'application/json',
+      extension: 'json',
+      compact: false,
+    },
+    {
+      id: 'jsonl',
+      aliases: ['ndjson'],
+      display_name: 'JSONL',
+      binary: false,
+      mime_type: 'application/x-ndjson',
+      extension: 'jsonl',
+      compact: false,
+    },
+    {
+      id: 'json-compact',
+      aliases: [],
+      display_name: 'JSON (compact)',
+      binary: false,
+      mime_type: 'application/json',
+      extension: 'json',
+      compact: true,
+    },
   ])
 }
 
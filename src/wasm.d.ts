@@ -19,8 +19,192 @@ declare module '../wasm/chatpack_wasm.js' {
     format: string,
     optionsJson: string,
   ): string
+  export function convert_async(
+    input: string,
+    source: string,
+    format: string,
+    optionsJson: string,
+  ): Promise<string>
+  export function convert_streaming(
+    input: string,
+    source: string,
+    format: string,
+    optionsJson: string,
+    onChunk: (chunk: string) => void,
+    onProgress?: (progress: string) => void,
+  ): string
+  export function convert_bytes(
+    input: Uint8Array,
+    source: string,
+    format: string,
+    optionsJson: string,
+  ): string
+  export function convert_to_bytes(
+    input: string,
+    source: string,
+    format: string,
+    optionsJson: string,
+  ): Uint8Array
+  export function convert_into(
+    input: string,
+    source: string,
+    format: string,
+    optionsJson: string,
+    target: Uint8Array,
+  ): number
+  export function required_output_size(
+    input: string,
+    source: string,
+    format: string,
+    optionsJson: string,
+  ): number
+  export function convert_with(input: string, options: Options): string
+  export function convert_multi(
+    input: string,
+    source: string,
+    formats: string[],
+    optionsJson: string,
+  ): string
+  export function convert_format(input: string, from: string, to: string, optionsJson: string): string
+  export function convert_with_transform(
+    input: string,
+    source: string,
+    format: string,
+    optionsJson: string,
+    transform: (content: string) => string,
+  ): string
+  export function convert_with_enricher(
+    input: string,
+    source: string,
+    format: string,
+    optionsJson: string,
+    enricher: (
+      batch: { index: number; sender: string; content: string }[],
+    ) => (Record<string, string | number | boolean | null> | null)[],
+    batchSize?: number,
+  ): string
+  export function convert_windows(
+    input: string,
+    source: string,
+    windowSize: number,
+    stride: number,
+    format: string,
+    optionsJson: string,
+  ): string
+  export function convert_split(
+    input: string,
+    source: string,
+    by: 'day' | 'month' | 'year',
+    format: string,
+    optionsJson: string,
+  ): string
+  export function convert_chunked(
+    input: string,
+    source: string,
+    format: string,
+    optionsJson: string,
+    budget: number,
+    unit: 'tokens' | 'bytes',
+    overlap: number,
+  ): string
+  export function list_chats(input: string): string
+  export function convert_chat(
+    input: string,
+    chatId: number,
+    format: string,
+    optionsJson: string,
+  ): string
+  export function convert_finetune(input: string, source: string, optionsJson: string): string
+  export function extract_pairs(input: string, source: string, optionsJson: string): string
+  export function extract_threads(input: string, source: string, optionsJson: string): string
   export function parse_chat(input: string, source: string, optionsJson: string): string
+  export function merge_conversations(inputsJson: string): string
+  export function diff(oldInput: string, newInput: string, source: string): string
+  export function export_new_only(
+    oldInput: string,
+    newInput: string,
+    source: string,
+    format: string,
+    optionsJson: string,
+  ): string
+  export function convert_merged(inputsJson: string, format: string, optionsJson: string): string
+  export function convert_batch(
+    itemsJson: string,
+    format: string,
+    optionsJson: string,
+    onProgress?: (progressJson: string) => void,
+  ): string
+  export function chat_kind(input: string, source: string): string
+  export function events(input: string, source: string): string
+  export function calls(input: string, source: string): string
+  export function fingerprint(input: string, source: string): string
+  export function export_calls_csv(input: string, source: string): string
+  export function export_calls_ics(input: string, source: string): string
+  export function tokenize(content: string, optionsJson: string): string
+  export function sender_volume(input: string, source: string, optionsJson: string): string
+  export function export_stats(
+    input: string,
+    source: string,
+    format: string,
+    optionsJson: string,
+  ): string
+  export function output_schema(): string
+  export function schema(format: string, optionsJson: string): string
+  export function date_span(input: string, source: string): string
   export function supported_sources(): string
+  export function supported_platforms(): string
+  export function platform_capabilities(source: string): string
   export function supported_formats(): string
   export function version(): string
+  export function memory_usage(): number
+  export class ChatSession {
+    static parse(input: string, source: string, optionsJson?: string): ChatSession
+    export(format: string, optionsJson: string): string
+    stats(optionsJson: string): string
+    participants(): string
+    search(query: string, optionsJson: string): string
+    message_count(): number
+    free(): void
+  }
+  export class Options {
+    constructor()
+    static from_json(source: string, format: string, optionsJson: string): Options
+    to_json(): string
+    with_source(source: string): Options
+    with_format(format: string): Options
+    with_timestamps(enabled: boolean): Options
+    with_ids(enabled: boolean): Options
+    with_replies(enabled: boolean): Options
+    with_edited(enabled: boolean): Options
+    with_merge(enabled: boolean): Options
+    with_merge_separator(separator?: string): Options
+    with_merge_keep(timestamp: 'first' | 'last', replyTo: 'first' | 'any' | 'drop'): Options
+    with_pretty(enabled: boolean): Options
+    with_sentiment(enabled: boolean): Options
+    with_sender(sender?: string): Options
+    with_senders(senders: string[]): Options
+    with_date_range(from?: string, to?: string): Options
+    with_source_timezone(timezone?: string): Options
+    with_anonymize(enabled: boolean): Options
+    with_anonymize_seed(seed?: string): Options
+    readonly source: string
+    readonly format: string
+    readonly timestamps: boolean
+    readonly ids: boolean
+    readonly replies: boolean
+    readonly edited: boolean
+    readonly merge: boolean
+    readonly merge_separator: string | undefined
+    readonly merge_keep: string
+    readonly pretty: boolean
+    readonly sentiment: boolean
+    readonly sender: string | undefined
+    readonly senders: string[]
+    readonly date_from: string | undefined
+    readonly date_to: string | undefined
+    readonly source_timezone: string | undefined
+    readonly anonymize: boolean
+    readonly anonymize_seed: string | undefined
+    free(): void
+  }
 }
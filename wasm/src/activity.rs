@@ -0,0 +1,384 @@
+//! Per-sender activity: daily counts exported as a file, and overall volume.
+
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+
+use chatpack::Message;
+use chrono::{FixedOffset, NaiveDate};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::csv_cells::formula_safe;
+use crate::{build_stats, js_error, non_empty, parse_options, prepare_messages, timezone, words};
+
+const CSV_DELIMITER: u8 = b';';
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ActivityOptions {
+    /// UTC offset the days are counted in; UTC if unset.
+    timezone: Option<String>,
+    /// `"pivot"` only: senders with a column of their own; the rest are summed into `others`.
+    top_n_senders: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct DailyActivity {
+    /// `YYYY-MM-DD`, or empty for messages without a timestamp.
+    date: String,
+    sender: String,
+    message_count: usize,
+    word_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct SenderVolume {
+    sender: String,
+    characters: usize,
+    message_count: usize,
+    /// Share of all characters, in percent.
+    share: f64,
+}
+
+/// Export conversion stats and per-sender daily activity as a file.
+///
+/// `format` is `"csv"` for one row per day and sender
+/// (`date;sender;message_count;word_count`, sorted by date, then sender) or
+/// `"json"` for `{stats, daily}`, where `stats` is the object
+/// `convert_with_report` returns and `daily` holds the same rows as the CSV.
+/// `"pivot"` is the same counts as a wide CSV: one row per day from the
+/// first to the last dated message (days without messages filled with
+/// zeros), a message count column per sender ordered by their total, then
+/// `total`; with `top_n_senders` only that many senders get a column and the
+/// rest are summed into `others`. Undated messages are left out of it.
+/// `options_json` takes the `convert_with_options` keys, so the counts are
+/// of the filtered (and, by default, merged) messages, plus `timezone`: the
+/// UTC offset (`+05:30`) days are counted in, UTC by default.
+#[wasm_bindgen]
+pub fn export_stats(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    stats_file(input, source, format, options_json).map_err(js_error)
+}
+
+/// How much each sender wrote, for a "who talks most" chart.
+///
+/// Returns a JSON array of `{sender, characters, message_count, share}`,
+/// sorted by `characters` descending (ties by sender), where `share` is the
+/// sender's percentage of all characters. `options_json` takes the
+/// `convert_with_options` keys: `message_count` counts merged turns while
+/// `merge_consecutive` is on (the default) and single messages otherwise.
+#[wasm_bindgen]
+pub fn sender_volume(
+    input: &str,
+    source: &str,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    let volume = volume_by_sender(input, source, options_json).map_err(js_error)?;
+
+    serde_json::to_string(&volume).map_err(|e| js_error(e.to_string()))
+}
+
+fn volume_by_sender(
+    input: &str,
+    source: &str,
+    options_json: &str,
+) -> std::result::Result<Vec<SenderVolume>, String> {
+    let options = parse_options(options_json)?;
+    let prepared = prepare_messages(input, source, &options)?;
+
+    let mut totals: BTreeMap<&str, (usize, usize)> = BTreeMap::new();
+    for message in &prepared.messages {
+        let entry = totals.entry(&message.sender).or_default();
+        entry.0 += message.content.chars().count();
+        entry.1 += 1;
+    }
+    let all_characters: usize = totals.values().map(|(characters, _)| characters).sum();
+
+    let mut volume: Vec<SenderVolume> = totals
+        .into_iter()
+        .map(|(sender, (characters, message_count))| SenderVolume {
+            sender: sender.to_string(),
+            characters,
+            message_count,
+            share: if all_characters == 0 {
+                0.0
+            } else {
+                characters as f64 * 100.0 / all_characters as f64
+            },
+        })
+        .collect();
+    // Stable sort keeps the BTreeMap's sender order for ties.
+    volume.sort_by_key(|v| std::cmp::Reverse(v.characters));
+
+    Ok(volume)
+}
+
+fn stats_file(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<String, String> {
+    let options = parse_options(options_json)?;
+    let activity_options = activity_options(options_json)?;
+    let offset = day_offset(&activity_options)?;
+    let prepared = prepare_messages(input, source, &options)?;
+    let daily = daily_activity(&prepared.messages, offset);
+
+    match format.trim().to_ascii_lowercase().as_str() {
+        "csv" => daily_csv(&daily, options.escape_formulas),
+        "pivot" => pivot_csv(
+            &prepared.messages,
+            offset,
+            activity_options.top_n_senders,
+            options.escape_formulas,
+        ),
+        "json" => {
+            let stats = build_stats(&prepared, &options, input.len(), 0);
+            serde_json::to_string_pretty(&serde_json::json!({ "stats": stats, "daily": daily }))
+                .map_err(|e| e.to_string())
+        }
+        other => Err(format!("Unknown format: '{other}'. Expected one of: csv, json, pivot")),
+    }
+}
+
+fn activity_options(options_json: &str) -> std::result::Result<ActivityOptions, String> {
+    let options: ActivityOptions = if options_json.trim().is_empty() {
+        ActivityOptions::default()
+    } else {
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid options JSON: {e}"))?
+    };
+    if options.top_n_senders == Some(0) {
+        return Err("Invalid top_n_senders: must be at least 1".to_string());
+    }
+
+    Ok(options)
+}
+
+fn day_offset(options: &ActivityOptions) -> std::result::Result<FixedOffset, String> {
+    match non_empty(options.timezone.as_deref()) {
+        Some(value) => {
+            timezone::parse_offset(value).map_err(|e| e.replacen("source_timezone", "timezone", 1))
+        }
+        None => Ok(FixedOffset::east_opt(0).expect("zero offset is valid")),
+    }
+}
+
+fn daily_activity(messages: &[Message], offset: FixedOffset) -> Vec<DailyActivity> {
+    let mut counts: BTreeMap<(String, &str), (usize, usize)> = BTreeMap::new();
+    for message in messages {
+        let date = message
+            .timestamp
+            .map(|t| t.with_timezone(&offset).format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let entry = counts.entry((date, &message.sender)).or_default();
+        entry.0 += 1;
+        entry.1 += words::word_count(&message.content);
+    }
+
+    counts
+        .into_iter()
+        .map(|((date, sender), (message_count, word_count))| DailyActivity {
+            date,
+            sender: sender.to_string(),
+            message_count,
+            word_count,
+        })
+        .collect()
+}
+
+fn daily_csv(
+    daily: &[DailyActivity],
+    escape_formulas: bool,
+) -> std::result::Result<String, String> {
+    let mut writer = csv::WriterBuilder::new().delimiter(CSV_DELIMITER).from_writer(Vec::new());
+    writer
+        .write_record(["date", "sender", "message_count", "word_count"])
+        .map_err(|e| e.to_string())?;
+    for row in daily {
+        writer
+            .write_record([
+                row.date.as_str(),
+                &formula_safe(&row.sender, escape_formulas),
+                &row.message_count.to_string(),
+                &row.word_count.to_string(),
+            ])
+            .map_err(|e| e.to_string())?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+/// Messages per day (rows) and sender (columns), on every day of the span.
+fn pivot_csv(
+    messages: &[Message],
+    offset: FixedOffset,
+    top_n: Option<usize>,
+    escape_formulas: bool,
+) -> std::result::Result<String, String> {
+    let mut days: BTreeMap<NaiveDate, HashMap<&str, usize>> = BTreeMap::new();
+    let mut totals: HashMap<&str, usize> = HashMap::new();
+    for message in messages {
+        let Some(timestamp) = message.timestamp else {
+            continue;
+        };
+        let day = timestamp.with_timezone(&offset).date_naive();
+        *days.entry(day).or_default().entry(&message.sender).or_default() += 1;
+        *totals.entry(&message.sender).or_default() += 1;
+    }
+
+    let mut senders: Vec<&str> = totals.keys().copied().collect();
+    senders.sort_by_key(|sender| (std::cmp::Reverse(totals[sender]), *sender));
+    let (columns, others) = senders.split_at(top_n.unwrap_or(senders.len()).min(senders.len()));
+
+    let mut writer = csv::WriterBuilder::new().delimiter(CSV_DELIMITER).from_writer(Vec::new());
+    let mut header = vec![Cow::Borrowed("date")];
+    header.extend(columns.iter().map(|sender| formula_safe(sender, escape_formulas)));
+    if !others.is_empty() {
+        header.push(Cow::Borrowed("others"));
+    }
+    header.push(Cow::Borrowed("total"));
+    writer.write_record(header.iter().map(|cell| cell.as_bytes())).map_err(|e| e.to_string())?;
+
+    if let (Some(&first), Some(&last)) = (days.keys().next(), days.keys().next_back()) {
+        let empty = HashMap::new();
+        for day in first.iter_days().take_while(|day| *day <= last) {
+            let counts = days.get(&day).unwrap_or(&empty);
+            let count = |sender: &&str| counts.get(sender).copied().unwrap_or(0);
+            let mut row = vec![day.format("%Y-%m-%d").to_string()];
+            row.extend(columns.iter().map(|sender| count(sender).to_string()));
+            if !others.is_empty() {
+                row.push(others.iter().map(count).sum::<usize>().to_string());
+            }
+            row.push(counts.values().sum::<usize>().to_string());
+            writer.write_record(&row).map_err(|e| e.to_string())?;
+        }
+    }
+
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TELEGRAM: &str = r#"{"messages": [
+    {"id": 1, "type": "message", "date_unixtime": "1705330800", "from": "Alice", "text": "Afternoon hello there"},
+    {"id": 2, "type": "message", "date_unixtime": "1705357800", "from": "Bob", "text": "Hi"},
+    {"id": 3, "type": "message", "date_unixtime": "1705359600", "from": "Bob", "text": "Still up?"}
+]}"#;
+
+    #[test]
+    fn test_csv_rows_per_day_and_sender() {
+        let utc =
+            stats_file(TELEGRAM, "telegram", "csv", r#"{"merge_consecutive":false}"#).unwrap();
+        let shifted = stats_file(
+            TELEGRAM,
+            "telegram",
+            "csv",
+            r#"{"merge_consecutive":false,"timezone":"+02:00"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            utc,
+            "date;sender;message_count;word_count\n2024-01-15;Alice;1;3\n2024-01-15;Bob;2;3\n"
+        );
+        assert!(shifted.contains("2024-01-16;Bob;2;3"));
+        assert!(shifted.contains("2024-01-15;Alice;1;3"));
+    }
+
+    #[test]
+    fn test_json_document_and_errors() {
+        let json: serde_json::Value =
+            serde_json::from_str(&stats_file(TELEGRAM, "telegram", "json", "").unwrap()).unwrap();
+
+        assert_eq!(json["stats"]["original_count"], 3);
+        assert_eq!(json["stats"]["merged_count"], 2);
+        assert_eq!(json["daily"][1]["sender"], "Bob");
+        assert_eq!(json["daily"][1]["message_count"], 1);
+        assert!(stats_file(TELEGRAM, "telegram", "xlsx", "").unwrap_err().contains("csv, json"));
+        assert!(stats_file(TELEGRAM, "telegram", "csv", r#"{"timezone":"Mars"}"#)
+            .unwrap_err()
+            .starts_with("Invalid timezone"));
+    }
+
+    #[test]
+    fn test_pivot_is_continuous_and_shifted_by_timezone() {
+        let unmerged = r#"{"merge_consecutive":false}"#;
+        let utc = stats_file(TELEGRAM, "telegram", "pivot", unmerged).unwrap();
+        // Bob's messages at 22:30 and 23:00 UTC fall on the next day at +02:00.
+        let shifted = stats_file(
+            TELEGRAM,
+            "telegram",
+            "pivot",
+            r#"{"merge_consecutive":false,"timezone":"+02:00"}"#,
+        )
+        .unwrap();
+        // 2024-01-15 and 2024-01-18 with a gap in between.
+        let gap = r#"{"messages": [
+    {"id": 1, "type": "message", "date_unixtime": "1705330800", "from": "Alice", "text": "Hi"},
+    {"id": 2, "type": "message", "date_unixtime": "1705590000", "from": "Bob", "text": "Back"}
+]}"#;
+
+        assert_eq!(utc, "date;Bob;Alice;total\n2024-01-15;2;1;3\n");
+        assert_eq!(shifted, "date;Bob;Alice;total\n2024-01-15;0;1;1\n2024-01-16;2;0;2\n");
+        assert_eq!(
+            stats_file(gap, "telegram", "pivot", "").unwrap(),
+            "date;Alice;Bob;total\n2024-01-15;1;0;1\n2024-01-16;0;0;0\n2024-01-17;0;0;0\n\
+             2024-01-18;0;1;1\n"
+        );
+        assert_eq!(stats_file("", "telegram", "pivot", "").unwrap(), "date;total\n");
+    }
+
+    #[test]
+    fn test_pivot_collapses_tail_into_others() {
+        let options = r#"{"merge_consecutive":false,"top_n_senders":1}"#;
+
+        assert_eq!(
+            stats_file(TELEGRAM, "telegram", "pivot", options).unwrap(),
+            "date;Bob;others;total\n2024-01-15;2;1;3\n"
+        );
+        assert!(stats_file(TELEGRAM, "telegram", "pivot", r#"{"top_n_senders":0}"#).is_err());
+    }
+
+    #[test]
+    fn test_sender_cells_escape_formulas() {
+        let telegram = TELEGRAM.replace("\"Bob\"", "\"=Bob\"");
+        let options = r#"{"merge_consecutive":false}"#;
+
+        let daily = stats_file(&telegram, "telegram", "csv", options).unwrap();
+        let pivot = stats_file(&telegram, "telegram", "pivot", options).unwrap();
+        let raw = stats_file(
+            &telegram,
+            "telegram",
+            "pivot",
+            r#"{"merge_consecutive":false,"escape_formulas":false}"#,
+        )
+        .unwrap();
+
+        assert!(daily.contains("\n2024-01-15;'=Bob;2;3\n"));
+        assert!(pivot.starts_with("date;'=Bob;Alice;total\n"));
+        assert!(raw.starts_with("date;=Bob;Alice;total\n"));
+    }
+
+    #[test]
+    fn test_sender_volume_sorted_by_characters() {
+        let merged = volume_by_sender(TELEGRAM, "telegram", "").unwrap();
+        let unmerged =
+            volume_by_sender(TELEGRAM, "telegram", r#"{"merge_consecutive":false}"#).unwrap();
+
+        assert_eq!(merged[0].sender, "Alice");
+        assert_eq!(merged[0].characters, 21);
+        assert_eq!(merged[1].message_count, 1);
+        assert_eq!(unmerged[1].message_count, 2);
+        assert!((merged.iter().map(|v| v.share).sum::<f64>() - 100.0).abs() < 1e-9);
+        assert!(volume_by_sender("", "telegram", "").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+
+use chatpack::parser::Platform;
+use chatpack::Message;
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::registry::Backend;
+use crate::{js_error, parse_messages, parse_platform};
+
+#[derive(Debug, Serialize)]
+struct ChatKind {
+    participant_count: usize,
+    is_group: bool,
+}
+
+/// Top-level export fields that describe the chat itself.
+///
+/// Only these keys are materialized; `messages` and everything else is skipped.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ExportHeader {
+    /// Telegram: `personal_chat`, `private_group`, `public_supergroup`, ...
+    #[serde(rename = "type")]
+    chat_type: Option<String>,
+    /// Instagram: every member of the thread.
+    participants: Option<Vec<IgnoredAny>>,
+    /// Discord (DiscordChatExporter): the exported channel.
+    channel: Option<ChannelHeader>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ChannelHeader {
+    #[serde(rename = "type")]
+    channel_type: Option<String>,
+}
+
+/// Count distinct senders and classify the chat as direct (1:1) or group.
+///
+/// Returns JSON `{participant_count, is_group}`. Two or fewer senders count
+/// as a direct chat unless the export's own metadata says otherwise (Telegram
+/// chat type, Instagram participant list, Discord channel type).
+#[wasm_bindgen]
+pub fn chat_kind(input: &str, source: &str) -> std::result::Result<String, JsValue> {
+    let backend = parse_platform(source).map_err(js_error)?;
+    let messages = parse_messages(input, source).map_err(js_error)?;
+    let participant_count = count_participants(&messages);
+    let is_group = metadata_is_group(input, backend).unwrap_or(participant_count > 2);
+
+    serde_json::to_string(&ChatKind { participant_count, is_group })
+        .map_err(|e| js_error(e.to_string()))
+}
+
+/// Distinct senders, compared after trimming so "Alice" and "Alice " are one person.
+fn count_participants(messages: &[Message]) -> usize {
+    messages.iter().map(|m| m.sender.trim()).collect::<HashSet<_>>().len()
+}
+
+/// Group/direct classification declared by the export, if the platform has one.
+fn metadata_is_group(input: &str, backend: Backend) -> Option<bool> {
+    if !matches!(
+        backend,
+        Backend::Core(Platform::Telegram | Platform::Instagram | Platform::Discord)
+    ) {
+        return None;
+    }
+
+    let header: ExportHeader = serde_json::from_str(input).ok()?;
+
+    if let Some(chat_type) = header.chat_type.as_deref() {
+        return match chat_type {
+            "personal_chat" | "bot_chat" | "saved_messages" => Some(false),
+            t if t.contains("group") || t.contains("channel") => Some(true),
+            _ => None,
+        };
+    }
+    if let Some(participants) = header.participants {
+        return Some(participants.len() > 2);
+    }
+
+    match header.channel?.channel_type.as_deref()? {
+        "DirectTextChat" => Some(false),
+        "DirectGroupTextChat" | "GuildTextChat" | "GuildPublicThread" | "GuildPrivateThread" => {
+            Some(true)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn telegram(chat_type: &str, senders: &[&str]) -> String {
+        let messages: Vec<String> = senders
+            .iter()
+            .enumerate()
+            .map(|(i, sender)| {
+                format!(
+                    r#"{{"id": {i}, "type": "message", "date_unixtime": "1705314600", "from": "{sender}", "text": "hi"}}"#
+                )
+            })
+            .collect();
+        format!(r#"{{"type": "{chat_type}", "messages": [{}]}}"#, messages.join(","))
+    }
+
+    fn kind(input: &str, source: &str) -> serde_json::Value {
+        let json = chat_kind(input, source)
+            .map_err(|e| e.as_string().unwrap_or_default())
+            .expect("chat_kind should succeed");
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_chat_kind_counts_senders() {
+        let direct = kind(&telegram("unknown", &["Alice", "Bob", "Alice "]), "telegram");
+        let group = kind(&telegram("unknown", &["Alice", "Bob", "Carol"]), "telegram");
+
+        assert_eq!(direct["participant_count"], 2);
+        assert_eq!(direct["is_group"], false);
+        assert_eq!(group["participant_count"], 3);
+        assert_eq!(group["is_group"], true);
+    }
+
+    #[test]
+    fn test_chat_kind_metadata_overrides_sender_count() {
+        let quiet_group = kind(&telegram("private_group", &["Alice", "Bob"]), "telegram");
+        let direct = kind(&telegram("personal_chat", &["Alice", "Bob", "Carol"]), "telegram");
+
+        assert_eq!(quiet_group["participant_count"], 2);
+        assert_eq!(quiet_group["is_group"], true);
+        assert_eq!(direct["is_group"], false);
+    }
+}
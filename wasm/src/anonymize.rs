@@ -0,0 +1,115 @@
+//! Replacing sender names with pseudonyms.
+//!
+//! Without a seed, senders become `Person 1`, `Person 2`, ... in order of
+//! their first message, which is stable for one export but gives the same
+//! person different labels in different files. With `anonymize_seed` the
+//! pseudonym is a 64-bit hash of the seed and the name alone
+//! (`Person 3fa91c0e5d2b7a48`), so every conversion using the same seed maps
+//! a name to the same label, whichever other senders a file has, and a whole
+//! folder of exports can be anonymized consistently. Senders' names
+//! are also replaced where they appear in contents, as whole words and
+//! ignoring case, like `redact_words`.
+
+use std::collections::HashMap;
+
+use chatpack::Message;
+
+use crate::redact;
+
+const PREFIX: &str = "Person";
+
+/// Replace every sender (and mentions of their name) with a pseudonym,
+/// returning the pseudonym of each original sender.
+pub(crate) fn anonymize(messages: &mut [Message], seed: Option<&str>) -> HashMap<String, String> {
+    let mut pseudonyms: HashMap<String, String> = HashMap::new();
+    for message in messages.iter() {
+        if pseudonyms.contains_key(&message.sender) {
+            continue;
+        }
+        let pseudonym = match seed {
+            Some(seed) => format!("{PREFIX} {:016x}", fnv1a(seed, &message.sender)),
+            None => format!("{PREFIX} {}", pseudonyms.len() + 1),
+        };
+        pseudonyms.insert(message.sender.clone(), pseudonym);
+    }
+
+    let mut names: Vec<(Vec<char>, &str)> = pseudonyms
+        .iter()
+        .filter(|(name, _)| !name.trim().is_empty())
+        .map(|(name, pseudonym)| (name.trim().chars().collect(), pseudonym.as_str()))
+        .collect();
+    names.sort_by_key(|(name, _)| std::cmp::Reverse(name.len()));
+
+    for message in messages.iter_mut() {
+        for (name, pseudonym) in &names {
+            redact::replace(&mut message.content, name, pseudonym);
+        }
+        if let Some(pseudonym) = pseudonyms.get(&message.sender) {
+            message.sender = pseudonym.clone();
+        }
+    }
+
+    pseudonyms
+}
+
+/// 64-bit FNV-1a of `seed` and `name`; fixed, unlike std's hashers, so
+/// pseudonyms stay the same across builds.
+fn fnv1a(seed: &str, name: &str) -> u64 {
+    let bytes = seed.bytes().chain([0]).chain(name.bytes());
+    bytes.fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_options;
+    use crate::test_support::convert;
+
+    const FIRST: &str = "[15.01.24, 10:30:00] Ann Lee: Hi Bob\n\
+[15.01.24, 10:31:00] Bob: Hi ann lee, see you at noon\n";
+    const SECOND: &str = "[16.01.24, 09:00:00] Carol: Morning\n\
+[16.01.24, 09:01:00] Bob: Morning Carol\n";
+
+    #[test]
+    fn test_sequential_pseudonyms_per_export() {
+        let first =
+            convert(FIRST, "whatsapp", "csv", r#"{"anonymize":true,"merge_consecutive":false}"#);
+        let second =
+            convert(SECOND, "whatsapp", "csv", r#"{"anonymize":true,"merge_consecutive":false}"#);
+
+        assert_eq!(
+            first,
+            "Sender;Content\nPerson 1;Hi Person 2\nPerson 2;Hi Person 1, see you at noon\n"
+        );
+        // Bob is "Person 2" in one file and "Person 1" in the other.
+        assert!(second.contains("Person 2;Morning Person 1\n"));
+    }
+
+    #[test]
+    fn test_same_seed_gives_same_pseudonyms_across_calls() {
+        let options =
+            r#"{"anonymize":true,"anonymize_seed":"project-x","merge_consecutive":false}"#;
+        let first = convert(FIRST, "whatsapp", "csv", options);
+        let second = convert(SECOND, "whatsapp", "csv", options);
+        let bob = |output: &str, row: usize| {
+            output.lines().nth(row).unwrap().split(';').next().unwrap().to_string()
+        };
+
+        assert_eq!(bob(&first, 2), bob(&second, 2));
+        assert!(bob(&first, 2).starts_with("Person "));
+        assert_eq!(bob(&first, 2).len(), "Person ".len() + 16);
+        assert!(!first.contains("Bob") && !first.contains("ann lee"));
+        assert_eq!(convert(FIRST, "whatsapp", "csv", options), first);
+        assert_ne!(
+            convert(
+                FIRST,
+                "whatsapp",
+                "csv",
+                r#"{"anonymize":true,"anonymize_seed":"other","merge_consecutive":false}"#
+            ),
+            first
+        );
+        assert!(parse_options(r#"{"anonymize_seed":"project-x"}"#).is_err());
+    }
+}
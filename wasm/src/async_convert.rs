@@ -0,0 +1,182 @@
+use std::future::Future;
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+use crate::stream::{batches, ChunkStitcher};
+use crate::{
+    js_error, limit_output, parse_options, process_export, read_export, registry, resolve_format,
+};
+
+/// Number of messages formatted between two yields to the event loop.
+const YIELD_EVERY_MESSAGES: usize = 5_000;
+
+/// Convert chat export without monopolizing the main thread.
+///
+/// Takes the same arguments as `convert_with_options` and returns a Promise
+/// resolving to the same output string, or rejecting with the same error
+/// string. Between pipeline stages and after every few thousand formatted
+/// messages the conversion awaits a resolved Promise, letting other queued
+/// work run. Parsing, filtering and merging each still run as one
+/// uninterrupted step, so for very large exports a Web Worker remains the
+/// better option; this is meant for pages that cannot use one.
+/// `max_output_bytes` and `dry_run` apply as they do there.
+#[wasm_bindgen]
+pub fn convert_async(
+    input: String,
+    source: String,
+    format: String,
+    options_json: String,
+) -> js_sys::Promise {
+    future_to_promise(async move {
+        convert_yielding(&input, &source, &format, &options_json, next_tick)
+            .await
+            .map(JsValue::from)
+            .map_err(js_error)
+    })
+}
+
+async fn next_tick() {
+    // A rejected tick cannot happen for `Promise.resolve`; ignore the result.
+    let _ = JsFuture::from(js_sys::Promise::resolve(&JsValue::NULL)).await;
+}
+
+async fn convert_yielding<Y, F>(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+    mut yield_now: Y,
+) -> std::result::Result<String, String>
+where
+    Y: FnMut() -> F,
+    F: Future<Output = ()>,
+{
+    let mut options = parse_options(options_json)?;
+    let output_format = resolve_format(format, &mut options)?;
+    let export = read_export(input, source, &options)?;
+    yield_now().await;
+
+    let platform = registry::find_platform(source)?;
+    let mut prepared = process_export(export, platform, &options)?;
+    yield_now().await;
+    if options.dry_run {
+        return Ok(String::new());
+    }
+    // Finding where the limit falls renders the output already.
+    if let Some((output, _)) = limit_output(&mut prepared, output_format, &options)? {
+        return Ok(output);
+    }
+
+    let mut stitcher = ChunkStitcher::new(output_format, options.pretty);
+    let mut output = String::new();
+
+    for batch in batches(&prepared.messages, YIELD_EVERY_MESSAGES, usize::MAX) {
+        let formatted = prepared.format_range(batch, output_format, &options)?;
+        output.push_str(&stitcher.push(formatted));
+        yield_now().await;
+    }
+    if let Some(tail) = stitcher.finish() {
+        output.push_str(&tail);
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    use super::*;
+    use crate::{run_conversion, ConvertOptions};
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    fn telegram(count: usize) -> String {
+        let messages: Vec<String> = (0..count)
+            .map(|i| {
+                let sender = if i % 2 == 0 { "Alice" } else { "Bob" };
+                format!(
+                    r#"{{"id": {i}, "type": "message", "date_unixtime": "{}", "from": "{sender}", "text": "message {i}"}}"#,
+                    1_705_314_600 + i
+                )
+            })
+            .collect();
+        format!(r#"{{"messages": [{}]}}"#, messages.join(","))
+    }
+
+    #[test]
+    fn test_async_output_matches_sync_conversion() {
+        let input = telegram(YIELD_EVERY_MESSAGES * 2 + 10);
+        let options = r#"{"include_timestamps":true}"#;
+
+        for format in ["csv", "json", "jsonl", "json-compact"] {
+            let mut ticks = 0;
+            let output = block_on(convert_yielding(&input, "telegram", format, options, || {
+                ticks += 1;
+                async {}
+            }))
+            .expect("conversion should succeed");
+            let expected =
+                run_conversion(&input, "telegram", format, &parse_options(options).unwrap())
+                    .unwrap();
+
+            assert_eq!(output, expected.output, "{format} output differs");
+            assert_eq!(ticks, 5, "parse, process and three formatting batches");
+        }
+    }
+
+    #[test]
+    fn test_async_reads_the_export_like_sync_conversion() {
+        let input = include_str!("../fixtures/whatsapp_group_events.txt")
+            .replace("Alice: Welcome!", "Alice: <Media omitted>");
+
+        for options in [r#"{"merge_consecutive":false}"#, r#"{"include_events":true}"#] {
+            let output =
+                block_on(convert_yielding(&input, "whatsapp", "csv", options, || async {}))
+                    .expect("conversion should succeed");
+            let expected =
+                run_conversion(&input, "whatsapp", "csv", &parse_options(options).unwrap());
+
+            assert_eq!(output, expected.unwrap().output, "{options}");
+        }
+    }
+
+    #[test]
+    fn test_async_applies_output_limit_and_dry_run() {
+        let input = telegram(40);
+        let full = run_conversion(&input, "telegram", "json", &ConvertOptions::default()).unwrap();
+        let limit = full.output.len() / 3;
+
+        for options in [format!(r#"{{"max_output_bytes":{limit}}}"#), r#"{"dry_run":true}"#.into()]
+        {
+            for format in ["csv", "json", "jsonl"] {
+                let output =
+                    block_on(convert_yielding(&input, "telegram", format, &options, || async {}))
+                        .expect("conversion should succeed");
+                let expected =
+                    run_conversion(&input, "telegram", format, &parse_options(&options).unwrap());
+
+                assert_eq!(output, expected.unwrap().output, "{format} {options}");
+                assert!(output.len() <= limit, "{format} {options}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_async_errors_match_sync_conversion() {
+        let error = block_on(convert_yielding("{}", "telegram", "xml", "", || async {}))
+            .expect_err("unknown format should fail");
+
+        assert!(error.starts_with("Unknown format"));
+    }
+}
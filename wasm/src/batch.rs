@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::detect::{self, detect_source};
+use crate::registry;
+use crate::{
+    describe_js_error, js_error, parse_options, resolve_format, run_conversion, ConversionStats,
+    ConvertOptions,
+};
+
+#[derive(Debug, Deserialize)]
+struct BatchItem {
+    id: String,
+    input: String,
+    source: String,
+}
+
+#[derive(Serialize)]
+struct BatchResult {
+    id: String,
+    ok: bool,
+    /// Platform the item was converted as; the detected one for `"auto"`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stats: Option<ConversionStats>,
+}
+
+#[derive(Serialize)]
+struct BatchProgress<'a> {
+    item_id: &'a str,
+    index: usize,
+    total: usize,
+    ok: bool,
+}
+
+/// Convert several independent exports with the same format and options.
+///
+/// `items_json` is a JSON array of `{id, input, source}`; `source` may be
+/// `"auto"` to detect each item's platform from its content. Items are
+/// converted one after another, and a failing item is reported in its
+/// result without stopping the others. After each item `on_progress`, if
+/// given, is called with a JSON string `{item_id, index, total, ok}`.
+///
+/// Returns a JSON array of `{id, ok, source, output, stats}` for converted
+/// items and `{id, ok, error}` for failed ones, in input order. Invalid
+/// `items_json`, options or format fail the whole call, as does an
+/// exception thrown by `on_progress`.
+#[wasm_bindgen]
+pub fn convert_batch(
+    items_json: &str,
+    format: &str,
+    options_json: &str,
+    on_progress: Option<js_sys::Function>,
+) -> std::result::Result<String, JsValue> {
+    let results = convert_items(items_json, format, options_json, |progress| {
+        let Some(on_progress) = &on_progress else {
+            return Ok(());
+        };
+        let progress = serde_json::to_string(progress).map_err(|e| e.to_string())?;
+        on_progress
+            .call1(&JsValue::NULL, &JsValue::from_str(&progress))
+            .map(drop)
+            .map_err(describe_js_error)
+    })
+    .map_err(js_error)?;
+
+    serde_json::to_string(&results).map_err(|e| js_error(e.to_string()))
+}
+
+fn convert_items<F>(
+    items_json: &str,
+    format: &str,
+    options_json: &str,
+    mut progress: F,
+) -> std::result::Result<Vec<BatchResult>, String>
+where
+    F: FnMut(&BatchProgress) -> std::result::Result<(), String>,
+{
+    let options = parse_options(options_json)?;
+    resolve_format(format, &mut options.clone())?;
+    let items: Vec<BatchItem> =
+        serde_json::from_str(items_json).map_err(|e| format!("Invalid batch items JSON: {e}"))?;
+
+    let total = items.len();
+    let mut results = Vec::with_capacity(total);
+    for (index, item) in items.into_iter().enumerate() {
+        let result = convert_item(item, format, &options);
+        progress(&BatchProgress { item_id: &result.id, index, total, ok: result.ok })?;
+        results.push(result);
+    }
+
+    Ok(results)
+}
+
+fn convert_item(item: BatchItem, format: &str, options: &ConvertOptions) -> BatchResult {
+    let converted = if item.source.trim().eq_ignore_ascii_case(detect::AUTO) {
+        detect_source(&item.input)
+    } else {
+        registry::find_platform(&item.source).map(|spec| spec.id)
+    }
+    .and_then(|source| {
+        run_conversion(&item.input, source, format, options).map(|report| (source, report))
+    });
+
+    match converted {
+        Ok((source, report)) => BatchResult {
+            id: item.id,
+            ok: true,
+            source: Some(source),
+            output: Some(report.output),
+            error: None,
+            stats: Some(report.stats),
+        },
+        Err(error) => BatchResult {
+            id: item.id,
+            ok: false,
+            source: None,
+            output: None,
+            error: Some(error),
+            stats: None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TELEGRAM;
+
+    const WHATSAPP: &str = "[15.01.24, 10:31:00] Alice: Also on WhatsApp\n";
+
+    fn items() -> String {
+        serde_json::json!([
+            {"id": "tg.json", "input": TELEGRAM, "source": "telegram"},
+            {"id": "broken.json", "input": "{not json", "source": "telegram"},
+            {"id": "chat.txt", "input": WHATSAPP, "source": "auto"},
+            {"id": "notes.txt", "input": "just some notes", "source": "auto"},
+        ])
+        .to_string()
+    }
+
+    #[test]
+    fn test_failures_do_not_stop_the_batch() {
+        let results = convert_items(&items(), "csv", "{}", |_| Ok(())).unwrap();
+        let summary: Vec<(&str, bool, Option<&str>)> =
+            results.iter().map(|r| (r.id.as_str(), r.ok, r.source)).collect();
+
+        assert_eq!(
+            summary,
+            [
+                ("tg.json", true, Some("telegram")),
+                ("broken.json", false, None),
+                ("chat.txt", true, Some("whatsapp")),
+                ("notes.txt", false, None),
+            ]
+        );
+        assert_eq!(results[0].output.as_deref(), Some("Sender;Content\nAlice;Hello\nBob;Hi\n"));
+        assert!(results[3].error.as_deref().unwrap().contains("Could not detect"));
+        assert!(convert_items(&items(), "xml", "{}", |_| Ok(())).is_err());
+    }
+
+    #[test]
+    fn test_progress_per_item() {
+        let mut calls = Vec::new();
+        convert_items(&items(), "jsonl", "{}", |progress| {
+            calls.push(serde_json::to_string(progress).unwrap());
+            Ok(())
+        })
+        .unwrap();
+
+        assert_eq!(calls.len(), 4);
+        assert_eq!(calls[1], r#"{"item_id":"broken.json","index":1,"total":4,"ok":false}"#);
+
+        let aborted = convert_items(&items(), "csv", "{}", |_| Err("cancelled".to_string()));
+        assert_eq!(aborted.err().as_deref(), Some("cancelled"));
+    }
+}
@@ -0,0 +1,253 @@
+//! Output as bytes, for handing results out of a Web Worker without copying.
+//!
+//! A string returned from WASM is decoded into a JS string, and
+//! `postMessage` then structurally clones it again. Bytes written into a
+//! `Uint8Array` can instead be transferred: `postMessage(buf, [buf.buffer])`
+//! moves the underlying `ArrayBuffer` to the other thread for free. The
+//! output is still produced as one Rust string first (chatpack's formatters
+//! return `String`); the only copy out of WASM memory is the final write.
+
+use wasm_bindgen::prelude::*;
+
+use chatpack::Message;
+use serde_json::Value;
+
+use crate::{
+    enrich, parse_format, parse_options, prepare_messages, run_conversion, urls, CodedError,
+    ConvertOptions, CONVERSION_FAILED,
+};
+
+/// Code for `convert_into` targets that cannot hold the output.
+const TARGET_TOO_SMALL: &str = "TARGET_TOO_SMALL";
+
+/// Allowance per message for its braces, line breaks and row delimiters.
+const RECORD_OVERHEAD: usize = 16;
+/// Allowance per field for its indentation, quoted key, colon and comma
+/// (pretty JSON) or delimiter (CSV), on top of the key itself.
+const FIELD_OVERHEAD: usize = 12;
+/// Indentation pretty JSON adds to each line of a nested value.
+const NESTED_INDENT: usize = 4;
+/// Longest written timestamp: quoted RFC 3339 with nanoseconds and an offset.
+const TIMESTAMP_LEN: usize = 37;
+/// Longest written id (`u64::MAX`).
+const ID_LEN: usize = 20;
+/// Allowance for headers, brackets and the trailing newline.
+const DOCUMENT_OVERHEAD: usize = 256;
+
+/// Convert chat export and return the output as UTF-8 bytes in a new `Uint8Array`.
+///
+/// The array owns its `ArrayBuffer`, so it can be transferred out of a
+/// worker. Errors are JSON `{code, message}`.
+#[wasm_bindgen]
+pub fn convert_to_bytes(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<Vec<u8>, JsValue> {
+    convert_output(input, source, format, options_json)
+        .map(String::into_bytes)
+        .map_err(CodedError::into_js)
+}
+
+/// Convert chat export and write the UTF-8 output to the start of `target`.
+///
+/// Returns the number of bytes written; the rest of `target` is left
+/// untouched. If `target` is too small nothing is written and the error is
+/// JSON `{code: "TARGET_TOO_SMALL", message, required_bytes}`, so the caller
+/// can allocate `required_bytes` and retry. Other errors are JSON
+/// `{code, message}`.
+#[wasm_bindgen]
+pub fn convert_into(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+    target: &js_sys::Uint8Array,
+) -> std::result::Result<usize, JsValue> {
+    let output =
+        convert_output(input, source, format, options_json).map_err(CodedError::into_js)?;
+    check_capacity(output.len(), target.length() as usize).map_err(CodedError::into_js)?;
+    target.subarray(0, output.len() as u32).copy_from(output.as_bytes());
+
+    Ok(output.len())
+}
+
+/// Estimate how many bytes `convert_into` needs for this conversion.
+///
+/// Parses and filters the input but does not format it, so it is cheaper
+/// than a conversion. The estimate is an upper bound: it counts every field
+/// the options add (timestamps, ids, replies, edits) and the values of every
+/// extra column (event actions, threads, pins, URLs, sentiment and the other
+/// option columns) at their longest, with every special character escaped.
+/// Allocate it as is; should it still fall short, `convert_into` reports the
+/// exact size.
+#[wasm_bindgen]
+pub fn required_output_size(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<usize, JsValue> {
+    let options = parse_options(options_json).map_err(js_failed)?;
+    parse_format(format).map_err(js_failed)?;
+    let prepared = prepare_messages(input, source, &options).map_err(js_failed)?;
+    let messages = &prepared.messages;
+    let mut columns = prepared.columns(messages, 0..messages.len(), &options);
+    columns.extend(enrich::extra_columns(messages, &options).map_err(js_failed)?);
+    if options.urls == urls::UrlMode::Extract {
+        columns.push(urls::extract(messages).1);
+    }
+
+    let headers: usize = columns.iter().map(|column| column.header.len() + 1).sum();
+    let size = messages.iter().enumerate().fold(DOCUMENT_OVERHEAD + headers, |size, (i, m)| {
+        let values = columns.iter().map(|column| {
+            column.values.get(i).map_or(0, |value| field_len(&column.name, value_len(value)))
+        });
+        size + record_len(m, &options) + values.sum::<usize>()
+    });
+
+    Ok(size)
+}
+
+/// Upper bound on the length of `message`'s own fields, as `options` select them.
+fn record_len(message: &Message, options: &ConvertOptions) -> usize {
+    let optional = [
+        (options.include_timestamps, "timestamp", TIMESTAMP_LEN),
+        (options.include_ids, "id", ID_LEN),
+        (options.include_replies, "reply_to", ID_LEN),
+        (options.include_edited, "edited", TIMESTAMP_LEN),
+    ];
+
+    RECORD_OVERHEAD
+        + field_len("sender", escaped_len(&message.sender))
+        + field_len("content", escaped_len(&message.content))
+        + optional
+            .iter()
+            .filter(|(on, ..)| *on)
+            .map(|(_, key, len)| field_len(key, *len))
+            .sum::<usize>()
+}
+
+fn field_len(key: &str, value_len: usize) -> usize {
+    FIELD_OVERHEAD + key.len() + value_len
+}
+
+/// Upper bound on the length of an extra column's value in any format: as
+/// pretty JSON nested one level deep, or as an escaped CSV cell.
+fn value_len(value: &Value) -> usize {
+    let json = serde_json::to_string_pretty(value).unwrap_or_default();
+    let lines = json.matches('\n').count();
+
+    escaped_len(&json) + lines * NESTED_INDENT
+}
+
+fn convert_output(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<String, CodedError> {
+    let failed = |message| CodedError::new(CONVERSION_FAILED, message);
+    let options = parse_options(options_json).map_err(failed)?;
+
+    run_conversion(input, source, format, &options).map(|report| report.output).map_err(failed)
+}
+
+fn check_capacity(required: usize, capacity: usize) -> std::result::Result<(), CodedError> {
+    if required <= capacity {
+        return Ok(());
+    }
+
+    Err(CodedError {
+        required_bytes: Some(required),
+        ..CodedError::new(
+            TARGET_TOO_SMALL,
+            format!("Output needs {required} bytes but the target holds {capacity}"),
+        )
+    })
+}
+
+/// Upper bound on the length of `text` once quoted for CSV or escaped for JSON.
+fn escaped_len(text: &str) -> usize {
+    // `\u00XX` is the longest escape: six bytes for one input byte.
+    let specials = text.bytes().filter(|b| matches!(b, b'"' | b'\\' | b';') || *b < 0x20).count();
+    text.len() + specials * 5 + 2
+}
+
+fn js_failed(message: String) -> JsValue {
+    CodedError::new(CONVERSION_FAILED, message).into_js()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TELEGRAM: &str = r#"{
+  "messages": [
+    {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Alice", "text": "Quote \"this\"; and\nthat"},
+    {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Bob", "text": "Привет\t👋", "reply_to_message_id": 1}
+  ]
+}"#;
+
+    #[test]
+    fn test_estimate_covers_output() {
+        let options = r#"{"include_timestamps":true,"include_ids":true,"include_replies":true,"include_edited":true,"merge_consecutive":false}"#;
+
+        for format in ["csv", "json", "jsonl"] {
+            let output = convert_output(TELEGRAM, "telegram", format, options)
+                .map_err(|e| e.message)
+                .unwrap();
+            let estimate = required_output_size(TELEGRAM, "telegram", format, options)
+                .map_err(|e| e.as_string().unwrap_or_default())
+                .unwrap();
+
+            assert!(estimate >= output.len(), "{format}: {estimate} < {}", output.len());
+        }
+    }
+
+    #[test]
+    fn test_estimate_covers_every_column() {
+        let input = r#"{
+  "messages": [
+    {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Alice", "text": "See https://example.com/a?q=\"x\"&b=1 and https://example.org", "edited_unixtime": "1705314700"},
+    {"id": 2, "type": "service", "date_unixtime": "1705314620", "actor": "Alice", "action": "pin_message", "message_id": 1, "text": ""},
+    {"id": 3, "type": "message", "date_unixtime": "1705314660", "from": "Bob", "text": "Привет\t👋; \"ok\"", "reply_to_message_id": 1}
+  ]
+}"#;
+        let mut options = serde_json::json!({
+            "include_timestamps": true, "include_ids": true, "include_replies": true,
+            "include_edited": true, "include_events": true, "include_pinned": true,
+            "urls": "extract", "self_sender": "Alice", "source_timezone": "+05:30",
+            "merge_consecutive": false,
+        });
+        if cfg!(feature = "sentiment") {
+            options["sentiment"] = true.into();
+        }
+        if cfg!(feature = "language") {
+            options["detect_language"] = true.into();
+        }
+        let options = options.to_string();
+
+        for format in ["csv", "json", "jsonl"] {
+            let output =
+                convert_output(input, "telegram", format, &options).map_err(|e| e.message).unwrap();
+            let estimate = required_output_size(input, "telegram", format, &options)
+                .map_err(|e| e.as_string().unwrap_or_default())
+                .unwrap();
+
+            assert!(output.contains("pinned") || output.contains("Pinned"), "{output}");
+            assert!(estimate >= output.len(), "{format}: {estimate} < {}", output.len());
+        }
+    }
+
+    #[test]
+    fn test_small_target_reports_required_size() {
+        let error = check_capacity(120, 64).unwrap_err();
+        let json = serde_json::to_value(&error).unwrap();
+
+        assert!(check_capacity(64, 64).is_ok());
+        assert_eq!(json["code"], TARGET_TOO_SMALL);
+        assert_eq!(json["required_bytes"], 120);
+    }
+}
@@ -0,0 +1,548 @@
+//! Call history: structured records, a spreadsheet, or an iCalendar file.
+//!
+//! Calls are the `phone_call` (voice) and `group_call` (video chat) service
+//! events of a Telegram export (see `events`), WhatsApp messages such as
+//! "Missed voice call" or "Video call, 23 min" in any locale of
+//! `whatsapp::CALL_LINES`, and DiscordChatExporter messages of type `Call`.
+//! Discord's export records when a call ended but not who joined, so its
+//! calls count as missed only when they have no length.
+//!
+//! For `export_calls_ics` each dated call becomes a `VEVENT` starting at
+//! the call's time, lasting its duration where the export records one, with
+//! the caller (and, in a Telegram personal chat, the other side) listed as
+//! participants.
+//!
+//! In conversions calls are of the `call` kind: `exclude_kinds: ["call"]`
+//! leaves out WhatsApp call messages, Discord `Call` messages and, with
+//! `include_events`, Telegram call events.
+
+// Each platform's calls are read with part of the records and helpers below;
+// builds without all three leave the rest unused.
+#![cfg_attr(
+    not(all(feature = "telegram", feature = "whatsapp", feature = "discord")),
+    allow(dead_code, unused_variables)
+)]
+
+#[cfg(feature = "discord")]
+use std::collections::HashSet;
+
+#[cfg(feature = "whatsapp")]
+use chatpack::parser::create_parser;
+#[cfg(any(feature = "telegram", feature = "whatsapp", feature = "discord"))]
+use chatpack::parser::Platform;
+#[cfg(any(feature = "whatsapp", feature = "discord"))]
+use chatpack::Message;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::csv_cells::formula_safe;
+#[cfg(feature = "telegram")]
+use crate::events;
+use crate::registry::{Backend, PlatformSpec};
+#[cfg(feature = "whatsapp")]
+use crate::whatsapp;
+use crate::{js_error, registry};
+
+const CSV_DELIMITER: u8 = b';';
+
+/// Longest content line of an iCalendar file, in octets (RFC 5545, 3.1).
+const ICS_LINE_OCTETS: usize = 75;
+
+/// Event kinds that are calls.
+pub(crate) const CALL_KINDS: &[&str] = &["phone_call", "group_call"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CallKind {
+    Voice,
+    Video,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CallStatus {
+    Missed,
+    Completed,
+}
+
+#[derive(Debug, Serialize)]
+struct CallRecord {
+    /// `YYYY-MM-DD HH:MM:SS`, UTC (local time on WhatsApp).
+    timestamp: Option<String>,
+    caller: Option<String>,
+    kind: CallKind,
+    status: CallStatus,
+    duration_seconds: Option<u64>,
+    #[serde(skip)]
+    time: Option<DateTime<Utc>>,
+    /// Message id, where the export numbers calls.
+    #[serde(skip)]
+    id: Option<u64>,
+}
+
+impl CallRecord {
+    fn new(
+        time: Option<DateTime<Utc>>,
+        caller: Option<String>,
+        kind: CallKind,
+        status: CallStatus,
+        duration_seconds: Option<u64>,
+    ) -> Self {
+        let timestamp = time.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string());
+        Self { timestamp, caller, kind, status, duration_seconds, time, id: None }
+    }
+
+    fn with_id(self, id: Option<u64>) -> Self {
+        Self { id, ..self }
+    }
+}
+
+#[cfg(feature = "discord")]
+#[derive(Debug, Deserialize)]
+struct DiscordExport {
+    #[serde(default)]
+    messages: Vec<DiscordMessage>,
+}
+
+#[cfg(feature = "discord")]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct DiscordMessage {
+    id: Option<String>,
+    #[serde(rename = "type")]
+    kind: String,
+    timestamp: Option<String>,
+    call_ended_timestamp: Option<String>,
+    author: DiscordAuthor,
+}
+
+#[cfg(feature = "discord")]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DiscordAuthor {
+    name: Option<String>,
+    nickname: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ChatHeader {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    chat_type: Option<String>,
+}
+
+/// Calls of an export as structured records.
+///
+/// Returns a JSON array of `{timestamp, caller, kind, status,
+/// duration_seconds}` in export order: `kind` is `"voice"` or `"video"`,
+/// `status` `"missed"` or `"completed"`, `timestamp` is `YYYY-MM-DD
+/// HH:MM:SS` (UTC; local time on WhatsApp) and `duration_seconds` is `null`
+/// where the export does not record a length. Platforms without calls
+/// return `[]`; an export that does not parse is an error.
+#[wasm_bindgen]
+pub fn calls(input: &str, source: &str) -> std::result::Result<String, JsValue> {
+    let backend = registry::find_platform(source).map_err(js_error)?.backend;
+    let records = call_records(input, backend).map_err(js_error)?;
+
+    serde_json::to_string(&records).map_err(|e| js_error(e.to_string()))
+}
+
+/// Calls of an export as a spreadsheet.
+///
+/// `;`-delimited CSV with a `Timestamp;Caller;Kind;Status;DurationSeconds`
+/// header and one row per call, holding the fields of `calls`; unknown
+/// values are empty cells.
+#[wasm_bindgen]
+pub fn export_calls_csv(input: &str, source: &str) -> std::result::Result<String, JsValue> {
+    let backend = registry::find_platform(source).map_err(js_error)?.backend;
+
+    call_records(input, backend).and_then(|records| calls_csv(&records)).map_err(js_error)
+}
+
+/// Calls of an export as an iCalendar document.
+///
+/// One `VEVENT` per call of `calls` that has a time, with `DTSTART` (UTC;
+/// floating local time on WhatsApp), `DURATION` when known, a summary such
+/// as "Call started by Alice" or "Missed video call from Bob" and the
+/// participants in the description. Lines end in CRLF and are folded at 75
+/// octets as RFC 5545 requires. Exports without calls give a calendar with
+/// no events; an export that does not parse is an error.
+#[wasm_bindgen]
+pub fn export_calls_ics(input: &str, source: &str) -> std::result::Result<String, JsValue> {
+    let platform = registry::find_platform(source).map_err(js_error)?;
+    let header: ChatHeader = serde_json::from_str(input).unwrap_or_default();
+    let peer = header.name.filter(|_| header.chat_type.as_deref() == Some("personal_chat"));
+    let records = call_records(input, platform.backend).map_err(js_error)?;
+
+    Ok(calendar(&records, platform, peer.as_deref()))
+}
+
+fn call_records(input: &str, backend: Backend) -> std::result::Result<Vec<CallRecord>, String> {
+    let records = match backend {
+        #[cfg(feature = "telegram")]
+        Backend::Core(Platform::Telegram) => events::telegram_service_events(input)?
+            .into_iter()
+            .filter(|event| CALL_KINDS.contains(&event.kind.as_str()))
+            .map(|event| {
+                let kind =
+                    if event.kind == "group_call" { CallKind::Video } else { CallKind::Voice };
+                let status = if event.description == "missed call" {
+                    CallStatus::Missed
+                } else {
+                    CallStatus::Completed
+                };
+                CallRecord::new(event.time, event.actor, kind, status, event.duration_seconds)
+                    .with_id(event.id)
+            })
+            .collect(),
+        #[cfg(feature = "whatsapp")]
+        Backend::Core(Platform::WhatsApp) => {
+            let messages = create_parser(Platform::WhatsApp)
+                .parse_str(input)
+                .map_err(|e| format!("Failed to parse WhatsApp export: {e}"))?;
+            messages
+                .into_iter()
+                .filter_map(|message| {
+                    let (kind, status, seconds) = whatsapp::call(&message.content)?;
+                    Some(CallRecord::new(
+                        message.timestamp,
+                        Some(message.sender),
+                        kind,
+                        status,
+                        seconds,
+                    ))
+                })
+                .collect()
+        }
+        #[cfg(feature = "discord")]
+        Backend::Core(Platform::Discord) => discord_calls(input)?,
+        _ => Vec::new(),
+    };
+
+    Ok(records)
+}
+
+#[cfg(feature = "discord")]
+fn discord_export(input: &str) -> std::result::Result<DiscordExport, String> {
+    serde_json::from_str(input).map_err(|e| format!("Failed to parse Discord export: {e}"))
+}
+
+#[cfg(feature = "discord")]
+fn discord_calls(input: &str) -> std::result::Result<Vec<CallRecord>, String> {
+    let export = discord_export(input)?;
+    let time = |value: Option<&str>| {
+        DateTime::parse_from_rfc3339(value?).ok().map(|time| time.with_timezone(&Utc))
+    };
+
+    Ok(export
+        .messages
+        .into_iter()
+        .filter(|message| message.kind == "Call")
+        .map(|message| {
+            let start = time(message.timestamp.as_deref());
+            let end = time(message.call_ended_timestamp.as_deref());
+            let seconds = start
+                .zip(end)
+                .and_then(|(start, end)| u64::try_from((end - start).num_seconds()).ok())
+                .filter(|seconds| *seconds > 0);
+            let status = if seconds.is_some() { CallStatus::Completed } else { CallStatus::Missed };
+            let author = message.author;
+            let caller = author.nickname.filter(|n| !n.trim().is_empty()).or(author.name);
+            let id = message.id.and_then(|id| id.parse().ok());
+            CallRecord::new(start, caller, CallKind::Voice, status, seconds).with_id(id)
+        })
+        .collect())
+}
+
+fn calls_csv(records: &[CallRecord]) -> std::result::Result<String, String> {
+    let mut writer = csv::WriterBuilder::new().delimiter(CSV_DELIMITER).from_writer(Vec::new());
+    writer
+        .write_record(["Timestamp", "Caller", "Kind", "Status", "DurationSeconds"])
+        .map_err(|e| e.to_string())?;
+    for record in records {
+        let kind = match record.kind {
+            CallKind::Voice => "voice",
+            CallKind::Video => "video",
+        };
+        let status = match record.status {
+            CallStatus::Missed => "missed",
+            CallStatus::Completed => "completed",
+        };
+        writer
+            .write_record([
+                record.timestamp.as_deref().unwrap_or_default(),
+                &formula_safe(record.caller.as_deref().unwrap_or_default(), true),
+                kind,
+                status,
+                &record.duration_seconds.map(|s| s.to_string()).unwrap_or_default(),
+            ])
+            .map_err(|e| e.to_string())?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+/// Drop call messages, for `exclude_kinds: ["call"]`: WhatsApp's by their
+/// text, Discord's by the ids of the export's `Call` messages.
+#[cfg(any(feature = "whatsapp", feature = "discord"))]
+pub(crate) fn drop_calls(messages: &mut Vec<Message>, input: &str, backend: Backend) {
+    match backend {
+        #[cfg(feature = "whatsapp")]
+        Backend::Core(Platform::WhatsApp) => {
+            messages.retain(|message| whatsapp::call(&message.content).is_none());
+        }
+        #[cfg(feature = "discord")]
+        Backend::Core(Platform::Discord) => {
+            let calls: HashSet<u64> = discord_export(input)
+                .map(|export| export.messages)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|message| message.kind == "Call")
+                .filter_map(|message| message.id?.parse().ok())
+                .collect();
+            messages.retain(|message| message.id.is_none_or(|id| !calls.contains(&id)));
+        }
+        _ => {}
+    }
+}
+
+fn calendar(calls: &[CallRecord], platform: &PlatformSpec, peer: Option<&str>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:-//chatpack//chatpack-wasm {}//EN", env!("CARGO_PKG_VERSION")),
+    ];
+    for (index, call) in calls.iter().enumerate() {
+        let Some(time) = call.time else {
+            continue;
+        };
+        let start = ics_time(time, platform.local_time);
+        let mut participants: Vec<&str> = call.caller.as_deref().into_iter().collect();
+        participants.extend(peer.filter(|peer| !participants.contains(peer)));
+
+        lines.push("BEGIN:VEVENT".to_string());
+        match call.id {
+            Some(id) => lines.push(format!("UID:{}-{id}@chatpack", platform.id)),
+            None => lines.push(format!("UID:{}-{start}-{index}@chatpack", platform.id)),
+        }
+        lines.push(format!("DTSTAMP:{}", ics_time(time, false)));
+        lines.push(format!("DTSTART:{start}"));
+        if let Some(seconds) = call.duration_seconds {
+            lines.push(format!("DURATION:PT{seconds}S"));
+        }
+        lines.push(format!("SUMMARY:{}", escape(&summary(call))));
+        if !participants.is_empty() {
+            lines.push(format!(
+                "DESCRIPTION:{}",
+                escape(&format!("Participants: {}", participants.join(", ")))
+            ));
+        }
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut ics: String = lines.iter().map(|line| fold(line)).collect::<Vec<_>>().join("\r\n");
+    ics.push_str("\r\n");
+    ics
+}
+
+/// "Call started by Alice", "Missed video call from Bob", ...
+fn summary(call: &CallRecord) -> String {
+    let what = match call.kind {
+        CallKind::Voice => "call",
+        CallKind::Video => "video call",
+    };
+    match (call.status, call.caller.as_deref()) {
+        (CallStatus::Missed, Some(caller)) => format!("Missed {what} from {caller}"),
+        (CallStatus::Missed, None) => format!("Missed {what}"),
+        (CallStatus::Completed, caller) => {
+            let mut summary = what.to_string();
+            summary.replace_range(..1, &what[..1].to_uppercase());
+            match caller {
+                Some(caller) => format!("{summary} started by {caller}"),
+                None => summary,
+            }
+        }
+    }
+}
+
+/// `time` as an iCalendar DATE-TIME: UTC, or floating (no zone) for
+/// exports that store wall-clock time.
+fn ics_time(time: DateTime<Utc>, local: bool) -> String {
+    let format = if local { "%Y%m%dT%H%M%S" } else { "%Y%m%dT%H%M%SZ" };
+    time.format(format).to_string()
+}
+
+/// Fold `line` into CRLF-separated pieces of at most 75 octets, each after
+/// the first starting with a space (RFC 5545, 3.1). Never splits a UTF-8
+/// character.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / ICS_LINE_OCTETS * 3);
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > ICS_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+
+    folded
+}
+
+/// Escape a TEXT value (RFC 5545, 3.3.11).
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+#[cfg(all(test, feature = "telegram", feature = "whatsapp", feature = "discord"))]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../fixtures/telegram_group_events.json");
+    const WHATSAPP: &str = "[15.01.24, 10:30:00] Alice: Call me?\n\
+[15.01.24, 10:31:00] Bob: \u{200e}Missed voice call\n\
+[15.01.24, 10:32:00] Alice: Video call, \u{200e}23 min\n\
+[15.01.24, 10:40:00] Bob: Voice call, 1 hr 5 min\n\
+[15.01.24, 11:45:00] Alice: Thanks\n";
+
+    fn ics(input: &str, source: &str) -> String {
+        export_calls_ics(input, source).map_err(|e| e.as_string().unwrap_or_default()).unwrap()
+    }
+
+    #[test]
+    fn test_calls_become_events() {
+        let ics = ics(FIXTURE, "telegram");
+        let lines: Vec<&str> = ics.split("\r\n").collect();
+
+        assert_eq!(lines.iter().filter(|l| **l == "BEGIN:VEVENT").count(), 2);
+        assert!(lines.contains(&"DTSTART:20240110T200000Z"));
+        assert!(lines.contains(&"DURATION:PT332S"));
+        assert!(lines.contains(&"SUMMARY:Video call started by Alice"));
+        assert!(lines.contains(&"SUMMARY:Call started by Bob"));
+        assert!(lines.contains(&"DURATION:PT95S"));
+        assert!(lines.contains(&"DESCRIPTION:Participants: Bob"));
+    }
+
+    #[test]
+    fn test_call_records_per_platform() {
+        let records = |input: &str, source: &str| {
+            let json = calls(input, source).map_err(|e| e.as_string().unwrap_or_default());
+            serde_json::from_str::<serde_json::Value>(&json.unwrap()).unwrap()
+        };
+
+        let telegram = records(FIXTURE, "telegram");
+        assert_eq!(telegram.as_array().unwrap().len(), 2);
+        assert_eq!(telegram[0]["kind"], "video");
+        assert_eq!(telegram[1]["caller"], "Bob");
+        assert_eq!(telegram[1]["status"], "completed");
+        assert_eq!(telegram[1]["duration_seconds"], 95);
+
+        let whatsapp = records(WHATSAPP, "whatsapp");
+        assert_eq!(whatsapp.as_array().unwrap().len(), 3);
+        assert_eq!(
+            whatsapp[0],
+            serde_json::json!({
+                "timestamp": "2024-01-15 10:31:00",
+                "caller": "Bob",
+                "kind": "voice",
+                "status": "missed",
+                "duration_seconds": null,
+            })
+        );
+        assert_eq!(whatsapp[2]["duration_seconds"], 3900);
+
+        let discord = serde_json::json!({"messages": [
+            {"id": "1", "type": "Call", "timestamp": "2024-01-15T10:00:00+00:00",
+             "callEndedTimestamp": "2024-01-15T10:23:00+00:00", "content": "",
+             "author": {"name": "ann"}},
+            {"id": "2", "type": "Default", "timestamp": "2024-01-15T10:30:00+00:00",
+             "content": "hi", "author": {"name": "ann"}},
+        ]});
+        let discord = records(&discord.to_string(), "discord");
+        assert_eq!(discord.as_array().unwrap().len(), 1);
+        assert_eq!(discord[0]["duration_seconds"], 1380);
+    }
+
+    #[test]
+    fn test_calls_csv_and_kind_filter() {
+        let csv = export_calls_csv(WHATSAPP, "whatsapp").ok().unwrap();
+        assert_eq!(
+            csv,
+            "Timestamp;Caller;Kind;Status;DurationSeconds\n\
+             2024-01-15 10:31:00;Bob;voice;missed;\n\
+             2024-01-15 10:32:00;Alice;video;completed;1380\n\
+             2024-01-15 10:40:00;Bob;voice;completed;3900\n"
+        );
+
+        let contents = |input: &str, source: &str, options: &str| {
+            let options = crate::parse_options(options).unwrap();
+            let prepared = crate::prepare_messages(input, source, &options).unwrap();
+            prepared.messages.into_iter().map(|m| m.content).collect::<Vec<_>>()
+        };
+        let excluded = r#"{"merge_consecutive":false,"exclude_kinds":["call"]}"#;
+        assert_eq!(contents(WHATSAPP, "whatsapp", r#"{"merge_consecutive":false}"#).len(), 5);
+        assert_eq!(contents(WHATSAPP, "whatsapp", excluded), ["Call me?", "Thanks"]);
+        let events =
+            r#"{"merge_consecutive":false,"include_events":true,"exclude_kinds":["call"]}"#;
+        assert!(!contents(FIXTURE, "telegram", events).iter().any(|c| c.contains("call")));
+
+        let discord = serde_json::json!({"messages": [
+            {"id": "1", "type": "Call", "timestamp": "2024-01-15T10:00:00+00:00",
+             "callEndedTimestamp": "2024-01-15T10:23:00+00:00",
+             "content": "Started a call that lasted 23 minutes.", "author": {"name": "ann"}},
+            {"id": "2", "type": "Default", "timestamp": "2024-01-15T10:30:00+00:00",
+             "content": "hi", "author": {"name": "bob"}},
+        ]})
+        .to_string();
+        assert_eq!(contents(&discord, "discord", r#"{"merge_consecutive":false}"#).len(), 2);
+        assert_eq!(contents(&discord, "discord", excluded), ["hi"]);
+    }
+
+    #[test]
+    fn test_unparsable_export_is_an_error() {
+        for platform in [Platform::Telegram, Platform::WhatsApp, Platform::Discord] {
+            let error = call_records("{not an export", Backend::Core(platform)).unwrap_err();
+            assert!(error.starts_with("Failed to parse"), "{platform:?}: {error}");
+        }
+    }
+
+    #[test]
+    fn test_calendar_covers_every_platform_and_folds_long_lines() {
+        let caller = "Bartholomew Alexander Montgomery-Fitzwilliam, Esq., of the Ünïcödé Society";
+        let whatsapp = ics(&WHATSAPP.replace("Bob:", &format!("{caller}:")), "whatsapp");
+        let lines: Vec<&str> = whatsapp.split("\r\n").collect();
+
+        assert_eq!(lines.iter().filter(|l| **l == "BEGIN:VEVENT").count(), 3);
+        assert!(lines.contains(&"DTSTART:20240115T103100"));
+        assert!(lines.contains(&"DURATION:PT1380S"));
+        assert!(lines.iter().all(|line| line.len() <= 75), "{whatsapp}");
+        let unfolded = whatsapp.replace("\r\n ", "");
+        assert!(unfolded.contains(&format!("SUMMARY:Missed call from {}\r\n", escape(caller))));
+
+        let discord = serde_json::json!({"messages": [
+            {"id": "7", "type": "Call", "timestamp": "2024-01-15T10:00:00+00:00",
+             "callEndedTimestamp": "2024-01-15T10:23:00+00:00", "content": "",
+             "author": {"name": "ann"}},
+        ]});
+        let discord = ics(&discord.to_string(), "discord");
+        assert!(discord.contains("UID:discord-7@chatpack\r\n"));
+        assert!(discord.contains("DTSTART:20240115T100000Z\r\nDURATION:PT1380S\r\n"));
+        assert!(discord.contains("SUMMARY:Call started by ann\r\n"));
+        assert_eq!(fold(&"é".repeat(40)), format!("{}\r\n {}", "é".repeat(37), "é".repeat(3)));
+    }
+
+    #[test]
+    fn test_no_calls_give_empty_calendar() {
+        let ics = ics("[15.01.24, 10:30:00] Alice: hi\n", "whatsapp");
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(!ics.contains("VEVENT"));
+        assert_eq!(escape("a,b;c\nd"), "a\\,b\\;c\\nd");
+    }
+}
@@ -0,0 +1,170 @@
+//! Telegram full-account exports, which hold every chat in one `result.json`.
+//!
+//! Chats live under `chats.list` (and `left_chats.list` for chats the user
+//! left). Each entry has the same shape as a single-chat export, so a chosen
+//! chat is converted by handing just that entry to the Telegram parser.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::{js_error, parse_options, run_conversion};
+
+#[derive(Debug, Deserialize)]
+struct ChatEntry {
+    id: i64,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(rename = "type", default)]
+    chat_type: Option<String>,
+    #[serde(default)]
+    messages: Vec<MessageKind>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageKind {
+    #[serde(rename = "type", default)]
+    kind: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ChatList {
+    list: Vec<ChatEntry>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AccountExport {
+    chats: ChatList,
+    left_chats: ChatList,
+}
+
+#[derive(Debug, Serialize)]
+struct ChatSummary {
+    id: i64,
+    name: Option<String>,
+    #[serde(rename = "type")]
+    chat_type: Option<String>,
+    message_count: usize,
+}
+
+/// List the chats in a Telegram export.
+///
+/// Returns a JSON array of `{id, name, type, message_count}`, where
+/// `message_count` excludes service messages. A single-chat export yields
+/// one entry, so callers can use this without knowing the export kind.
+#[wasm_bindgen]
+pub fn list_chats(input: &str) -> std::result::Result<String, JsValue> {
+    let chats = read_chats(input).map_err(js_error)?;
+    let summaries: Vec<ChatSummary> = chats
+        .into_iter()
+        .map(|chat| ChatSummary {
+            id: chat.id,
+            name: chat.name,
+            chat_type: chat.chat_type,
+            message_count: chat
+                .messages
+                .iter()
+                .filter(|m| m.kind.as_deref().unwrap_or("message") == "message")
+                .count(),
+        })
+        .collect();
+
+    serde_json::to_string(&summaries).map_err(|e| js_error(e.to_string()))
+}
+
+/// Convert one chat of a Telegram export, selected by the `id` from `list_chats`.
+///
+/// Takes the same `format` and `options_json` as `convert_with_options`.
+#[wasm_bindgen]
+pub fn convert_chat(
+    input: &str,
+    chat_id: f64,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    let options = parse_options(options_json).map_err(js_error)?;
+    let chat = extract_chat(input, chat_id).map_err(js_error)?;
+
+    run_conversion(&chat, "telegram", format, &options)
+        .map(|report| report.output)
+        .map_err(js_error)
+}
+
+fn read_chats(input: &str) -> std::result::Result<Vec<ChatEntry>, String> {
+    let root: Value = serde_json::from_str(input).map_err(|e| format!("Invalid JSON: {e}"))?;
+    if root.get("messages").is_some() {
+        let chat =
+            ChatEntry::deserialize(&root).map_err(|e| format!("Invalid Telegram chat: {e}"))?;
+        return Ok(vec![chat]);
+    }
+
+    let export =
+        AccountExport::deserialize(&root).map_err(|e| format!("Invalid Telegram export: {e}"))?;
+    let chats: Vec<ChatEntry> =
+        export.chats.list.into_iter().chain(export.left_chats.list).collect();
+    if chats.is_empty() {
+        return Err("Invalid Telegram export: no chats found (expected chats.list)".to_string());
+    }
+
+    Ok(chats)
+}
+
+/// Serialize the chat with `chat_id` as a standalone single-chat export.
+fn extract_chat(input: &str, chat_id: f64) -> std::result::Result<String, String> {
+    let mut root: Value = serde_json::from_str(input).map_err(|e| format!("Invalid JSON: {e}"))?;
+    let matches = |chat: &Value| chat["id"].as_f64() == Some(chat_id);
+
+    if root.get("messages").is_some() && matches(&root) {
+        return Ok(input.to_string());
+    }
+
+    let mut available = Vec::new();
+    for key in ["chats", "left_chats"] {
+        let Some(list) = root[key]["list"].as_array_mut() else {
+            continue;
+        };
+        if let Some(position) = list.iter().position(matches) {
+            return serde_json::to_string(&list.swap_remove(position)).map_err(|e| e.to_string());
+        }
+        available.extend(list.iter().filter_map(|chat| chat["id"].as_i64()));
+    }
+
+    let available: Vec<String> = available.iter().map(i64::to_string).collect();
+    Err(format!("Chat not found: {chat_id}. Available ids: {}", available.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../fixtures/telegram_full_export.json");
+
+    fn ok(result: std::result::Result<String, JsValue>) -> String {
+        result.map_err(|e| e.as_string().unwrap_or_default()).expect("call should succeed")
+    }
+
+    #[test]
+    fn test_list_chats() {
+        let chats: Value = serde_json::from_str(&ok(list_chats(FIXTURE))).unwrap();
+
+        assert_eq!(chats.as_array().unwrap().len(), 2);
+        assert_eq!(chats[0]["id"], 4242);
+        assert_eq!(chats[0]["name"], "Bob");
+        assert_eq!(chats[0]["type"], "personal_chat");
+        assert_eq!(chats[1]["message_count"], 2);
+    }
+
+    #[test]
+    fn test_convert_selected_chat() {
+        let output = ok(convert_chat(FIXTURE, 777.0, "jsonl", r#"{"merge_consecutive":false}"#));
+        let single = extract_chat(FIXTURE, 4242.0).unwrap();
+
+        assert_eq!(output.lines().count(), 2);
+        assert!(output.contains("First book: Dune"));
+        assert!(!output.contains("Hi Bob"));
+        assert_eq!(extract_chat(&single, 4242.0).unwrap(), single);
+        assert!(extract_chat(FIXTURE, 1.0).unwrap_err().contains("4242, 777"));
+    }
+}
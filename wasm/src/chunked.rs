@@ -0,0 +1,251 @@
+use serde::Serialize;
+use std::ops::Range;
+use wasm_bindgen::prelude::*;
+
+use crate::tokens::estimate_tokens;
+use crate::{js_error, parse_options, prepare_messages, resolve_format};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Unit {
+    Tokens,
+    Bytes,
+}
+
+impl Unit {
+    fn parse(unit: &str) -> std::result::Result<Self, String> {
+        match unit.trim().to_ascii_lowercase().as_str() {
+            "tokens" => Ok(Self::Tokens),
+            "bytes" => Ok(Self::Bytes),
+            _ => Err(format!("Unknown budget unit: '{unit}'. Expected one of: tokens, bytes")),
+        }
+    }
+
+    fn measure(self, text: &str) -> usize {
+        match self {
+            Self::Tokens => estimate_tokens(text),
+            Self::Bytes => text.len(),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Tokens => "tokens",
+            Self::Bytes => "bytes",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ChunkedOutput {
+    chunks: Vec<String>,
+    warnings: Vec<String>,
+}
+
+/// Convert chat export into chunks that each fit a size budget.
+///
+/// Filters and merging are applied first, then messages are packed into
+/// chunks of at most `budget` `unit`s (`"tokens"`, estimated at roughly
+/// four characters per token, or `"bytes"` of UTF-8), counting the whole
+/// formatted chunk including its own CSV header or JSON brackets. Chunks
+/// are cut between messages, never inside one. With `overlap > 0` each
+/// chunk starts with up to `overlap` messages repeated from the end of the
+/// previous one, fewer if they would not fit next to a new message. A
+/// message that exceeds the budget on its own becomes a chunk by itself
+/// and is reported in `warnings`.
+///
+/// Returns JSON `{chunks, warnings}` where `chunks` is an array of output
+/// strings, each valid in `format` on its own.
+#[wasm_bindgen]
+pub fn convert_chunked(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+    budget: usize,
+    unit: &str,
+    overlap: usize,
+) -> std::result::Result<String, JsValue> {
+    let output = chunk_conversion(input, source, format, options_json, budget, unit, overlap)
+        .map_err(js_error)?;
+
+    serde_json::to_string(&output).map_err(|e| js_error(e.to_string()))
+}
+
+fn chunk_conversion(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+    budget: usize,
+    unit: &str,
+    overlap: usize,
+) -> std::result::Result<ChunkedOutput, String> {
+    if budget == 0 {
+        return Err("Invalid budget: must be at least 1".to_string());
+    }
+    let unit = Unit::parse(unit)?;
+    let mut options = parse_options(options_json)?;
+    let output_format = resolve_format(format, &mut options)?;
+    let prepared = prepare_messages(input, source, &options)?;
+
+    let format = |range| prepared.format_range(range, output_format, &options);
+    Chunker::new(prepared.messages.len(), &format, unit, budget)?.run(overlap)
+}
+
+/// Output for the messages in a range.
+type Formatter<'a> = dyn Fn(Range<usize>) -> std::result::Result<String, String> + 'a;
+
+struct Chunker<'a> {
+    len: usize,
+    format: &'a Formatter<'a>,
+    unit: Unit,
+    budget: usize,
+    /// Size of an empty document (header, brackets).
+    base: usize,
+    /// `prefix[i]` is the estimated size added by messages `0..i`.
+    prefix: Vec<usize>,
+}
+
+impl<'a> Chunker<'a> {
+    fn new(
+        len: usize,
+        format: &'a Formatter<'a>,
+        unit: Unit,
+        budget: usize,
+    ) -> std::result::Result<Self, String> {
+        let base = unit.measure(&format(0..0)?);
+        let mut prefix = Vec::with_capacity(len + 1);
+        prefix.push(0);
+        for index in 0..len {
+            let alone = unit.measure(&format(index..index + 1)?);
+            prefix.push(prefix[prefix.len() - 1] + alone.saturating_sub(base));
+        }
+
+        Ok(Self { len, format, unit, budget, base, prefix })
+    }
+
+    fn estimate(&self, start: usize, end: usize) -> usize {
+        self.base + self.prefix[end] - self.prefix[start]
+    }
+
+    fn run(self, overlap: usize) -> std::result::Result<ChunkedOutput, String> {
+        let mut output = ChunkedOutput { chunks: Vec::new(), warnings: Vec::new() };
+        if self.len == 0 {
+            output.chunks.push((self.format)(0..0)?);
+            return Ok(output);
+        }
+
+        let mut next = 0;
+        while next < self.len {
+            let (start, end, chunk) = self.fit(next, overlap)?;
+            let size = self.unit.measure(&chunk);
+            if size > self.budget {
+                output.warnings.push(format!(
+                    "Message {start} alone is {size} {}, over the budget of {}; it was put in its own chunk",
+                    self.unit.name(),
+                    self.budget
+                ));
+            }
+            output.chunks.push(chunk);
+            next = end;
+        }
+
+        Ok(output)
+    }
+
+    /// Largest chunk containing message `next`, with up to `overlap` messages before it.
+    fn fit(
+        &self,
+        next: usize,
+        overlap: usize,
+    ) -> std::result::Result<(usize, usize, String), String> {
+        let len = self.len;
+        let mut start = next - overlap.min(next);
+        let mut end = next + 1;
+        while start < next && self.estimate(start, end) > self.budget {
+            start += 1;
+        }
+        while end < len && self.estimate(start, end + 1) <= self.budget {
+            end += 1;
+        }
+
+        // The estimate ignores separators between items; correct it exactly.
+        let mut chunk = (self.format)(start..end)?;
+        while self.unit.measure(&chunk) > self.budget && (end > next + 1 || start < next) {
+            if end > next + 1 {
+                end -= 1;
+            } else {
+                start += 1;
+            }
+            chunk = (self.format)(start..end)?;
+        }
+
+        Ok((start, end, chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn telegram(texts: &[&str]) -> String {
+        let messages: Vec<String> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let sender = if i % 2 == 0 { "Alice" } else { "Bob" };
+                format!(
+                    r#"{{"id": {i}, "type": "message", "date_unixtime": "{}", "from": "{sender}", "text": "{text}"}}"#,
+                    1_705_314_600 + i
+                )
+            })
+            .collect();
+        format!(r#"{{"messages": [{}]}}"#, messages.join(","))
+    }
+
+    #[test]
+    fn test_chunks_fit_budget_and_stand_alone() {
+        let input = telegram(&["one", "two", "three", "four", "five", "six", "seven"]);
+
+        for format in ["csv", "json", "jsonl"] {
+            let output = chunk_conversion(&input, "telegram", format, "", 80, "bytes", 0).unwrap();
+            let contents: String = output.chunks.concat();
+
+            assert!(output.chunks.len() > 1, "{format} should be split");
+            assert!(output.chunks.iter().all(|c| c.len() <= 80), "{format} chunk over budget");
+            assert!(output.warnings.is_empty());
+            for text in ["one", "four", "seven"] {
+                assert_eq!(contents.matches(text).count(), 1, "{format}: {text}");
+            }
+            if format == "json" {
+                for chunk in &output.chunks {
+                    serde_json::from_str::<serde_json::Value>(chunk).expect("valid JSON chunk");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_overlap_repeats_previous_messages() {
+        let input = telegram(&["one", "two", "three", "four", "five"]);
+        let output = chunk_conversion(&input, "telegram", "jsonl", "", 110, "bytes", 1).unwrap();
+
+        assert!(output.chunks.len() > 1);
+        for pair in output.chunks.windows(2) {
+            let last = pair[0].lines().last().unwrap();
+            assert_eq!(pair[1].lines().next(), Some(last));
+        }
+    }
+
+    #[test]
+    fn test_oversized_message_gets_own_chunk_with_warning() {
+        let long = "word ".repeat(100);
+        let input = telegram(&["hi", long.trim(), "bye"]);
+        let output = chunk_conversion(&input, "telegram", "csv", "", 30, "tokens", 0).unwrap();
+
+        assert_eq!(output.chunks.len(), 3);
+        assert_eq!(output.warnings.len(), 1);
+        assert!(output.warnings[0].starts_with("Message 1 alone is"));
+        assert!(chunk_conversion(&input, "telegram", "csv", "", 30, "words", 0).is_err());
+    }
+}
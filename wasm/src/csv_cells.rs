@@ -0,0 +1,155 @@
+//! Cell-level clean-up of messages before chatpack writes them as CSV.
+//!
+//! Two independent steps, applied in this order:
+//!
+//! 1. `csv_newlines`: line breaks in content are kept (a multi-line quoted
+//!    cell, valid CSV that many line-based tools still split), escaped as
+//!    the two characters `\n`, or collapsed into a single space.
+//! 2. `escape_formulas`: Excel, LibreOffice and Google Sheets evaluate a
+//!    cell that starts with `=`, `+`, `-` or `@` as a formula, and chat
+//!    content is written by whoever is in the chat. Following the OWASP
+//!    guidance, such cells (and cells starting with a tab or carriage
+//!    return) get a leading `'`, which spreadsheets treat as "text" and do
+//!    not display. The price is that a pasted negative number such as `-5`
+//!    is exported as `'-5` too.
+//!
+//! Formulas are checked last so the test sees the cell as it is written.
+//! The other CSV writers (extra columns, activity and call sheets) pass
+//! their text cells through [`formula_safe`].
+
+use std::borrow::Cow;
+
+use chatpack::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::ConvertOptions;
+
+const FORMULA_TRIGGERS: &[char] = &['=', '+', '-', '@', '\t', '\r'];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CsvNewlines {
+    #[default]
+    Keep,
+    Escape,
+    Space,
+}
+
+/// Copies of `messages` prepared for CSV per `options`, or `None` if no
+/// field needs changing (the common case, which avoids copying every
+/// message). Reply targets are numeric ids and never need it.
+pub(crate) fn prepare_messages(
+    messages: &[Message],
+    options: &ConvertOptions,
+) -> Option<Vec<Message>> {
+    let newlines = options.csv_newlines;
+    let changes = |m: &Message| {
+        (newlines != CsvNewlines::Keep && m.content.contains(['\n', '\r']))
+            || (options.escape_formulas && (is_formula(&m.sender) || is_formula(&m.content)))
+    };
+    if !messages.iter().any(changes) {
+        return None;
+    }
+
+    Some(
+        messages
+            .iter()
+            .map(|message| {
+                let mut message = message.clone();
+                message.content = convert_newlines(&message.content, newlines);
+                if options.escape_formulas {
+                    escape_formula(&mut message.sender);
+                    escape_formula(&mut message.content);
+                }
+                message
+            })
+            .collect(),
+    )
+}
+
+fn convert_newlines(text: &str, newlines: CsvNewlines) -> String {
+    match newlines {
+        CsvNewlines::Keep => text.to_string(),
+        CsvNewlines::Escape => {
+            text.replace("\r\n", "\\n").replace('\n', "\\n").replace('\r', "\\r")
+        }
+        CsvNewlines::Space => {
+            let mut collapsed = String::with_capacity(text.len());
+            let mut in_break = false;
+            for c in text.chars() {
+                let is_break = c == '\n' || c == '\r';
+                if is_break && !in_break {
+                    collapsed.push(' ');
+                } else if !is_break {
+                    collapsed.push(c);
+                }
+                in_break = is_break;
+            }
+            collapsed
+        }
+    }
+}
+
+fn is_formula(cell: &str) -> bool {
+    cell.starts_with(FORMULA_TRIGGERS)
+}
+
+/// `cell`, with a leading `'` if `escape` is set and a spreadsheet would
+/// run it as a formula.
+pub(crate) fn formula_safe(cell: &str, escape: bool) -> Cow<'_, str> {
+    if escape && is_formula(cell) {
+        Cow::Owned(format!("'{cell}"))
+    } else {
+        Cow::Borrowed(cell)
+    }
+}
+
+fn escape_formula(cell: &mut String) {
+    if is_formula(cell) {
+        cell.insert(0, '\'');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prepared(messages: &[Message], options: &str) -> Option<Vec<(String, String)>> {
+        let options = crate::parse_options(options).unwrap();
+        prepare_messages(messages, &options)
+            .map(|messages| messages.into_iter().map(|m| (m.sender, m.content)).collect())
+    }
+
+    #[test]
+    fn test_escape_formulas() {
+        let messages = vec![
+            Message::new("=cmd", "=HYPERLINK(\"http://x\")"),
+            Message::new("Bob", "-5"),
+            Message::new("@carol", "\tindented"),
+            Message::new("Dan", "a = b + c"),
+        ];
+        let fields = prepared(&messages, "{}").expect("some fields need escaping");
+
+        assert_eq!(fields[0], ("'=cmd".into(), "'=HYPERLINK(\"http://x\")".into()));
+        // A negative number is escaped as well, deliberately.
+        assert_eq!(fields[1].1, "'-5");
+        assert_eq!(fields[2], ("'@carol".into(), "'\tindented".into()));
+        assert_eq!(fields[3].1, "a = b + c");
+        assert!(prepared(&messages[3..], "{}").is_none());
+        assert!(prepared(&messages, r#"{"escape_formulas":false}"#).is_none());
+    }
+
+    #[test]
+    fn test_newlines_then_formulas() {
+        let messages =
+            vec![Message::new("Ann", "one\r\ntwo\n\nthree"), Message::new("Bob", "\r=1+1")];
+        let content = |options: &str| -> Vec<String> {
+            prepared(&messages, options).unwrap().into_iter().map(|(_, content)| content).collect()
+        };
+
+        assert_eq!(content("{}"), ["one\r\ntwo\n\nthree", "'\r=1+1"]);
+        assert_eq!(content(r#"{"csv_newlines":"escape"}"#), ["one\\ntwo\\n\\nthree", "\\r=1+1"]);
+        assert_eq!(content(r#"{"csv_newlines":"space"}"#), ["one two three", " =1+1"]);
+        assert!(prepared(&messages[..1], r#"{"csv_newlines":"keep"}"#).is_none());
+    }
+}
@@ -0,0 +1,142 @@
+//! Guessing the source of an export from its content (`source: "auto"`).
+//!
+//! JSON exports are told apart by their top-level keys and the fields of
+//! their first message; text exports by their first lines. Delimited files
+//! with a header row that no platform claims are read as `generic-csv`.
+
+use serde_json::Value;
+
+/// Source name that asks for detection instead of naming a platform.
+pub(crate) const AUTO: &str = "auto";
+
+/// Canonical platform id of `input`.
+pub(crate) fn detect_source(input: &str) -> std::result::Result<&'static str, String> {
+    let input = input.trim_start_matches('\u{feff}').trim_start();
+    let detected = match serde_json::from_str::<Value>(input) {
+        Ok(value) => detect_json(&value).or_else(|| detect_text(input)),
+        Err(_) => detect_text(input),
+    };
+
+    detected.ok_or_else(|| {
+        "Could not detect the source of this export; pass its platform instead of \"auto\""
+            .to_string()
+    })
+}
+
+fn detect_json(value: &Value) -> Option<&'static str> {
+    if let Some(messages) = value.as_array() {
+        let first = messages.first()?;
+        return (first.get("sender").is_some() && first.get("content").is_some())
+            .then_some("chatpack-json");
+    }
+    if value.get("chats").is_some() {
+        return Some("telegram");
+    }
+    if let Some(messages) = value.get("messages").and_then(Value::as_array) {
+        let first = messages.first().unwrap_or(&Value::Null);
+        return Some(if first.get("sender_name").is_some() {
+            "instagram"
+        } else if value.get("guild").is_some() || first.get("author").is_some() {
+            "discord"
+        } else {
+            "telegram"
+        });
+    }
+    if let Some(conversations) = value.get("conversations").and_then(Value::as_array) {
+        let first = conversations.first().unwrap_or(&Value::Null);
+        return Some(if first.get("MessageList").is_some() { "skype" } else { "hangouts" });
+    }
+
+    value.get("value").is_some_and(Value::is_array).then_some("teams")
+}
+
+fn detect_text(input: &str) -> Option<&'static str> {
+    let lines: Vec<&str> = input.lines().filter(|line| !line.trim().is_empty()).take(5).collect();
+    let first = *lines.first()?;
+
+    if first.starts_with("[LINE]") {
+        return Some("line");
+    }
+    if lines.iter().any(|line| line.starts_with("---------------")) {
+        return Some("kakaotalk");
+    }
+    if looks_like_whatsapp(first) {
+        return Some("whatsapp");
+    }
+    if serde_json::from_str::<Value>(first).is_ok_and(|line| line.get("sender").is_some()) {
+        return Some("chatpack-jsonl");
+    }
+
+    let header: Vec<String> = first
+        .split([',', ';', '\t', '|'])
+        .map(|cell| cell.trim().trim_matches('"').to_lowercase())
+        .collect();
+    let has = |names: &[&str]| header.iter().any(|cell| names.contains(&cell.as_str()));
+    if has(&["sender"]) && has(&["content"]) && first.contains(';') {
+        Some("chatpack-csv")
+    } else if has(&["name", "sender"]) && has(&["date"]) && has(&["message", "text"]) {
+        Some("viber")
+    } else {
+        (header.len() > 1).then_some("generic-csv")
+    }
+}
+
+/// `[15.01.24, 10:30:00] ...` (iOS) or `15/01/2024, 10:30 - ...` (Android).
+fn looks_like_whatsapp(line: &str) -> bool {
+    let line = line.trim_start_matches('\u{200e}');
+    let (date, rest) = match line.strip_prefix('[') {
+        Some(rest) => match rest.split_once("] ") {
+            Some(parts) => parts,
+            None => return false,
+        },
+        None => match line.split_once(" - ") {
+            Some(parts) => parts,
+            None => return false,
+        },
+    };
+
+    date.starts_with(|c: char| c.is_ascii_digit()) && date.contains(", ") && !rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixtures_are_detected() {
+        let fixtures = [
+            ("telegram", include_str!("../fixtures/telegram_entities.json")),
+            ("telegram", include_str!("../fixtures/telegram_full_export.json")),
+            ("whatsapp", include_str!("../fixtures/whatsapp_es.txt")),
+            ("viber", include_str!("../fixtures/viber.csv")),
+            ("skype", include_str!("../fixtures/skype.json")),
+            ("line", include_str!("../fixtures/line.txt")),
+            ("kakaotalk", include_str!("../fixtures/kakaotalk.txt")),
+            ("hangouts", include_str!("../fixtures/hangouts.json")),
+            ("teams", include_str!("../fixtures/teams.json")),
+            ("generic-csv", include_str!("../fixtures/generic.csv")),
+        ];
+
+        for (source, input) in fixtures {
+            assert_eq!(detect_source(input), Ok(source));
+        }
+    }
+
+    #[test]
+    fn test_inline_exports_are_detected() {
+        let exports = [
+            ("whatsapp", "15/01/2024, 10:30 - Alice: hi\n"),
+            ("instagram", r#"{"participants": [], "messages": [{"sender_name": "Ann"}]}"#),
+            ("discord", r#"{"guild": {}, "channel": {}, "messages": []}"#),
+            ("chatpack-json", r#"[{"sender": "Ann", "content": "hi"}]"#),
+            ("chatpack-jsonl", "{\"sender\":\"Ann\",\"content\":\"hi\"}\n"),
+            ("chatpack-csv", "Sender;Content\nAnn;hi\n"),
+        ];
+
+        for (source, input) in exports {
+            assert_eq!(detect_source(input), Ok(source));
+        }
+        assert!(detect_source("just some notes").is_err());
+        assert!(detect_source("").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+//! The exchanges between two people in a group chat.
+//!
+//! `extract_dialogue` keeps a message of either participant only when it is
+//! part of a conversation with the other: it replies to the other's
+//! message or is replied to by them, or the other spoke right before or
+//! right after it within `max_gap_minutes`. Everything else is dropped:
+//! other senders' messages, and the participants' messages that address
+//! the group at large.
+
+use std::collections::{HashMap, HashSet};
+
+use chatpack::Message;
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+
+/// Longest pause between two turns of one exchange when `max_gap_minutes` is unset.
+const DEFAULT_MAX_GAP_MINUTES: u64 = 30;
+
+/// The `extract_dialogue` conversion option.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct DialogueOptions {
+    /// The two senders whose exchanges are kept.
+    pub participants: Vec<String>,
+    #[serde(default)]
+    pub max_gap_minutes: Option<u64>,
+}
+
+/// What `extract` dropped.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Excluded {
+    /// Messages of other senders.
+    pub by_sender: usize,
+    /// Participants' messages not linked to the other participant.
+    pub by_heuristic: usize,
+}
+
+pub(crate) fn validate(options: &DialogueOptions) -> std::result::Result<(), String> {
+    let names: HashSet<String> =
+        options.participants.iter().map(|name| name.trim().to_lowercase()).collect();
+    if options.participants.len() != 2 || names.len() != 2 || names.contains("") {
+        return Err(
+            "Invalid extract_dialogue: participants must name two different senders".to_string()
+        );
+    }
+
+    Ok(())
+}
+
+/// Keep the messages of the two participants' exchanges, in order.
+pub(crate) fn extract(
+    messages: Vec<Message>,
+    options: &DialogueOptions,
+) -> (Vec<Message>, Excluded) {
+    let gap = Duration::minutes(
+        options.max_gap_minutes.unwrap_or(DEFAULT_MAX_GAP_MINUTES).try_into().unwrap_or(i64::MAX),
+    );
+    let names: Vec<String> = options.participants.iter().map(|n| n.trim().to_lowercase()).collect();
+    // Index of the participant who sent each message, if either did.
+    let party: Vec<Option<usize>> = messages
+        .iter()
+        .map(|m| names.iter().position(|name| *name == m.sender.trim().to_lowercase()))
+        .collect();
+    let party_by_id: HashMap<u64, usize> =
+        messages.iter().zip(&party).filter_map(|(m, party)| Some((m.id?, (*party)?))).collect();
+    // Ids of messages the other participant replied to.
+    let mut answered: HashSet<(u64, usize)> = HashSet::new();
+    for (message, party) in messages.iter().zip(&party) {
+        if let (Some(target), Some(party)) = (message.reply_to, party) {
+            answered.insert((target, *party));
+        }
+    }
+
+    let close = |a: &Message, b: &Message| match (a.timestamp, b.timestamp) {
+        (Some(a), Some(b)) => (b - a).abs() <= gap,
+        _ => true,
+    };
+    let linked = |index: usize, own: usize| {
+        let message = &messages[index];
+        let other = 1 - own;
+        let replies_to_other =
+            message.reply_to.and_then(|target| party_by_id.get(&target)) == Some(&other);
+        let answered_by_other = message.id.is_some_and(|id| answered.contains(&(id, other)));
+        let adjacent = |neighbour: Option<usize>| {
+            neighbour.is_some_and(|n| party[n] == Some(other) && close(message, &messages[n]))
+        };
+
+        replies_to_other
+            || answered_by_other
+            || adjacent(index.checked_sub(1))
+            || adjacent(Some(index + 1).filter(|&n| n < messages.len()))
+    };
+
+    let mut excluded = Excluded::default();
+    let keep: Vec<bool> = party
+        .iter()
+        .enumerate()
+        .map(|(index, party)| match party {
+            None => {
+                excluded.by_sender += 1;
+                false
+            }
+            Some(own) if linked(index, *own) => true,
+            Some(_) => {
+                excluded.by_heuristic += 1;
+                false
+            }
+        })
+        .collect();
+
+    let kept = messages.into_iter().zip(keep).filter_map(|(m, keep)| keep.then_some(m)).collect();
+    (kept, excluded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_options, prepare_messages};
+
+    const TELEGRAM: &str = r#"{"messages": [
+  {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Alice", "text": "Morning all!"},
+  {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Carol", "text": "Morning"},
+  {"id": 3, "type": "message", "date_unixtime": "1705314720", "from": "Alice", "text": "Bob, did you book it?"},
+  {"id": 4, "type": "message", "date_unixtime": "1705314780", "from": "Bob", "text": "Yes, for six"},
+  {"id": 5, "type": "message", "date_unixtime": "1705314840", "from": "Carol", "text": "Book what?"},
+  {"id": 6, "type": "message", "date_unixtime": "1705318800", "from": "Bob", "text": "Reminder: meeting at 3"},
+  {"id": 7, "type": "message", "date_unixtime": "1705322400", "from": "Alice", "text": "Thanks again", "reply_to_message_id": 4},
+  {"id": 8, "type": "message", "date_unixtime": "1705326000", "from": "Carol", "text": "Bye"}
+]}"#;
+
+    fn run(options: &str) -> (Vec<String>, Vec<String>) {
+        let options = parse_options(options).unwrap();
+        let prepared = prepare_messages(TELEGRAM, "telegram", &options).unwrap();
+        (prepared.messages.into_iter().map(|m| m.content).collect(), prepared.warnings)
+    }
+
+    #[test]
+    fn test_keeps_adjacent_turns_and_replies() {
+        let (contents, warnings) = run(
+            r#"{"merge_consecutive":false,"extract_dialogue":{"participants":["alice","Bob"]}}"#,
+        );
+
+        // Bob's reminder an hour later, with Carol before it, addresses the group.
+        assert_eq!(contents, ["Bob, did you book it?", "Yes, for six", "Thanks again"]);
+        assert!(warnings.iter().any(|w| w.contains("3 from other senders")
+            && w.contains("2 of theirs not addressed to each other")));
+    }
+
+    #[test]
+    fn test_gap_and_validation() {
+        let gap = |minutes: u64| {
+            let messages = vec![
+                Message::new("Ann", "a")
+                    .with_timestamp(chrono::DateTime::from_timestamp(0, 0).unwrap()),
+                Message::new("Ben", "b")
+                    .with_timestamp(chrono::DateTime::from_timestamp(600, 0).unwrap()),
+            ];
+            let options = DialogueOptions {
+                participants: vec!["Ann".into(), "Ben".into()],
+                max_gap_minutes: Some(minutes),
+            };
+            extract(messages, &options)
+        };
+
+        assert_eq!(gap(10).0.len(), 2);
+        assert_eq!(gap(5).1, Excluded { by_sender: 0, by_heuristic: 2 });
+        assert!(parse_options(r#"{"extract_dialogue":{"participants":["Ann"]}}"#).is_err());
+        assert!(parse_options(r#"{"extract_dialogue":{"participants":["Ann","ann "]}}"#).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! Differences between two exports of the same chat.
+
+use std::collections::{HashMap, VecDeque};
+
+use chatpack::Message;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    format_report, js_error, parse_options, process_export, read_export, registry, resolve_format,
+    ConvertOptions, ReadExport,
+};
+
+/// What identifies a message across exports.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    /// Platform message id, stable across edits.
+    Id(u64),
+    /// Without an id, a message is only recognized if nothing about it changed.
+    Content(Option<DateTime<Utc>>, String, String),
+}
+
+impl Key {
+    fn of(message: &Message, use_ids: bool) -> Self {
+        match message.id.filter(|_| use_ids) {
+            Some(id) => Self::Id(id),
+            None => {
+                Self::Content(message.timestamp, message.sender.clone(), message.content.clone())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Change {
+    before: Message,
+    after: Message,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct MessageDiff {
+    added: Vec<Message>,
+    removed: Vec<Message>,
+    changed: Vec<Change>,
+}
+
+/// Compare two exports of the same chat from `source`.
+///
+/// Messages are matched by id where the platform provides ids, otherwise by
+/// timestamp, sender and content. Returns JSON `{added, removed, changed}`:
+/// `added` are only in `new_input`, `removed` only in `old_input` (both in
+/// export order), and `changed` lists `{before, after}` for messages matched
+/// by id whose content or edit time differs. Filters and merging are not
+/// applied, so the lists hold messages exactly as parsed.
+#[wasm_bindgen]
+pub fn diff(
+    old_input: &str,
+    new_input: &str,
+    source: &str,
+) -> std::result::Result<String, JsValue> {
+    let diff = diff_exports(old_input, new_input, source).map_err(js_error)?;
+
+    serde_json::to_string(&diff).map_err(|e| js_error(e.to_string()))
+}
+
+/// Convert only the messages `new_input` added since `old_input`.
+///
+/// Runs `diff` on both exports read with `options_json` (so with
+/// `include_events` new events count as added), then passes the `added`
+/// messages through the usual filters, merging and formatting. Takes the
+/// same `format` and `options_json` as `convert_with_options`.
+#[wasm_bindgen]
+pub fn export_new_only(
+    old_input: &str,
+    new_input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    new_only(old_input, new_input, source, format, options_json).map_err(js_error)
+}
+
+fn new_only(
+    old_input: &str,
+    new_input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<String, String> {
+    let mut options = parse_options(options_json)?;
+    let output_format = resolve_format(format, &mut options)?;
+    let platform = registry::find_platform(source)?;
+    let (old, mut new) = read_both(old_input, new_input, source, &options)?;
+    let messages = std::mem::take(&mut new.messages);
+    new.messages = diff_messages(old.messages, messages, platform.supports_ids).added;
+    let prepared = process_export(new, platform, &options)?;
+
+    format_report(prepared, new_input.len(), output_format, &options).map(|report| report.output)
+}
+
+fn diff_exports(
+    old_input: &str,
+    new_input: &str,
+    source: &str,
+) -> std::result::Result<MessageDiff, String> {
+    let use_ids = registry::find_platform(source)?.supports_ids;
+    let (old, new) = read_both(old_input, new_input, source, &ConvertOptions::default())?;
+
+    Ok(diff_messages(old.messages, new.messages, use_ids))
+}
+
+fn read_both(
+    old_input: &str,
+    new_input: &str,
+    source: &str,
+    options: &ConvertOptions,
+) -> std::result::Result<(ReadExport, ReadExport), String> {
+    let old = read_export(old_input, source, options).map_err(|e| format!("Old export: {e}"))?;
+    let new = read_export(new_input, source, options).map_err(|e| format!("New export: {e}"))?;
+
+    Ok((old, new))
+}
+
+fn diff_messages(old: Vec<Message>, new: Vec<Message>, use_ids: bool) -> MessageDiff {
+    let mut unmatched: HashMap<Key, VecDeque<usize>> = HashMap::new();
+    for (index, message) in old.iter().enumerate() {
+        unmatched.entry(Key::of(message, use_ids)).or_default().push_back(index);
+    }
+
+    let mut diff = MessageDiff::default();
+    let mut matched = vec![false; old.len()];
+    for message in new {
+        let key = Key::of(&message, use_ids);
+        let Some(index) = unmatched.get_mut(&key).and_then(VecDeque::pop_front) else {
+            diff.added.push(message);
+            continue;
+        };
+        matched[index] = true;
+        let before = &old[index];
+        if before.content != message.content || before.edited != message.edited {
+            diff.changed.push(Change { before: before.clone(), after: message });
+        }
+    }
+
+    diff.removed =
+        old.into_iter().zip(matched).filter(|(_, matched)| !matched).map(|(m, _)| m).collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = r#"{"messages": [
+    {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Alice", "text": "Hello"},
+    {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Bob", "text": "Typo"},
+    {"id": 3, "type": "message", "date_unixtime": "1705314720", "from": "Alice", "text": "Gone soon"}
+]}"#;
+
+    const NEW: &str = r#"{"messages": [
+    {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Alice", "text": "Hello"},
+    {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Bob", "text": "Fixed",
+     "edited_unixtime": "1705314700"},
+    {"id": 4, "type": "message", "date_unixtime": "1705314780", "from": "Bob", "text": "New one"}
+]}"#;
+
+    #[test]
+    fn test_diff_by_id() {
+        let diff = diff_exports(OLD, NEW, "telegram").unwrap();
+
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].id, Some(4));
+        assert_eq!(diff.removed.len(), 1);
+        assert_eq!(diff.removed[0].content, "Gone soon");
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].before.content, "Typo");
+        assert_eq!(diff.changed[0].after.content, "Fixed");
+    }
+
+    #[test]
+    fn test_diff_by_content_and_export_new_only() {
+        let old = "[15.01.24, 10:30:00] Alice: one\n[15.01.24, 10:31:00] Bob: two\n";
+        let new =
+            format!("{old}[15.01.24, 10:32:00] Alice: three\n[15.01.24, 10:33:00] Alice: four\n");
+        let diff = diff_exports(old, &new, "whatsapp").unwrap();
+        let csv = new_only(old, &new, "whatsapp", "csv", "").unwrap();
+
+        assert_eq!(diff.added.len(), 2);
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+        assert_eq!(csv, "Sender;Content\nAlice;\"three\nfour\"\n");
+    }
+
+    #[test]
+    fn test_everything_new_matches_sync_conversion() {
+        let input = include_str!("../fixtures/whatsapp_group_events.txt")
+            .replace("Alice: Welcome!", "Alice: <Media omitted>");
+
+        for options in [r#"{"merge_consecutive":false}"#, r#"{"include_events":true}"#] {
+            let output = new_only("", &input, "whatsapp", "csv", options).unwrap();
+            let expected =
+                crate::run_conversion(&input, "whatsapp", "csv", &parse_options(options).unwrap());
+
+            assert_eq!(output, expected.unwrap().output, "{options}");
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Discord threads and forum posts.
+//!
+//! DiscordChatExporter writes a thread (and each post of a forum channel)
+//! as an export of its own, whose `channel.type` is one of the `Guild*Thread`
+//! types. Such an export can be converted on its own or next to its parent
+//! channel in `convert_merged`; a combined export may also carry its threads
+//! nested under a top-level `threads` array, each entry shaped like a thread
+//! export (`{channel, messages}`). chatpack only reads the top-level
+//! `messages`, so nested threads are parsed here, one at a time, with the
+//! same parser.
+//!
+//! Per the `threads` option, thread messages are interleaved with the
+//! channel's by time (`"inline"`, default), written after them thread by
+//! thread (`"separate"`), or left out (`"skip"`). Kept thread messages get
+//! a `thread` field with the thread's name (`null` on channel messages).
+//! Discord ids are global snowflakes, so `reply_to` inside a thread keeps
+//! pointing at the message it answers in that thread.
+
+use std::collections::HashMap;
+
+#[cfg(feature = "discord")]
+use chatpack::parser::{create_parser, Platform};
+use chatpack::Message;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[cfg(feature = "discord")]
+use crate::embeds;
+use crate::enrich::ExtraColumn;
+#[cfg(feature = "discord")]
+use crate::registry::Backend;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ThreadMode {
+    #[default]
+    Inline,
+    Separate,
+    Skip,
+}
+
+#[cfg(feature = "discord")]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DiscordExport {
+    channel: Channel,
+    threads: Vec<Value>,
+}
+
+#[cfg(feature = "discord")]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Channel {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    name: String,
+}
+
+#[cfg(feature = "discord")]
+impl Channel {
+    fn is_thread(&self) -> bool {
+        self.kind.ends_with("Thread")
+    }
+
+    fn label(&self) -> String {
+        if self.name.trim().is_empty() {
+            format!("thread {}", self.id)
+        } else {
+            self.name.clone()
+        }
+    }
+}
+
+/// Add, order or drop thread messages per `mode`, returning the thread name
+/// of each kept thread message by message id.
+#[cfg(feature = "discord")]
+pub(crate) fn apply_threads(
+    messages: &mut Vec<Message>,
+    input: &str,
+    backend: Backend,
+    mode: ThreadMode,
+    expand_embeds: bool,
+) -> HashMap<u64, String> {
+    let mut names = HashMap::new();
+    if !matches!(backend, Backend::Core(Platform::Discord)) {
+        return names;
+    }
+    let Ok(export) = serde_json::from_str::<DiscordExport>(input) else {
+        return names;
+    };
+
+    if export.channel.is_thread() {
+        if mode == ThreadMode::Skip {
+            messages.clear();
+        }
+        tag(messages, &export.channel.label(), &mut names);
+    }
+    if mode == ThreadMode::Skip || export.threads.is_empty() {
+        return names;
+    }
+
+    for thread in export.threads {
+        let channel = thread.get("channel").cloned().unwrap_or(Value::Null);
+        let label = serde_json::from_value::<Channel>(channel).unwrap_or_default().label();
+        let thread = thread.to_string();
+        let Ok(mut thread_messages) = create_parser(Platform::Discord).parse_str(&thread) else {
+            continue;
+        };
+        embeds::fill_media_messages(&mut thread_messages, &thread, backend, expand_embeds);
+        tag(&thread_messages, &label, &mut names);
+        messages.extend(thread_messages);
+    }
+    if mode == ThreadMode::Inline {
+        messages.sort_by_key(|m| (m.timestamp, m.id));
+    }
+
+    names
+}
+
+#[cfg(feature = "discord")]
+fn tag(messages: &[Message], label: &str, names: &mut HashMap<u64, String>) {
+    names.extend(messages.iter().filter_map(|m| Some((m.id?, label.to_string()))));
+}
+
+/// `thread` column for `messages`, if any of them is in a thread.
+pub(crate) fn thread_column(
+    messages: &[Message],
+    names: &HashMap<u64, String>,
+) -> Option<ExtraColumn> {
+    if names.is_empty() {
+        return None;
+    }
+    let values = messages
+        .iter()
+        .map(|m| {
+            m.id.and_then(|id| names.get(&id)).map_or(Value::Null, |n| Value::from(n.as_str()))
+        })
+        .collect();
+
+    Some(ExtraColumn::new("thread", values))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_support::convert_rows;
+    use crate::{parse_options, run_conversion};
+
+    fn message(id: u64, minute: u32, author: &str, content: &str, reply_to: Option<u64>) -> Value {
+        let mut message = json!({
+            "id": id.to_string(),
+            "timestamp": format!("2024-01-15T10:{minute:02}:00+00:00"),
+            "content": content,
+            "author": {"id": "7", "name": author},
+        });
+        if let Some(reply_to) = reply_to {
+            message["reference"] = json!({"messageId": reply_to.to_string()});
+        }
+        message
+    }
+
+    fn combined() -> String {
+        json!({
+            "guild": {"id": "1", "name": "Club"},
+            "channel": {"id": "2", "type": "GuildTextChat", "name": "general"},
+            "messages": [
+                message(100, 0, "alice", "Who read Dune?", None),
+                message(103, 30, "bob", "Back to general", None),
+            ],
+            "threads": [{
+                "channel": {"id": "50", "type": "GuildPublicThread", "name": "Dune"},
+                "messages": [
+                    message(101, 10, "bob", "Me, loved it", None),
+                    message(102, 40, "alice", "Same here", Some(101)),
+                ],
+            }],
+        })
+        .to_string()
+    }
+
+    fn contents(lines: &[Value]) -> Vec<&str> {
+        lines.iter().map(|line| line["content"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_nested_threads_inline_separate_skip() {
+        let base = r#""merge_consecutive":false,"include_replies":true"#;
+        let inline = convert_rows(&combined(), "discord", &format!("{{{base}}}"));
+        let separate =
+            convert_rows(&combined(), "discord", &format!(r#"{{{base},"threads":"separate"}}"#));
+        let skip = convert_rows(&combined(), "discord", &format!(r#"{{{base},"threads":"skip"}}"#));
+
+        assert_eq!(
+            contents(&inline),
+            ["Who read Dune?", "Me, loved it", "Back to general", "Same here"]
+        );
+        assert_eq!(inline[0]["thread"], Value::Null);
+        assert_eq!(inline[1]["thread"], "Dune");
+        assert_eq!(inline[3]["reply_to"], 101);
+        assert_eq!(
+            contents(&separate),
+            ["Who read Dune?", "Back to general", "Me, loved it", "Same here"]
+        );
+        assert_eq!(contents(&skip), ["Who read Dune?", "Back to general"]);
+        assert_eq!(skip[0].get("thread"), None);
+    }
+
+    #[test]
+    fn test_thread_export_on_its_own_and_merged() {
+        let thread = json!({
+            "channel": {"id": "60", "type": "GuildPublicThread", "name": "Forum post"},
+            "messages": [message(201, 5, "carol", "Posting here", None)],
+        })
+        .to_string();
+        assert_eq!(convert_rows(&thread, "discord", "{}")[0]["thread"], "Forum post");
+        assert!(convert_rows(&thread, "discord", r#"{"threads":"skip"}"#).is_empty());
+
+        let inputs = json!([
+            {"input": combined(), "source": "discord"},
+            {"input": thread, "source": "discord"},
+        ])
+        .to_string();
+        let merged = crate::convert_merged(
+            &inputs,
+            "jsonl",
+            r#"{"merge_consecutive":false,"threads":"separate"}"#,
+        )
+        .unwrap();
+        let lines: Vec<Value> = merged.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        let threads: Vec<&Value> = lines.iter().map(|line| &line["thread"]).collect();
+
+        assert_eq!(lines.len(), 5);
+        assert_eq!(
+            threads,
+            [&Value::Null, &Value::Null, &json!("Dune"), &json!("Dune"), &json!("Forum post")]
+        );
+    }
+
+    #[test]
+    fn test_thread_column_on_every_output_path() {
+        let options = r#"{"merge_consecutive":false}"#;
+        let expected = convert_rows(&combined(), "discord", options);
+        let lines = |output: &str| -> Vec<Value> {
+            output.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+        };
+
+        let windows =
+            crate::convert_windows(&combined(), "discord", 3, 3, "jsonl", options).unwrap();
+        let windows: Vec<String> = serde_json::from_str(&windows).unwrap();
+        assert_eq!(windows.len(), 2);
+        assert_eq!(lines(&windows.concat()), expected);
+
+        let csv = run_conversion(&combined(), "discord", "csv", &parse_options(options).unwrap());
+        let reformatted = crate::convert_format(&csv.unwrap().output, "csv", "jsonl", "").unwrap();
+        assert_eq!(lines(&reformatted), expected);
+        assert_eq!(expected[1]["thread"], "Dune");
+    }
+}
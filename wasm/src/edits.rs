@@ -0,0 +1,155 @@
+//! Messages that appear more than once under the same id.
+//!
+//! An export holds only the final text of an edited message, so the same id
+//! shows up twice when two exports of one chat are stitched together (or the
+//! same file is imported twice), possibly with different content. With
+//! `edits: "latest"` only the most recently edited version is kept; with
+//! `"all"` every distinct version is, ordered by edit time within its
+//! export. Exact copies (same content and edit time) are always collapsed.
+
+use std::collections::{HashMap, HashSet};
+
+use chatpack::Message;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum EditPolicy {
+    #[default]
+    Latest,
+    All,
+}
+
+/// Collapse messages sharing an id across `lists` (exports of one platform)
+/// per `policy`; returns the number of messages removed.
+pub(crate) fn dedupe(lists: &mut [Vec<Message>], policy: EditPolicy) -> usize {
+    let mut versions: HashMap<u64, Vec<(usize, usize)>> = HashMap::new();
+    for (list, messages) in lists.iter().enumerate() {
+        for (position, message) in messages.iter().enumerate() {
+            if let Some(id) = message.id {
+                versions.entry(id).or_default().push((list, position));
+            }
+        }
+    }
+    versions.retain(|_, positions| positions.len() > 1);
+    if versions.is_empty() {
+        return 0;
+    }
+
+    let mut dropped: HashSet<(usize, usize)> = HashSet::new();
+    for positions in versions.values_mut() {
+        match policy {
+            EditPolicy::Latest => {
+                let keep = positions
+                    .iter()
+                    .copied()
+                    .max_by_key(|&(list, position)| (lists[list][position].edited, list, position));
+                dropped.extend(positions.iter().filter(|&&at| Some(at) != keep));
+            }
+            EditPolicy::All => {
+                let mut seen = HashSet::new();
+                for &(list, position) in positions.iter() {
+                    let message = &lists[list][position];
+                    if !seen.insert((message.content.as_str(), message.edited)) {
+                        dropped.insert((list, position));
+                    }
+                }
+                positions.retain(|at| !dropped.contains(at));
+                sort_by_edit_time(lists, positions);
+            }
+        }
+    }
+
+    for (list, messages) in lists.iter_mut().enumerate() {
+        let mut position = 0;
+        messages.retain(|_| {
+            position += 1;
+            !dropped.contains(&(list, position - 1))
+        });
+    }
+
+    dropped.len()
+}
+
+/// Reorder the versions of one message within each export by edit time,
+/// unedited first, keeping the slots they occupy.
+fn sort_by_edit_time(lists: &mut [Vec<Message>], positions: &[(usize, usize)]) {
+    for (list, messages) in lists.iter_mut().enumerate() {
+        let slots: Vec<usize> =
+            positions.iter().filter(|(l, _)| *l == list).map(|(_, position)| *position).collect();
+        if slots.len() < 2 {
+            continue;
+        }
+        let mut versions: Vec<Message> = slots.iter().map(|&slot| messages[slot].clone()).collect();
+        versions.sort_by_key(|message| message.edited);
+        for (slot, version) in slots.into_iter().zip(versions) {
+            messages[slot] = version;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_options, run_conversion};
+    use chrono::DateTime;
+
+    fn message(id: u64, content: &str, edited: Option<i64>) -> Message {
+        let message = Message::new("Ann", content).with_id(id);
+        match edited.and_then(|secs| DateTime::from_timestamp(secs, 0)) {
+            Some(time) => message.with_edited(time),
+            None => message,
+        }
+    }
+
+    fn contents(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|m| m.content.as_str()).collect()
+    }
+
+    #[test]
+    fn test_latest_keeps_most_recent_edit() {
+        let mut lists = [
+            vec![message(1, "helo", None), message(2, "bye", None)],
+            vec![message(1, "hello", Some(100)), message(2, "bye", None)],
+        ];
+
+        assert_eq!(dedupe(&mut lists, EditPolicy::Latest), 2);
+        assert_eq!(contents(&lists[0]), Vec::<&str>::new());
+        assert_eq!(contents(&lists[1]), ["hello", "bye"]);
+    }
+
+    #[test]
+    fn test_all_keeps_versions_ordered_by_edit_time() {
+        let mut lists = [vec![
+            message(1, "hello!", Some(200)),
+            message(1, "helo", None),
+            message(2, "bye", None),
+            message(1, "hello", Some(100)),
+            message(2, "bye", None),
+        ]];
+
+        assert_eq!(dedupe(&mut lists, EditPolicy::All), 1);
+        assert_eq!(contents(&lists[0]), ["helo", "hello", "bye", "hello!"]);
+    }
+
+    #[test]
+    fn test_double_import_is_deduplicated() {
+        let telegram = r#"{"messages": [
+  {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Ann", "text": "Hello"},
+  {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Bob", "text": "Hi"},
+  {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Ann", "text": "Hello"},
+  {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Bob", "text": "Hi", "edited_unixtime": "1705314700"}
+]}"#;
+        let convert = |options: &str| {
+            run_conversion(telegram, "telegram", "jsonl", &parse_options(options).unwrap()).unwrap()
+        };
+
+        let latest = convert(r#"{"merge_consecutive":false}"#);
+        assert_eq!(latest.output.lines().count(), 2);
+        assert!(latest.stats.warnings.iter().any(|w| w.contains("2 duplicate")));
+        assert_eq!(
+            convert(r#"{"merge_consecutive":false,"edits":"all"}"#).output.lines().count(),
+            3
+        );
+    }
+}
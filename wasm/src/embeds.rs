@@ -0,0 +1,218 @@
+//! Content for Discord messages whose text is empty.
+//!
+//! A Discord message that is "just a link", a file or a sticker has an
+//! empty `content` in DiscordChatExporter's JSON; what it shows lives in
+//! `embeds[]`, `attachments[]` and `stickers[]`. chatpack's Discord parser
+//! skips such messages, so they are read from the export again and added
+//! back with synthesized content: a `[media]` placeholder by default, or,
+//! with `expand_embeds`, each embed as `{title} — {description} ({url})`
+//! and each attachment as `{fileName} ({url})`. Stickers are always
+//! written as `[sticker: {name}]`.
+
+use std::collections::HashMap;
+
+use chatpack::parser::Platform;
+use chatpack::Message;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::kinds::MEDIA_PLACEHOLDER;
+use crate::registry::Backend;
+
+#[derive(Debug, Deserialize)]
+struct DiscordExport {
+    #[serde(default)]
+    messages: Vec<RawMessage>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct RawMessage {
+    id: String,
+    timestamp: Option<String>,
+    content: String,
+    author: Author,
+    embeds: Vec<Embed>,
+    attachments: Vec<Attachment>,
+    stickers: Vec<Sticker>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Author {
+    name: Option<String>,
+    nickname: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Embed {
+    title: Option<String>,
+    description: Option<String>,
+    url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Attachment {
+    file_name: Option<String>,
+    url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Sticker {
+    name: Option<String>,
+}
+
+impl RawMessage {
+    fn has_media(&self) -> bool {
+        !self.embeds.is_empty() || !self.attachments.is_empty() || !self.stickers.is_empty()
+    }
+
+    fn synthesized_content(&self, expand: bool) -> String {
+        let stickers = self
+            .stickers
+            .iter()
+            .map(|s| format!("[sticker: {}]", s.name.as_deref().unwrap_or("unknown")));
+        let mut parts: Vec<String> = Vec::new();
+        if expand {
+            parts.extend(self.embeds.iter().filter_map(Embed::render));
+            parts.extend(self.attachments.iter().filter_map(Attachment::render));
+        } else if !self.embeds.is_empty() || !self.attachments.is_empty() {
+            parts.push(MEDIA_PLACEHOLDER.to_string());
+        }
+        parts.extend(stickers);
+
+        if parts.is_empty() {
+            MEDIA_PLACEHOLDER.to_string()
+        } else {
+            parts.join("\n")
+        }
+    }
+
+    fn sender(&self) -> &str {
+        let nickname = self.author.nickname.as_deref().filter(|n| !n.trim().is_empty());
+        nickname.or(self.author.name.as_deref()).unwrap_or_default()
+    }
+}
+
+impl Embed {
+    fn render(&self) -> Option<String> {
+        let heading = match (non_empty(&self.title), non_empty(&self.description)) {
+            (Some(title), Some(description)) => Some(format!("{title} — {description}")),
+            (heading, None) | (None, heading) => heading.map(str::to_string),
+        };
+
+        match (heading, non_empty(&self.url)) {
+            (Some(heading), Some(url)) => Some(format!("{heading} ({url})")),
+            (heading, url) => heading.or_else(|| url.map(str::to_string)),
+        }
+    }
+}
+
+impl Attachment {
+    fn render(&self) -> Option<String> {
+        match (self.file_name.as_deref(), self.url.as_deref()) {
+            (Some(name), Some(url)) => Some(format!("{name} ({url})")),
+            (name, url) => name.or(url).map(str::to_string),
+        }
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+/// Add back the media-only messages chatpack's Discord parser skipped.
+pub(crate) fn fill_media_messages(
+    messages: &mut Vec<Message>,
+    input: &str,
+    backend: Backend,
+    expand: bool,
+) {
+    if !matches!(backend, Backend::Core(Platform::Discord)) {
+        return;
+    }
+    let Ok(export) = serde_json::from_str::<DiscordExport>(input) else {
+        return;
+    };
+
+    let positions: HashMap<u64, usize> =
+        messages.iter().enumerate().filter_map(|(i, m)| Some((m.id?, i))).collect();
+    let mut added = false;
+    for raw in export.messages.iter().filter(|raw| raw.content.trim().is_empty() && raw.has_media())
+    {
+        let id = raw.id.parse::<u64>().ok();
+        let content = raw.synthesized_content(expand);
+        match id.and_then(|id| positions.get(&id)) {
+            Some(&index) => messages[index].content = content,
+            None => {
+                let mut message = Message::new(raw.sender(), content);
+                if let Some(id) = id {
+                    message = message.with_id(id);
+                }
+                if let Some(timestamp) = raw.timestamp.as_deref().and_then(parse_rfc3339) {
+                    message = message.with_timestamp(timestamp);
+                }
+                messages.push(message);
+                added = true;
+            }
+        }
+    }
+    if added {
+        messages.sort_by_key(|m| (m.timestamp, m.id));
+    }
+}
+
+fn parse_rfc3339(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value).ok().map(|t| t.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_options, prepare_messages};
+
+    const DISCORD: &str = r#"{
+  "guild": {"id": "1", "name": "Club"},
+  "channel": {"id": "2", "type": "GuildTextChat", "name": "general"},
+  "messages": [
+    {"id": "100", "timestamp": "2024-01-15T10:30:00+00:00", "content": "Look at this",
+     "author": {"id": "7", "name": "alice", "nickname": "Alice"}, "embeds": [], "attachments": []},
+    {"id": "101", "timestamp": "2024-01-15T10:31:00+00:00", "content": "",
+     "author": {"id": "8", "name": "bob", "nickname": "Bob"},
+     "embeds": [{"title": "Dune", "description": "A novel", "url": "https://example.com/dune"}],
+     "attachments": [{"id": "9", "fileName": "cover.png", "url": "https://cdn.example.com/cover.png"}]},
+    {"id": "102", "timestamp": "2024-01-15T10:32:00+00:00", "content": "",
+     "author": {"id": "7", "name": "alice", "nickname": "Alice"},
+     "stickers": [{"id": "3", "name": "Wave"}]},
+    {"id": "103", "timestamp": "2024-01-15T10:33:00+00:00", "content": "Nice",
+     "author": {"id": "8", "name": "bob", "nickname": "Bob"}}
+  ]
+}"#;
+
+    fn contents(options: &str) -> Vec<String> {
+        let options = parse_options(options).unwrap();
+        let prepared = prepare_messages(DISCORD, "discord", &options).unwrap();
+        prepared.messages.into_iter().map(|m| m.content).collect()
+    }
+
+    #[test]
+    fn test_media_messages_get_placeholder() {
+        assert_eq!(
+            contents(r#"{"merge_consecutive":false}"#),
+            ["Look at this", "[media]", "[sticker: Wave]", "Nice"]
+        );
+    }
+
+    #[test]
+    fn test_expand_embeds_and_attachments() {
+        let expanded = contents(r#"{"merge_consecutive":false,"expand_embeds":true}"#);
+
+        assert_eq!(
+            expanded[1],
+            "Dune — A novel (https://example.com/dune)\ncover.png (https://cdn.example.com/cover.png)"
+        );
+        assert_eq!(expanded[2], "[sticker: Wave]");
+    }
+}
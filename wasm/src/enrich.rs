@@ -0,0 +1,368 @@
+//! Extra per-message fields appended to chatpack's formatted output.
+//!
+//! chatpack's formatters only know the core `Message` fields. Features that
+//! derive additional values per message (sentiment, ...) describe them as
+//! [`ExtraColumn`]s, which are spliced into the already formatted output:
+//! trailing columns for CSV, trailing keys for JSON/JSONL. The original
+//! fields keep their order and formatting, and nothing is re-parsed unless
+//! at least one extra column is requested.
+
+use std::fmt;
+
+use chatpack::format::OutputFormat;
+use chatpack::Message;
+use serde::de::{Deserializer, MapAccess, Visitor};
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::csv_cells::formula_safe;
+use crate::pairs::{role_of, Role};
+use crate::{non_empty, ConvertOptions};
+
+const CSV_DELIMITER: u8 = b';';
+
+/// One additional field, with a value for every formatted message.
+#[derive(Debug, Clone)]
+pub(crate) struct ExtraColumn {
+    /// Key used in JSON/JSONL output.
+    pub name: String,
+    /// Column title used in the CSV header.
+    pub header: String,
+    pub values: Vec<Value>,
+}
+
+impl ExtraColumn {
+    /// Column whose CSV header is the PascalCase form of `name`, matching chatpack's headers.
+    pub(crate) fn new(name: &str, values: Vec<Value>) -> Self {
+        Self { name: name.to_string(), header: pascal_case(name), values }
+    }
+
+    /// This column's values for the messages at `positions`, in that order.
+    pub(crate) fn select(&self, positions: impl IntoIterator<Item = usize>) -> Self {
+        Self {
+            name: self.name.clone(),
+            header: self.header.clone(),
+            values: positions.into_iter().map(|index| self.values[index].clone()).collect(),
+        }
+    }
+}
+
+/// Compute the extra columns requested by `options` for `messages`.
+pub(crate) fn extra_columns(
+    messages: &[Message],
+    options: &ConvertOptions,
+) -> std::result::Result<Vec<ExtraColumn>, String> {
+    let mut columns = Vec::new();
+
+    if options.sentiment {
+        columns.push(sentiment_column(messages)?);
+    }
+    if options.detect_language {
+        columns.push(language_column(messages)?);
+    }
+    match non_empty(options.self_sender.as_deref()) {
+        Some(self_sender) => columns.push(self_column(messages, self_sender, options.self_roles)),
+        None if options.self_roles => {
+            return Err("Invalid self_roles: requires self_sender".to_string());
+        }
+        None => {}
+    }
+
+    Ok(columns)
+}
+
+/// `is_self` flags, or `role`s (`assistant` for `self_sender`) with `roles`.
+fn self_column(messages: &[Message], self_sender: &str, roles: bool) -> ExtraColumn {
+    let role = |message: &Message| role_of(&message.sender, self_sender, None);
+    if roles {
+        let values = messages
+            .iter()
+            .map(|m| match role(m) {
+                Some(Role::Assistant) => Value::from("assistant"),
+                _ => Value::from("user"),
+            })
+            .collect();
+        ExtraColumn::new("role", values)
+    } else {
+        let values =
+            messages.iter().map(|m| Value::from(role(m) == Some(Role::Assistant))).collect();
+        ExtraColumn::new("is_self", values)
+    }
+}
+
+#[cfg(feature = "sentiment")]
+fn sentiment_column(messages: &[Message]) -> std::result::Result<ExtraColumn, String> {
+    let values =
+        messages.iter().map(|m| Value::from(crate::sentiment::score(&m.content))).collect();
+
+    Ok(ExtraColumn::new("sentiment", values))
+}
+
+#[cfg(not(feature = "sentiment"))]
+fn sentiment_column(_messages: &[Message]) -> std::result::Result<ExtraColumn, String> {
+    Err("Sentiment scoring is not available in this build (enable the `sentiment` feature)"
+        .to_string())
+}
+
+/// Error for language options in a build without the `language` feature.
+#[cfg(not(feature = "language"))]
+pub(crate) const LANGUAGE_UNAVAILABLE: &str =
+    "Language detection is not available in this build (enable the `language` feature)";
+
+#[cfg(feature = "language")]
+fn language_column(messages: &[Message]) -> std::result::Result<ExtraColumn, String> {
+    let values =
+        messages.iter().map(|m| Value::from(crate::language::detect(&m.content))).collect();
+
+    Ok(ExtraColumn::new("language", values))
+}
+
+#[cfg(not(feature = "language"))]
+fn language_column(_messages: &[Message]) -> std::result::Result<ExtraColumn, String> {
+    Err(LANGUAGE_UNAVAILABLE.to_string())
+}
+
+/// Append `columns` to output produced by chatpack for the same messages.
+pub(crate) fn append_columns(
+    output: &str,
+    format: OutputFormat,
+    columns: &[ExtraColumn],
+    escape_formulas: bool,
+) -> std::result::Result<String, String> {
+    match format {
+        OutputFormat::Csv => append_csv(output, columns, escape_formulas),
+        OutputFormat::Json => {
+            let mut items: Vec<OrderedObject> =
+                serde_json::from_str(output).map_err(|e| e.to_string())?;
+            for (index, item) in items.iter_mut().enumerate() {
+                item.extend(columns, index);
+            }
+            serde_json::to_string_pretty(&items).map_err(|e| e.to_string())
+        }
+        _ => {
+            let mut enriched = String::with_capacity(output.len());
+            let mut index = 0;
+            for line in output.split_inclusive('\n') {
+                let body = line.trim_end_matches(['\r', '\n']);
+                if body.trim().is_empty() {
+                    enriched.push_str(line);
+                    continue;
+                }
+                let mut item: OrderedObject =
+                    serde_json::from_str(body).map_err(|e| e.to_string())?;
+                item.extend(columns, index);
+                index += 1;
+                enriched.push_str(&serde_json::to_string(&item).map_err(|e| e.to_string())?);
+                enriched.push_str(&line[body.len()..]);
+            }
+            Ok(enriched)
+        }
+    }
+}
+
+/// `output` with `columns` appended to each row; their headers and text
+/// cells are escaped like chatpack's with `escape_formulas`.
+fn append_csv(
+    output: &str,
+    columns: &[ExtraColumn],
+    escape_formulas: bool,
+) -> std::result::Result<String, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(CSV_DELIMITER)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(output.as_bytes());
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(CSV_DELIMITER)
+        .from_writer(Vec::with_capacity(output.len() + output.len() / 4));
+
+    for (index, record) in reader.records().enumerate() {
+        let mut record = record.map_err(|e| e.to_string())?;
+        for column in columns {
+            match index.checked_sub(1) {
+                None => record.push_field(&formula_safe(&column.header, escape_formulas)),
+                Some(row) => record.push_field(&cell(column.values.get(row), escape_formulas)),
+            }
+        }
+        writer.write_record(&record).map_err(|e| e.to_string())?;
+    }
+
+    let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+/// CSV text of `value`; numbers and bools are never escaped, a list is
+/// escaped as the joined cell.
+fn cell(value: Option<&Value>, escape_formulas: bool) -> String {
+    match value {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => formula_safe(s, escape_formulas).into_owned(),
+        Some(Value::Array(items)) => {
+            let joined = items.iter().map(|item| cell(Some(item), false)).collect::<Vec<_>>();
+            formula_safe(&joined.join(", "), escape_formulas).into_owned()
+        }
+        Some(other) => other.to_string(),
+    }
+}
+
+pub(crate) fn pascal_case(name: &str) -> String {
+    name.split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        })
+        .collect::<Vec<String>>()
+        .concat()
+}
+
+/// A JSON object that keeps its keys in document order.
+pub(crate) struct OrderedObject(pub(crate) Vec<(String, Value)>);
+
+impl OrderedObject {
+    fn extend(&mut self, columns: &[ExtraColumn], index: usize) {
+        for column in columns {
+            let value = column.values.get(index).cloned().unwrap_or(Value::Null);
+            self.0.push((column.name.clone(), value));
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for OrderedObject {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ObjectVisitor;
+
+        impl<'de> Visitor<'de> for ObjectVisitor {
+            type Value = OrderedObject;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<OrderedObject, A::Error> {
+                let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(OrderedObject(entries))
+            }
+        }
+
+        deserializer.deserialize_map(ObjectVisitor)
+    }
+}
+
+impl Serialize for OrderedObject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chatpack::core::OutputConfig;
+    use chatpack::format::to_format_string;
+
+    use super::*;
+
+    fn messages() -> Vec<Message> {
+        vec![Message::new("Alice", "Hello; \"world\""), Message::new("Bob", "Line\nbreak")]
+    }
+
+    fn columns() -> Vec<ExtraColumn> {
+        vec![
+            ExtraColumn::new("is_self", vec![Value::Bool(true), Value::Bool(false)]),
+            ExtraColumn::new("score", vec![Value::from(0.5), Value::Null]),
+        ]
+    }
+
+    #[test]
+    fn test_append_columns_keeps_original_fields() {
+        let messages = messages();
+        for format in [OutputFormat::Csv, OutputFormat::Json, OutputFormat::Jsonl] {
+            let plain = to_format_string(&messages, format, &OutputConfig::new()).unwrap();
+            let enriched = append_columns(&plain, format, &columns(), true).unwrap();
+            let without: Vec<ExtraColumn> = Vec::new();
+
+            assert_eq!(append_columns(&plain, format, &without, true).unwrap(), plain, "{format}");
+            assert_ne!(enriched, plain, "{format}");
+        }
+    }
+
+    #[test]
+    fn test_append_columns_per_format() {
+        let messages = messages();
+        let config = OutputConfig::new();
+        let csv = to_format_string(&messages, OutputFormat::Csv, &config).unwrap();
+        let jsonl = to_format_string(&messages, OutputFormat::Jsonl, &config).unwrap();
+        let json = to_format_string(&messages, OutputFormat::Json, &config).unwrap();
+
+        let csv = append_columns(&csv, OutputFormat::Csv, &columns(), true).unwrap();
+        let jsonl = append_columns(&jsonl, OutputFormat::Jsonl, &columns(), true).unwrap();
+        let json = append_columns(&json, OutputFormat::Json, &columns(), true).unwrap();
+        let first_line: serde_json::Value =
+            serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+        let items: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert!(csv.starts_with("Sender;Content;IsSelf;Score\n"));
+        assert!(csv.contains(";true;0.5\n"));
+        assert!(csv.ends_with(";false;\n"));
+        assert_eq!(first_line["is_self"], true);
+        assert!(jsonl.lines().next().unwrap().ends_with(r#""is_self":true,"score":0.5}"#));
+        assert_eq!(items[1]["score"], Value::Null);
+        assert_eq!(items[1]["content"], "Line\nbreak");
+    }
+
+    #[test]
+    fn test_csv_text_cells_escape_formulas() {
+        let csv = to_format_string(&messages(), OutputFormat::Csv, &OutputConfig::new()).unwrap();
+        let mut thread = ExtraColumn::new("thread", vec![Value::from("=HYPERLINK()"), Value::Null]);
+        thread.header = "@thread".to_string();
+        let columns = vec![
+            thread,
+            ExtraColumn::new("score", vec![Value::from(-0.5), Value::from(vec!["-a", "b"])]),
+        ];
+
+        let escaped = append_columns(&csv, OutputFormat::Csv, &columns, true).unwrap();
+        let raw = append_columns(&csv, OutputFormat::Csv, &columns, false).unwrap();
+
+        assert!(escaped.starts_with("Sender;Content;'@thread;Score\n"));
+        assert!(escaped.contains(";'=HYPERLINK();-0.5\n"));
+        assert!(escaped.ends_with(";;'-a, b\n"));
+        assert!(raw.contains(";=HYPERLINK();-0.5\n"));
+    }
+
+    #[test]
+    fn test_self_sender_roles() {
+        let telegram = r#"{"messages": [
+  {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Ann", "text": "How do I reset it?"},
+  {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Me", "text": "Hold the button"}
+]}"#;
+        let convert = |options: &str| {
+            let options = crate::parse_options(options).unwrap();
+            let report = crate::run_conversion(telegram, "telegram", "json", &options).unwrap();
+            serde_json::from_str::<Value>(&report.output).unwrap()
+        };
+
+        let flagged = convert(r#"{"self_sender":"Me"}"#);
+        assert_eq!((&flagged[0]["is_self"], &flagged[1]["is_self"]), (&false.into(), &true.into()));
+
+        let roles = convert(r#"{"self_sender":"Me","self_roles":true}"#);
+        assert_eq!(roles[0]["role"], "user");
+        assert_eq!(roles[1]["role"], "assistant");
+        assert_eq!(roles[1].get("is_self"), None);
+        assert!(crate::parse_options(r#"{"self_roles":true}"#).is_err());
+
+        let anonymized = convert(r#"{"self_sender":"Me","anonymize":true}"#);
+        assert_eq!(anonymized[1]["sender"], "Person 2");
+        assert_eq!(anonymized[1]["is_self"], true);
+        assert_eq!(anonymized[0]["is_self"], false);
+    }
+}
@@ -0,0 +1,280 @@
+//! Per-message fields supplied by a JS callback.
+//!
+//! `convert_with_enricher` runs the usual conversion, then hands the
+//! processed messages to the caller in batches and appends the fields it
+//! returns to each output message (see [`crate::enrich`]), so a classifier
+//! living in JS can tag messages without the caller re-serializing the
+//! output. Fields are appended after the built-in extra columns, sorted by
+//! name; a message that did not get a field has `null` (an empty CSV cell).
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::enrich::ExtraColumn;
+use crate::{
+    describe_js_error, format_report, parse_options, prepare_messages, resolve_format, CodedError,
+    CONVERSION_FAILED,
+};
+
+/// Code for an enricher that threw or returned something other than one
+/// flat object per message.
+const ENRICH_FAILED: &str = "ENRICH_FAILED";
+
+const DEFAULT_BATCH_SIZE: usize = 500;
+
+/// Field names the output already uses, which enrichers may not return.
+const RESERVED: &[&str] = &[
+    "sender",
+    "content",
+    "timestamp",
+    "id",
+    "reply_to",
+    "edited",
+    "action",
+    "thread",
+    "urls",
+    "is_self",
+    "role",
+    "sentiment",
+    "language",
+];
+
+#[derive(Serialize)]
+struct BatchMessage<'a> {
+    index: usize,
+    sender: &'a str,
+    content: &'a str,
+}
+
+/// Convert chat export, adding the fields `enricher` returns for each message.
+///
+/// `enricher` is called synchronously with arrays of up to `batch_size`
+/// (default 500) `{index, sender, content}` objects, `index` being the
+/// message's position in the output, after filters and merging. It must
+/// return an array of the same length holding a flat object (or `null`) per
+/// message; the objects' keys become extra JSON fields and CSV columns, in
+/// sorted order. Keys the output already has (`sender`, `content`, ...) and
+/// nested values are rejected. Takes the same other arguments as
+/// `convert_with_options`.
+///
+/// If the callback throws or returns something else, the conversion stops
+/// and the error is JSON `{code: "ENRICH_FAILED", message, batch_index}`.
+/// Other errors are JSON `{code, message}`.
+#[wasm_bindgen]
+pub fn convert_with_enricher(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+    enricher: &js_sys::Function,
+    batch_size: Option<usize>,
+) -> std::result::Result<String, JsValue> {
+    enrich_conversion(input, source, format, options_json, batch_size, |batch| {
+        let batch = js_sys::JSON::parse(batch).map_err(describe_js_error)?;
+        let fields = enricher.call1(&JsValue::NULL, &batch).map_err(describe_js_error)?;
+        js_sys::JSON::stringify(&fields)
+            .map_err(describe_js_error)?
+            .as_string()
+            .ok_or_else(|| "Enricher must return an array".to_string())
+    })
+    .map_err(CodedError::into_js)
+}
+
+fn enrich_conversion<F>(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+    batch_size: Option<usize>,
+    mut enricher: F,
+) -> std::result::Result<String, CodedError>
+where
+    F: FnMut(&str) -> std::result::Result<String, String>,
+{
+    let failed = |message| CodedError::new(CONVERSION_FAILED, message);
+    let batch_size = batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+    if batch_size == 0 {
+        return Err(failed("Invalid batch_size: must be at least 1".to_string()));
+    }
+    let mut options = parse_options(options_json).map_err(failed)?;
+    let output_format = resolve_format(format, &mut options).map_err(failed)?;
+    let mut prepared = prepare_messages(input, source, &options).map_err(failed)?;
+
+    let total = prepared.messages.len();
+    let mut fields: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    for (batch_index, batch) in prepared.messages.chunks(batch_size).enumerate() {
+        let first = batch_index * batch_size;
+        let batch_failed = |message: String| CodedError {
+            batch_index: Some(batch_index),
+            ..CodedError::new(
+                ENRICH_FAILED,
+                format!(
+                    "Batch {batch_index} (messages {first}-{}): {message}",
+                    first + batch.len() - 1
+                ),
+            )
+        };
+        let messages: Vec<BatchMessage> = batch
+            .iter()
+            .enumerate()
+            .map(|(offset, m)| BatchMessage {
+                index: first + offset,
+                sender: &m.sender,
+                content: &m.content,
+            })
+            .collect();
+        let messages = serde_json::to_string(&messages).map_err(|e| failed(e.to_string()))?;
+        let returned = enricher(&messages).map_err(batch_failed)?;
+        let returned = batch_fields(&returned, batch.len()).map_err(batch_failed)?;
+
+        for (offset, object) in returned.into_iter().enumerate() {
+            for (name, value) in object {
+                let values = fields.entry(name).or_insert_with(|| vec![Value::Null; total]);
+                values[first + offset] = value;
+            }
+        }
+    }
+
+    prepared.extra =
+        fields.into_iter().map(|(name, values)| ExtraColumn::new(&name, values)).collect();
+    format_report(prepared, input.len(), output_format, &options)
+        .map(|report| report.output)
+        .map_err(failed)
+}
+
+/// The `expected` flat objects in the enricher's JSON result.
+fn batch_fields(
+    returned: &str,
+    expected: usize,
+) -> std::result::Result<Vec<Map<String, Value>>, String> {
+    let items: Vec<Value> = serde_json::from_str(returned)
+        .map_err(|_| "Enricher must return an array of objects".to_string())?;
+    if items.len() != expected {
+        return Err(format!("Enricher returned {} item(s) for {expected} message(s)", items.len()));
+    }
+
+    items
+        .into_iter()
+        .map(|item| {
+            let object = match item {
+                Value::Null => Map::new(),
+                Value::Object(object) => object,
+                other => return Err(format!("Enricher returned {other} instead of an object")),
+            };
+            for (name, value) in &object {
+                if RESERVED.contains(&name.as_str()) {
+                    return Err(format!("Field '{name}' collides with a built-in field"));
+                }
+                if name.trim().is_empty() {
+                    return Err("Field names must not be empty".to_string());
+                }
+                if value.is_object() || value.is_array() {
+                    return Err(format!("Field '{name}' must be a string, number, bool or null"));
+                }
+            }
+            Ok(object)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHATSAPP: &str = "[15.01.24, 10:30:00] Alice: I love it\n\
+[15.01.24, 10:31:00] Bob: meh\n\
+[15.01.24, 10:32:00] Alice: great!\n";
+
+    fn tag(batch: &str) -> String {
+        let batch: Vec<Value> = serde_json::from_str(batch).unwrap();
+        let fields: Vec<Value> = batch
+            .iter()
+            .map(|m| {
+                let index = m["index"].as_u64().unwrap();
+                if m["content"] == "meh" {
+                    serde_json::json!({"mood": "flat", "score": index})
+                } else {
+                    serde_json::json!({"score": index})
+                }
+            })
+            .collect();
+        serde_json::to_string(&fields).unwrap()
+    }
+
+    #[test]
+    fn test_fields_become_sorted_columns() {
+        let mut batches = 0;
+        let convert = |format: &str, batches: &mut usize| {
+            enrich_conversion(WHATSAPP, "whatsapp", format, "", Some(2), |batch| {
+                *batches += 1;
+                Ok(tag(batch))
+            })
+            .unwrap()
+        };
+
+        let csv = convert("csv", &mut batches);
+        assert_eq!(batches, 2);
+        assert_eq!(
+            csv,
+            "Sender;Content;Mood;Score\nAlice;I love it;;0\nBob;meh;flat;1\nAlice;great!;;2\n"
+        );
+
+        let jsonl = convert("jsonl", &mut batches);
+        let first: Value = serde_json::from_str(jsonl.lines().next().unwrap()).unwrap();
+        assert_eq!(first["mood"], Value::Null);
+        assert_eq!(first["score"], 0);
+    }
+
+    #[test]
+    fn test_fields_follow_truncation_and_reformatting() {
+        let enrich = |options: &str| {
+            enrich_conversion(WHATSAPP, "whatsapp", "csv", options, None, |b| Ok(tag(b))).unwrap()
+        };
+        let csv = enrich("");
+
+        let truncated = enrich(r#"{"max_output_bytes":50}"#);
+        assert_eq!(truncated, "Sender;Content;Mood;Score\nAlice;I love it;;0\n");
+
+        let jsonl = crate::convert_format(&csv, "csv", "jsonl", "").unwrap();
+        let second: Value = serde_json::from_str(jsonl.lines().nth(1).unwrap()).unwrap();
+        assert_eq!(second["mood"], "flat");
+        assert_eq!(crate::convert_format(&jsonl, "jsonl", "csv", "").unwrap(), csv);
+    }
+
+    #[test]
+    fn test_errors_name_the_batch() {
+        let fail = |returned: &'static str| {
+            enrich_conversion(WHATSAPP, "whatsapp", "csv", "", Some(2), |batch| {
+                if batch.contains("great!") {
+                    Ok(returned.to_string())
+                } else {
+                    Ok(tag(batch))
+                }
+            })
+            .unwrap_err()
+        };
+
+        let thrown = enrich_conversion(WHATSAPP, "whatsapp", "csv", "", None, |_| {
+            Err("model not loaded".to_string())
+        })
+        .unwrap_err();
+        assert_eq!((thrown.code, thrown.batch_index), (ENRICH_FAILED, Some(0)));
+        assert_eq!(thrown.message, "Batch 0 (messages 0-2): model not loaded");
+
+        let collision = fail(r#"[{"sender": "x"}]"#);
+        assert_eq!(collision.batch_index, Some(1));
+        assert!(collision.message.contains("'sender' collides"));
+        assert!(fail(r#"[{"tags": ["a"]}]"#).message.contains("'tags' must be"));
+        assert!(fail("[{}, {}]").message.contains("2 item(s) for 1 message(s)"));
+        assert!(fail("42").message.contains("array of objects"));
+        assert_eq!(
+            enrich_conversion(WHATSAPP, "whatsapp", "csv", "", Some(0), |_| unreachable!())
+                .unwrap_err()
+                .code,
+            CONVERSION_FAILED
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Telegram rich text, re-rendered from the export's entity arrays.
+//!
+//! Telegram writes `text` as a plain string, or, for formatted messages,
+//! as an array of strings and entity objects (`{"type": "bold", "text":
+//! ...}`, links, mentions, custom emoji, ...). The message content is
+//! rebuilt from that array, matched to chatpack's messages by id, so that
+//! every entity's text appears in order, link targets can be kept, and
+//! formatting can be written as Markdown. Entity types not known here
+//! still contribute their text.
+//!
+//! `preserve_links` keeps what plain text loses without the rest of the
+//! Markdown: hidden links become `[text](url)` and mentions of users
+//! without a username (`mention_name`, whose text is the display name)
+//! become `@name`.
+
+#[cfg(feature = "telegram")]
+use std::collections::HashMap;
+
+#[cfg(feature = "telegram")]
+use chatpack::Message;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "telegram")]
+use serde_json::Value;
+
+#[cfg(feature = "telegram")]
+use crate::telegram::TelegramExport;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum EntityMode {
+    /// Plain text of every entity.
+    #[default]
+    Flatten,
+    /// Bold, italic, strikethrough, code and links written as Markdown.
+    Markdown,
+}
+
+/// How the target of a hidden link (`text_link`) is kept.
+#[cfg(feature = "telegram")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LinkStyle {
+    /// Only the link text, unless `EntityMode::Markdown`.
+    Text,
+    /// `text (url)`, from `include_link_targets`.
+    Target,
+    /// `[text](url)`, with mentions as `@name`, from `preserve_links`.
+    Markdown,
+}
+
+#[cfg(feature = "telegram")]
+impl LinkStyle {
+    pub(crate) fn of(preserve_links: bool, include_link_targets: bool) -> Self {
+        match (preserve_links, include_link_targets) {
+            (true, _) => Self::Markdown,
+            (false, true) => Self::Target,
+            (false, false) => Self::Text,
+        }
+    }
+}
+
+/// Rebuild the content of Telegram messages whose `text` is an entity array.
+#[cfg(feature = "telegram")]
+pub(crate) fn render_entities(
+    messages: &mut [Message],
+    export: &TelegramExport,
+    mode: EntityMode,
+    links: LinkStyle,
+) {
+    if messages.is_empty() {
+        return;
+    }
+
+    let rendered: HashMap<u64, String> = export
+        .messages
+        .iter()
+        .filter_map(|raw| {
+            let parts = raw.text.as_array()?;
+            Some((raw.id, render(parts, mode, links)))
+        })
+        .collect();
+    for message in messages {
+        if let Some(content) = message.id.and_then(|id| rendered.get(&id)) {
+            message.content.clone_from(content);
+        }
+    }
+}
+
+#[cfg(feature = "telegram")]
+fn render(parts: &[Value], mode: EntityMode, links: LinkStyle) -> String {
+    let mut text = String::new();
+    for part in parts {
+        match part {
+            Value::String(plain) => text.push_str(plain),
+            Value::Object(entity) => {
+                let inner = match entity.get("text").unwrap_or(&Value::Null) {
+                    Value::String(inner) => inner.clone(),
+                    Value::Array(nested) => render(nested, mode, links),
+                    _ => continue,
+                };
+                let kind = entity.get("type").and_then(Value::as_str).unwrap_or_default();
+                let href = entity.get("href").and_then(Value::as_str).filter(|href| *href != inner);
+                text.push_str(&render_entity(kind, &inner, href, entity, mode, links));
+            }
+            _ => {}
+        }
+    }
+
+    text
+}
+
+#[cfg(feature = "telegram")]
+fn render_entity(
+    kind: &str,
+    text: &str,
+    href: Option<&str>,
+    entity: &serde_json::Map<String, Value>,
+    mode: EntityMode,
+    links: LinkStyle,
+) -> String {
+    if text.trim().is_empty() {
+        return text.to_string();
+    }
+
+    let markdown_links = mode == EntityMode::Markdown || links == LinkStyle::Markdown;
+    match (mode, kind) {
+        (EntityMode::Markdown, "bold") => format!("**{text}**"),
+        (EntityMode::Markdown, "italic") => format!("*{text}*"),
+        (EntityMode::Markdown, "strikethrough") => format!("~~{text}~~"),
+        (EntityMode::Markdown, "code") => format!("`{text}`"),
+        (EntityMode::Markdown, "pre") => {
+            let language = entity.get("language").and_then(Value::as_str).unwrap_or_default();
+            format!("```{language}\n{text}\n```")
+        }
+        (_, "text_link") if markdown_links => match href {
+            Some(href) => format!("[{text}]({href})"),
+            None => text.to_string(),
+        },
+        (_, "text_link") if links == LinkStyle::Target => match href {
+            Some(href) => format!("{text} ({href})"),
+            None => text.to_string(),
+        },
+        (_, "mention_name") if links == LinkStyle::Markdown && !text.starts_with('@') => {
+            format!("@{text}")
+        }
+        _ => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_options, prepare_messages};
+
+    const FIXTURE: &str = include_str!("../fixtures/telegram_entities.json");
+
+    fn contents(options: &str) -> Vec<String> {
+        let options = parse_options(options).unwrap();
+        let prepared = prepare_messages(FIXTURE, "telegram", &options).unwrap();
+        prepared.messages.into_iter().map(|m| m.content).collect()
+    }
+
+    #[test]
+    fn test_flatten_keeps_every_entity_text() {
+        let flat = contents(r#"{"merge_consecutive":false}"#);
+        let linked = contents(r#"{"merge_consecutive":false,"include_link_targets":true}"#);
+
+        assert_eq!(flat[0], "Plain message");
+        assert_eq!(flat[1], "Read the docs, ping @alice or see https://example.com");
+        assert_eq!(flat[2], "Big and slanted, run cargo test ending 🔥 quoted");
+        assert_eq!(
+            linked[1],
+            "Read the docs (https://example.com/docs), ping @alice or see https://example.com"
+        );
+    }
+
+    #[test]
+    fn test_markdown_mode() {
+        let markdown = contents(r#"{"merge_consecutive":false,"entities":"markdown"}"#);
+
+        assert_eq!(
+            markdown[1],
+            "Read [the docs](https://example.com/docs), ping @alice or see https://example.com"
+        );
+        assert_eq!(markdown[2], "**Big** and *slanted*, run `cargo test` ending 🔥 quoted");
+        assert_eq!(markdown[3], "```rust\nfn main() {}\n```");
+    }
+
+    #[test]
+    fn test_preserve_links() {
+        const LINKS: &str = include_str!("../fixtures/telegram_text_links.json");
+        let options = parse_options(r#"{"merge_consecutive":false,"preserve_links":true}"#);
+        let prepared = prepare_messages(LINKS, "telegram", &options.unwrap()).unwrap();
+        let contents: Vec<String> = prepared.messages.into_iter().map(|m| m.content).collect();
+
+        assert_eq!(
+            contents[0],
+            "Slides are [here](https://example.com/slides), thanks @Bob Stone and @carol"
+        );
+        // Other formatting stays plain; a link showing its own URL is left as is.
+        assert_eq!(contents[1], "Really important: https://example.com");
+        assert!(parse_options(r#"{"preserve_links":true,"include_link_targets":true}"#).is_err());
+    }
+}
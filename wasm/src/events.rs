@@ -0,0 +1,487 @@
+//! Service messages (joins, pins, calls, title changes) as events.
+//!
+//! chatpack's Telegram parser keeps only `"type": "message"` entries, so the
+//! `"type": "service"` ones are taken from the raw export (`telegram`). Each becomes an event
+//! with the `action` as its kind, the acting user, the members it concerns,
+//! and the one detail worth keeping (call duration, new title, added
+//! members, pinned message id). WhatsApp group notices are read by
+//! `whatsapp::events`.
+//!
+//! Events stay out of conversions unless `include_events` (events mixed into
+//! the messages in time order) or `events_only` is set; as messages, the
+//! actor is the sender and the content a readable sentence, and the output
+//! gains an `action` field naming the service action (`null` on ordinary
+//! messages). `drop_system` keeps them out regardless. Event rows are never
+//! merged with the messages around them.
+
+// Telegram and WhatsApp each build events their own way; builds without
+// both leave part of `Event` unused.
+#![cfg_attr(
+    not(all(feature = "telegram", feature = "whatsapp")),
+    allow(dead_code, unused_variables)
+)]
+
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[cfg(any(feature = "telegram", feature = "whatsapp"))]
+use chatpack::parser::Platform;
+use chatpack::Message;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+use crate::calls::CALL_KINDS;
+use crate::enrich::ExtraColumn;
+use crate::kinds::MessageKind;
+use crate::registry::{self, Backend};
+#[cfg(feature = "telegram")]
+use crate::telegram::{self, RawMessage, TelegramExport};
+#[cfg(feature = "whatsapp")]
+use crate::whatsapp;
+use crate::{js_error, ConvertOptions};
+
+/// Sender of events whose actor the export does not name.
+const UNKNOWN_ACTOR: &str = "Telegram";
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Event {
+    pub kind: String,
+    pub actor: Option<String>,
+    target: Option<String>,
+    timestamp: Option<String>,
+    detail: Option<String>,
+    /// Message id, on Telegram.
+    #[serde(skip)]
+    pub id: Option<u64>,
+    #[serde(skip)]
+    pub time: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub description: String,
+    /// Call length in seconds, on calls.
+    #[serde(skip)]
+    pub duration_seconds: Option<u64>,
+}
+
+impl Event {
+    pub(crate) fn new(
+        kind: &str,
+        actor: Option<String>,
+        target: Option<String>,
+        detail: Option<String>,
+        time: Option<DateTime<Utc>>,
+        description: String,
+    ) -> Self {
+        Event {
+            kind: kind.to_string(),
+            actor,
+            target,
+            timestamp: time.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()),
+            detail,
+            id: None,
+            time,
+            description,
+            duration_seconds: None,
+        }
+    }
+
+    fn into_message(self) -> Message {
+        let mut message =
+            Message::new(self.actor.as_deref().unwrap_or(UNKNOWN_ACTOR), self.description);
+        if let Some(id) = self.id {
+            message = message.with_id(id);
+        }
+        if let Some(time) = self.time {
+            message = message.with_timestamp(time);
+        }
+        message
+    }
+}
+
+/// Service events of a Telegram export or group notices of a WhatsApp one.
+///
+/// Returns a JSON array of `{kind, actor, target, timestamp, detail}`:
+/// `kind` is Telegram's action name (`pin_message`, `phone_call`,
+/// `edit_group_title`, ...) or, on WhatsApp, one of `member_added`,
+/// `member_removed`, `member_left`, `member_joined`, `group_created`,
+/// `subject_changed`, `description_changed`, `icon_changed` and
+/// `icon_deleted`. `target` names the members added or removed,
+/// `timestamp` is `YYYY-MM-DD HH:MM:SS` (UTC; local time on WhatsApp), and
+/// `detail` the new title or subject, call duration (`m:ss`), member list
+/// or pinned message id where the event has one. Other platforms have no
+/// service messages and return `[]`; an export that does not parse is an
+/// error.
+#[wasm_bindgen]
+pub fn events(input: &str, source: &str) -> std::result::Result<String, JsValue> {
+    let backend = registry::find_platform(source).map_err(js_error)?.backend;
+    let events = parse_events(input, backend).map_err(js_error)?;
+
+    serde_json::to_string(&events).map_err(|e| js_error(e.to_string()))
+}
+
+/// Next id for an event row the export does not number (WhatsApp notices).
+/// Marker ids count down from the top of the range, far above any id an
+/// export uses, and stay unique across reads so that `convert_merged` never
+/// takes two of them for the same message.
+static NEXT_MARKER: AtomicU64 = AtomicU64::new(u64::MAX);
+
+/// The event rows among a conversion's messages, by message id: the
+/// export's own where it numbers them (Telegram), else a marker id given
+/// when the row is added. Rows are told apart by id alone, so they stay
+/// event rows whatever later steps do to their content.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EventRows {
+    ids: HashSet<u64>,
+    markers: HashSet<u64>,
+}
+
+impl EventRows {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.ids.is_empty() && self.markers.is_empty()
+    }
+
+    pub(crate) fn contains(&self, message: &Message) -> bool {
+        message.id.is_some_and(|id| self.ids.contains(&id) || self.markers.contains(&id))
+    }
+
+    /// Take the marker ids off `messages` again, once nothing needs to tell
+    /// event rows apart, so they are written without an id as before.
+    pub(crate) fn clear_markers(&self, messages: &mut [Message]) {
+        if self.markers.is_empty() {
+            return;
+        }
+        for message in messages {
+            if message.id.is_some_and(|id| self.markers.contains(&id)) {
+                message.id = None;
+            }
+        }
+    }
+}
+
+/// Add or substitute events per `include_events` / `events_only`, returning
+/// the action of each added event by message id, and the added rows.
+///
+/// Added events go in their place in time: before the first message dated
+/// after them (at the same time, with a higher id). The messages keep their
+/// order, so an undated message stays where it was; `sort` orders
+/// everything later. `service` holds the events already read from the raw
+/// export (Telegram); without it they are parsed from `input`.
+pub(crate) fn apply_events(
+    messages: &mut Vec<Message>,
+    input: &str,
+    backend: Backend,
+    service: Option<Vec<Event>>,
+    options: &ConvertOptions,
+) -> std::result::Result<(HashMap<u64, String>, EventRows), String> {
+    if !wanted(options) {
+        return Ok((HashMap::new(), EventRows::default()));
+    }
+
+    let mut events = match service {
+        Some(events) => events,
+        None => parse_events(input, backend)?,
+    };
+    if options.exclude_kinds.contains(&MessageKind::Call) {
+        events.retain(|event| !CALL_KINDS.contains(&event.kind.as_str()));
+    }
+    let actions = events.iter().filter_map(|event| Some((event.id?, event.kind.clone()))).collect();
+    let mut events: Vec<Message> = events.into_iter().map(Event::into_message).collect();
+    let mut rows = EventRows::default();
+    for event in &mut events {
+        match event.id {
+            Some(id) => rows.ids.insert(id),
+            None => {
+                let marker = NEXT_MARKER.fetch_sub(1, Ordering::Relaxed);
+                event.id = Some(marker);
+                rows.markers.insert(marker)
+            }
+        };
+    }
+
+    if options.events_only {
+        *messages = events;
+    } else {
+        let mut events = events.into_iter().peekable();
+        let mut merged = Vec::with_capacity(messages.len() + events.len());
+        for message in messages.drain(..) {
+            while let Some(event) = events.next_if(|event| precedes(event, &message)) {
+                merged.push(event);
+            }
+            merged.push(message);
+        }
+        merged.extend(events);
+        *messages = merged;
+    }
+
+    Ok((actions, rows))
+}
+
+/// Whether `options` add events to a conversion.
+pub(crate) fn wanted(options: &ConvertOptions) -> bool {
+    !options.drop_system && (options.include_events || options.events_only)
+}
+
+/// Whether `event` goes before `message`: it is dated earlier, or at the
+/// same time with a lower id.
+fn precedes(event: &Message, message: &Message) -> bool {
+    match (event.timestamp, message.timestamp) {
+        (Some(event_time), Some(message_time)) if event_time == message_time => {
+            matches!((event.id, message.id), (Some(a), Some(b)) if a < b)
+        }
+        (Some(event_time), Some(message_time)) => event_time < message_time,
+        _ => false,
+    }
+}
+
+/// `action` column for `messages`, if any of them is an event.
+pub(crate) fn action_column(
+    messages: &[Message],
+    actions: &HashMap<u64, String>,
+) -> Option<ExtraColumn> {
+    if actions.is_empty() {
+        return None;
+    }
+    let values = messages
+        .iter()
+        .map(|m| {
+            m.id.and_then(|id| actions.get(&id)).map_or(Value::Null, |a| Value::from(a.as_str()))
+        })
+        .collect();
+
+    Some(ExtraColumn::new("action", values))
+}
+
+/// Events of `input`, or why it does not parse. Blank input is an export
+/// without events, as it is an export without messages.
+pub(crate) fn parse_events(
+    input: &str,
+    backend: Backend,
+) -> std::result::Result<Vec<Event>, String> {
+    if input.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let events = match backend {
+        #[cfg(feature = "telegram")]
+        Backend::Core(Platform::Telegram) => telegram_service_events(input)?,
+        #[cfg(feature = "whatsapp")]
+        Backend::Core(Platform::WhatsApp) => whatsapp::events(input),
+        _ => Vec::new(),
+    };
+
+    Ok(events)
+}
+
+/// Service events of a Telegram export, or why `input` is not one.
+#[cfg(feature = "telegram")]
+pub(crate) fn telegram_service_events(input: &str) -> std::result::Result<Vec<Event>, String> {
+    Ok(telegram_events(&telegram::parse(input)?))
+}
+
+/// Service events of a parsed Telegram export.
+#[cfg(feature = "telegram")]
+pub(crate) fn telegram_events(export: &TelegramExport) -> Vec<Event> {
+    export.messages.iter().filter(|raw| raw.kind == "service").filter_map(event).collect()
+}
+
+#[cfg(feature = "telegram")]
+fn event(raw: &RawMessage) -> Option<Event> {
+    let kind = raw.action.clone()?;
+    let time = raw
+        .date_unixtime
+        .as_deref()
+        .and_then(|secs| secs.trim().parse().ok())
+        .and_then(|secs| DateTime::from_timestamp(secs, 0));
+    let members: Vec<&str> = raw.members.iter().flatten().map(String::as_str).collect();
+    let call = |seconds: Option<u64>| seconds.map(|s| format!("{}:{:02}", s / 60, s % 60));
+    let duration_seconds = match kind.as_str() {
+        "phone_call" => raw.duration_seconds,
+        "group_call" => raw.duration,
+        _ => None,
+    };
+
+    let target = match kind.as_str() {
+        "invite_members" | "remove_members" => Some(members.join(", ")),
+        _ => None,
+    };
+    let (detail, description) = match kind.as_str() {
+        "create_group" | "create_channel" => {
+            (raw.title.clone(), quoted("created the group", raw.title.as_deref()))
+        }
+        "edit_group_title" => {
+            (raw.title.clone(), quoted("changed the group title to", raw.title.as_deref()))
+        }
+        "edit_group_photo" => (None, "changed the group photo".to_string()),
+        "delete_group_photo" => (None, "removed the group photo".to_string()),
+        "invite_members" => (Some(members.join(", ")), format!("added {}", members.join(", "))),
+        "remove_members" => (Some(members.join(", ")), format!("removed {}", members.join(", "))),
+        "join_group_by_link" => (None, "joined the group via invite link".to_string()),
+        "pin_message" => (raw.message_id.map(|id| id.to_string()), "pinned a message".to_string()),
+        "phone_call" if raw.discard_reason.as_deref() == Some("missed") => {
+            (None, "missed call".to_string())
+        }
+        "phone_call" => {
+            let duration = call(raw.duration_seconds);
+            (duration.clone(), with_duration("started a call", duration.as_deref()))
+        }
+        "group_call" => {
+            let duration = call(raw.duration);
+            (duration.clone(), with_duration("started a video chat", duration.as_deref()))
+        }
+        "migrate_to_supergroup" | "migrate_from_group" => {
+            (None, "upgraded the group to a supergroup".to_string())
+        }
+        other => (None, other.replace('_', " ")),
+    };
+
+    Some(Event {
+        id: Some(raw.id),
+        duration_seconds,
+        ..Event::new(&kind, raw.actor.clone(), target, detail, time, description)
+    })
+}
+
+#[cfg(feature = "telegram")]
+fn quoted(text: &str, value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("{text} \"{value}\""),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(feature = "telegram")]
+fn with_duration(text: &str, duration: Option<&str>) -> String {
+    match duration {
+        Some(duration) => format!("{text} ({duration})"),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_options, prepare_messages};
+
+    const FIXTURE: &str = include_str!("../fixtures/telegram_group_events.json");
+
+    fn messages(options: &str) -> Vec<(String, String)> {
+        let options = parse_options(options).unwrap();
+        let prepared = prepare_messages(FIXTURE, "telegram", &options).unwrap();
+        prepared.messages.into_iter().map(|m| (m.sender, m.content)).collect()
+    }
+
+    #[test]
+    fn test_events_structured() {
+        let events: serde_json::Value =
+            serde_json::from_str(&parse_events_json(FIXTURE, "telegram")).unwrap();
+
+        assert_eq!(events.as_array().unwrap().len(), 9);
+        assert_eq!(events[0]["kind"], "create_group");
+        assert_eq!(events[0]["detail"], "Book club");
+        assert_eq!(events[1]["detail"], "11");
+        assert_eq!(events[3]["kind"], "group_call");
+        assert_eq!(events[3]["detail"], "5:32");
+        assert_eq!(events[4]["detail"], "Sci-fi club");
+        assert_eq!(events[6]["detail"], "1:35");
+        assert_eq!(events[6]["timestamp"], "2024-01-11 08:02:00");
+        assert_eq!(parse_events_json(FIXTURE, "whatsapp"), "[]");
+    }
+
+    #[test]
+    fn test_events_in_conversion() {
+        let default = messages(r#"{"merge_consecutive":false}"#);
+        let included = messages(r#"{"merge_consecutive":false,"include_events":true}"#);
+        let only = messages(r#"{"events_only":true,"merge_consecutive":false}"#);
+
+        assert_eq!(default.len(), 2);
+        assert_eq!(included.len(), 11);
+        assert_eq!(included[0], ("Carol".into(), "created the group \"Book club\"".into()));
+        assert_eq!(included[1].1, "First book: Dune");
+        assert_eq!(included[2].1, "pinned a message");
+        assert_eq!(only.len(), 9);
+        assert_eq!(only[3], ("Alice".into(), "started a video chat (5:32)".into()));
+        assert_eq!(only[8].1, "set chat theme");
+    }
+
+    #[test]
+    fn test_events_keep_message_order_and_stay_unmerged() {
+        let undated = r#"{"id": 99, "type": "message", "from": "Dave", "text": "Undated"},"#;
+        let input = FIXTURE.replacen(r#"{"id": 12,"#, &format!(r#"{undated}{{"id": 12,"#), 1);
+        let options = parse_options(r#"{"include_events":true}"#).unwrap();
+        let prepared = prepare_messages(&input, "telegram", &options).unwrap();
+        let contents: Vec<&str> = prepared.messages.iter().map(|m| m.content.as_str()).collect();
+
+        assert_eq!(
+            contents[..5],
+            [
+                "created the group \"Book club\"",
+                "First book: Dune",
+                "Undated",
+                "pinned a message",
+                "added Bob",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_action_field_and_drop_system() {
+        let options =
+            parse_options(r#"{"include_events":true,"merge_consecutive":false}"#).unwrap();
+        let report = crate::run_conversion(FIXTURE, "telegram", "jsonl", &options).unwrap();
+        let lines: Vec<serde_json::Value> =
+            report.output.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+
+        assert_eq!(lines[1]["action"], serde_json::Value::Null);
+        assert_eq!(lines[2]["action"], "pin_message");
+        assert_eq!(lines[8]["action"], "phone_call");
+
+        let dropped =
+            messages(r#"{"include_events":true,"drop_system":true,"merge_consecutive":false}"#);
+        assert_eq!(dropped.len(), 2);
+        assert!(parse_options(r#"{"events_only":true,"drop_system":true}"#).is_err());
+    }
+
+    #[test]
+    fn test_unnumbered_event_rows_stay_apart_from_messages() {
+        let group = include_str!("../fixtures/whatsapp_group_events.txt");
+        let input = format!(
+            "{group}[15.01.24, 10:08:00] Bob: left\n[15.01.24, 10:09:00] Bob: my keys here\n"
+        )
+        .replace("\"Lisbon trip\"", "\"https://lisbon.example.com\"");
+        let options =
+            parse_options(r#"{"include_events":true,"urls":"drop","strip_control_chars":true}"#);
+        let prepared = prepare_messages(&input, "whatsapp", &options.unwrap()).unwrap();
+        let rows: Vec<(&str, &str, Option<u64>)> = prepared
+            .messages
+            .iter()
+            .map(|m| (m.sender.as_str(), m.content.as_str(), m.id))
+            .collect();
+
+        assert_eq!(rows[2], ("Alice", "Welcome!", None));
+        assert_eq!(rows[3], ("Alice", "changed the subject to \"\"", None));
+        assert_eq!(
+            rows[6..],
+            [
+                ("Bob", "left", None),
+                ("Alice", "Bye Bob", None),
+                ("Bob", "left\nmy keys here", None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unparsable_export_is_an_error() {
+        let backend = registry::find_platform("telegram").unwrap().backend;
+
+        assert!(parse_events("{not an export", backend)
+            .unwrap_err()
+            .starts_with("Failed to parse"));
+        assert_eq!(parse_events_json("  ", "telegram"), "[]");
+    }
+
+    fn parse_events_json(input: &str, source: &str) -> String {
+        let backend = registry::find_platform(source).unwrap().backend;
+        serde_json::to_string(&parse_events(input, backend).unwrap()).unwrap()
+    }
+}
@@ -0,0 +1,302 @@
+//! Chat-format fine-tuning examples, one conversation per JSONL line.
+
+use chatpack::Message;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::pairs::{role_of, session_gap, session_starts, Role};
+use crate::tokens::estimate_tokens;
+use crate::{js_error, parse_options, prepare_messages};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Style {
+    /// `{"messages": [{"role", "content"}]}`
+    #[default]
+    OpenAi,
+    /// `{"conversations": [{"from", "value"}]}`
+    ShareGpt,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct FinetuneOptions {
+    style: Style,
+    assistant: Option<String>,
+    user: Option<String>,
+    system_prompt: Option<String>,
+    max_turns: Option<usize>,
+    max_tokens_per_example: Option<usize>,
+    require_assistant_last: bool,
+    session_gap_minutes: Option<u64>,
+    /// Same as `assistant`, named as in `convert_with_options`.
+    self_sender: Option<String>,
+    /// Same as `max_turns`.
+    window_size: Option<usize>,
+}
+
+impl Default for FinetuneOptions {
+    fn default() -> Self {
+        Self {
+            style: Style::OpenAi,
+            assistant: None,
+            user: None,
+            system_prompt: None,
+            max_turns: None,
+            max_tokens_per_example: None,
+            require_assistant_last: true,
+            session_gap_minutes: None,
+            self_sender: None,
+            window_size: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Turn {
+    role: Role,
+    content: String,
+}
+
+/// Convert chat export into fine-tuning conversations, one JSON object per line.
+///
+/// `options_json` takes the `convert_with_options` keys (filters, merging)
+/// plus:
+/// - `assistant` (required; `self_sender` is accepted instead) and `user`:
+///   senders mapped to the two roles, as in `extract_pairs`; other senders
+///   are left out and adjacent turns of the same role are merged. With
+///   `anonymize` they still name the original senders
+/// - `style`: `"openai"` (`{"messages": [{role, content}]}`, default) or
+///   `"sharegpt"` (`{"conversations": [{from, value}]}`)
+/// - `system_prompt`: prepended to every example as a system turn
+/// - `max_turns` (or `window_size`): user and assistant turns per example
+///   (at least 2)
+/// - `max_tokens_per_example`: estimated tokens per example, system prompt
+///   included
+/// - `require_assistant_last` (default `true`): drop a trailing user turn
+///   that got no reply
+/// - `session_gap_minutes`: examples never span a longer silence
+///
+/// Examples start on a user turn, and an example over a limit is split
+/// between an assistant turn and the next user turn; one user/assistant
+/// exchange always stays together, even if it alone is over the token cap.
+#[wasm_bindgen]
+pub fn convert_finetune(
+    input: &str,
+    source: &str,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    finetune(input, source, options_json).map_err(js_error)
+}
+
+fn finetune(input: &str, source: &str, options_json: &str) -> std::result::Result<String, String> {
+    let options = parse_options(options_json)?;
+    let mut finetune_options = parse_finetune_options(options_json)?;
+    let prepared = prepare_messages(input, source, &options)?;
+    for name in [&mut finetune_options.assistant, &mut finetune_options.user].into_iter().flatten()
+    {
+        *name = prepared.sender_named(name).to_string();
+    }
+
+    let mut lines = String::new();
+    for session in sessions(&prepared.messages, &finetune_options) {
+        for example in examples(session, &finetune_options) {
+            lines.push_str(&render(&example, &finetune_options).to_string());
+            lines.push('\n');
+        }
+    }
+
+    Ok(lines)
+}
+
+fn parse_finetune_options(options_json: &str) -> std::result::Result<FinetuneOptions, String> {
+    let mut options: FinetuneOptions = if options_json.trim().is_empty() {
+        FinetuneOptions::default()
+    } else {
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid options JSON: {e}"))?
+    };
+    options.assistant = options.assistant.take().or(options.self_sender.take());
+    options.max_turns = options.max_turns.or(options.window_size);
+    if options.assistant.as_deref().is_none_or(|name| name.trim().is_empty()) {
+        return Err("Missing option: 'assistant' must name the sender of completions".to_string());
+    }
+    if options.max_turns.is_some_and(|turns| turns < 2) {
+        return Err("Invalid max_turns: must be at least 2".to_string());
+    }
+
+    Ok(options)
+}
+
+/// Role turns per session, starting on a user turn.
+fn sessions(messages: &[Message], options: &FinetuneOptions) -> Vec<Vec<Turn>> {
+    let assistant = options.assistant.as_deref().unwrap_or_default();
+    let starts = session_starts(messages, session_gap(options.session_gap_minutes));
+
+    let mut sessions: Vec<Vec<Turn>> = Vec::new();
+    for (message, start) in messages.iter().zip(starts) {
+        if start {
+            sessions.push(Vec::new());
+        }
+        let Some(role) = role_of(&message.sender, assistant, options.user.as_deref()) else {
+            continue;
+        };
+        let Some(session) = sessions.last_mut() else {
+            continue;
+        };
+        match session.last_mut() {
+            Some(last) if last.role == role => {
+                last.content.push('\n');
+                last.content.push_str(&message.content);
+            }
+            None if role == Role::Assistant => {}
+            _ => session.push(Turn { role, content: message.content.clone() }),
+        }
+    }
+
+    sessions
+}
+
+/// Split one session into examples at exchange boundaries.
+fn examples(session: Vec<Turn>, options: &FinetuneOptions) -> Vec<Vec<Turn>> {
+    let system_tokens = options.system_prompt.as_deref().map_or(0, estimate_tokens);
+    let over_limit = |turns: usize, tokens: usize| {
+        options.max_turns.is_some_and(|max| turns > max)
+            || options.max_tokens_per_example.is_some_and(|max| tokens > max)
+    };
+
+    let mut examples = Vec::new();
+    let mut current: Vec<Turn> = Vec::new();
+    let mut current_tokens = system_tokens;
+    let mut turns = session.into_iter().peekable();
+    while let Some(user) = turns.next() {
+        let user_tokens = estimate_tokens(&user.content);
+        let Some(reply) = turns.next_if(|turn| turn.role == Role::Assistant) else {
+            // Only the last turn of a session can lack a reply.
+            if !options.require_assistant_last
+                && !over_limit(current.len() + 1, current_tokens + user_tokens)
+            {
+                current.push(user);
+            }
+            break;
+        };
+
+        let exchange_tokens = user_tokens + estimate_tokens(&reply.content);
+        if !current.is_empty() && over_limit(current.len() + 2, current_tokens + exchange_tokens) {
+            examples.push(std::mem::take(&mut current));
+            current_tokens = system_tokens;
+        }
+        current_tokens += exchange_tokens;
+        current.extend([user, reply]);
+    }
+    if !current.is_empty() {
+        examples.push(current);
+    }
+
+    examples
+}
+
+fn render(example: &[Turn], options: &FinetuneOptions) -> Value {
+    let (role_key, content_key, system, user, assistant) = match options.style {
+        Style::OpenAi => ("role", "content", "system", "user", "assistant"),
+        Style::ShareGpt => ("from", "value", "system", "human", "gpt"),
+    };
+    let entry = |role: &str, content: &str| json!({ role_key: role, content_key: content });
+
+    let mut turns: Vec<Value> = Vec::with_capacity(example.len() + 1);
+    if let Some(prompt) = options.system_prompt.as_deref().filter(|p| !p.is_empty()) {
+        turns.push(entry(system, prompt));
+    }
+    for turn in example {
+        let role = if turn.role == Role::Assistant { assistant } else { user };
+        turns.push(entry(role, &turn.content));
+    }
+
+    match options.style {
+        Style::OpenAi => json!({ "messages": turns }),
+        Style::ShareGpt => json!({ "conversations": turns }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHATSAPP: &str = "[15.01.24, 10:00:00] Bot: welcome\n\
+[15.01.24, 10:00:10] Ann: hi\n\
+[15.01.24, 10:00:20] Ann: question one\n\
+[15.01.24, 10:01:00] Bot: answer one\n\
+[15.01.24, 10:02:00] Ann: question two\n\
+[15.01.24, 10:03:00] Bot: answer two\n\
+[15.01.24, 10:04:00] Ann: question three\n\
+[15.01.24, 10:05:00] Bot: answer three\n\
+[15.01.24, 10:06:00] Ann: unanswered\n";
+
+    fn examples_for(options: &str) -> Vec<Value> {
+        finetune(WHATSAPP, "whatsapp", options)
+            .expect("finetune should succeed")
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_openai_examples_split_by_max_turns() {
+        let examples =
+            examples_for(r#"{"assistant":"Bot","system_prompt":"Be nice","max_turns":4}"#);
+
+        assert_eq!(examples.len(), 2);
+        let first = examples[0]["messages"].as_array().unwrap();
+        assert_eq!(first.len(), 5);
+        assert_eq!(first[0], json!({"role": "system", "content": "Be nice"}));
+        assert_eq!(first[1], json!({"role": "user", "content": "hi\nquestion one"}));
+        assert_eq!(first[4]["content"], "answer two");
+        let second = examples[1]["messages"].as_array().unwrap();
+        assert_eq!(second.last().unwrap()["role"], "assistant");
+        assert_eq!(second[1]["content"], "question three");
+    }
+
+    #[test]
+    fn test_sharegpt_style_and_trailing_user_turn() {
+        let examples = examples_for(
+            r#"{"assistant":"Bot","style":"sharegpt","require_assistant_last":false}"#,
+        );
+
+        assert_eq!(examples.len(), 1);
+        let turns = examples[0]["conversations"].as_array().unwrap();
+        assert_eq!(turns[0], json!({"from": "human", "value": "hi\nquestion one"}));
+        assert_eq!(turns[1]["from"], "gpt");
+        assert_eq!(turns.last().unwrap()["value"], "unanswered");
+        assert!(finetune(WHATSAPP, "whatsapp", r#"{"assistant":"Bot","max_turns":1}"#).is_err());
+    }
+
+    #[test]
+    fn test_token_cap_splits_between_exchanges() {
+        let examples = examples_for(r#"{"assistant":"Bot","max_tokens_per_example":8}"#);
+
+        assert_eq!(examples.len(), 3);
+        assert!(examples.iter().all(|e| e["messages"].as_array().unwrap().len() == 2));
+    }
+
+    #[test]
+    fn test_self_sender_and_window_size() {
+        let examples = examples_for(r#"{"self_sender":"Bot","window_size":2}"#);
+
+        assert_eq!(examples.len(), 3);
+        for example in &examples {
+            let turns = example["messages"].as_array().unwrap();
+            let roles: Vec<&str> = turns.iter().map(|t| t["role"].as_str().unwrap()).collect();
+            assert_eq!(roles, ["user", "assistant"]);
+            assert!(turns.iter().all(|t| t.as_object().unwrap().len() == 2));
+        }
+        assert_eq!(examples[2]["messages"][1]["content"], "answer three");
+    }
+
+    #[test]
+    fn test_roles_match_anonymized_senders() {
+        let seeded = r#"{"self_sender":"Bot","user":"Ann","anonymize":true,"anonymize_seed":"s"}"#;
+
+        assert_eq!(examples_for(seeded), examples_for(r#"{"self_sender":"Bot","user":"Ann"}"#));
+        assert_eq!(examples_for(r#"{"assistant":"Bot","anonymize":true}"#).len(), 1);
+    }
+}
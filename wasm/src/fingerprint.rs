@@ -0,0 +1,85 @@
+//! A content hash identifying a conversation across export formats.
+//!
+//! The fingerprint is SHA-256 over the parsed messages in export order,
+//! each as its sender, content and timestamp. It depends only on those:
+//! conversion options are not involved, and the same messages read from a
+//! platform export or from this converter's own CSV/JSON/JSONL output give
+//! the same hash. Surrounding whitespace and CRLF line endings are
+//! normalized, and timestamps count whole seconds since the epoch, since
+//! that is what every format keeps. Each field is length-prefixed, so no
+//! two different message lists hash the same input bytes.
+
+use chatpack::Message;
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+
+use crate::{js_error, parse_messages};
+
+/// Fingerprint of the conversation in `input`.
+///
+/// Returns SHA-256 as 64 lowercase hex digits. Two exports with the same
+/// senders, contents and timestamps in the same order get the same
+/// fingerprint whatever their format; a changed, added or removed message
+/// changes it. A message's id, reply and edit time are not part of it.
+#[wasm_bindgen]
+pub fn fingerprint(input: &str, source: &str) -> std::result::Result<String, JsValue> {
+    let messages = parse_messages(input, source).map_err(js_error)?;
+
+    Ok(fingerprint_of(&messages))
+}
+
+pub(crate) fn fingerprint_of(messages: &[Message]) -> String {
+    let mut hasher = Sha256::new();
+    for message in messages {
+        let content = message.content.replace("\r\n", "\n");
+        let timestamp = message.timestamp.map(|t| t.timestamp().to_string()).unwrap_or_default();
+        for field in [message.sender.trim(), content.trim(), &timestamp] {
+            hasher.update((field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+    }
+
+    hasher.finalize().iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_options, run_conversion};
+
+    const TELEGRAM: &str = r#"{"messages": [
+  {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Alice", "text": "Hello"},
+  {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Bob", "text": "Hi; \"there\"\nsecond line"},
+  {"id": 3, "type": "message", "date_unixtime": "1705314720", "from": "Alice", "text": "Bye", "reply_to_message_id": 2}
+]}"#;
+
+    fn of(input: &str, source: &str) -> String {
+        fingerprint(input, source).map_err(|e| e.as_string().unwrap_or_default()).unwrap()
+    }
+
+    #[test]
+    fn test_same_content_in_different_formats_matches() {
+        let original = of(TELEGRAM, "telegram");
+        let options =
+            parse_options(r#"{"merge_consecutive":false,"include_timestamps":true}"#).unwrap();
+        let export = |format: &str| run_conversion(TELEGRAM, "telegram", format, &options).unwrap();
+
+        assert_eq!(original.len(), 64);
+        assert_eq!(of(&export("csv").output, "chatpack-csv"), original);
+        assert_eq!(of(&export("jsonl").output, "chatpack-jsonl"), original);
+        assert_eq!(of(&export("json").output, "chatpack-json"), original);
+    }
+
+    #[test]
+    fn test_content_changes_the_fingerprint() {
+        let original = of(TELEGRAM, "telegram");
+
+        assert_ne!(of(&TELEGRAM.replace("Bye", "Bye!"), "telegram"), original);
+        assert_ne!(of(&TELEGRAM.replace("1705314720", "1705314721"), "telegram"), original);
+        assert_eq!(of(&TELEGRAM.replace(", \"reply_to_message_id\": 2", ""), "telegram"), original);
+        assert_eq!(
+            of("", "telegram"),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
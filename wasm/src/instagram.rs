@@ -0,0 +1,234 @@
+//! Instagram shares, reels and story replies as text.
+//!
+//! A DM that forwards a reel or post carries a `share` object (`link`,
+//! `share_text`, `original_content_owner`) and at most Meta's "sent an
+//! attachment." in `content`; a story reply has a `story_share` marker next
+//! to the reply text. chatpack keeps the boilerplate or drops the entry, so
+//! the export is read again and these messages get a content of their own:
+//! the `[media]` placeholder by default, or, with `expand_embeds`,
+//! `[shared a reel: ...]`, `[shared a post by ...]` or `[replied to your
+//! story]`. Meta changes this schema often; a share whose shape is not
+//! recognized becomes the placeholder rather than an error.
+
+use std::collections::{HashMap, HashSet};
+
+use chatpack::parser::Platform;
+use chatpack::Message;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::kinds::{MessageKind, MEDIA_PLACEHOLDER};
+use crate::registry::Backend;
+
+/// What Meta writes as the content of a message that only shares something.
+const ATTACHMENT_BOILERPLATE: &str = "sent an attachment.";
+
+#[derive(Debug, Deserialize)]
+struct InstagramExport {
+    #[serde(default)]
+    messages: Vec<RawMessage>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawMessage {
+    sender_name: String,
+    timestamp_ms: Option<i64>,
+    content: Option<String>,
+    share: Option<Value>,
+    story_share: Option<Value>,
+}
+
+impl RawMessage {
+    fn is_share(&self) -> bool {
+        self.share.is_some() || self.story_share.is_some()
+    }
+
+    /// Reply or caption text the sender typed, without Meta's boilerplate.
+    fn text(&self) -> Option<String> {
+        let content = fix_encoding(self.content.as_deref()?.trim());
+        (!content.is_empty() && !content.ends_with(ATTACHMENT_BOILERPLATE)).then_some(content)
+    }
+
+    fn render(&self, expand: bool) -> String {
+        let marker = if expand { self.describe() } else { MEDIA_PLACEHOLDER.to_string() };
+        match self.text() {
+            Some(text) => format!("{marker}\n{text}"),
+            None => marker,
+        }
+    }
+
+    fn describe(&self) -> String {
+        let share = self.share.as_ref();
+        let field = |key: &str| {
+            share
+                .and_then(|share| share.get(key))
+                .and_then(Value::as_str)
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(fix_encoding)
+        };
+        let link = field("link");
+
+        if self.story_share.is_some() || link.as_deref().is_some_and(|l| l.contains("/stories/")) {
+            "[replied to your story]".to_string()
+        } else if let Some(reel) = link.as_deref().filter(|l| l.contains("/reel/")) {
+            format!("[shared a reel: {reel}]")
+        } else if let Some(owner) = field("original_content_owner") {
+            format!("[shared a post by {owner}]")
+        } else if let Some(caption) = field("share_text") {
+            format!("[shared a post: {caption}]")
+        } else if let Some(link) = link {
+            format!("[shared a link: {link}]")
+        } else {
+            MEDIA_PLACEHOLDER.to_string()
+        }
+    }
+}
+
+/// Sender and time a share is matched to chatpack's message by.
+type ShareKey = (String, Option<DateTime<Utc>>);
+
+/// Give shared reels, posts and story replies their content, adding the
+/// ones chatpack skipped, unless `exclude` lists [`MessageKind::Media`].
+pub(crate) fn render_shares(
+    messages: &mut Vec<Message>,
+    input: &str,
+    backend: Backend,
+    expand: bool,
+    exclude: &[MessageKind],
+) {
+    if !matches!(backend, Backend::Core(Platform::Instagram)) {
+        return;
+    }
+    let Ok(export) = serde_json::from_str::<InstagramExport>(input) else {
+        return;
+    };
+
+    // In export order (newest first); a repeated key keeps the last one.
+    let shares: Vec<(ShareKey, String)> = export
+        .messages
+        .iter()
+        .filter(|raw| raw.is_share())
+        .map(|raw| {
+            let time = raw.timestamp_ms.and_then(DateTime::from_timestamp_millis);
+            ((fix_encoding(&raw.sender_name), time), raw.render(expand))
+        })
+        .collect();
+    if shares.is_empty() {
+        return;
+    }
+    let rendered: HashMap<&ShareKey, &String> =
+        shares.iter().map(|(key, content)| (key, content)).collect();
+    let excluded = exclude.contains(&MessageKind::Media);
+
+    let mut seen = HashSet::new();
+    messages.retain_mut(|message| {
+        let key = (message.sender.clone(), message.timestamp);
+        let Some(content) = rendered.get(&key) else {
+            return true;
+        };
+        message.content.clone_from(content);
+        seen.insert(key);
+        !excluded
+    });
+    if excluded {
+        return;
+    }
+
+    // Oldest first, so shares with the same timestamp keep their order.
+    let before = messages.len();
+    for (key, content) in shares.iter().rev() {
+        if !seen.insert(key.clone()) {
+            continue;
+        }
+        let (sender, time) = key;
+        let mut message = Message::new(sender.clone(), content.clone());
+        if let Some(time) = time {
+            message = message.with_timestamp(*time);
+        }
+        messages.push(message);
+    }
+    if messages.len() > before {
+        messages.sort_by_key(|m| m.timestamp);
+    }
+}
+
+/// Undo Meta's encoding of UTF-8 bytes as Latin-1 code points.
+fn fix_encoding(text: &str) -> String {
+    if text.is_ascii() || text.chars().any(|c| u32::from(c) > 0xff) {
+        return text.to_string();
+    }
+    let bytes: Vec<u8> = text.chars().map(|c| c as u8).collect();
+
+    String::from_utf8(bytes).unwrap_or_else(|_| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_options, prepare_messages};
+
+    const INSTAGRAM: &str = r#"{
+  "participants": [{"name": "Ann"}, {"name": "Ben"}],
+  "messages": [
+    {"sender_name": "Ben", "timestamp_ms": 1705315000000, "content": "haha same"},
+    {"sender_name": "Ann", "timestamp_ms": 1705314900000, "content": "so true", "story_share": "Ben's story"},
+    {"sender_name": "Ben", "timestamp_ms": 1705314800000, "content": "Ben sent an attachment.",
+     "share": {"link": "https://www.instagram.com/p/Cx1/", "share_text": "Sunset", "original_content_owner": "natgeo"}},
+    {"sender_name": "Ann", "timestamp_ms": 1705314700000,
+     "share": {"link": "https://www.instagram.com/reel/Cx2/"}},
+    {"sender_name": "Ann", "timestamp_ms": 1705314600000, "share": {"profile_share_id": 42}}
+  ]
+}"#;
+
+    fn contents(options: &str) -> Vec<String> {
+        let options = parse_options(options).unwrap();
+        let prepared = prepare_messages(INSTAGRAM, "instagram", &options).unwrap();
+        prepared.messages.into_iter().map(|m| m.content).collect()
+    }
+
+    #[test]
+    fn test_shares_expanded() {
+        assert_eq!(
+            contents(r#"{"merge_consecutive":false,"expand_embeds":true}"#),
+            [
+                "[media]",
+                "[shared a reel: https://www.instagram.com/reel/Cx2/]",
+                "[shared a post by natgeo]",
+                "[replied to your story]\nso true",
+                "haha same",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shares_as_placeholders_or_excluded() {
+        assert_eq!(
+            contents(r#"{"merge_consecutive":false}"#),
+            ["[media]", "[media]", "[media]", "[media]\nso true", "haha same"]
+        );
+        assert_eq!(
+            contents(r#"{"merge_consecutive":false,"exclude_kinds":["media"]}"#),
+            ["haha same"]
+        );
+        assert_eq!(fix_encoding("caf\u{c3}\u{a9}"), "café");
+    }
+
+    #[test]
+    fn test_added_shares_with_one_timestamp_keep_export_order() {
+        let input = r#"{"messages": [
+    {"sender_name": "Cy", "timestamp_ms": 1705314600000, "share": {"link": "https://www.instagram.com/p/C/"}},
+    {"sender_name": "Ben", "timestamp_ms": 1705314600000, "share": {"link": "https://www.instagram.com/p/B/"}},
+    {"sender_name": "Ann", "timestamp_ms": 1705314600000, "share": {"link": "https://www.instagram.com/p/A/"}}
+]}"#;
+        let options = parse_options(r#"{"merge_consecutive":false}"#).unwrap();
+
+        for _ in 0..8 {
+            let prepared = prepare_messages(input, "instagram", &options).unwrap();
+            let senders: Vec<_> = prepared.messages.iter().map(|m| m.sender.as_str()).collect();
+            assert_eq!(senders, ["Ann", "Ben", "Cy"]);
+        }
+    }
+}
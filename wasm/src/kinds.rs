@@ -0,0 +1,29 @@
+//! Kinds of messages whose content is rendered from structured fields.
+//!
+//! Telegram polls, locations and contacts (`shares`), Instagram shares
+//! (`instagram`) and calls (`calls`) each get a content of their own, and
+//! `exclude_kinds` leaves whole kinds out. The kinds are known in every
+//! build, so the option parses the same whichever platforms are compiled in.
+
+use serde::{Deserialize, Serialize};
+
+/// Content of a message that only carries media.
+#[cfg(any(feature = "discord", feature = "instagram", feature = "whatsapp"))]
+pub(crate) const MEDIA_PLACEHOLDER: &str = "[media]";
+
+/// Kinds of messages whose content is rendered from structured fields, as
+/// named in `exclude_kinds`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum MessageKind {
+    Poll,
+    /// A pinned or live location.
+    Location,
+    /// A location with a place name.
+    Venue,
+    Contact,
+    /// A shared post, reel or story (Instagram).
+    Media,
+    /// A call (see `calls`).
+    Call,
+}
@@ -0,0 +1,137 @@
+//! Per-message language identification (`language` feature).
+//!
+//! Detection uses `whatlang`'s trigram models and reports ISO 639-1 codes.
+//! Text that gives a trigram model too little to go on, such as short
+//! replies, emoji, bare links or a guess whatlang itself flags as unreliable,
+//! is reported as unknown (`None`) instead of as a random language.
+
+use std::collections::BTreeMap;
+
+use chatpack::Message;
+
+use crate::words::{tokens, TokenKind, TokenizeOptions};
+
+/// Fewest letters (in words, so not counting links) worth running detection on.
+const MIN_LETTERS: usize = 12;
+
+/// Key for messages without a detected language in `distribution`.
+const UNKNOWN: &str = "unknown";
+
+/// whatlang's ISO 639-3 codes with their ISO 639-1 equivalents.
+const ISO_639_1: &[(&str, &str)] = &[
+    ("afr", "af"),
+    ("aka", "ak"),
+    ("amh", "am"),
+    ("ara", "ar"),
+    ("aze", "az"),
+    ("bel", "be"),
+    ("ben", "bn"),
+    ("bul", "bg"),
+    ("cat", "ca"),
+    ("ces", "cs"),
+    ("cmn", "zh"),
+    ("dan", "da"),
+    ("deu", "de"),
+    ("ell", "el"),
+    ("eng", "en"),
+    ("epo", "eo"),
+    ("est", "et"),
+    ("fin", "fi"),
+    ("fra", "fr"),
+    ("guj", "gu"),
+    ("heb", "he"),
+    ("hin", "hi"),
+    ("hrv", "hr"),
+    ("hun", "hu"),
+    ("hye", "hy"),
+    ("ind", "id"),
+    ("ita", "it"),
+    ("jav", "jv"),
+    ("jpn", "ja"),
+    ("kan", "kn"),
+    ("kat", "ka"),
+    ("khm", "km"),
+    ("kor", "ko"),
+    ("lat", "la"),
+    ("lav", "lv"),
+    ("lit", "lt"),
+    ("mal", "ml"),
+    ("mar", "mr"),
+    ("mkd", "mk"),
+    ("mya", "my"),
+    ("nep", "ne"),
+    ("nld", "nl"),
+    ("nob", "nb"),
+    ("ori", "or"),
+    ("pan", "pa"),
+    ("pes", "fa"),
+    ("pol", "pl"),
+    ("por", "pt"),
+    ("ron", "ro"),
+    ("rus", "ru"),
+    ("sin", "si"),
+    ("slk", "sk"),
+    ("slv", "sl"),
+    ("sna", "sn"),
+    ("spa", "es"),
+    ("srp", "sr"),
+    ("swe", "sv"),
+    ("tam", "ta"),
+    ("tel", "te"),
+    ("tgl", "tl"),
+    ("tha", "th"),
+    ("tuk", "tk"),
+    ("tur", "tr"),
+    ("ukr", "uk"),
+    ("urd", "ur"),
+    ("uzb", "uz"),
+    ("vie", "vi"),
+    ("yid", "yi"),
+    ("zul", "zu"),
+];
+
+/// ISO 639-1 code of `text`'s language, or `None` if it cannot be told reliably.
+pub(crate) fn detect(text: &str) -> Option<&'static str> {
+    let words: Vec<_> = tokens(text, &TokenizeOptions::default())
+        .into_iter()
+        .filter(|token| token.kind == TokenKind::Word)
+        .map(|token| token.text)
+        .collect();
+    let text = words.join(" ");
+    if text.chars().filter(|c| c.is_alphabetic()).count() < MIN_LETTERS {
+        return None;
+    }
+
+    let info = whatlang::detect(&text).filter(whatlang::Info::is_reliable)?;
+    let code = info.lang().code();
+    ISO_639_1.binary_search_by_key(&code, |&(iso3, _)| iso3).ok().map(|i| ISO_639_1[i].1)
+}
+
+/// Message count per detected language, with undetected ones under `"unknown"`.
+pub(crate) fn distribution(messages: &[Message]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for message in messages {
+        *counts.entry(detect(&message.content).unwrap_or(UNKNOWN).to_string()).or_default() += 1;
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_is_sorted_for_binary_search() {
+        assert!(ISO_639_1.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_detect_skips_text_too_short_to_tell() {
+        assert_eq!(detect("Are you coming to the meeting later today?"), Some("en"));
+        assert_eq!(detect("Ты придёшь сегодня вечером на встречу?"), Some("ru"));
+        assert_eq!(detect("ok"), None);
+        assert_eq!(detect("😂😂😂 🎉"), None);
+        assert_eq!(detect("https://example.com/a/very/long/path/to/something"), None);
+    }
+}
@@ -1,15 +1,118 @@
-use std::str::FromStr;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
 
 use chatpack::core::{
     apply_filters, merge_consecutive, FilterConfig, OutputConfig, ProcessingStats,
 };
 use chatpack::format::{to_format_string, OutputFormat};
-use chatpack::parser::{create_parser, Platform};
+use chatpack::parser::create_parser;
+#[cfg(feature = "telegram")]
+use chatpack::parser::Platform;
 use chatpack::Message;
 use serde::{Deserialize, Serialize};
 use wasm_bindgen::prelude::*;
 
-#[derive(Debug, Clone, Deserialize)]
+use crate::registry::Backend;
+
+mod activity;
+mod analysis;
+mod anonymize;
+mod async_convert;
+mod batch;
+mod buffer;
+mod calls;
+mod chats;
+mod chunked;
+mod csv_cells;
+mod detect;
+mod dialogue;
+mod diff;
+mod discord;
+mod edits;
+#[cfg(feature = "discord")]
+mod embeds;
+mod enrich;
+mod enricher;
+mod entities;
+mod events;
+mod finetune;
+mod fingerprint;
+#[cfg(feature = "instagram")]
+mod instagram;
+mod kinds;
+#[cfg(feature = "language")]
+mod language;
+mod memory;
+mod multi;
+mod options;
+mod order;
+mod pairs;
+mod parsers;
+mod pinned;
+mod questions;
+mod redact;
+mod reformat;
+mod registry;
+mod rejoin;
+mod sample;
+mod schema;
+mod search;
+mod senders;
+#[cfg(feature = "sentiment")]
+mod sentiment;
+mod session;
+#[cfg(feature = "telegram")]
+mod shares;
+mod span;
+mod split;
+mod stream;
+#[cfg(feature = "telegram")]
+mod telegram;
+#[cfg(test)]
+mod test_support;
+mod text;
+mod threads;
+mod timeline;
+mod timezone;
+mod tokens;
+mod transform;
+mod urls;
+#[cfg(feature = "whatsapp")]
+mod whatsapp;
+mod windows;
+mod words;
+
+pub use activity::{export_stats, sender_volume};
+pub use analysis::chat_kind;
+pub use async_convert::convert_async;
+pub use batch::convert_batch;
+pub use buffer::{convert_into, convert_to_bytes, required_output_size};
+pub use calls::{calls, export_calls_csv, export_calls_ics};
+pub use chats::{convert_chat, list_chats};
+pub use chunked::convert_chunked;
+pub use diff::{diff, export_new_only};
+pub use enricher::convert_with_enricher;
+pub use events::events;
+pub use finetune::convert_finetune;
+pub use fingerprint::fingerprint;
+pub use memory::memory_usage;
+pub use multi::convert_multi;
+pub use options::{convert_with, Options};
+pub use pairs::extract_pairs;
+pub use reformat::convert_format;
+pub use schema::{output_schema, schema};
+pub use session::ChatSession;
+pub use span::date_span;
+pub use split::convert_split;
+pub use stream::convert_streaming;
+pub use text::convert_bytes;
+pub use threads::extract_threads;
+pub use timeline::{convert_merged, merge_conversations};
+pub use transform::convert_with_transform;
+pub use windows::convert_windows;
+pub use words::tokenize;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(default)]
 struct ConvertOptions {
     include_timestamps: bool,
@@ -18,8 +121,58 @@ struct ConvertOptions {
     include_edited: bool,
     merge_consecutive: bool,
     filter_sender: Option<String>,
+    filter_senders: Vec<String>,
     date_from: Option<String>,
     date_to: Option<String>,
+    chunk_messages: usize,
+    chunk_bytes: usize,
+    sentiment: bool,
+    pretty: bool,
+    merge_separator: Option<String>,
+    #[serde(rename = "merge")]
+    merge_keep: MergeKeep,
+    source_timezone: Option<String>,
+    sample: Option<sample::SampleOptions>,
+    sample_every: Option<usize>,
+    sample_every_per_sender: bool,
+    detect_language: bool,
+    languages_include: Vec<String>,
+    escape_formulas: bool,
+    sender_merge_by: senders::SenderMergeBy,
+    csv_newlines: csv_cells::CsvNewlines,
+    entities: entities::EntityMode,
+    include_link_targets: bool,
+    preserve_links: bool,
+    dry_run: bool,
+    include_events: bool,
+    events_only: bool,
+    drop_system: bool,
+    include_pinned: bool,
+    only_pinned: bool,
+    expand_embeds: bool,
+    exclude_kinds: Vec<kinds::MessageKind>,
+    strip_control_chars: bool,
+    redact_words: Vec<String>,
+    redact_senders: bool,
+    redact_mask: Option<String>,
+    max_output_bytes: Option<usize>,
+    threads_only: bool,
+    thread_min_length: usize,
+    only_questions: bool,
+    column_map: parsers::generic_csv::ColumnMap,
+    max_per_sender: Option<usize>,
+    unknown_sender: senders::UnknownSender,
+    edits: edits::EditPolicy,
+    sort: bool,
+    self_sender: Option<String>,
+    self_roles: bool,
+    rejoin_split_messages: bool,
+    urls: urls::UrlMode,
+    threads: discord::ThreadMode,
+    anonymize: bool,
+    anonymize_seed: Option<String>,
+    reverse: bool,
+    extract_dialogue: Option<dialogue::DialogueOptions>,
 }
 
 impl Default for ConvertOptions {
@@ -31,8 +184,57 @@ impl Default for ConvertOptions {
             include_edited: false,
             merge_consecutive: true,
             filter_sender: None,
+            filter_senders: Vec::new(),
             date_from: None,
             date_to: None,
+            chunk_messages: stream::DEFAULT_CHUNK_MESSAGES,
+            chunk_bytes: stream::DEFAULT_CHUNK_BYTES,
+            sentiment: false,
+            pretty: true,
+            merge_separator: None,
+            merge_keep: MergeKeep::default(),
+            source_timezone: None,
+            sample: None,
+            sample_every: None,
+            sample_every_per_sender: false,
+            detect_language: false,
+            languages_include: Vec::new(),
+            escape_formulas: true,
+            sender_merge_by: senders::SenderMergeBy::Name,
+            csv_newlines: csv_cells::CsvNewlines::Keep,
+            entities: entities::EntityMode::Flatten,
+            include_link_targets: false,
+            preserve_links: false,
+            dry_run: false,
+            include_events: false,
+            events_only: false,
+            drop_system: false,
+            include_pinned: false,
+            only_pinned: false,
+            expand_embeds: false,
+            exclude_kinds: Vec::new(),
+            strip_control_chars: true,
+            redact_words: Vec::new(),
+            redact_senders: false,
+            redact_mask: None,
+            max_output_bytes: None,
+            threads_only: false,
+            thread_min_length: threads::DEFAULT_MIN_LENGTH,
+            only_questions: false,
+            column_map: parsers::generic_csv::ColumnMap::default(),
+            max_per_sender: None,
+            unknown_sender: senders::UnknownSender::Placeholder,
+            edits: edits::EditPolicy::Latest,
+            sort: false,
+            self_sender: None,
+            self_roles: false,
+            rejoin_split_messages: false,
+            urls: urls::UrlMode::Keep,
+            threads: discord::ThreadMode::Inline,
+            anonymize: false,
+            anonymize_seed: None,
+            reverse: false,
+            extract_dialogue: None,
         }
     }
 }
@@ -43,6 +245,102 @@ struct PreparedMessages {
     original_count: usize,
     filtered_count: usize,
     filters_active: bool,
+    merged: bool,
+    warnings: Vec<String>,
+    /// Telegram action of each service message, by message id.
+    actions: HashMap<u64, String>,
+    /// Discord thread name of each thread message, by message id.
+    threads: HashMap<u64, String>,
+    /// Ids of pinned messages, for `include_pinned`.
+    pinned: HashSet<u64>,
+    /// Replacements per `redact_words` phrase, if any are set.
+    redactions: Option<BTreeMap<String, usize>>,
+    /// Caller-provided fields, from `convert_with_enricher`.
+    extra: Vec<enrich::ExtraColumn>,
+    /// Pseudonym of each original sender, when `anonymize` renamed them.
+    pseudonyms: HashMap<String, String>,
+}
+
+impl PreparedMessages {
+    /// Output for `messages[range]` with the columns the conversion carries
+    /// alongside its messages: event actions, Discord threads and
+    /// caller-provided fields. Every output path formats through this, so
+    /// chunked and streamed output has the same columns as a whole one.
+    fn format_range(
+        &self,
+        range: Range<usize>,
+        format: OutputFormat,
+        options: &ConvertOptions,
+    ) -> std::result::Result<String, String> {
+        self.format_positions(&self.messages[range.clone()], range, format, options)
+    }
+
+    /// [`Self::format_range`] for the messages at `indices`, in that order.
+    fn format_selection(
+        &self,
+        indices: &[usize],
+        format: OutputFormat,
+        options: &ConvertOptions,
+    ) -> std::result::Result<String, String> {
+        let messages: Vec<Message> = indices.iter().map(|&i| self.messages[i].clone()).collect();
+        self.format_positions(&messages, indices.iter().copied(), format, options)
+    }
+
+    /// `messages`, which are this conversion's messages at `positions`.
+    fn format_positions(
+        &self,
+        messages: &[Message],
+        positions: impl Iterator<Item = usize> + Clone,
+        format: OutputFormat,
+        options: &ConvertOptions,
+    ) -> std::result::Result<String, String> {
+        let columns = self.columns(messages, positions, options);
+        let renamed;
+        let self_pseudonym = non_empty(options.self_sender.as_deref())
+            .and_then(|self_sender| self.pseudonyms.get(self_sender.trim()));
+        let options = match self_pseudonym {
+            Some(pseudonym) => {
+                renamed =
+                    ConvertOptions { self_sender: Some(pseudonym.clone()), ..options.clone() };
+                &renamed
+            }
+            None => options,
+        };
+
+        format_with_columns(messages, format, options, columns)
+    }
+
+    /// The columns carried alongside `messages`, which are this
+    /// conversion's messages at `positions`.
+    fn columns(
+        &self,
+        messages: &[Message],
+        positions: impl Iterator<Item = usize> + Clone,
+        options: &ConvertOptions,
+    ) -> Vec<enrich::ExtraColumn> {
+        let actions = events::action_column(messages, &self.actions);
+        let threads = discord::thread_column(messages, &self.threads);
+        let pinned = options.include_pinned.then(|| pinned::pinned_column(messages, &self.pinned));
+        let extra = self.extra.iter().map(|column| column.select(positions.clone()));
+
+        actions.into_iter().chain(threads).chain(pinned).chain(extra).collect()
+    }
+
+    /// `sender` as the messages name them: their pseudonym if `anonymize`
+    /// renamed them, otherwise `sender` itself. Options naming a sender
+    /// that are matched after processing look the sender up through this.
+    fn sender_named<'a>(&'a self, sender: &'a str) -> &'a str {
+        self.pseudonyms.get(sender.trim()).map_or(sender, String::as_str)
+    }
+
+    /// [`Self::format_range`] over all messages.
+    fn format(
+        &self,
+        format: OutputFormat,
+        options: &ConvertOptions,
+    ) -> std::result::Result<String, String> {
+        self.format_range(0..self.messages.len(), format, options)
+    }
 }
 
 #[derive(Serialize)]
@@ -69,21 +367,74 @@ struct ConversionStats {
     output_bytes: usize,
     filters_active: bool,
     merged: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sentiment_by_sender: Option<BTreeMap<String, f64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language_distribution: Option<BTreeMap<String, usize>>,
+    /// Distinct senders in order of first message; dry runs only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    senders: Option<Vec<String>>,
+    /// Earliest and latest timestamp; dry runs only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date_span: Option<DateSpan>,
+    /// Replacements per `redact_words` phrase.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    redactions: Option<BTreeMap<String, usize>>,
+    /// Output was cut short at `max_output_bytes`.
+    truncated: bool,
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct DateSpan {
+    first: Option<String>,
+    last: Option<String>,
+}
+
+/// Code for failures that have no more specific code.
+const CONVERSION_FAILED: &str = "CONVERSION_FAILED";
+
+/// Error with a machine-readable `code`, thrown as JSON by the byte-oriented exports.
+#[derive(Debug, Serialize)]
+struct CodedError {
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    required_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    batch_index: Option<usize>,
+}
+
+impl CodedError {
+    fn new(code: &'static str, message: String) -> Self {
+        Self { code, message, required_bytes: None, message_index: None, batch_index: None }
+    }
+
+    fn into_js(self) -> JsValue {
+        js_error(serde_json::to_string(&self).unwrap_or(self.message))
+    }
 }
 
 #[derive(Serialize)]
 struct SupportedSource {
     id: &'static str,
-    label: String,
+    label: &'static str,
     default_extension: &'static str,
 }
 
-#[derive(Serialize)]
-struct SupportedFormat {
-    id: &'static str,
-    label: String,
-    extension: &'static str,
-    mime_type: &'static str,
+#[derive(Debug, Serialize)]
+struct Capabilities {
+    has_timestamps: bool,
+    has_replies: bool,
+    has_ids: bool,
+    has_edits: bool,
+    has_pins: bool,
+    /// `Message` has no reaction or media fields, so these are `false` for
+    /// every platform until it does.
+    has_reactions: bool,
+    has_media: bool,
 }
 
 /// Backwards-compatible conversion API.
@@ -105,8 +456,130 @@ pub fn convert(
 ///
 /// `options_json` accepts:
 /// - `include_timestamps`, `include_ids`, `include_replies`, `include_edited`
-/// - `merge_consecutive`
-/// - `filter_sender`, `date_from`, `date_to`
+/// - `merge_consecutive`, `merge_separator` (text placed between merged
+///   messages; defaults to a newline)
+/// - `rejoin_split_messages`: join texts that Telegram (4096 characters) or
+///   Discord (2000) split into consecutive messages back into one, without
+///   a separator; see `rejoin`
+/// - `merge`: metadata a merged message keeps, `{"timestamp": "first"
+///   | "last", "reply_to": "first" | "any" | "drop"}`; defaults to the first
+///   message's timestamp and reply (see `merge_with_policy`)
+/// - `filter_sender`, `date_from`, `date_to`; `filter_senders` keeps
+///   messages from any of the listed senders (case-insensitive, like
+///   `filter_sender`)
+/// - `entities`: how Telegram's formatted text is written, `"flatten"`
+///   (plain text, default) or `"markdown"` (bold, italic, strikethrough,
+///   code and links as Markdown); `include_link_targets` adds the URL after
+///   the text of hidden links when flattening
+/// - `preserve_links`: write Telegram's hidden links as `[text](url)` and
+///   mentions of users without a username as `@name`, leaving the rest of
+///   the formatting flattened; cannot be combined with `include_link_targets`
+/// - `include_events`: add Telegram service messages (joins, pins, calls,
+///   title changes) and WhatsApp group notices (members added or leaving,
+///   subject changes) as messages from their actor with a readable content;
+///   `events_only` converts nothing else; retained events carry their
+///   Telegram `action` as an extra field, `null` on ordinary messages.
+///   `drop_system` keeps service messages out even when `include_events`
+///   is set
+/// - `include_pinned`: add a `pinned` field (`true` on messages a
+///   `pin_message` service entry names); `only_pinned` keeps only those.
+///   Telegram only (see `supports_pins`): other exports do not record pins,
+///   so elsewhere the field is always `false`, `only_pinned` keeps nothing,
+///   and either option adds a warning
+/// - `expand_embeds`: Discord messages without text get their embeds
+///   (`{title} — {description} ({url})`) and attachments (`{fileName}
+///   ({url})`) as content instead of a `[media]` placeholder; likewise
+///   Instagram shares become `[shared a reel: ...]`, `[shared a post by
+///   ...]` or `[replied to your story]`
+/// - `exclude_kinds`: Telegram polls, locations, venues and contacts are
+///   written as text (`[poll: ...]`, `[location: lat,lon]`, `[contact:
+///   ...]`); list `"poll"`, `"location"`, `"venue"` or `"contact"` here to
+///   leave them out, `"media"` for Instagram shares, or `"call"` for
+///   WhatsApp and Discord call messages and Telegram call events (see
+///   `calls`)
+/// - `threads`: Discord thread and forum-post messages (nested under a
+///   combined export's `threads`, or a thread's own export) are interleaved
+///   with the channel's by time (`"inline"`, default), written after them
+///   (`"separate"`), or left out (`"skip"`); kept ones carry the thread name
+///   in a `thread` field, `null` on channel messages
+/// - `column_map`: header names of the `timestamp`, `sender`, `content`,
+///   `id` and `reply_to` columns of a `generic-csv` source (any delimited
+///   file with a header row); unset fields fall back to common names such
+///   as `from` or `text`. Ignored by other sources
+/// - `sender_merge_by`: `"name"` (default) or `"id"` to treat every name an
+///   account used as one sender, shown under its latest name (Telegram only)
+/// - `edits`: messages repeated under one id (stitched or doubly imported
+///   exports) keep only their most recently edited version (`"latest"`,
+///   default) or every distinct version, ordered by edit time (`"all"`)
+/// - `unknown_sender`: messages whose sender cannot be resolved (deleted
+///   accounts, anonymous posts) are attributed to the account id when the
+///   export has one, else to `"Unknown"` (`"placeholder"`, default), left
+///   out (`"skip"`), or fail the conversion (`"error"`)
+/// - `source_timezone`: UTC offset (e.g. `+05:30`) of exports that store
+///   local time (WhatsApp, Viber, LINE, KakaoTalk); without it their
+///   timestamps are kept as UTC and stats carry a warning
+/// - `sort`: order messages by timestamp (stable; undated messages keep
+///   their place). Without it, timestamps that go backwards are reported in
+///   a warning with the count and the index of the first one
+/// - `reverse`: write the newest message first; the last ordering step, so
+///   `max_output_bytes`, chunks and splits all start from the newest
+/// - `chunk_messages`, `chunk_bytes` (only used by `convert_streaming`)
+/// - `self_sender`: the sender who is "me" in a two-person chat; adds a
+///   per-message `is_self` flag. With `self_roles` the field is a `role`
+///   instead, `"assistant"` for `self_sender` and `"user"` for the other
+///   side, as fine-tuning datasets expect. Give the real name; with
+///   `anonymize` it still flags that sender under their pseudonym
+/// - `sentiment`: add a per-message `sentiment` score (-1..1) and per-sender
+///   averages in stats; requires the `sentiment` cargo feature
+/// - `detect_language`: add a per-message `language` (ISO 639-1 code, or
+///   `null` when the text is too short or ambiguous to tell) and the message
+///   count per language in stats; requires the `language` cargo feature
+/// - `languages_include`: keep only messages detected as one of these codes
+///   (undetected messages are dropped); also requires the `language` feature
+/// - `pretty`: indent JSON output (default `true`); the `json-compact` format
+///   is JSON with `pretty: false`
+/// - `escape_formulas`: prefix CSV text cells that a spreadsheet would run
+///   as a formula (starting with `=`, `+`, `-`, `@`, tab or CR) with `'`:
+///   senders, contents, extra column headers and values, and activity
+///   senders (default `true`)
+/// - `csv_newlines`: line breaks in CSV content are `"keep"` (default),
+///   `"escape"` (written as the two characters `\n`) or `"space"` (each run
+///   collapsed into one space); applied before `escape_formulas`
+/// - `strip_control_chars`: remove C0 control characters other than line
+///   breaks and tabs from contents (default `true`)
+/// - `urls`: links (`http://`, `https://` or `www.`, without trailing
+///   punctuation) stay in the content with `"keep"` (default), are removed
+///   before any filter sees the text with `"drop"`, or move into a
+///   per-message `urls` list with `"extract"`, leaving `[url]` in their place
+/// - `redact_words`: phrases replaced by `redact_mask` (default
+///   `"[REDACTED]"`) in contents, and in senders with `redact_senders`;
+///   matching ignores case and never hits inside a longer word. Stats list
+///   the number of replacements per phrase under `redactions`
+/// - `anonymize`: replace senders, and their names in contents, with
+///   pseudonyms: `Person 1`, `Person 2`, ... in order of appearance, or,
+///   with `anonymize_seed`, a label derived from the seed and the name, so
+///   conversions sharing a seed give a person the same pseudonym in every
+///   file
+/// - `sample_every`: keep every Nth message, starting with the first;
+///   counted per sender with `sample_every_per_sender`
+/// - `only_questions`: keep only messages that look like questions: a line
+///   ending with `?`, or starting with an interrogative word (English,
+///   Spanish, Portuguese, German, French, Russian) and not ending with `.`
+///   or `!`; a heuristic, applied after the other filters
+/// - `threads_only`: keep only messages in a reply thread (a replied-to
+///   message and its replies, down the chain) of at least
+///   `thread_min_length` messages (default 2); applied before the filters
+/// - `extract_dialogue`: `{participants: [a, b], max_gap_minutes}` keeps
+///   only the two participants' exchanges: their messages that reply to
+///   the other, are replied to by the other, or directly follow or precede
+///   a message of the other within `max_gap_minutes` (default 30). Applied
+///   to the whole chat before the filters; a warning counts the messages of
+///   other senders and the participants' own messages it left out
+/// - `max_per_sender`: keep at most this many messages of each sender, the
+///   most recent ones, after the filters and before merging
+/// - `max_output_bytes`: stop before the message that would take the output
+///   past this size; the output stays valid for its format, and the report's
+///   stats set `truncated` and count only the messages written
 #[wasm_bindgen]
 pub fn convert_with_options(
     input: &str,
@@ -120,6 +593,10 @@ pub fn convert_with_options(
 }
 
 /// Convert chat export and return a JSON report with output and processing stats.
+///
+/// With the `dry_run` option the output is not formatted at all: `output`
+/// is empty, `output_bytes` is 0, and stats also list the `senders` and the
+/// `date_span` (`{first, last}`), so a conversion can be previewed cheaply.
 #[wasm_bindgen]
 pub fn convert_with_report(
     input: &str,
@@ -142,50 +619,78 @@ pub fn parse_chat(
 ) -> std::result::Result<String, JsValue> {
     let options = parse_options(options_json).map_err(js_error)?;
     let prepared = prepare_messages(input, source, &options).map_err(js_error)?;
-    let final_count = prepared.messages.len();
-    let stats = build_stats(
-        input,
-        0,
-        prepared.original_count,
-        prepared.filtered_count,
-        final_count,
-        prepared.filters_active,
-        options.merge_consecutive,
-    );
+    let stats = build_stats(&prepared, &options, input.len(), 0);
     let report = ParseReport { messages: prepared.messages, stats };
 
     serde_json::to_string(&report).map_err(|e| js_error(e.to_string()))
 }
 
 /// Return supported sources as JSON.
+///
+/// Kept for older frontends; `supported_platforms` carries the full metadata.
 #[wasm_bindgen]
 pub fn supported_sources() -> std::result::Result<String, JsValue> {
-    let sources: Vec<SupportedSource> = Platform::all()
-        .iter()
-        .map(|platform| SupportedSource {
-            id: platform_id(*platform),
-            label: platform.to_string(),
-            default_extension: platform.default_extension(),
+    let sources: Vec<SupportedSource> = registry::enabled_platforms()
+        .map(|spec| SupportedSource {
+            id: spec.id,
+            label: spec.display_name,
+            default_extension: spec.default_extension,
         })
         .collect();
 
     serde_json::to_string(&sources).map_err(|e| js_error(e.to_string()))
 }
 
+/// Return supported input platforms as JSON.
+///
+/// Each entry has `id`, `aliases`, `display_name`, `input_kind`
+/// (`"text"` | `"json"`), `default_extension`, `supports_replies`,
+/// `supports_timestamps`, `supports_ids`, `supports_edits`, `supports_pins`
+/// (pinned messages are recorded, see `include_pinned`), `local_time`
+/// (timestamps carry no UTC offset, see `source_timezone`) and
+/// `max_message_length` (characters per message, or `null` if unknown).
+/// Platforms compiled out of this build (see the crate's cargo features) are
+/// not listed.
+#[wasm_bindgen]
+pub fn supported_platforms() -> std::result::Result<String, JsValue> {
+    let platforms: Vec<&registry::PlatformSpec> = registry::enabled_platforms().collect();
+
+    serde_json::to_string(&platforms).map_err(|e| js_error(e.to_string()))
+}
+
+/// Return which message fields the parser for `source` can fill, as JSON.
+///
+/// `{has_timestamps, has_replies, has_ids, has_edits, has_pins,
+/// has_reactions, has_media}`; an option whose field is `false` (e.g. `include_replies` for
+/// WhatsApp) would only produce empty values.
+#[wasm_bindgen]
+pub fn platform_capabilities(source: &str) -> std::result::Result<String, JsValue> {
+    let capabilities = capabilities(source).map_err(js_error)?;
+
+    serde_json::to_string(&capabilities).map_err(|e| js_error(e.to_string()))
+}
+
+fn capabilities(source: &str) -> std::result::Result<Capabilities, String> {
+    let spec = registry::find_platform(source)?;
+
+    Ok(Capabilities {
+        has_timestamps: spec.supports_timestamps,
+        has_replies: spec.supports_replies,
+        has_ids: spec.supports_ids,
+        has_edits: spec.supports_edits,
+        has_pins: spec.supports_pins,
+        has_reactions: false,
+        has_media: false,
+    })
+}
+
 /// Return supported output formats as JSON.
+///
+/// Each entry has `id`, `aliases`, `display_name`, `binary`, `mime_type` and
+/// `extension`.
 #[wasm_bindgen]
 pub fn supported_formats() -> std::result::Result<String, JsValue> {
-    let formats: Vec<SupportedFormat> = OutputFormat::all()
-        .iter()
-        .map(|format| SupportedFormat {
-            id: format_id(*format),
-            label: format.to_string(),
-            extension: format.extension(),
-            mime_type: format.mime_type(),
-        })
-        .collect();
-
-    serde_json::to_string(&formats).map_err(|e| js_error(e.to_string()))
+    serde_json::to_string(registry::FORMATS).map_err(|e| js_error(e.to_string()))
 }
 
 /// Get WASM binding version.
@@ -200,81 +705,767 @@ fn run_conversion(
     format: &str,
     options: &ConvertOptions,
 ) -> std::result::Result<ConversionReport, String> {
-    let output_format = parse_format(format)?;
-    let prepared = prepare_messages(input, source, options)?;
-    let output_config = output_config(options);
-    let output = to_format_string(&prepared.messages, output_format, &output_config)
-        .map_err(|e| e.to_string())?;
-    let stats = build_stats(
-        input,
-        output.len(),
-        prepared.original_count,
-        prepared.filtered_count,
-        prepared.messages.len(),
-        prepared.filters_active,
-        options.merge_consecutive,
-    );
+    let mut options = options.clone();
+    let output_format = resolve_format(format, &mut options)?;
+    let prepared = prepare_messages(input, source, &options)?;
+
+    format_report(prepared, input.len(), output_format, &options)
+}
+
+fn format_report(
+    mut prepared: PreparedMessages,
+    input_bytes: usize,
+    format: OutputFormat,
+    options: &ConvertOptions,
+) -> std::result::Result<ConversionReport, String> {
+    let (output, truncated) = if options.dry_run {
+        (String::new(), false)
+    } else {
+        match limit_output(&mut prepared, format, options)? {
+            Some(limited) => limited,
+            None => (prepared.format(format, options)?, false),
+        }
+    };
+    let mut stats = build_stats(&prepared, options, input_bytes, output.len());
+    stats.truncated = truncated;
 
     Ok(ConversionReport { output, stats })
 }
 
+/// Apply `max_output_bytes`: drop the messages after the last one whose
+/// output fits, and return the output of those kept (rendered to find them)
+/// and whether any were dropped. `None` without a limit. Reports, streams
+/// and async conversions all run this, so a limited conversion stops at the
+/// same message in each.
+fn limit_output(
+    prepared: &mut PreparedMessages,
+    format: OutputFormat,
+    options: &ConvertOptions,
+) -> std::result::Result<Option<(String, bool)>, String> {
+    let Some(limit) = options.max_output_bytes else {
+        return Ok(None);
+    };
+    let output = prepared.format(format, options)?;
+    if output.len() <= limit {
+        return Ok(Some((output, false)));
+    }
+    let render = |range| prepared.format_range(range, format, options);
+    let (kept, output) = fit_output(prepared.messages.len(), limit, render)?;
+    prepared.messages.truncate(kept);
+
+    Ok(Some((output, true)))
+}
+
+/// Number of leading messages (out of `len`) whose output fits in `limit`
+/// bytes, and that output. Falls back to the output of no messages (a CSV
+/// header or an empty array) if even that is larger.
+fn fit_output(
+    len: usize,
+    limit: usize,
+    render: impl Fn(Range<usize>) -> std::result::Result<String, String>,
+) -> std::result::Result<(usize, String), String> {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if render(0..mid)?.len() <= limit {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+
+    Ok((low, render(0..low)?))
+}
+
+/// Format messages with chatpack and append the per-message fields
+/// `options` request, then the `extra` columns.
+fn format_with_columns(
+    messages: &[Message],
+    format: OutputFormat,
+    options: &ConvertOptions,
+    extra: Vec<enrich::ExtraColumn>,
+) -> std::result::Result<String, String> {
+    // Compact JSON is chatpack's JSONL (one compact object per line) wrapped into an array.
+    let compact = matches!(format, OutputFormat::Json) && !options.pretty;
+    let format = if compact { OutputFormat::Jsonl } else { format };
+    let (extracted, url_column) = match options.urls {
+        urls::UrlMode::Extract => {
+            let (messages, column) = urls::extract(messages);
+            (Some(messages), Some(column))
+        }
+        _ => (None, None),
+    };
+    let messages = extracted.as_deref().unwrap_or(messages);
+
+    let csv_ready = matches!(format, OutputFormat::Csv)
+        .then(|| csv_cells::prepare_messages(messages, options))
+        .flatten();
+    let mut output =
+        to_format_string(csv_ready.as_deref().unwrap_or(messages), format, &output_config(options))
+            .map_err(|e| e.to_string())?;
+    let mut columns = enrich::extra_columns(messages, options)?;
+    columns.extend(url_column);
+    columns.extend(extra);
+    if !columns.is_empty() {
+        output = enrich::append_columns(&output, format, &columns, options.escape_formulas)?;
+    }
+
+    Ok(if compact { jsonl_to_array(&output) } else { output })
+}
+
+fn jsonl_to_array(jsonl: &str) -> String {
+    let mut array = String::with_capacity(jsonl.len() + 2);
+    array.push('[');
+    for (index, line) in jsonl.lines().filter(|line| !line.trim().is_empty()).enumerate() {
+        if index > 0 {
+            array.push(',');
+        }
+        array.push_str(line);
+    }
+    array.push(']');
+
+    array
+}
+
 fn prepare_messages(
     input: &str,
     source: &str,
     options: &ConvertOptions,
 ) -> std::result::Result<PreparedMessages, String> {
+    let platform = registry::find_platform(source)?;
+    let export = read_export(input, source, options)?;
+
+    process_export(export, platform, options)
+}
+
+/// Messages of an export after the steps that read the raw export again,
+/// with what those steps found out about them.
+#[derive(Debug, Clone, Default)]
+struct ReadExport {
+    messages: Vec<Message>,
+    warnings: Vec<String>,
+    /// Telegram action of each service message, by message id.
+    actions: HashMap<u64, String>,
+    /// Event rows added by `include_events` / `events_only`.
+    events: events::EventRows,
+    /// Discord thread name of each thread message, by message id.
+    threads: HashMap<u64, String>,
+    /// Ids of the messages the export records as pinned.
+    pinned: HashSet<u64>,
+}
+
+/// Options the steps of `read_export` depend on; exports read with equal
+/// `ReadOptions` hold the same messages.
+#[derive(Debug, Clone, PartialEq)]
+struct ReadOptions {
+    entities: entities::EntityMode,
+    include_link_targets: bool,
+    preserve_links: bool,
+    exclude_kinds: Vec<kinds::MessageKind>,
+    expand_embeds: bool,
+    threads: discord::ThreadMode,
+    include_events: bool,
+    events_only: bool,
+    drop_system: bool,
+    sender_merge_by: senders::SenderMergeBy,
+    unknown_sender: senders::UnknownSender,
+    column_map: parsers::generic_csv::ColumnMap,
+    source_timezone: Option<String>,
+}
+
+impl ReadOptions {
+    fn of(options: &ConvertOptions) -> Self {
+        Self {
+            entities: options.entities,
+            include_link_targets: options.include_link_targets,
+            preserve_links: options.preserve_links,
+            exclude_kinds: options.exclude_kinds.clone(),
+            expand_embeds: options.expand_embeds,
+            threads: options.threads,
+            include_events: options.include_events,
+            events_only: options.events_only,
+            drop_system: options.drop_system,
+            sender_merge_by: options.sender_merge_by,
+            unknown_sender: options.unknown_sender,
+            column_map: options.column_map.clone(),
+            source_timezone: options.source_timezone.clone(),
+        }
+    }
+}
+
+/// Parse `input` and run every step that needs the raw export besides the
+/// parsed messages: entities, shares, embeds, Discord threads, WhatsApp
+/// normalization, events, calls and sender resolution. Every conversion
+/// entry point starts here, so they all see the same messages. Each
+/// platform's steps are only compiled with its cargo feature.
+///
+/// A Telegram export is deserialized once more, in `read_telegram`, and
+/// every Telegram step reads that. Entities and shares run at default
+/// options too, since they rewrite message content; the other steps return
+/// early unless their option is set.
+fn read_export(
+    input: &str,
+    source: &str,
+    options: &ConvertOptions,
+) -> std::result::Result<ReadExport, String> {
+    let (mut messages, mut warnings) = parse_with(input, source, options)?;
+    let platform = registry::find_platform(source)?;
+    #[cfg(feature = "telegram")]
+    let raw = match platform.backend {
+        Backend::Core(Platform::Telegram) if !input.trim().is_empty() => {
+            read_telegram(&mut messages, input, options)?
+        }
+        _ => RawDetails::default(),
+    };
+    #[cfg(not(feature = "telegram"))]
+    let raw = RawDetails::default();
+    #[cfg(feature = "instagram")]
+    instagram::render_shares(
+        &mut messages,
+        input,
+        platform.backend,
+        options.expand_embeds,
+        &options.exclude_kinds,
+    );
+    #[cfg(feature = "discord")]
+    embeds::fill_media_messages(&mut messages, input, platform.backend, options.expand_embeds);
+    #[cfg(feature = "discord")]
+    let threads = discord::apply_threads(
+        &mut messages,
+        input,
+        platform.backend,
+        options.threads,
+        options.expand_embeds,
+    );
+    #[cfg(not(feature = "discord"))]
+    let threads = HashMap::new();
+    #[cfg(feature = "whatsapp")]
+    warnings.extend(whatsapp::normalize(&mut messages, platform.backend));
+    let (actions, events) =
+        events::apply_events(&mut messages, input, platform.backend, raw.events, options)?;
+    #[cfg(any(feature = "whatsapp", feature = "discord"))]
+    if options.exclude_kinds.contains(&kinds::MessageKind::Call) {
+        calls::drop_calls(&mut messages, input, platform.backend);
+    }
+    warnings.extend(senders::group_senders(
+        &mut messages,
+        raw.accounts.as_ref(),
+        options.sender_merge_by,
+    ));
+    senders::resolve_unknown(&mut messages, &raw.unnamed, options.unknown_sender)?;
+
+    Ok(ReadExport { messages, warnings, actions, events, threads, pinned: raw.pinned })
+}
+
+/// What the steps of `read_export` after the first few need from the raw
+/// export besides chatpack's messages. Only Telegram's is kept here; the
+/// other platforms' steps read what they need from `input`.
+#[derive(Debug, Default)]
+struct RawDetails {
+    /// Service events, when `include_events` or `events_only` asks for them.
+    events: Option<Vec<events::Event>>,
+    /// Latest name of each message's account, for `sender_merge_by: "id"`.
+    accounts: Option<HashMap<u64, String>>,
+    /// Messages without a sender name, with their raw sender id.
+    unnamed: HashMap<u64, Option<String>>,
+    /// Ids of pinned messages.
+    pinned: HashSet<u64>,
+}
+
+/// Deserialize a Telegram export, render its entities and shared items into
+/// `messages` and keep what the later steps of `read_export` need.
+#[cfg(feature = "telegram")]
+fn read_telegram(
+    messages: &mut Vec<Message>,
+    input: &str,
+    options: &ConvertOptions,
+) -> std::result::Result<RawDetails, String> {
+    let export = telegram::parse(input)?;
+    entities::render_entities(
+        messages,
+        &export,
+        options.entities,
+        entities::LinkStyle::of(options.preserve_links, options.include_link_targets),
+    );
+    shares::render_shares(messages, &export, &options.exclude_kinds);
+    let merge_by_id = options.sender_merge_by == senders::SenderMergeBy::Id;
+
+    Ok(RawDetails {
+        events: events::wanted(options).then(|| events::telegram_events(&export)),
+        accounts: merge_by_id.then(|| senders::telegram_accounts(&export)),
+        unnamed: senders::telegram_unnamed(&export),
+        pinned: pinned::telegram_pinned(&export),
+    })
+}
+
+/// `process_messages` on a read export, keeping its warnings and columns.
+fn process_export(
+    export: ReadExport,
+    platform: &registry::PlatformSpec,
+    options: &ConvertOptions,
+) -> std::result::Result<PreparedMessages, String> {
+    let mut prepared =
+        process_messages(export.messages, &export.events, &export.pinned, platform, options)?;
+    prepared.warnings.splice(0..0, export.warnings);
+    prepared.actions = export.actions;
+    prepared.threads = export.threads;
+    prepared.pinned = export.pinned;
+
+    Ok(prepared)
+}
+
+/// Parse `input` with the parser registered for `source`, and run the
+/// `read_export` steps with default options.
+///
+/// Blank input is a valid export without messages, so every format
+/// produces its empty document (CSV header, `[]`, empty JSONL).
+fn parse_messages(input: &str, source: &str) -> std::result::Result<Vec<Message>, String> {
+    read_export(input, source, &ConvertOptions::default()).map(|export| export.messages)
+}
+
+/// `parse_messages` with the options that affect parsing: `column_map` for
+/// mapped parsers. A mapped parser's local
+/// timestamps are resolved here, by `source_timezone` or with a warning,
+/// since only it knows which ones they are.
+fn parse_with(
+    input: &str,
+    source: &str,
+    options: &ConvertOptions,
+) -> std::result::Result<(Vec<Message>, Vec<String>), String> {
+    let backend = parse_platform(source)?;
     if input.trim().is_empty() {
-        return Err("Empty input. File is empty or contains no data".to_string());
+        return Ok((Vec::new(), Vec::new()));
     }
+    text::check_text(input)?;
+
+    let mut warnings = Vec::new();
+    let messages = match backend {
+        Backend::Core(platform) => {
+            create_parser(platform).parse_str(input).map_err(|e| e.to_string())?
+        }
+        Backend::Local(parse) => parse(input)?,
+        Backend::Mapped(parse) => {
+            let (mut messages, local) = parse(input, &options.column_map)?;
+            let timezone = non_empty(options.source_timezone.as_deref());
+            warnings.extend(timezone::resolve_local(&mut messages, &local, timezone)?);
+            messages
+        }
+        Backend::Disabled => return Err(registry::not_enabled(source.trim())),
+    };
+
+    Ok((messages, warnings))
+}
 
-    let platform = parse_platform(source)?;
-    let parser = create_parser(platform);
-    let mut messages = parser.parse_str(input).map_err(|e| e.to_string())?;
+/// Apply the source timezone, filters, merging and sampling to parsed messages.
+///
+/// This is the only place the processing steps run, always in this order:
+///
+/// 0. `edits`: messages repeated under one id are collapsed first, so every
+///    later step and count sees each message once.
+/// 1. `strip_control_chars`: stray control characters leave the contents,
+///    and with `urls: "drop"` so do links, so language detection and the
+///    question filter see only the text around them.
+/// 2. `source_timezone`: local timestamps are shifted to UTC (a mapped
+///    parser's were, per message, in `parse_with`), so the date filters
+///    below compare real UTC times; then `sort` orders them (or timestamps
+///    that go backwards are reported).
+/// 3. `rejoin_split_messages`, before anything that looks at single
+///    messages, so no filter or merge sees half a text.
+/// 4. `threads_only` and `extract_dialogue`, on the whole chat, so a thread
+///    is never cut short by a filter that drops one of its messages, and
+///    who spoke before and after a message is who really did.
+/// 5. Filters (`filter_sender`, `filter_senders`, `date_from`, `date_to`,
+///    `languages_include`, `only_questions`, `only_pinned`) on single
+///    messages, then
+///    `max_per_sender`.
+/// 6. `merge_consecutive`, on what the filters kept. Merging after them means
+///    a filter never sees (and never splits or drops part of) a merged block,
+///    and messages that become adjacent once others are filtered out merge.
+///    `events` (rows added by `include_events`) are never merged.
+/// 7. `sample`: a deterministic random subset of the (merged) messages, or
+///    of whole sessions, so a sampled turn is never cut in half.
+/// 8. `sample_every`: every Nth of the remaining messages, so it composes
+///    with `sample` and is always the same slice of the same input.
+/// 9. `redact_words`: phrases are masked in what is left, last, so the
+///    counts in the report match the output and later steps (formatting)
+///    only ever see masked text.
+/// 10. `anonymize`: senders become pseudonyms, after the steps that match
+///     real names (`filter_sender`, `redact_words`). Senders named by
+///     options matched later (`self_sender` when formatting, the roles of
+///     pairs and fine-tuning examples) are looked up under their pseudonym.
+/// 11. `reverse`: the result is flipped newest-first, after everything that
+///     depends on order (merging, sampling, pseudonym numbering), so it only
+///     changes the order messages are written in.
+///
+/// Messages keep their parsed order throughout unless `sort` is set.
+/// `platform` is the source's entry in the registry. Takes ownership so
+/// callers decide whether the originals are kept around (as `ChatSession`
+/// does) or consumed (as the one-shot conversions do).
+fn process_messages(
+    mut messages: Vec<Message>,
+    events: &events::EventRows,
+    pinned: &HashSet<u64>,
+    platform: &registry::PlatformSpec,
+    options: &ConvertOptions,
+) -> std::result::Result<PreparedMessages, String> {
+    let mut warnings = Vec::new();
+    let duplicates = edits::dedupe(std::slice::from_mut(&mut messages), options.edits);
+    if duplicates > 0 {
+        warnings.push(format!("{duplicates} duplicate messages with a repeated id were removed"));
+    }
     let original_count = messages.len();
+
+    if options.strip_control_chars {
+        text::strip_control_chars(&mut messages);
+    }
+    if options.urls == urls::UrlMode::Drop {
+        urls::drop_urls(&mut messages);
+    }
+
+    // A mapped parser's local timestamps were resolved when parsing.
+    if platform.local_time && !matches!(platform.backend, Backend::Mapped(_)) {
+        match non_empty(options.source_timezone.as_deref()) {
+            Some(timezone) => {
+                timezone::shift_to_utc(&mut messages, timezone::parse_offset(timezone)?)
+            }
+            None => warnings.extend(timezone::ambiguity_warning(&messages)),
+        }
+    }
+    if options.sort {
+        order::sort_by_time(&mut messages);
+    } else {
+        warnings.extend(order::out_of_order_warning(&messages));
+    }
+
+    if options.rejoin_split_messages {
+        match platform.max_message_length {
+            Some(limit) => {
+                let joined = rejoin::rejoin(&mut messages, limit);
+                if joined > 0 {
+                    warnings.push(format!(
+                        "{joined} message(s) split by the {limit}-character limit were joined \
+                         to the message before them"
+                    ));
+                }
+            }
+            None => warnings.push(format!(
+                "rejoin_split_messages: {} has no message length limit, so nothing was joined",
+                platform.display_name
+            )),
+        }
+    }
+
+    if options.threads_only {
+        if !messages.iter().any(|m| m.reply_to.is_some()) {
+            warnings.push("threads_only: this export has no replies, so no threads".to_string());
+        }
+        messages = threads::threads_only(messages, options.thread_min_length);
+    }
+    if let Some(dialogue_options) = &options.extract_dialogue {
+        let (kept, excluded) = dialogue::extract(messages, dialogue_options);
+        messages = kept;
+        warnings.push(format!(
+            "extract_dialogue: left out {} from other senders and {} of theirs not addressed \
+             to each other",
+            excluded.by_sender, excluded.by_heuristic
+        ));
+    }
+
     let filter = filter_config(options)?;
-    let filters_active = filter.is_active();
+    let filters_active = filter.is_active()
+        || !options.filter_senders.is_empty()
+        || !options.languages_include.is_empty()
+        || options.threads_only
+        || options.extract_dialogue.is_some()
+        || options.only_questions
+        || options.only_pinned
+        || options.max_per_sender.is_some();
 
-    if filters_active {
+    if filter.is_active() {
         messages = apply_filters(messages, &filter);
     }
+    if !options.filter_senders.is_empty() {
+        messages
+            .retain(|m| options.filter_senders.iter().any(|s| m.sender.eq_ignore_ascii_case(s)));
+    }
+    if !options.languages_include.is_empty() {
+        messages = filter_languages(messages, &options.languages_include)?;
+    }
+    if options.only_questions {
+        messages = questions::only_questions(messages);
+    }
+    if options.only_pinned {
+        messages = pinned::only_pinned(messages, pinned);
+    }
+    for (set, name) in
+        [(options.include_pinned, "include_pinned"), (options.only_pinned, "only_pinned")]
+    {
+        if set && !platform.supports_pins {
+            warnings.push(format!(
+                "{name}: {} exports do not record pinned messages",
+                platform.display_name
+            ));
+        }
+    }
+
+    if let Some(max) = options.max_per_sender {
+        messages = sample::cap_per_sender(messages, max);
+    }
 
     let filtered_count = messages.len();
 
     if options.merge_consecutive {
-        messages = merge_consecutive(messages);
+        let separator = options.merge_separator.as_deref().unwrap_or(DEFAULT_MERGE_SEPARATOR);
+        messages = merge_between(messages, events, |run| {
+            if separator == DEFAULT_MERGE_SEPARATOR && options.merge_keep.is_default() {
+                merge_consecutive(run)
+            } else {
+                merge_with_policy(run, separator, options.merge_keep)
+            }
+        });
+    }
+    events.clear_markers(&mut messages);
+
+    if let Some(sample_options) = &options.sample {
+        messages = sample::sample(messages, sample_options, &mut warnings);
+    }
+    if let Some(every) = options.sample_every {
+        messages = sample::every(messages, every, options.sample_every_per_sender);
+    }
+
+    let redactions = (!options.redact_words.is_empty()).then(|| {
+        let mask = options.redact_mask.as_deref().unwrap_or(redact::DEFAULT_MASK);
+        redact::redact(&mut messages, &options.redact_words, mask, options.redact_senders)
+    });
+
+    let pseudonyms = match options.anonymize {
+        true => anonymize::anonymize(&mut messages, non_empty(options.anonymize_seed.as_deref())),
+        false => HashMap::new(),
+    };
+    if options.reverse {
+        messages.reverse();
     }
 
-    Ok(PreparedMessages { messages, original_count, filtered_count, filters_active })
+    Ok(PreparedMessages {
+        messages,
+        original_count,
+        filtered_count,
+        filters_active,
+        merged: options.merge_consecutive,
+        warnings,
+        actions: HashMap::new(),
+        threads: HashMap::new(),
+        pinned: HashSet::new(),
+        redactions,
+        extra: Vec::new(),
+        pseudonyms,
+    })
+}
+
+/// Separator chatpack's `merge_consecutive` puts between merged messages.
+const DEFAULT_MERGE_SEPARATOR: &str = "\n";
+
+/// Which timestamp a merged message keeps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum KeepTimestamp {
+    /// When the run started (chatpack's choice).
+    #[default]
+    First,
+    /// When the run ended.
+    Last,
+}
+
+/// Which reply a merged message keeps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum KeepReply {
+    /// The first message's reply, if it has one (chatpack's choice).
+    #[default]
+    First,
+    /// The first reply anywhere in the run.
+    Any,
+    /// No reply.
+    Drop,
+}
+
+/// The `merge` option: what a merged message keeps.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+struct MergeKeep {
+    timestamp: KeepTimestamp,
+    reply_to: KeepReply,
+}
+
+impl MergeKeep {
+    fn is_default(self) -> bool {
+        self == Self::default()
+    }
+}
+
+/// `merge` applied to the messages between event rows; the rows themselves
+/// are kept as they are, so nothing merges into or across them.
+fn merge_between(
+    messages: Vec<Message>,
+    events: &events::EventRows,
+    merge: impl Fn(Vec<Message>) -> Vec<Message>,
+) -> Vec<Message> {
+    if events.is_empty() {
+        return merge(messages);
+    }
+
+    let mut merged = Vec::with_capacity(messages.len());
+    let mut run = Vec::new();
+    for message in messages {
+        if events.contains(&message) {
+            merged.extend(merge(std::mem::take(&mut run)));
+            merged.push(message);
+        } else {
+            run.push(message);
+        }
+    }
+    merged.extend(merge(run));
+
+    merged
+}
+
+/// `merge_consecutive` with a custom separator and `merge` policy.
+///
+/// Each run of same-sender messages is still merged by chatpack, so the
+/// merged message starts out with the first message's metadata; its content
+/// is then rebuilt from the original parts, and its timestamp and reply are
+/// replaced as `keep` asks: `timestamp: "last"` takes the last message's
+/// time, `reply_to: "any"` the first reply found in the run and `"drop"`
+/// none. Other metadata (id, edit time) stays the first message's.
+fn merge_with_policy(messages: Vec<Message>, separator: &str, keep: MergeKeep) -> Vec<Message> {
+    let mut merged = Vec::with_capacity(messages.len());
+    let mut messages = messages.into_iter().peekable();
+
+    while let Some(first) = messages.next() {
+        let mut run = vec![first];
+        while let Some(next) = messages.next_if(|next| next.sender == run[0].sender) {
+            run.push(next);
+        }
+
+        let parts: Vec<String> = run.iter().map(|m| m.content.clone()).collect();
+        let last_timestamp = run.last().and_then(|m| m.timestamp);
+        let any_reply = run.iter().find_map(|m| m.reply_to);
+        let mut run = merge_consecutive(run);
+        if let [message] = run.as_mut_slice() {
+            message.content = parts.join(separator);
+            if keep.timestamp == KeepTimestamp::Last {
+                message.timestamp = last_timestamp;
+            }
+            match keep.reply_to {
+                KeepReply::First => {}
+                KeepReply::Any => message.reply_to = any_reply,
+                KeepReply::Drop => message.reply_to = None,
+            }
+        }
+        merged.extend(run);
+    }
+
+    merged
 }
 
 fn build_stats(
-    input: &str,
+    prepared: &PreparedMessages,
+    options: &ConvertOptions,
+    input_bytes: usize,
     output_bytes: usize,
-    original_count: usize,
-    filtered_count: usize,
-    merged_count: usize,
-    filters_active: bool,
-    merged: bool,
 ) -> ConversionStats {
-    let mut stats = ProcessingStats::new(original_count, merged_count);
-    if filters_active {
-        stats = stats.with_filtered(filtered_count);
+    let merged_count = prepared.messages.len();
+    let mut stats = ProcessingStats::new(prepared.original_count, merged_count);
+    if prepared.filters_active {
+        stats = stats.with_filtered(prepared.filtered_count);
     }
 
     ConversionStats {
-        original_count,
-        filtered_count,
+        original_count: prepared.original_count,
+        filtered_count: prepared.filtered_count,
         merged_count,
         messages_saved: stats.messages_saved(),
         compression_ratio: stats.compression_ratio(),
         merge_ratio: stats.merge_ratio(),
-        input_bytes: input.len(),
+        input_bytes,
         output_bytes,
-        filters_active,
-        merged,
+        filters_active: prepared.filters_active,
+        merged: prepared.merged,
+        sentiment_by_sender: sentiment_by_sender(&prepared.messages, options),
+        language_distribution: language_distribution(&prepared.messages, options),
+        senders: options.dry_run.then(|| distinct_senders(&prepared.messages)),
+        date_span: options.dry_run.then(|| date_span_of(&prepared.messages)),
+        redactions: prepared.redactions.clone(),
+        truncated: false,
+        warnings: prepared.warnings.clone(),
     }
 }
 
+fn distinct_senders(messages: &[Message]) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    messages.iter().filter(|m| seen.insert(m.sender.as_str())).map(|m| m.sender.clone()).collect()
+}
+
+fn date_span_of(messages: &[Message]) -> DateSpan {
+    let timestamps = messages.iter().filter_map(|m| m.timestamp);
+    let format = |t: chrono::DateTime<chrono::Utc>| t.format("%Y-%m-%d %H:%M:%S").to_string();
+
+    DateSpan { first: timestamps.clone().min().map(format), last: timestamps.max().map(format) }
+}
+
+#[cfg(feature = "sentiment")]
+fn sentiment_by_sender(
+    messages: &[Message],
+    options: &ConvertOptions,
+) -> Option<BTreeMap<String, f64>> {
+    options.sentiment.then(|| sentiment::by_sender(messages))
+}
+
+#[cfg(not(feature = "sentiment"))]
+fn sentiment_by_sender(
+    _messages: &[Message],
+    _options: &ConvertOptions,
+) -> Option<BTreeMap<String, f64>> {
+    None
+}
+
+#[cfg(feature = "language")]
+fn language_distribution(
+    messages: &[Message],
+    options: &ConvertOptions,
+) -> Option<BTreeMap<String, usize>> {
+    options.detect_language.then(|| language::distribution(messages))
+}
+
+#[cfg(not(feature = "language"))]
+fn language_distribution(
+    _messages: &[Message],
+    _options: &ConvertOptions,
+) -> Option<BTreeMap<String, usize>> {
+    None
+}
+
+#[cfg(feature = "language")]
+fn filter_languages(
+    mut messages: Vec<Message>,
+    languages: &[String],
+) -> std::result::Result<Vec<Message>, String> {
+    messages.retain(|message| {
+        language::detect(&message.content)
+            .is_some_and(|code| languages.iter().any(|l| l.trim().eq_ignore_ascii_case(code)))
+    });
+
+    Ok(messages)
+}
+
+#[cfg(not(feature = "language"))]
+fn filter_languages(
+    _messages: Vec<Message>,
+    _languages: &[String],
+) -> std::result::Result<Vec<Message>, String> {
+    Err(enrich::LANGUAGE_UNAVAILABLE.to_string())
+}
+
 fn output_config(options: &ConvertOptions) -> OutputConfig {
     let mut config = OutputConfig::new();
 
@@ -315,68 +1506,101 @@ fn parse_options(options_json: &str) -> std::result::Result<ConvertOptions, Stri
         return Ok(ConvertOptions::default());
     }
 
-    serde_json::from_str(options_json).map_err(|e| format!("Invalid options JSON: {e}"))
+    let options =
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid options JSON: {e}"))?;
+    validate_options(&options)?;
+
+    Ok(options)
 }
 
-fn parse_platform(s: &str) -> std::result::Result<Platform, String> {
-    Platform::from_str(s)
+/// Check option values up front, shared by the JSON path and the `Options` builder.
+fn validate_options(options: &ConvertOptions) -> std::result::Result<(), String> {
+    filter_config(options)?;
+    enrich::extra_columns(&[], options)?;
+    if !options.languages_include.is_empty() {
+        filter_languages(Vec::new(), &options.languages_include)?;
+    }
+    if let Some(timezone) = non_empty(options.source_timezone.as_deref()) {
+        timezone::parse_offset(timezone)?;
+    }
+    if let Some(sample_options) = &options.sample {
+        sample::validate(sample_options)?;
+    }
+    if let Some(dialogue_options) = &options.extract_dialogue {
+        dialogue::validate(dialogue_options)?;
+    }
+    redact::validate(&options.redact_words)?;
+    if options.thread_min_length < threads::DEFAULT_MIN_LENGTH {
+        return Err("Invalid thread_min_length: a thread has at least 2 messages".to_string());
+    }
+    if options.max_per_sender == Some(0) {
+        return Err("Invalid max_per_sender: must be at least 1".to_string());
+    }
+    if options.max_output_bytes == Some(0) {
+        return Err("Invalid max_output_bytes: must be at least 1".to_string());
+    }
+    if options.anonymize_seed.is_some() && !options.anonymize {
+        return Err("Invalid anonymize_seed: requires anonymize".to_string());
+    }
+    if options.sample_every == Some(0) {
+        return Err("Invalid sample_every: must be at least 1".to_string());
+    }
+    if options.preserve_links && options.include_link_targets {
+        return Err(
+            "Conflicting options: 'preserve_links' with 'include_link_targets' writes links twice"
+                .to_string(),
+        );
+    }
+    if options.drop_system && options.events_only {
+        return Err(
+            "Conflicting options: 'drop_system' with 'events_only' leaves nothing to convert"
+                .to_string(),
+        );
+    }
+
+    Ok(())
 }
 
-fn parse_format(s: &str) -> std::result::Result<OutputFormat, String> {
-    OutputFormat::from_str(s)
+fn parse_platform(s: &str) -> std::result::Result<Backend, String> {
+    registry::find_platform(s).map(|spec| spec.backend)
 }
 
-fn non_empty(value: Option<&str>) -> Option<&str> {
-    value.map(str::trim).filter(|value| !value.is_empty())
+fn parse_format(s: &str) -> std::result::Result<OutputFormat, String> {
+    registry::find_format(s).map(|spec| spec.format)
 }
 
-fn platform_id(platform: Platform) -> &'static str {
-    match platform {
-        Platform::Telegram => "telegram",
-        Platform::WhatsApp => "whatsapp",
-        Platform::Instagram => "instagram",
-        Platform::Discord => "discord",
-        _ => "unknown",
+/// Like `parse_format`, also applying options implied by the name (`json-compact`).
+fn resolve_format(
+    s: &str,
+    options: &mut ConvertOptions,
+) -> std::result::Result<OutputFormat, String> {
+    let spec = registry::find_format(s)?;
+    if spec.compact {
+        options.pretty = false;
     }
+
+    Ok(spec.format)
 }
 
-fn format_id(format: OutputFormat) -> &'static str {
-    match format {
-        OutputFormat::Csv => "csv",
-        OutputFormat::Json => "json",
-        OutputFormat::Jsonl => "jsonl",
-        _ => "unknown",
-    }
+fn non_empty(value: Option<&str>) -> Option<&str> {
+    value.map(str::trim).filter(|value| !value.is_empty())
 }
 
 fn js_error(message: String) -> JsValue {
     JsValue::from_str(&message)
 }
 
+fn describe_js_error(value: JsValue) -> String {
+    value
+        .as_string()
+        .or_else(|| value.dyn_ref::<js_sys::Error>().map(|e| e.message().into()))
+        .unwrap_or_else(|| "JavaScript callback threw a non-Error value".to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    const TELEGRAM: &str = r#"{
-  "messages": [
-    {
-      "id": 1,
-      "type": "message",
-      "date_unixtime": "1705314600",
-      "from": "Alice",
-      "text": "Hello"
-    },
-    {
-      "id": 2,
-      "type": "message",
-      "date_unixtime": "1705314660",
-      "from": "Bob",
-      "text": "Reply",
-      "reply_to_message_id": 1,
-      "edited_unixtime": "1705314700"
-    }
-  ]
-}"#;
+    use crate::test_support::TELEGRAM;
 
     #[test]
     fn test_version() {
@@ -414,7 +1638,7 @@ mod tests {
         assert!(output.contains("ID;Timestamp;Sender;Content;ReplyTo;Edited"));
         assert!(output.contains("1;"));
         assert!(output.contains("2;"));
-        assert!(output.contains("Bob;Reply;1;"));
+        assert!(output.contains("Bob;Hi;1;"));
     }
 
     #[test]
@@ -437,6 +1661,267 @@ mod tests {
         assert!(report["output"].as_str().unwrap().contains("Alice"));
     }
 
+    #[cfg(feature = "sentiment")]
+    #[test]
+    fn test_sentiment_adds_scores_and_averages() {
+        let report_json = convert_with_report(TELEGRAM, "telegram", "csv", r#"{"sentiment":true}"#)
+            .map_err(|e| e.as_string().unwrap_or_default())
+            .expect("report should serialize");
+        let report: serde_json::Value = serde_json::from_str(&report_json).unwrap();
+        let output = report["output"].as_str().unwrap();
+
+        assert!(output.lines().next().unwrap().ends_with(";Sentiment"));
+        assert!(report["stats"]["sentiment_by_sender"]["Alice"].is_number());
+    }
+
+    #[cfg(not(feature = "sentiment"))]
+    #[test]
+    fn test_sentiment_requires_feature() {
+        let error = parse_options(r#"{"sentiment":true}"#).unwrap_err();
+
+        assert!(error.contains("`sentiment` feature"));
+    }
+
+    #[cfg(feature = "language")]
+    #[test]
+    fn test_detect_language_tags_and_filters() {
+        let input = r#"{"messages": [
+    {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Alice", "text": "Are you coming to the meeting later today?"},
+    {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Bob", "text": "Ты придёшь сегодня вечером на встречу?"},
+    {"id": 3, "type": "message", "date_unixtime": "1705314720", "from": "Alice", "text": "ok"}
+]}"#;
+        let report = |options: &str| -> serde_json::Value {
+            let json = convert_with_report(input, "telegram", "jsonl", options)
+                .map_err(|e| e.as_string().unwrap_or_default())
+                .expect("report should serialize");
+            serde_json::from_str(&json).unwrap()
+        };
+
+        let tagged = report(r#"{"detect_language":true}"#);
+        let output = tagged["output"].as_str().unwrap();
+        assert!(output.lines().next().unwrap().ends_with(r#""language":"en"}"#));
+        assert!(output.lines().nth(2).unwrap().ends_with(r#""language":null}"#));
+        assert_eq!(tagged["stats"]["language_distribution"]["ru"], 1);
+        assert_eq!(tagged["stats"]["language_distribution"]["unknown"], 1);
+
+        let filtered = report(r#"{"languages_include":["RU"]}"#);
+        assert_eq!(filtered["stats"]["filtered_count"], 1);
+        assert!(filtered["stats"]["filters_active"].as_bool().unwrap());
+    }
+
+    #[cfg(not(feature = "language"))]
+    #[test]
+    fn test_language_options_require_feature() {
+        assert!(parse_options(r#"{"detect_language":true}"#).unwrap_err().contains("`language`"));
+        assert!(parse_options(r#"{"languages_include":["en"]}"#)
+            .unwrap_err()
+            .contains("`language`"));
+    }
+
+    #[test]
+    fn test_compact_json_matches_pretty_json() {
+        let options = ConvertOptions { include_ids: true, ..ConvertOptions::default() };
+        let compact_options = ConvertOptions { pretty: false, ..options.clone() };
+        let pretty = run_conversion(TELEGRAM, "telegram", "json", &options).unwrap().output;
+        let compact =
+            run_conversion(TELEGRAM, "telegram", "json", &compact_options).unwrap().output;
+        let alias = run_conversion(TELEGRAM, "telegram", "json-compact", &options).unwrap().output;
+
+        assert!(!compact.contains('\n'));
+        assert_eq!(compact, alias);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&compact).unwrap(),
+            serde_json::from_str::<serde_json::Value>(&pretty).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_merge_separator_joins_merged_parts() {
+        let input = "[15.01.24, 10:30:00] Alice: one\n\
+[15.01.24, 10:30:05] Alice: two\n\
+[15.01.24, 10:30:09] Alice: three\n\
+[15.01.24, 10:31:00] Bob: four\n";
+        let prepare = |separator: Option<&str>| {
+            let options = ConvertOptions {
+                merge_separator: separator.map(str::to_string),
+                ..ConvertOptions::default()
+            };
+            prepare_messages(input, "whatsapp", &options).unwrap().messages
+        };
+
+        let piped = prepare(Some(" | "));
+        let default = prepare(None);
+
+        assert_eq!(piped.len(), 2);
+        assert_eq!(piped[0].content, "one | two | three");
+        assert_eq!(piped[1].content, "four");
+        assert_eq!(piped[0].timestamp, default[0].timestamp);
+        assert_eq!(prepare(Some("\n"))[0].content, default[0].content);
+    }
+
+    #[test]
+    fn test_merge_keep_policies() {
+        let input = r#"{"messages": [
+{"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Bob", "text": "question"},
+{"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Alice", "text": "hmm"},
+{"id": 3, "type": "message", "date_unixtime": "1705314670", "from": "Alice", "text": "answer", "reply_to_message_id": 1},
+{"id": 4, "type": "message", "date_unixtime": "1705314720", "from": "Alice", "text": "done"}
+]}"#;
+        let merged = |keep: &str| {
+            let options = parse_options(&format!(r#"{{"merge":{keep}}}"#)).unwrap();
+            prepare_messages(input, "telegram", &options).unwrap().messages.remove(1)
+        };
+
+        let default = merged("{}");
+        let last_any = merged(r#"{"timestamp":"last","reply_to":"any"}"#);
+        let dropped = merged(r#"{"reply_to":"drop"}"#);
+
+        assert_eq!(default.timestamp.unwrap().timestamp(), 1_705_314_660);
+        assert_eq!(default.reply_to, None);
+        assert_eq!(default.content, "hmm\nanswer\ndone");
+        assert_eq!(last_any.timestamp.unwrap().timestamp(), 1_705_314_720);
+        assert_eq!(last_any.reply_to, Some(1));
+        assert_eq!(last_any.id, Some(2));
+        assert_eq!(last_any.content, default.content);
+        assert_eq!(dropped.reply_to, None);
+        assert!(parse_options(r#"{"merge":{"timestamp":"middle"}}"#).is_err());
+    }
+
+    #[test]
+    fn test_dry_run_skips_output_and_adds_metadata() {
+        let dry = run_conversion(
+            TELEGRAM,
+            "telegram",
+            "csv",
+            &parse_options(r#"{"dry_run":true}"#).unwrap(),
+        )
+        .unwrap();
+        let full = run_conversion(TELEGRAM, "telegram", "csv", &ConvertOptions::default()).unwrap();
+        let stats = serde_json::to_value(&dry.stats).unwrap();
+
+        assert!(dry.output.is_empty());
+        assert_eq!(stats["output_bytes"], 0);
+        assert_eq!(stats["merged_count"], full.stats.merged_count);
+        assert_eq!(stats["senders"], serde_json::json!(["Alice", "Bob"]));
+        assert!(
+            stats["date_span"]["first"].as_str().unwrap()
+                <= stats["date_span"]["last"].as_str().unwrap()
+        );
+        assert!(serde_json::to_value(&full.stats).unwrap().get("senders").is_none());
+    }
+
+    #[test]
+    fn test_csv_formulas_escaped_unless_opted_out() {
+        let input = "[15.01.24, 10:30:00] =Mallory: =1+1\n[15.01.24, 10:31:00] Bob: -5\n";
+        let options = |json: &str| parse_options(json).unwrap();
+
+        let escaped = run_conversion(input, "whatsapp", "csv", &options("{}")).unwrap().output;
+        let raw =
+            run_conversion(input, "whatsapp", "csv", &options(r#"{"escape_formulas":false}"#))
+                .unwrap()
+                .output;
+        let json = run_conversion(input, "whatsapp", "jsonl", &options("{}")).unwrap().output;
+
+        assert_eq!(escaped, "Sender;Content\n'=Mallory;'=1+1\nBob;'-5\n");
+        assert_eq!(raw, "Sender;Content\n=Mallory;=1+1\nBob;-5\n");
+        assert!(json.contains(r#""content":"=1+1""#));
+    }
+
+    #[test]
+    fn test_local_timestamps_warn_without_source_timezone() {
+        let input = "[15.01.24, 10:30:00] Alice: one\n[15.01.24, 10:31:00] Bob: two\n";
+        let options = |json: &str| parse_options(json).unwrap();
+
+        let ambiguous = run_conversion(input, "whatsapp", "csv", &options("{}")).unwrap();
+        let shifted =
+            prepare_messages(input, "whatsapp", &options(r#"{"source_timezone":"+05:00"}"#))
+                .unwrap();
+        let absolute = run_conversion(TELEGRAM, "telegram", "csv", &options("{}")).unwrap();
+
+        assert_eq!(ambiguous.stats.warnings.len(), 1);
+        assert!(ambiguous.stats.warnings[0].starts_with("2 message(s) have local timestamps"));
+        assert!(shifted.warnings.is_empty());
+        assert_eq!(
+            shifted.messages[0].timestamp.map(|t| t.to_rfc3339()).as_deref(),
+            Some("2024-01-15T05:30:00+00:00")
+        );
+        assert!(absolute.stats.warnings.is_empty());
+        assert!(parse_options(r#"{"source_timezone":"Mars/Olympus"}"#).is_err());
+    }
+
+    #[test]
+    fn test_reimported_output_converts_identically() {
+        let options = parse_options(
+            r#"{"include_timestamps":true,"include_ids":true,"include_replies":true,"include_edited":true}"#,
+        )
+        .unwrap();
+
+        for format in ["csv", "json", "jsonl", "json-compact"] {
+            let first = run_conversion(TELEGRAM, "telegram", format, &options).unwrap().output;
+            let source = format!("chatpack-{}", format.trim_end_matches("-compact"));
+            let second = run_conversion(&first, &source, format, &options)
+                .unwrap_or_else(|e| panic!("{format} should re-import: {e}"))
+                .output;
+
+            assert_eq!(second, first, "{format} round trip differs");
+        }
+    }
+
+    #[test]
+    fn test_pipeline_filters_before_merging() {
+        let input = "[15.01.24, 10:30:00] Alice: a1\n\
+[15.01.24, 10:31:00] Bob: b1\n\
+[15.01.24, 10:32:00] Alice: a2\n\
+[16.01.24, 09:00:00] Alice: a3\n";
+        let options = parse_options(r#"{"filter_sender":"Alice","date_to":"2024-01-15"}"#).unwrap();
+        let prepared = prepare_messages(input, "whatsapp", &options).unwrap();
+
+        assert_eq!(prepared.original_count, 4);
+        assert_eq!(prepared.filtered_count, 2);
+        assert_eq!(prepared.messages.len(), 1);
+        assert_eq!(prepared.messages[0].content, "a1\na2");
+    }
+
+    #[test]
+    fn test_pipeline_applies_timezone_before_date_filters() {
+        let input = "[16.01.24, 03:00:00] Alice: late\n[16.01.24, 09:00:00] Bob: morning\n";
+        let options =
+            parse_options(r#"{"source_timezone":"+05:00","date_to":"2024-01-15"}"#).unwrap();
+        let prepared = prepare_messages(input, "whatsapp", &options).unwrap();
+
+        assert_eq!(prepared.messages.len(), 1);
+        assert_eq!(prepared.messages[0].content, "late");
+    }
+
+    #[test]
+    fn test_platform_capabilities() {
+        let telegram = capabilities("tg").unwrap();
+        let whatsapp = capabilities("whatsapp").unwrap();
+
+        assert!(telegram.has_replies && telegram.has_ids && telegram.has_edits);
+        assert!(whatsapp.has_timestamps);
+        assert!(!whatsapp.has_replies && !whatsapp.has_ids && !whatsapp.has_edits);
+        assert!(capabilities("myspace").unwrap_err().starts_with("Unknown source"));
+    }
+
+    #[test]
+    fn test_empty_input_gives_empty_documents() {
+        let expected =
+            [("csv", "Sender;Content\n"), ("json", "[]"), ("json-compact", "[]"), ("jsonl", "")];
+
+        for (format, empty) in expected {
+            for input in ["", "  \n"] {
+                let report =
+                    run_conversion(input, "telegram", format, &ConvertOptions::default()).unwrap();
+
+                assert_eq!(report.output, empty, "{format}");
+                assert_eq!(report.stats.original_count, 0);
+                assert_eq!(report.stats.merged_count, 0);
+            }
+        }
+        assert!(parse_messages("", "myspace").unwrap_err().starts_with("Unknown source"));
+    }
+
     #[test]
     fn test_parse_chat_returns_messages() {
         let report_json = parse_chat(TELEGRAM, "telegram", r#"{"merge_consecutive":false}"#)
@@ -449,6 +1934,32 @@ mod tests {
         assert_eq!(report["stats"]["merged_count"], 2);
     }
 
+    #[test]
+    fn test_max_output_bytes_truncates_on_message_boundary() {
+        let chat: String = (0..20)
+            .map(|i| {
+                format!("[15.01.24, 10:{i:02}:00] {}: message number {i}\n", ["Ann", "Bob"][i % 2])
+            })
+            .collect();
+        let full = run_conversion(&chat, "whatsapp", "json", &ConvertOptions::default()).unwrap();
+        let limit = full.output.len() / 2;
+        let options = parse_options(&format!(r#"{{"max_output_bytes":{limit}}}"#)).unwrap();
+
+        let json = run_conversion(&chat, "whatsapp", "json", &options).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&json.output).unwrap();
+        let written = parsed.as_array().unwrap().len();
+        assert!(json.output.len() <= limit);
+        assert!(json.stats.truncated);
+        assert!(written > 0 && written < 20);
+        assert_eq!(json.stats.merged_count, written);
+
+        let csv = run_conversion(&chat, "whatsapp", "csv", &options).unwrap();
+        assert!(csv.output.len() <= limit);
+        assert!(csv.output.ends_with('\n'));
+        assert!(!full.stats.truncated);
+        assert!(parse_options(r#"{"max_output_bytes":0}"#).is_err());
+    }
+
     #[test]
     fn test_supported_sources_and_formats() {
         let sources = supported_sources()
@@ -458,8 +1969,19 @@ mod tests {
             .map_err(|e| e.as_string().unwrap_or_default())
             .expect("formats should serialize");
 
+        let platforms = supported_platforms()
+            .map_err(|e| e.as_string().unwrap_or_default())
+            .expect("platforms should serialize");
+        let platforms: serde_json::Value = serde_json::from_str(&platforms).unwrap();
+        let formats: serde_json::Value = serde_json::from_str(&formats).unwrap();
+
         assert!(sources.contains("telegram"));
-        assert!(formats.contains("jsonl"));
-        assert!(formats.contains("application/x-ndjson"));
+        assert_eq!(platforms[0]["id"], "telegram");
+        assert_eq!(platforms[0]["aliases"][0], "tg");
+        assert_eq!(platforms[1]["input_kind"], "text");
+        assert_eq!(formats[2]["id"], "jsonl");
+        assert_eq!(formats[2]["aliases"][0], "ndjson");
+        assert_eq!(formats[2]["mime_type"], "application/x-ndjson");
+        assert_eq!(formats[2]["binary"], false);
     }
 }
@@ -0,0 +1,42 @@
+//! WebAssembly memory reporting.
+//!
+//! A WebAssembly instance's linear memory only ever grows: pages the
+//! allocator got for one large conversion stay reserved, and are reused by
+//! the next one, until the module is instantiated again. Almost everything
+//! a conversion allocates is free for reuse as soon as it returns. The
+//! exceptions are `ChatSession` and `Options` objects, which hold their data
+//! until `free()` is called on them from JS, and the URL pattern in `urls`,
+//! compiled on first use and kept for the life of the module. No call
+//! releases the pattern, and WebAssembly cannot hand pages back to the
+//! browser; re-instantiate the module to get the memory back.
+
+use wasm_bindgen::prelude::*;
+
+/// Size of a WebAssembly memory page.
+const PAGE_BYTES: usize = 64 * 1024;
+
+/// Bytes of linear memory this module currently reserves (0 outside WASM).
+#[wasm_bindgen]
+pub fn memory_usage() -> f64 {
+    pages() as f64 * PAGE_BYTES as f64
+}
+
+#[cfg(target_arch = "wasm32")]
+fn pages() -> usize {
+    core::arch::wasm32::memory_size(0)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn pages() -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_is_whole_pages() {
+        assert_eq!(memory_usage() as usize % PAGE_BYTES, 0);
+    }
+}
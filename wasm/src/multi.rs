@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{js_error, parse_options, prepare_messages, resolve_format};
+
+/// Convert chat export into several formats with one parse.
+///
+/// Parsing, filtering and merging run once; each entry of `formats` is then
+/// formatted from the same messages, honouring its own implied options
+/// (`json-compact`). Takes the same `source` and `options_json` as
+/// `convert_with_options`.
+///
+/// Returns a JSON object mapping each requested format name, as given, to
+/// its output. An unknown format fails the whole call before any parsing.
+#[wasm_bindgen]
+pub fn convert_multi(
+    input: &str,
+    source: &str,
+    formats: Vec<String>,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    let outputs = convert_formats(input, source, &formats, options_json).map_err(js_error)?;
+
+    serde_json::to_string(&outputs).map_err(|e| js_error(e.to_string()))
+}
+
+fn convert_formats(
+    input: &str,
+    source: &str,
+    formats: &[String],
+    options_json: &str,
+) -> std::result::Result<BTreeMap<String, String>, String> {
+    let options = parse_options(options_json)?;
+    let targets = formats
+        .iter()
+        .map(|format| {
+            let mut format_options = options.clone();
+            let output_format = resolve_format(format, &mut format_options)?;
+            Ok((format, output_format, format_options))
+        })
+        .collect::<std::result::Result<Vec<_>, String>>()?;
+    let prepared = prepare_messages(input, source, &options)?;
+
+    targets
+        .into_iter()
+        .map(|(name, output_format, format_options)| {
+            let output = prepared.format(output_format, &format_options)?;
+            Ok((name.clone(), output))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_conversion;
+    use crate::test_support::TELEGRAM;
+
+    #[test]
+    fn test_multi_matches_single_conversions() {
+        let formats: Vec<String> =
+            ["csv", "json", "jsonl", "json-compact"].iter().map(|f| f.to_string()).collect();
+        let options = r#"{"include_timestamps":true}"#;
+        let outputs = convert_formats(TELEGRAM, "telegram", &formats, options).unwrap();
+
+        assert_eq!(outputs.len(), 4);
+        for format in &formats {
+            let expected =
+                run_conversion(TELEGRAM, "telegram", format, &parse_options(options).unwrap())
+                    .unwrap();
+            assert_eq!(outputs[format], expected.output, "{format}");
+        }
+        let unknown = vec!["csv".to_string(), "xml".to_string()];
+        assert!(convert_formats(TELEGRAM, "telegram", &unknown, "").is_err());
+    }
+}
@@ -0,0 +1,457 @@
+use wasm_bindgen::prelude::*;
+
+use crate::{js_error, registry, run_conversion, validate_options, ConvertOptions};
+
+/// Conversion settings built from JS with chainable, eagerly validated setters.
+///
+/// ```js
+/// const options = new Options().with_source('wa').with_format('jsonl').with_timestamps(true)
+/// const output = convert_with(input, options)
+/// ```
+///
+/// Every `with_*` method consumes the object and returns the updated one, so
+/// keep using the returned value. Invalid values throw right away with the
+/// same message `convert_with_options` would produce. `to_json` and
+/// `from_json` round-trip through the `convert_with_options` JSON, which
+/// makes it easy to persist and restore user settings.
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Options {
+    source: &'static str,
+    format: &'static str,
+    options: ConvertOptions,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            source: registry::PLATFORMS[0].id,
+            format: registry::FORMATS[0].id,
+            options: ConvertOptions::default(),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl Options {
+    /// Telegram to CSV with the `convert_with_options` defaults.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Options {
+        Self::default()
+    }
+
+    /// Restore options saved with `to_json`; `source` and `format` are passed separately.
+    pub fn from_json(
+        source: &str,
+        format: &str,
+        options_json: &str,
+    ) -> std::result::Result<Options, JsValue> {
+        let options = crate::parse_options(options_json).map_err(js_error)?;
+
+        Self { options, ..Self::default() }.with_source(source)?.with_format(format)
+    }
+
+    /// The options as `convert_with_options` JSON (without source and format).
+    pub fn to_json(&self) -> std::result::Result<String, JsValue> {
+        serde_json::to_string(&self.options).map_err(|e| js_error(e.to_string()))
+    }
+
+    pub fn with_source(self, source: &str) -> std::result::Result<Options, JsValue> {
+        self.try_with_source(source).map_err(js_error)
+    }
+
+    pub fn with_format(self, format: &str) -> std::result::Result<Options, JsValue> {
+        self.try_with_format(format).map_err(js_error)
+    }
+
+    pub fn with_timestamps(mut self, enabled: bool) -> Options {
+        self.options.include_timestamps = enabled;
+        self
+    }
+
+    pub fn with_ids(mut self, enabled: bool) -> Options {
+        self.options.include_ids = enabled;
+        self
+    }
+
+    pub fn with_replies(mut self, enabled: bool) -> Options {
+        self.options.include_replies = enabled;
+        self
+    }
+
+    pub fn with_edited(mut self, enabled: bool) -> Options {
+        self.options.include_edited = enabled;
+        self
+    }
+
+    pub fn with_merge(mut self, enabled: bool) -> Options {
+        self.options.merge_consecutive = enabled;
+        self
+    }
+
+    /// Text placed between merged messages; `undefined` restores the default newline.
+    pub fn with_merge_separator(mut self, separator: Option<String>) -> Options {
+        self.options.merge_separator = separator;
+        self
+    }
+
+    /// Metadata a merged message keeps: `timestamp` is `"first"` or `"last"`,
+    /// `reply_to` is `"first"`, `"any"` or `"drop"` (the `merge` option).
+    pub fn with_merge_keep(
+        self,
+        timestamp: &str,
+        reply_to: &str,
+    ) -> std::result::Result<Options, JsValue> {
+        self.try_with_merge_keep(timestamp, reply_to).map_err(js_error)
+    }
+
+    /// Indent JSON output (default) or write it on a single line.
+    pub fn with_pretty(mut self, enabled: bool) -> Options {
+        self.options.pretty = enabled;
+        self
+    }
+
+    pub fn with_sentiment(self, enabled: bool) -> std::result::Result<Options, JsValue> {
+        self.try_with_sentiment(enabled).map_err(js_error)
+    }
+
+    /// Keep only messages from `sender`; `undefined` or an empty string clears the filter.
+    pub fn with_sender(self, sender: Option<String>) -> std::result::Result<Options, JsValue> {
+        self.try_with_sender(sender).map_err(js_error)
+    }
+
+    /// Keep only messages from any of `senders`; an empty list clears the filter.
+    pub fn with_senders(self, senders: Vec<String>) -> std::result::Result<Options, JsValue> {
+        self.try_with_senders(senders).map_err(js_error)
+    }
+
+    /// Keep only messages between `from` and `to` (`YYYY-MM-DD`, both inclusive, either optional).
+    pub fn with_date_range(
+        self,
+        from: Option<String>,
+        to: Option<String>,
+    ) -> std::result::Result<Options, JsValue> {
+        self.try_with_date_range(from, to).map_err(js_error)
+    }
+
+    /// UTC offset (e.g. `+05:30`) of exports that store local time; `undefined` keeps them as UTC.
+    pub fn with_source_timezone(
+        self,
+        timezone: Option<String>,
+    ) -> std::result::Result<Options, JsValue> {
+        self.try_with_source_timezone(timezone).map_err(js_error)
+    }
+
+    /// Replace senders, and their names in contents, with pseudonyms.
+    pub fn with_anonymize(self, enabled: bool) -> std::result::Result<Options, JsValue> {
+        self.try_with_anonymize(enabled).map_err(js_error)
+    }
+
+    /// Seed pseudonyms so every conversion with the same seed gives a person
+    /// the same one; requires `with_anonymize(true)` first, `undefined` clears it.
+    pub fn with_anonymize_seed(
+        self,
+        seed: Option<String>,
+    ) -> std::result::Result<Options, JsValue> {
+        self.try_with_anonymize_seed(seed).map_err(js_error)
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn source(&self) -> String {
+        self.source.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn format(&self) -> String {
+        self.format.to_string()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn timestamps(&self) -> bool {
+        self.options.include_timestamps
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn ids(&self) -> bool {
+        self.options.include_ids
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn replies(&self) -> bool {
+        self.options.include_replies
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn edited(&self) -> bool {
+        self.options.include_edited
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn merge(&self) -> bool {
+        self.options.merge_consecutive
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn merge_separator(&self) -> Option<String> {
+        self.options.merge_separator.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn pretty(&self) -> bool {
+        self.options.pretty
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sentiment(&self) -> bool {
+        self.options.sentiment
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn sender(&self) -> Option<String> {
+        self.options.filter_sender.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn senders(&self) -> Vec<String> {
+        self.options.filter_senders.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn date_from(&self) -> Option<String> {
+        self.options.date_from.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn date_to(&self) -> Option<String> {
+        self.options.date_to.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn source_timezone(&self) -> Option<String> {
+        self.options.source_timezone.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn anonymize(&self) -> bool {
+        self.options.anonymize
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn anonymize_seed(&self) -> Option<String> {
+        self.options.anonymize_seed.clone()
+    }
+
+    /// The `merge` option as `{timestamp, reply_to}` (the `merge` getter is
+    /// `merge_consecutive`).
+    #[wasm_bindgen(getter)]
+    pub fn merge_keep(&self) -> std::result::Result<String, JsValue> {
+        serde_json::to_string(&self.options.merge_keep).map_err(|e| js_error(e.to_string()))
+    }
+}
+
+// The fallible setters, returning the message the JS setters throw.
+impl Options {
+    fn try_with_source(mut self, source: &str) -> std::result::Result<Options, String> {
+        self.source = registry::find_platform(source)?.id;
+        Ok(self)
+    }
+
+    fn try_with_format(mut self, format: &str) -> std::result::Result<Options, String> {
+        self.format = registry::find_format(format)?.id;
+        Ok(self)
+    }
+
+    fn try_with_merge_keep(
+        mut self,
+        timestamp: &str,
+        reply_to: &str,
+    ) -> std::result::Result<Options, String> {
+        let keep = serde_json::json!({ "timestamp": timestamp, "reply_to": reply_to });
+        self.options.merge_keep =
+            serde_json::from_value(keep).map_err(|e| format!("Invalid merge: {e}"))?;
+        Ok(self)
+    }
+
+    fn try_with_sentiment(mut self, enabled: bool) -> std::result::Result<Options, String> {
+        self.options.sentiment = enabled;
+        self.validated()
+    }
+
+    fn try_with_sender(mut self, sender: Option<String>) -> std::result::Result<Options, String> {
+        self.options.filter_sender = sender;
+        self.validated()
+    }
+
+    fn try_with_senders(mut self, senders: Vec<String>) -> std::result::Result<Options, String> {
+        self.options.filter_senders = senders;
+        self.validated()
+    }
+
+    fn try_with_date_range(
+        mut self,
+        from: Option<String>,
+        to: Option<String>,
+    ) -> std::result::Result<Options, String> {
+        self.options.date_from = from;
+        self.options.date_to = to;
+        self.validated()
+    }
+
+    fn try_with_source_timezone(
+        mut self,
+        timezone: Option<String>,
+    ) -> std::result::Result<Options, String> {
+        self.options.source_timezone = timezone;
+        self.validated()
+    }
+
+    fn try_with_anonymize(mut self, enabled: bool) -> std::result::Result<Options, String> {
+        self.options.anonymize = enabled;
+        self.validated()
+    }
+
+    fn try_with_anonymize_seed(
+        mut self,
+        seed: Option<String>,
+    ) -> std::result::Result<Options, String> {
+        self.options.anonymize_seed = seed;
+        self.validated()
+    }
+
+    fn validated(self) -> std::result::Result<Options, String> {
+        validate_options(&self.options)?;
+        Ok(self)
+    }
+}
+
+/// Convert chat export using an `Options` object.
+///
+/// Same output and errors as `convert_with_options` called with the options' values.
+#[wasm_bindgen]
+pub fn convert_with(input: &str, options: &Options) -> std::result::Result<String, JsValue> {
+    run_conversion(input, options.source, options.format, &options.options)
+        .map(|report| report.output)
+        .map_err(js_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHATSAPP: &str = "[15.01.24, 10:30:00] Alice: Hello\n\
+[15.01.24, 10:31:00] Bob: Hi there\n";
+
+    fn message(error: JsValue) -> String {
+        error.as_string().unwrap_or_default()
+    }
+
+    #[test]
+    fn test_setters_validate_eagerly() {
+        let options = Options::new()
+            .with_format("NDJSON")
+            .and_then(|o| o.with_date_range(Some("2024-01-15".to_string()), None))
+            .map_err(message)
+            .unwrap();
+        // What `convert_with_options` throws for the same values.
+        let json_error = |format: &str, options: &str| {
+            crate::parse_options(options)
+                .and_then(|options| crate::run_conversion(WHATSAPP, "whatsapp", format, &options))
+                .err()
+                .unwrap()
+        };
+
+        assert_eq!(options.format(), "jsonl");
+        assert_eq!(options.date_from().as_deref(), Some("2024-01-15"));
+        assert_eq!(
+            Options::new().try_with_format("parquett").err().unwrap(),
+            json_error("parquett", "{}")
+        );
+        assert_eq!(
+            Options::new().try_with_date_range(None, Some("2024-13-45".to_string())).err().unwrap(),
+            json_error("csv", r#"{"date_to":"2024-13-45"}"#)
+        );
+        assert_eq!(
+            Options::new().try_with_source_timezone(Some("+25:00".to_string())).err().unwrap(),
+            json_error("csv", r#"{"source_timezone":"+25:00"}"#)
+        );
+    }
+
+    #[test]
+    fn test_with_senders_keeps_any_listed_sender() {
+        let input = format!("{WHATSAPP}[15.01.24, 10:32:00] Carol: Hey\n");
+        let options = Options::new()
+            .with_source("whatsapp")
+            .and_then(|o| o.with_senders(vec!["alice".to_string(), "Carol".to_string()]))
+            .map_err(message)
+            .unwrap();
+        let expected = crate::run_conversion(
+            &input,
+            "whatsapp",
+            "csv",
+            &crate::parse_options(r#"{"filter_senders":["alice","Carol"]}"#).unwrap(),
+        )
+        .unwrap()
+        .output;
+        let output = convert_with(&input, &options).map_err(message).unwrap();
+
+        assert_eq!(options.senders(), ["alice", "Carol"]);
+        assert_eq!(output, expected);
+        assert!(output.contains("Alice") && output.contains("Carol") && !output.contains("Bob"));
+    }
+
+    #[test]
+    fn test_convert_with_matches_json_path() {
+        let options = Options::new()
+            .with_source("wa")
+            .and_then(|o| o.with_sender(Some("Bob".to_string())))
+            .and_then(|o| o.with_merge_keep("last", "drop"))
+            .map_err(message)
+            .unwrap()
+            .with_timestamps(true);
+        let json: serde_json::Value =
+            serde_json::from_str(&options.to_json().map_err(message).unwrap()).unwrap();
+        let restored =
+            Options::from_json("whatsapp", "csv", &options.to_json().map_err(message).unwrap())
+                .map_err(message)
+                .unwrap();
+        let expected =
+            crate::convert_with_options(WHATSAPP, "whatsapp", "csv", &options.to_json().unwrap())
+                .map_err(message)
+                .unwrap();
+
+        assert_eq!(convert_with(WHATSAPP, &options).map_err(message).unwrap(), expected);
+        assert_eq!(convert_with(WHATSAPP, &restored).map_err(message).unwrap(), expected);
+        assert_eq!(restored.sender().as_deref(), Some("Bob"));
+        assert!(restored.timestamps());
+        assert_eq!(json["merge"], serde_json::json!({"timestamp": "last", "reply_to": "drop"}));
+        assert_eq!(
+            restored.merge_keep().map_err(message).unwrap(),
+            r#"{"timestamp":"last","reply_to":"drop"}"#
+        );
+    }
+
+    #[test]
+    fn test_anonymize_setters() {
+        let options = Options::new()
+            .with_source("wa")
+            .and_then(|o| o.with_anonymize(true))
+            .and_then(|o| o.with_anonymize_seed(Some("project-x".to_string())))
+            .map_err(message)
+            .unwrap();
+        let expected = crate::convert_with_options(
+            WHATSAPP,
+            "whatsapp",
+            "csv",
+            r#"{"anonymize":true,"anonymize_seed":"project-x"}"#,
+        )
+        .map_err(message)
+        .unwrap();
+
+        assert_eq!(convert_with(WHATSAPP, &options).map_err(message).unwrap(), expected);
+        assert!(options.anonymize());
+        assert_eq!(options.anonymize_seed().as_deref(), Some("project-x"));
+        let mut unseeded = options.clone();
+        unseeded.options.anonymize = false;
+        assert!(validate_options(&unseeded.options).is_err());
+    }
+}
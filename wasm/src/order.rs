@@ -0,0 +1,130 @@
+//! Chronological order of a conversation.
+//!
+//! Exports list messages in the order they were sent, but stitched,
+//! re-imported or corrupted files do not always: a timestamp earlier than
+//! the one before it quietly skews anything computed over time (sessions,
+//! response times, date splits). Messages without a timestamp are ignored
+//! by the check and stay where they are when sorting.
+
+use chatpack::Message;
+
+/// Number of dated messages older than the dated message before them, and
+/// the index of the first one.
+fn backwards_steps(messages: &[Message]) -> Option<(usize, usize)> {
+    let mut latest = None;
+    let mut first = None;
+    let mut count = 0;
+    for (index, timestamp) in
+        messages.iter().enumerate().filter_map(|(i, m)| Some((i, m.timestamp?)))
+    {
+        if latest.is_some_and(|latest| timestamp < latest) {
+            count += 1;
+            first.get_or_insert(index);
+        } else {
+            latest = Some(timestamp);
+        }
+    }
+
+    first.map(|first| (count, first))
+}
+
+/// Warning for timestamps that go backwards, if they do anywhere.
+pub(crate) fn out_of_order_warning(messages: &[Message]) -> Option<String> {
+    backwards_steps(messages).map(|(count, first)| {
+        format!(
+            "{count} message(s) are older than a message before them (first at index {first}); \
+             set sort to order messages by time"
+        )
+    })
+}
+
+/// Stable sort of the dated messages by timestamp, in the slots dated
+/// messages occupy, so undated ones keep their position.
+pub(crate) fn sort_by_time(messages: &mut [Message]) {
+    let slots: Vec<usize> = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, message)| message.timestamp.is_some())
+        .map(|(slot, _)| slot)
+        .collect();
+    let mut dated: Vec<Message> = slots.iter().map(|&slot| messages[slot].clone()).collect();
+    dated.sort_by_key(|message| message.timestamp);
+    for (slot, message) in slots.into_iter().zip(dated) {
+        messages[slot] = message;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_options, run_conversion};
+
+    const FIXTURE: &str = include_str!("../fixtures/telegram_out_of_order.json");
+
+    #[test]
+    fn test_out_of_order_fixture_warns() {
+        let report = run_conversion(FIXTURE, "telegram", "csv", &parse_options("{}").unwrap())
+            .expect("conversion should succeed");
+
+        let warning = report.stats.warnings.iter().find(|w| w.contains("older than")).unwrap();
+        assert!(warning.starts_with("2 message(s)"));
+        assert!(warning.contains("first at index 2"));
+        assert!(warning.contains("set sort"));
+    }
+
+    #[test]
+    fn test_sort_orders_and_silences_warning() {
+        let options = parse_options(r#"{"sort":true,"merge_consecutive":false}"#).unwrap();
+        let report = run_conversion(FIXTURE, "telegram", "csv", &options).unwrap();
+        let contents: Vec<&str> =
+            report.output.lines().skip(1).map(|line| line.split(';').nth(1).unwrap()).collect();
+
+        assert!(!report.stats.warnings.iter().any(|w| w.contains("older than")));
+        assert_eq!(contents, ["First", "Second", "Third", "Fourth", "Fifth"]);
+    }
+
+    #[test]
+    fn test_reverse_is_the_last_ordering_step() {
+        let contents = |options: &str| {
+            let report =
+                run_conversion(FIXTURE, "telegram", "csv", &parse_options(options).unwrap())
+                    .unwrap();
+            report
+                .output
+                .lines()
+                .skip(1)
+                .map(|line| line.split(';').nth(1).unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            contents(r#"{"sort":true,"reverse":true,"merge_consecutive":false}"#),
+            ["Fifth", "Fourth", "Third", "Second", "First"]
+        );
+        // Truncation keeps the newest messages once reversed.
+        assert_eq!(
+            contents(
+                r#"{"sort":true,"reverse":true,"merge_consecutive":false,"max_output_bytes":40}"#
+            ),
+            ["Fifth", "Fourth"]
+        );
+        // Merged turns stay whole; only their order flips.
+        assert_eq!(contents(r#"{"reverse":true}"#).len(), contents("{}").len());
+    }
+
+    #[test]
+    fn test_undated_messages_are_ignored_and_kept_in_place() {
+        let dated = |secs, content| {
+            Message::new("Ann", content)
+                .with_timestamp(chrono::DateTime::from_timestamp(secs, 0).unwrap())
+        };
+        let mut messages =
+            vec![dated(20, "b"), Message::new("Ann", "undated"), dated(10, "a"), dated(30, "c")];
+
+        assert_eq!(backwards_steps(&messages), Some((1, 2)));
+        sort_by_time(&mut messages);
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["a", "undated", "b", "c"]);
+        assert_eq!(backwards_steps(&messages), None);
+    }
+}
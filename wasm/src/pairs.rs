@@ -0,0 +1,251 @@
+//! Prompt/completion pairs for fine-tuning and evaluation datasets.
+//!
+//! Messages are grouped into turns (consecutive messages of one sender, as
+//! `merge_consecutive` produces them), the turns are split into sessions at
+//! long silences, and each user turn directly answered by the assistant
+//! becomes a pair.
+
+use chatpack::Message;
+use chrono::Duration;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{js_error, parse_options, prepare_messages};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct PairOptions {
+    /// Sender whose turns are completions.
+    assistant: Option<String>,
+    /// Sender whose turns are prompts; any sender other than `assistant` if unset.
+    user: Option<String>,
+    /// Number of turns before the prompt turn folded into the prompt.
+    context_turns: usize,
+    /// How each turn is written when context is folded in.
+    context_template: String,
+    /// Text between the stacked turns of a prompt with context.
+    context_separator: String,
+    /// Silence (in minutes) after which a new session starts.
+    session_gap_minutes: Option<u64>,
+}
+
+impl Default for PairOptions {
+    fn default() -> Self {
+        Self {
+            assistant: None,
+            user: None,
+            context_turns: 0,
+            context_template: "{sender}: {content}".to_string(),
+            context_separator: "\n".to_string(),
+            session_gap_minutes: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Pair {
+    prompt: String,
+    completion: String,
+    timestamp: Option<String>,
+}
+
+/// Pair each user turn with the assistant turn that directly follows it.
+///
+/// `options_json` takes the `convert_with_options` keys (filters, merging)
+/// plus:
+/// - `assistant` (required): sender whose turns are completions
+/// - `user`: sender whose turns are prompts; defaults to anyone else. With
+///   `anonymize`, `assistant` and `user` still name the original senders
+/// - `context_turns`: preceding turns folded into the prompt (default 0)
+/// - `context_template`: how turns are written when context is folded in,
+///   with `{sender}` and `{content}` placeholders (default
+///   `"{sender}: {content}"`); the prompt turn itself is written the same way
+/// - `context_separator`: text between stacked turns (default newline)
+/// - `session_gap_minutes`: a silence longer than this between two turns'
+///   first messages starts a new session; pairs and context never cross it
+///
+/// Consecutive messages of one sender form one turn only while
+/// `merge_consecutive` is on (the default). Returns a JSON array of
+/// `{prompt, completion, timestamp}`, where `timestamp` is the prompt turn's
+/// time (`YYYY-MM-DD HH:MM:SS`, UTC) or `null`.
+#[wasm_bindgen]
+pub fn extract_pairs(
+    input: &str,
+    source: &str,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    let pairs = pairs_from(input, source, options_json).map_err(js_error)?;
+
+    serde_json::to_string(&pairs).map_err(|e| js_error(e.to_string()))
+}
+
+fn pairs_from(
+    input: &str,
+    source: &str,
+    options_json: &str,
+) -> std::result::Result<Vec<Pair>, String> {
+    let options = parse_options(options_json)?;
+    let mut pair_options = parse_pair_options(options_json)?;
+    let prepared = prepare_messages(input, source, &options)?;
+    for name in [&mut pair_options.assistant, &mut pair_options.user].into_iter().flatten() {
+        *name = prepared.sender_named(name).to_string();
+    }
+
+    Ok(pairs(&prepared.messages, &pair_options))
+}
+
+fn parse_pair_options(options_json: &str) -> std::result::Result<PairOptions, String> {
+    let options: PairOptions = if options_json.trim().is_empty() {
+        PairOptions::default()
+    } else {
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid options JSON: {e}"))?
+    };
+    if options.assistant.as_deref().is_none_or(|name| name.trim().is_empty()) {
+        return Err("Missing option: 'assistant' must name the sender of completions".to_string());
+    }
+
+    Ok(options)
+}
+
+/// Which side of a conversation a turn is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Role {
+    User,
+    Assistant,
+}
+
+/// Role of `sender` when `assistant` answers and `user` (or, if unset,
+/// anyone else) asks; `None` for senders outside a set `user`.
+pub(crate) fn role_of(sender: &str, assistant: &str, user: Option<&str>) -> Option<Role> {
+    if sender == assistant.trim() {
+        Some(Role::Assistant)
+    } else if user.is_none_or(|user| sender == user.trim()) {
+        Some(Role::User)
+    } else {
+        None
+    }
+}
+
+/// `session_gap_minutes` as a duration; a gap too large to represent cannot
+/// be exceeded, so it means no gap.
+pub(crate) fn session_gap(minutes: Option<u64>) -> Option<Duration> {
+    minutes.and_then(|minutes| i64::try_from(minutes).ok()).and_then(Duration::try_minutes)
+}
+
+/// Indices where a new session starts: the first turn and every turn that
+/// follows a silence longer than `gap`. Turns without a timestamp never
+/// start a session.
+pub(crate) fn session_starts(turns: &[Message], gap: Option<Duration>) -> Vec<bool> {
+    turns
+        .iter()
+        .enumerate()
+        .map(|(index, turn)| {
+            index == 0
+                || gap.is_some_and(|gap| {
+                    let previous = turns[index - 1].timestamp;
+                    matches!((previous, turn.timestamp), (Some(a), Some(b)) if b - a > gap)
+                })
+        })
+        .collect()
+}
+
+fn pairs(turns: &[Message], options: &PairOptions) -> Vec<Pair> {
+    let assistant = options.assistant.as_deref().unwrap_or_default();
+    let role = |turn: &Message| role_of(&turn.sender, assistant, options.user.as_deref());
+    let starts = session_starts(turns, session_gap(options.session_gap_minutes));
+    let render = |turn: &Message| {
+        options
+            .context_template
+            .replace("{sender}", &turn.sender)
+            .replace("{content}", &turn.content)
+    };
+
+    let mut pairs = Vec::new();
+    let mut session_start = 0;
+    for index in 0..turns.len() {
+        if starts[index] {
+            session_start = index;
+        }
+        let Some(reply) = turns.get(index + 1) else {
+            break;
+        };
+        let prompt = &turns[index];
+        if role(prompt) != Some(Role::User)
+            || role(reply) != Some(Role::Assistant)
+            || starts[index + 1]
+        {
+            continue;
+        }
+
+        let prompt_text = if options.context_turns == 0 {
+            prompt.content.clone()
+        } else {
+            let first = index.saturating_sub(options.context_turns).max(session_start);
+            let stacked: Vec<String> = turns[first..=index].iter().map(render).collect();
+            stacked.join(&options.context_separator)
+        };
+        pairs.push(Pair {
+            prompt: prompt_text,
+            completion: reply.content.clone(),
+            timestamp: prompt.timestamp.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()),
+        });
+    }
+
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHATSAPP: &str = "[15.01.24, 10:00:00] Ann: hi\n\
+[15.01.24, 10:00:30] Ann: are you there?\n\
+[15.01.24, 10:01:00] Bot: yes\n\
+[15.01.24, 10:02:00] Ann: weather?\n\
+[15.01.24, 10:03:00] Bot: sunny\n\
+[15.01.24, 18:00:00] Ann: thanks\n\
+[15.01.24, 18:00:10] Carl: bye\n\
+[16.01.24, 09:00:00] Bot: you're welcome\n";
+
+    fn extract(options: &str) -> Vec<Pair> {
+        pairs_from(WHATSAPP, "whatsapp", options).expect("pairs should extract")
+    }
+
+    #[test]
+    fn test_pairs_user_turns_with_assistant_replies() {
+        let pairs = extract(r#"{"assistant":"Bot","user":"Ann"}"#);
+
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[0].prompt, "hi\nare you there?");
+        assert_eq!(pairs[0].completion, "yes");
+        assert_eq!(pairs[0].timestamp.as_deref(), Some("2024-01-15 10:00:00"));
+        assert_eq!(pairs[1].prompt, "weather?");
+        assert!(pairs_from(WHATSAPP, "whatsapp", "{}").unwrap_err().contains("assistant"));
+    }
+
+    #[test]
+    fn test_context_and_sessions() {
+        let pairs = extract(
+            r#"{"assistant":"Bot","context_turns":2,"context_separator":" | ","session_gap_minutes":60}"#,
+        );
+
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[1].prompt, "Ann: hi\nare you there? | Bot: yes | Ann: weather?");
+        assert_eq!(pairs[1].completion, "sunny");
+
+        let across_gap = extract(r#"{"assistant":"Bot"}"#);
+        assert_eq!(across_gap.len(), 3);
+        assert_eq!(across_gap[2].prompt, "bye");
+    }
+
+    #[test]
+    fn test_roles_match_anonymized_senders() {
+        let plain = extract(r#"{"assistant":"Bot","user":"Ann","context_turns":1}"#);
+        let anonymized =
+            extract(r#"{"assistant":"Bot","user":"Ann","context_turns":1,"anonymize":true}"#);
+
+        assert_eq!(anonymized.len(), plain.len());
+        assert_eq!(anonymized[1].completion, "sunny");
+        assert_eq!(anonymized[1].prompt, "Person 2: yes\nPerson 1: weather?");
+    }
+}
@@ -0,0 +1,256 @@
+//! Any delimited chat log (CSV, TSV, `;` or `|` separated) with a header row.
+//!
+//! Columns are picked by the `column_map` option, which names the header of
+//! each field (`{"timestamp": "date", "sender": "from", "content": "text"}`).
+//! Fields left out of the map fall back to common header names (`sender`,
+//! `from`, `author`; `content`, `text`, `message`, ...), compared without
+//! regard to case. `sender` and `content` are required; `timestamp`, `id`
+//! and `reply_to` are read when present. The delimiter is the one of `,`,
+//! `;`, tab and `|` that occurs most often in the header.
+//!
+//! Timestamps with a UTC offset (RFC 3339) or as Unix time are absolute;
+//! the other layouts are local time, flagged per message so that only
+//! those are shifted by `source_timezone` or counted in its warning.
+
+use chatpack::Message;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Header names of each field, from the `column_map` option.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ColumnMap {
+    pub timestamp: Option<String>,
+    pub sender: Option<String>,
+    pub content: Option<String>,
+    pub id: Option<String>,
+    pub reply_to: Option<String>,
+}
+
+/// Messages and, for each, whether its timestamp is local time.
+pub(crate) type LocalFlagged = (Vec<Message>, Vec<bool>);
+
+const DELIMITERS: &[u8] = b",;\t|";
+
+/// `(field, header names tried when the map leaves it out)`.
+const DEFAULT_HEADERS: &[(&str, &[&str])] = &[
+    ("timestamp", &["timestamp", "date", "datetime", "time", "sent_at", "created_at"]),
+    ("sender", &["sender", "from", "author", "user", "username", "name"]),
+    ("content", &["content", "text", "message", "body"]),
+    ("id", &["id", "message_id"]),
+    ("reply_to", &["reply_to", "replyto", "reply_to_id", "in_reply_to"]),
+];
+
+/// Timestamp layouts tried in order after RFC 3339 and Unix time.
+const TIMESTAMP_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%d.%m.%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M",
+    "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y %H:%M",
+];
+
+struct Columns {
+    timestamp: Option<usize>,
+    sender: usize,
+    content: usize,
+    id: Option<usize>,
+    reply_to: Option<usize>,
+}
+
+/// Parse with the columns named in `map`, flagging each message whose
+/// timestamp is local time.
+pub(crate) fn parse_mapped(
+    input: &str,
+    map: &ColumnMap,
+) -> std::result::Result<LocalFlagged, String> {
+    let fail = |e: &dyn std::fmt::Display| format!("Failed to parse CSV: {e}");
+    let input = input.trim_start_matches('\u{feff}');
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(sniff_delimiter(input))
+        .flexible(true)
+        .from_reader(input.as_bytes());
+
+    let headers = reader.headers().map_err(|e| fail(&e))?.clone();
+    let columns = find_columns(&headers, map).map_err(|e| fail(&e))?;
+
+    let mut messages = Vec::new();
+    let mut local = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| fail(&e))?;
+        let row = index + 2;
+        let field = |column: Option<usize>| {
+            column.and_then(|column| record.get(column)).map(str::trim).filter(|v| !v.is_empty())
+        };
+        let number = |column: Option<usize>, name: &str| {
+            field(column)
+                .map(|value| {
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| fail(&format_args!("row {row}: invalid {name} '{value}'")))
+                })
+                .transpose()
+        };
+
+        let mut message = Message::new(
+            field(Some(columns.sender)).unwrap_or_default(),
+            record.get(columns.content).unwrap_or_default(),
+        );
+        let mut is_local = false;
+        if let Some(value) = field(columns.timestamp) {
+            let (timestamp, naive) = parse_timestamp(value).ok_or_else(|| {
+                fail(&format_args!("row {row}: unrecognized timestamp '{value}'"))
+            })?;
+            message = message.with_timestamp(timestamp);
+            is_local = naive;
+        }
+        if let Some(id) = number(columns.id, "id")? {
+            message = message.with_id(id);
+        }
+        if let Some(reply_to) = number(columns.reply_to, "reply_to")? {
+            message = message.with_reply_to(reply_to);
+        }
+        messages.push(message);
+        local.push(is_local);
+    }
+
+    Ok((messages, local))
+}
+
+fn sniff_delimiter(input: &str) -> u8 {
+    let header = input.lines().next().unwrap_or_default();
+
+    DELIMITERS
+        .iter()
+        .copied()
+        .max_by_key(|delimiter| header.bytes().filter(|b| b == delimiter).count())
+        .filter(|delimiter| header.as_bytes().contains(delimiter))
+        .unwrap_or(b',')
+}
+
+fn find_columns(
+    headers: &csv::StringRecord,
+    map: &ColumnMap,
+) -> std::result::Result<Columns, String> {
+    let find = |field: &str, mapped: &Option<String>| {
+        let position =
+            |name: &str| headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name.trim()));
+        match mapped {
+            Some(name) => position(name).map(Some).ok_or_else(|| {
+                format!("column_map.{field}: no column named '{name}' in the header")
+            }),
+            None => Ok(DEFAULT_HEADERS
+                .iter()
+                .find(|(name, _)| *name == field)
+                .and_then(|(_, names)| names.iter().find_map(|name| position(name)))),
+        }
+    };
+    let required = |field: &str, mapped: &Option<String>| {
+        find(field, mapped)?
+            .ok_or_else(|| format!("no {field} column; name it in column_map.{field}"))
+    };
+
+    Ok(Columns {
+        timestamp: find("timestamp", &map.timestamp)?,
+        sender: required("sender", &map.sender)?,
+        content: required("content", &map.content)?,
+        id: find("id", &map.id)?,
+        reply_to: find("reply_to", &map.reply_to)?,
+    })
+}
+
+/// RFC 3339, Unix seconds or milliseconds, or one of [`TIMESTAMP_FORMATS`]
+/// (taken as UTC), and whether it was the latter: a local time.
+fn parse_timestamp(value: &str) -> Option<(DateTime<Utc>, bool)> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Some((timestamp.with_timezone(&Utc), false));
+    }
+    if let Ok(number) = value.parse::<i64>() {
+        let timestamp = if value.len() > 10 {
+            DateTime::from_timestamp_millis(number)
+        } else {
+            DateTime::from_timestamp(number, 0)
+        };
+        return timestamp.map(|timestamp| (timestamp, false));
+    }
+
+    TIMESTAMP_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .map(|naive| (naive.and_utc(), true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_options, run_conversion};
+
+    const FIXTURE: &str = include_str!("../../fixtures/generic.csv");
+
+    #[test]
+    fn test_column_map() {
+        let map = ColumnMap {
+            timestamp: Some("Date".into()),
+            sender: Some("From".into()),
+            content: Some("Body".into()),
+            ..ColumnMap::default()
+        };
+        let (messages, local) = parse_mapped(FIXTURE, &map).unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].sender, "Ann");
+        assert_eq!(messages[1].content, "Yes, at noon\nby the fountain");
+        assert_eq!(messages[2].reply_to, Some(1));
+        assert_eq!(messages[0].timestamp.unwrap().to_rfc3339(), "2024-01-15T10:30:00+00:00");
+        assert_eq!(local, [true, true, true]);
+    }
+
+    #[test]
+    fn test_defaults_delimiters_and_missing_columns() {
+        let tsv = "id\tauthor\ttext\n7\tBob\thello\n";
+        let (messages, _) = parse_mapped(tsv, &ColumnMap::default()).unwrap();
+        assert_eq!((messages[0].id, messages[0].sender.as_str()), (Some(7), "Bob"));
+
+        let error = parse_mapped("when,who,what\n1,Ann,hi\n", &ColumnMap::default()).unwrap_err();
+        assert!(error.contains("no sender column"));
+        let map = ColumnMap { sender: Some("Author".into()), ..ColumnMap::default() };
+        assert!(parse_mapped(FIXTURE, &map).unwrap_err().contains("no column named 'Author'"));
+    }
+
+    #[test]
+    fn test_column_map_option() {
+        let options = parse_options(
+            r#"{"column_map":{"timestamp":"Date","sender":"From","content":"Body"},"merge_consecutive":false}"#,
+        )
+        .unwrap();
+        let report = run_conversion(FIXTURE, "generic-csv", "jsonl", &options).unwrap();
+
+        assert_eq!(report.output.lines().count(), 3);
+        assert!(parse_options(r#"{"column_map":{"author":"From"}}"#).is_err());
+    }
+
+    #[test]
+    fn test_only_local_timestamps_are_shifted_or_warned() {
+        let csv = "timestamp,sender,text\n\
+2024-01-15T10:30:00+02:00,Ann,offset\n\
+1705314600,Bob,unix\n\
+2024-01-15 10:30:00,Ann,local\n";
+        let convert = |options: &str| {
+            let options = parse_options(options).unwrap();
+            crate::prepare_messages(csv, "generic-csv", &options).unwrap()
+        };
+        let times = |options: &str| -> Vec<String> {
+            let messages = convert(options).messages;
+            messages.iter().map(|m| m.timestamp.unwrap().to_rfc3339()).collect()
+        };
+
+        assert_eq!(
+            times(r#"{"merge_consecutive":false,"source_timezone":"+05:00"}"#),
+            ["2024-01-15T08:30:00+00:00", "2024-01-15T10:30:00+00:00", "2024-01-15T05:30:00+00:00"]
+        );
+        let warnings = convert(r#"{"merge_consecutive":false}"#).warnings;
+        assert!(warnings.iter().any(|w| w.starts_with("1 message(s) have local timestamps")));
+    }
+}
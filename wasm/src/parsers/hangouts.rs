@@ -0,0 +1,156 @@
+//! Google Hangouts export (`Hangouts.json` from Google Takeout).
+//!
+//! Every conversation is `conversations[]` with its participants under
+//! `conversation.conversation.participant_data[]` and its history under
+//! `events[]`. A chat message's text is split into formatted segments at
+//! `events[].chat_message.message_content.segment[]`; senders are only
+//! referenced by id and are resolved to names through the participant data.
+//! Older Takeouts nest the same data as
+//! `conversation_state[].conversation_state.{conversation, event}`, which is
+//! accepted as well. Hangouts event ids are opaque strings, so messages get
+//! no `id`.
+
+use std::collections::HashMap;
+
+use chatpack::Message;
+use chrono::DateTime;
+use serde_json::Value;
+
+/// Parse a Hangouts export into messages, oldest first.
+///
+/// All conversations in the file are combined into one list. Calls,
+/// membership changes and messages without text (photo-only) are skipped.
+pub(crate) fn parse(input: &str) -> std::result::Result<Vec<Message>, String> {
+    let root: Value = serde_json::from_str(input).map_err(|e| format!("Invalid JSON: {e}"))?;
+    let conversations = conversations(&root).ok_or_else(|| {
+        "Failed to parse Hangouts export: expected conversations[].events".to_string()
+    })?;
+
+    let mut names = HashMap::new();
+    for (conversation, _) in &conversations {
+        collect_names(conversation, &mut names);
+    }
+
+    let mut messages: Vec<Message> = conversations
+        .iter()
+        .flat_map(|(_, events)| events.iter())
+        .filter_map(|event| parse_event(event, &names))
+        .collect();
+    messages.sort_by_key(|m| (m.timestamp.is_none(), m.timestamp));
+
+    Ok(messages)
+}
+
+/// Each conversation's metadata object and its events, in either layout.
+fn conversations(root: &Value) -> Option<Vec<(&Value, &Vec<Value>)>> {
+    if let Some(conversations) = root["conversations"].as_array() {
+        return Some(
+            conversations
+                .iter()
+                .filter_map(|c| Some((&c["conversation"]["conversation"], c["events"].as_array()?)))
+                .collect(),
+        );
+    }
+
+    let states = root["conversation_state"].as_array()?;
+    Some(
+        states
+            .iter()
+            .map(|state| &state["conversation_state"])
+            .filter_map(|state| Some((&state["conversation"], state["event"].as_array()?)))
+            .collect(),
+    )
+}
+
+fn collect_names(conversation: &Value, names: &mut HashMap<String, String>) {
+    let Some(participants) = conversation["participant_data"].as_array() else {
+        return;
+    };
+
+    for participant in participants {
+        let name = participant["fallback_name"].as_str().map(str::trim).unwrap_or_default();
+        if let (Some(id), false) = (participant_id(&participant["id"]), name.is_empty()) {
+            names.entry(id.to_string()).or_insert_with(|| name.to_string());
+        }
+    }
+}
+
+/// A participant is identified by `gaia_id`; `chat_id` is the same value in
+/// every export seen so far and is only used when `gaia_id` is missing.
+fn participant_id(id: &Value) -> Option<&str> {
+    id["gaia_id"].as_str().or_else(|| id["chat_id"].as_str()).filter(|id| !id.is_empty())
+}
+
+fn parse_event(event: &Value, names: &HashMap<String, String>) -> Option<Message> {
+    let segments = event["chat_message"]["message_content"]["segment"].as_array()?;
+    let content = segments_text(segments);
+    if content.is_empty() {
+        return None;
+    }
+
+    let sender_id = participant_id(&event["sender_id"])?;
+    let sender = names.get(sender_id).cloned().unwrap_or_else(|| sender_id.to_string());
+    let mut message = Message::new(sender, content);
+    if let Some(timestamp) = micros(&event["timestamp"]).and_then(DateTime::from_timestamp_micros) {
+        message = message.with_timestamp(timestamp);
+    }
+
+    Some(message)
+}
+
+/// Join segment texts; a `LINE_BREAK` segment may come without its `"\n"`.
+fn segments_text(segments: &[Value]) -> String {
+    let mut text = String::new();
+    for segment in segments {
+        match (segment["type"].as_str(), segment["text"].as_str()) {
+            (Some("LINE_BREAK"), _) => text.push('\n'),
+            (_, Some(part)) => text.push_str(part),
+            _ => {}
+        }
+    }
+
+    text.trim().to_string()
+}
+
+/// Timestamps are microseconds since the epoch, written as a string.
+fn micros(value: &Value) -> Option<i64> {
+    value.as_i64().or_else(|| value.as_str()?.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../fixtures/hangouts.json");
+
+    #[test]
+    fn test_parse_fixture() {
+        let messages = parse(FIXTURE).expect("fixture should parse");
+        let senders: Vec<&str> = messages.iter().map(|m| m.sender.as_str()).collect();
+
+        assert_eq!(senders, vec!["Alice", "Bob", "303"]);
+        assert_eq!(messages[0].content, "Lunch at 1?");
+        assert_eq!(messages[1].content, "Sure, see https://example.com/menu\nfor the menu");
+        assert_eq!(messages[0].timestamp.unwrap().timestamp(), 1_705_314_600);
+        assert!(messages.iter().all(|m| m.id.is_none()));
+    }
+
+    #[test]
+    fn test_legacy_layout() {
+        let input = r#"{"conversation_state": [{"conversation_state": {
+            "conversation": {"participant_data": [{"id": {"chat_id": "9"}, "fallback_name": "Dora"}]},
+            "event": [
+                {"sender_id": {"chat_id": "9"}, "timestamp": 1705314600000000,
+                 "chat_message": {"message_content": {"segment": [
+                    {"type": "TEXT", "text": "one"}, {"type": "LINE_BREAK"}, {"type": "TEXT", "text": "two"}]}}},
+                {"sender_id": {"chat_id": "9"}, "chat_message": {"message_content": {"segment": []}}}
+            ]}}]}"#;
+        let messages = parse(input).expect("legacy layout should parse");
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].sender, "Dora");
+        assert_eq!(messages[0].content, "one\ntwo");
+        assert!(messages[0].timestamp.is_some());
+        assert!(parse(r#"{"chats": []}"#).unwrap_err().contains("conversations[].events"));
+    }
+}
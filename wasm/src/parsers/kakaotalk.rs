@@ -0,0 +1,105 @@
+//! KakaoTalk chat export from the desktop app (`KakaoTalk_Chat_….txt`).
+//!
+//! After a title and save date, each day starts with a dashed header
+//! (`--------------- 2024년 1월 15일 월요일 ---------------`, or
+//! `Monday, January 15, 2024` in English) followed by
+//! `[sender] [time] message` rows, where the time is e.g. `오후 1:05` or
+//! `1:05 PM`. Lines that are not rows continue the previous message.
+
+use chatpack::Message;
+use chrono::{NaiveDate, NaiveTime};
+
+use super::{continue_message, finish_messages, parse_clock};
+
+const HEADER_RULE: &str = "---------------";
+
+/// Parse a KakaoTalk export into messages, in file order.
+pub(crate) fn parse(input: &str) -> std::result::Result<Vec<Message>, String> {
+    let mut messages = Vec::new();
+    let mut date = None;
+
+    for line in input.trim_start_matches('\u{feff}').lines() {
+        if let Some(header) = parse_date_header(line) {
+            date = Some(header);
+            continue;
+        }
+
+        match parse_row(line) {
+            Some((sender, time, content)) => {
+                let mut message = Message::new(sender, content);
+                if let Some(date) = date {
+                    message = message.with_timestamp(date.and_time(time).and_utc());
+                }
+                messages.push(message);
+            }
+            None => continue_message(&mut messages, line),
+        }
+    }
+
+    if messages.is_empty() {
+        return Err(
+            "Failed to parse KakaoTalk export: no `[sender] [time] message` rows found".to_string()
+        );
+    }
+
+    finish_messages(&mut messages);
+    Ok(messages)
+}
+
+fn parse_row(line: &str) -> Option<(&str, NaiveTime, &str)> {
+    let rest = line.strip_prefix('[')?;
+    let (sender, rest) = rest.split_once("] [")?;
+    let (time, content) = rest.split_once(']')?;
+    let time = parse_clock(time)?;
+    let sender = sender.trim();
+
+    (!sender.is_empty()).then_some((sender, time, content.strip_prefix(' ').unwrap_or(content)))
+}
+
+fn parse_date_header(line: &str) -> Option<NaiveDate> {
+    let inner = line.trim().strip_prefix(HEADER_RULE)?.strip_suffix(HEADER_RULE)?.trim();
+
+    NaiveDate::parse_from_str(inner, "%A, %B %d, %Y").ok().or_else(|| parse_korean_date(inner))
+}
+
+/// `2024년 1월 15일 월요일`
+fn parse_korean_date(text: &str) -> Option<NaiveDate> {
+    let (year, rest) = text.split_once('년')?;
+    let (month, rest) = rest.split_once('월')?;
+    let (day, _) = rest.split_once('일')?;
+
+    NaiveDate::from_ymd_opt(
+        year.trim().parse().ok()?,
+        month.trim().parse().ok()?,
+        day.trim().parse().ok()?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../fixtures/kakaotalk.txt");
+
+    #[test]
+    fn test_parse_fixture() {
+        let messages = parse(FIXTURE).expect("fixture should parse");
+        let timestamp = |i: usize| messages[i].timestamp.unwrap().format("%Y-%m-%d %H:%M");
+
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].sender, "Alice");
+        assert_eq!(messages[1].content, "점심 먹었어요?\n아직이면 같이 가요");
+        assert_eq!(messages[2].content, "좋아요");
+        assert_eq!(timestamp(0).to_string(), "2024-01-15 10:30");
+        assert_eq!(timestamp(2).to_string(), "2024-01-15 12:05");
+        assert_eq!(timestamp(3).to_string(), "2024-01-16 09:02");
+    }
+
+    #[test]
+    fn test_rows_need_sender_and_time() {
+        assert!(parse_row("[Alice] [오전 10:30] hi").is_some());
+        assert!(parse_row("[Alice] hi").is_none());
+        assert!(parse_row("[] [10:30] hi").is_none());
+        assert!(parse("Alice: hi\n").unwrap_err().contains("KakaoTalk"));
+    }
+}
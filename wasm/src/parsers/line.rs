@@ -0,0 +1,120 @@
+//! LINE chat history (`[LINE] Chat history with ….txt`).
+//!
+//! The log starts with a title and save date, then one block per day: a date
+//! header (`Mon, 01/15/2024`, or `2024/01/15(Mon)` in Japanese exports)
+//! followed by tab-separated `time<TAB>sender<TAB>message` rows. Multi-line
+//! messages are wrapped in double quotes with inner quotes doubled.
+
+use chatpack::Message;
+use chrono::{NaiveDate, NaiveTime};
+
+use super::{continue_message, finish_messages, parse_clock};
+
+const DATE_FORMATS: &[&str] = &["%m/%d/%Y", "%Y/%m/%d", "%Y.%m.%d", "%d.%m.%Y"];
+
+/// Parse a LINE export into messages, in file order.
+pub(crate) fn parse(input: &str) -> std::result::Result<Vec<Message>, String> {
+    let mut messages = Vec::new();
+    let mut date = None;
+
+    for line in input.trim_start_matches('\u{feff}').lines() {
+        if let Some(header) = parse_date_header(line) {
+            date = Some(header);
+            continue;
+        }
+
+        match parse_row(line) {
+            Some((time, sender, content)) => {
+                let mut message = Message::new(sender, content);
+                if let Some(date) = date {
+                    message = message.with_timestamp(date.and_time(time).and_utc());
+                }
+                messages.push(message);
+            }
+            None => continue_message(&mut messages, line),
+        }
+    }
+
+    if messages.is_empty() {
+        return Err(
+            "Failed to parse LINE export: no `time<TAB>sender<TAB>message` rows found".to_string()
+        );
+    }
+
+    finish_messages(&mut messages);
+    for message in &mut messages {
+        unquote(&mut message.content);
+    }
+
+    Ok(messages)
+}
+
+fn parse_row(line: &str) -> Option<(NaiveTime, &str, &str)> {
+    let mut parts = line.splitn(3, '\t');
+    let time = parse_clock(parts.next()?)?;
+    let sender = parts.next()?.trim();
+    let content = parts.next().unwrap_or_default();
+
+    (!sender.is_empty()).then_some((time, sender, content))
+}
+
+/// A line holding only a date, optionally with a weekday before or after it.
+fn parse_date_header(line: &str) -> Option<NaiveDate> {
+    let line = line.trim();
+    if line.is_empty() || line.contains('\t') {
+        return None;
+    }
+
+    let date = line.split('(').next().unwrap_or(line);
+    let mut tokens = date.split([',', ' ']).map(str::trim).filter(|t| !t.is_empty());
+    let date = tokens.find_map(|token| {
+        DATE_FORMATS.iter().find_map(|f| NaiveDate::parse_from_str(token, f).ok())
+    })?;
+
+    // "Saved on: 2024/01/20 09:00" has a date too, but more than a weekday around it.
+    (line.split_whitespace().count() <= 2).then_some(date)
+}
+
+/// Undo LINE's quoting of multi-line messages.
+fn unquote(content: &mut String) {
+    let quoted = content.len() >= 2
+        && content.starts_with('"')
+        && content.ends_with('"')
+        && content.contains('\n');
+    if quoted {
+        *content = content[1..content.len() - 1].replace("\"\"", "\"");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../fixtures/line.txt");
+
+    #[test]
+    fn test_parse_fixture() {
+        let messages = parse(FIXTURE).expect("fixture should parse");
+        let timestamp = |i: usize| messages[i].timestamp.unwrap().format("%Y-%m-%d %H:%M");
+
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].sender, "Alice");
+        assert_eq!(messages[0].content, "Morning!");
+        assert_eq!(messages[1].content, "Shopping list:\n- bread\n- \"good\" cheese");
+        assert_eq!(messages[2].content, "Good night");
+        assert_eq!(timestamp(2).to_string(), "2024-01-15 22:05");
+        assert_eq!(timestamp(3).to_string(), "2024-01-16 09:02");
+    }
+
+    #[test]
+    fn test_parse_japanese_headers() {
+        let input = "[LINE] トークの履歴\n\n2024/01/15(月)\n午後1:05\tAlice\tこんにちは\n";
+        let messages = parse(input).expect("japanese export should parse");
+
+        assert_eq!(
+            messages[0].timestamp.unwrap().format("%Y-%m-%d %H:%M").to_string(),
+            "2024-01-15 13:05"
+        );
+        assert!(parse("just some text\n").unwrap_err().contains("LINE"));
+    }
+}
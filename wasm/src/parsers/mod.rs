@@ -0,0 +1,99 @@
+//! Parsers for platforms that chatpack does not support itself.
+//!
+//! Each parser exposes `parse(&str) -> Result<Vec<Message>, String>` and is
+//! registered as a [`Backend::Local`](crate::registry::Backend::Local) entry
+//! in the platform registry; parsers that take the `column_map` option are
+//! [`Backend::Mapped`](crate::registry::Backend::Mapped) entries instead.
+//! Platform parsers are only compiled with their cargo feature, and so are
+//! the line-based helpers below, which only LINE and KakaoTalk use.
+
+#[cfg(any(feature = "line", feature = "kakaotalk"))]
+use chatpack::Message;
+#[cfg(any(feature = "line", feature = "kakaotalk"))]
+use chrono::{NaiveTime, Timelike};
+
+pub(crate) mod generic_csv;
+#[cfg(feature = "hangouts")]
+pub(crate) mod hangouts;
+#[cfg(feature = "kakaotalk")]
+pub(crate) mod kakaotalk;
+#[cfg(feature = "line")]
+pub(crate) mod line;
+pub(crate) mod reimport;
+#[cfg(feature = "skype")]
+pub(crate) mod skype;
+#[cfg(feature = "teams")]
+pub(crate) mod teams;
+#[cfg(feature = "viber")]
+pub(crate) mod viber;
+
+/// AM/PM markers as they appear in English, Korean and Japanese exports.
+#[cfg(any(feature = "line", feature = "kakaotalk"))]
+const MERIDIEMS: &[(&str, bool)] = &[
+    ("AM", false),
+    ("am", false),
+    ("PM", true),
+    ("pm", true),
+    ("오전", false),
+    ("오후", true),
+    ("午前", false),
+    ("午後", true),
+];
+
+/// Parse a wall-clock time such as `10:30`, `10:30:15`, `9:02 AM` or `오후 1:05`.
+#[cfg(any(feature = "line", feature = "kakaotalk"))]
+pub(crate) fn parse_clock(text: &str) -> Option<NaiveTime> {
+    let text = text.trim();
+    let (pm, clock) = MERIDIEMS
+        .iter()
+        .find_map(|(marker, pm)| {
+            let clock = text.strip_prefix(marker).or_else(|| text.strip_suffix(marker))?;
+            Some((Some(*pm), clock.trim()))
+        })
+        .unwrap_or((None, text));
+
+    let time = NaiveTime::parse_from_str(clock, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(clock, "%H:%M"))
+        .ok()?;
+
+    match pm {
+        None => Some(time),
+        Some(pm) => time.with_hour(time.hour() % 12 + if pm { 12 } else { 0 }),
+    }
+}
+
+/// Append a line that is not a new message to the last message, if any.
+///
+/// Lines before the first message (export title, save date) are dropped.
+#[cfg(any(feature = "line", feature = "kakaotalk"))]
+pub(crate) fn continue_message(messages: &mut [Message], line: &str) {
+    if let Some(last) = messages.last_mut() {
+        last.content.push('\n');
+        last.content.push_str(line);
+    }
+}
+
+/// Trim trailing blank lines picked up as continuations before a date header.
+#[cfg(any(feature = "line", feature = "kakaotalk"))]
+pub(crate) fn finish_messages(messages: &mut [Message]) {
+    for message in messages {
+        let trimmed = message.content.trim_end().len();
+        message.content.truncate(trimmed);
+    }
+}
+
+#[cfg(all(test, any(feature = "line", feature = "kakaotalk")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clock_formats() {
+        let clock = |text| parse_clock(text).map(|t| t.format("%H:%M").to_string());
+
+        assert_eq!(clock("10:30").as_deref(), Some("10:30"));
+        assert_eq!(clock("12:05 AM").as_deref(), Some("00:05"));
+        assert_eq!(clock("PM 1:05").as_deref(), Some("13:05"));
+        assert_eq!(clock("오후 12:05").as_deref(), Some("12:05"));
+        assert_eq!(clock("Alice"), None);
+    }
+}
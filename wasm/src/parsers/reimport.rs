@@ -0,0 +1,313 @@
+//! This converter's own CSV, JSON and JSONL output, read back into messages.
+//!
+//! Lets a previous conversion stand in for a deleted original export. Fields
+//! follow `output_schema`: only `sender` and `content` are required, `id`,
+//! `timestamp`, `reply_to` and `edited` are read when present, and anything
+//! else (extra columns such as `sentiment`) is ignored. CSV columns are
+//! matched by their header title, so any column selection and order works.
+//! Columns that cannot be recomputed from the messages, such as an event's
+//! `action`, a Discord `thread` or caller-provided fields, are read
+//! separately by [`carried_columns`].
+
+use chatpack::format::OutputFormat;
+use chatpack::Message;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::enrich::{pascal_case, ExtraColumn, OrderedObject};
+
+const CSV_DELIMITER: u8 = b';';
+
+/// Fields the `parse_*` functions read into the message, and extra
+/// columns that conversion options recompute. Any other field, such as an
+/// event's `action` or a caller-provided one, is carried over as written.
+const NOT_CARRIED: &[&str] = &[
+    "id",
+    "timestamp",
+    "sender",
+    "content",
+    "reply_to",
+    "edited",
+    "sentiment",
+    "language",
+    "is_self",
+    "role",
+    "urls",
+];
+
+/// Layout chatpack's formatters write timestamps in (always UTC).
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// One message as chatpack writes it.
+#[derive(Debug, Deserialize)]
+struct OutputMessage {
+    #[serde(default)]
+    id: Option<u64>,
+    #[serde(default)]
+    timestamp: Option<String>,
+    sender: String,
+    content: String,
+    #[serde(default)]
+    reply_to: Option<u64>,
+    #[serde(default)]
+    edited: Option<String>,
+}
+
+impl OutputMessage {
+    fn into_message(self) -> std::result::Result<Message, String> {
+        let mut message = Message::new(self.sender, self.content);
+        if let Some(id) = self.id {
+            message = message.with_id(id);
+        }
+        if let Some(timestamp) = non_empty(self.timestamp) {
+            message = message.with_timestamp(parse_timestamp(&timestamp)?);
+        }
+        if let Some(reply_to) = self.reply_to {
+            message = message.with_reply_to(reply_to);
+        }
+        if let Some(edited) = non_empty(self.edited) {
+            message = message.with_edited(parse_timestamp(&edited)?);
+        }
+
+        Ok(message)
+    }
+}
+
+struct Columns {
+    id: Option<usize>,
+    timestamp: Option<usize>,
+    sender: usize,
+    content: usize,
+    reply_to: Option<usize>,
+    edited: Option<usize>,
+}
+
+/// Parse `csv` output (`;`-delimited, with a header row).
+pub(crate) fn parse_csv(input: &str) -> std::result::Result<Vec<Message>, String> {
+    let fail = |e: &dyn std::fmt::Display| format!("Failed to parse chatpack CSV: {e}");
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(CSV_DELIMITER)
+        .from_reader(input.trim_start_matches('\u{feff}').as_bytes());
+
+    let headers = reader.headers().map_err(|e| fail(&e))?;
+    let columns = find_columns(headers)
+        .ok_or_else(|| fail(&"expected a header row with Sender and Content columns"))?;
+
+    let mut messages = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let record = record.map_err(|e| fail(&e))?;
+        let field = |column: Option<usize>| {
+            column.and_then(|column| record.get(column)).map(str::to_string)
+        };
+        let number = |column: Option<usize>, name: &str| {
+            non_empty(field(column))
+                .map(|value| {
+                    value.parse::<u64>().map_err(|_| {
+                        fail(&format_args!("row {}: invalid {name} '{value}'", index + 2))
+                    })
+                })
+                .transpose()
+        };
+
+        let raw = OutputMessage {
+            id: number(columns.id, "ID")?,
+            timestamp: field(columns.timestamp),
+            sender: field(Some(columns.sender)).unwrap_or_default(),
+            content: field(Some(columns.content)).unwrap_or_default(),
+            reply_to: number(columns.reply_to, "ReplyTo")?,
+            edited: field(columns.edited),
+        };
+        messages
+            .push(raw.into_message().map_err(|e| fail(&format_args!("row {}: {e}", index + 2)))?);
+    }
+
+    Ok(messages)
+}
+
+/// Parse `json` or `json-compact` output (an array of message objects).
+pub(crate) fn parse_json(input: &str) -> std::result::Result<Vec<Message>, String> {
+    let raw: Vec<OutputMessage> =
+        serde_json::from_str(input).map_err(|e| format!("Failed to parse chatpack JSON: {e}"))?;
+
+    raw.into_iter()
+        .enumerate()
+        .map(|(index, raw)| {
+            raw.into_message()
+                .map_err(|e| format!("Failed to parse chatpack JSON: item {index}: {e}"))
+        })
+        .collect()
+}
+
+/// Parse `jsonl` output (one message object per line, blank lines skipped).
+pub(crate) fn parse_jsonl(input: &str) -> std::result::Result<Vec<Message>, String> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            let fail = |e: &dyn std::fmt::Display| {
+                format!("Failed to parse chatpack JSONL: line {}: {e}", index + 1)
+            };
+            serde_json::from_str::<OutputMessage>(line)
+                .map_err(|e| fail(&e))?
+                .into_message()
+                .map_err(|e| fail(&e))
+        })
+        .collect()
+}
+
+/// The columns of `format` output other than [`NOT_CARRIED`] ones, in
+/// the order they first appear, one value per message in the order the
+/// `parse_*` functions return them.
+pub(crate) fn carried_columns(
+    input: &str,
+    format: OutputFormat,
+) -> std::result::Result<Vec<ExtraColumn>, String> {
+    let records: Vec<OrderedObject> = match format {
+        OutputFormat::Csv => csv_records(input)?,
+        OutputFormat::Json => serde_json::from_str(input).map_err(|e| e.to_string())?,
+        _ => input
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|e| e.to_string()))
+            .collect::<std::result::Result<_, _>>()?,
+    };
+
+    let mut names: Vec<&str> = Vec::new();
+    for (name, _) in records.iter().flat_map(|record| &record.0) {
+        if !NOT_CARRIED.contains(&name.as_str()) && !names.contains(&name.as_str()) {
+            names.push(name);
+        }
+    }
+
+    Ok(names
+        .into_iter()
+        .map(|name| {
+            let values = records
+                .iter()
+                .map(|record| {
+                    let field = record.0.iter().find(|(field, _)| field == name);
+                    field.map_or(Value::Null, |(_, value)| value.clone())
+                })
+                .collect();
+            ExtraColumn::new(name, values)
+        })
+        .collect())
+}
+
+/// The cells of each CSV row, keyed by the field name of their header
+/// (`ReplyTo` is `reply_to`); empty cells are null.
+fn csv_records(input: &str) -> std::result::Result<Vec<OrderedObject>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(CSV_DELIMITER)
+        .from_reader(input.trim_start_matches('\u{feff}').as_bytes());
+    let names: Vec<String> = reader
+        .headers()
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|header| {
+            let header = header.trim();
+            let known =
+                NOT_CARRIED.iter().find(|name| header.eq_ignore_ascii_case(&pascal_case(name)));
+            known.map_or_else(|| snake_case(header), |name| name.to_string())
+        })
+        .collect();
+
+    reader
+        .records()
+        .map(|record| {
+            let record = record.map_err(|e| e.to_string())?;
+            let cells = names.iter().zip(record.iter()).map(|(name, cell)| {
+                let value = if cell.is_empty() { Value::Null } else { Value::from(cell) };
+                (name.clone(), value)
+            });
+            Ok(OrderedObject(cells.collect()))
+        })
+        .collect()
+}
+
+/// `field_name` for a `FieldName` CSV header.
+fn snake_case(header: &str) -> String {
+    let mut name = String::with_capacity(header.len() + 4);
+    for (index, c) in header.chars().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            name.push('_');
+        }
+        name.extend(c.to_lowercase());
+    }
+
+    name
+}
+
+fn find_columns(headers: &csv::StringRecord) -> Option<Columns> {
+    let position = |names: &[&str]| {
+        headers
+            .iter()
+            .position(|header| names.iter().any(|name| header.trim().eq_ignore_ascii_case(name)))
+    };
+
+    Some(Columns {
+        id: position(&["id"]),
+        timestamp: position(&["timestamp"]),
+        sender: position(&["sender"])?,
+        content: position(&["content"])?,
+        reply_to: position(&["replyto", "reply_to"]),
+        edited: position(&["edited"]),
+    })
+}
+
+/// Read a timestamp as written by chatpack; RFC 3339 is accepted as well.
+fn parse_timestamp(value: &str) -> std::result::Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT)
+        .map(|naive| naive.and_utc())
+        .or_else(|_| DateTime::parse_from_rfc3339(value).map(|t| t.with_timezone(&Utc)))
+        .map_err(|_| format!("invalid timestamp '{value}', expected YYYY-MM-DD HH:MM:SS"))
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|value| !value.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_by_header_name() {
+        let input = "Sender;Timestamp;Content;ReplyTo;Sentiment\n\
+Alice;2024-01-15 10:30:00;\"Hi;\nthere\";;0.5\n\
+Bob;;Reply;1;0\n";
+        let messages = parse_csv(input).expect("csv should parse");
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].content, "Hi;\nthere");
+        assert_eq!(
+            messages[0].timestamp.map(|t| t.format(TIMESTAMP_FORMAT).to_string()).as_deref(),
+            Some("2024-01-15 10:30:00")
+        );
+        assert_eq!(messages[1].timestamp, None);
+        assert_eq!(messages[1].reply_to, Some(1));
+        assert!(parse_csv("Name;Text\nAlice;Hi\n").unwrap_err().contains("Sender and Content"));
+        assert!(parse_csv("Sender;Content;ID\nAlice;Hi;x\n").unwrap_err().contains("row 2"));
+    }
+
+    #[test]
+    fn test_parse_json_and_jsonl() {
+        let jsonl = r#"{"id":1,"sender":"Alice","content":"Hi","edited":"2024-01-15 10:31:00"}
+
+{"sender":"Bob","content":"Yo","reply_to":1,"sentiment":0.2}
+"#;
+        let from_jsonl = parse_jsonl(jsonl).expect("jsonl should parse");
+        let from_json = parse_json(&format!("[{}]", jsonl.trim().replace("\n\n", ",")))
+            .expect("json should parse");
+
+        assert_eq!(from_jsonl.len(), 2);
+        assert_eq!(from_jsonl[0].id, Some(1));
+        assert!(from_jsonl[0].edited.is_some());
+        assert_eq!(from_jsonl[1].reply_to, Some(1));
+        assert_eq!(from_json.len(), 2);
+        assert!(parse_jsonl(r#"{"sender":"Alice"}"#).unwrap_err().contains("line 1"));
+    }
+}
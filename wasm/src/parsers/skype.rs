@@ -0,0 +1,197 @@
+//! Skype data export (`messages.json` from the GDPR download).
+//!
+//! The export holds every conversation as `conversations[].MessageList[]`,
+//! newest message first; a bare `{"messages": [...]}` or top-level array of
+//! message objects is accepted as well. Message `content` is XML-ish markup:
+//! quotes, links, emoticons and edit markers are tags, text is entity-escaped.
+//! Skype exports vary a lot between client versions, so every field is
+//! optional and messages that cannot be understood are skipped.
+
+use chatpack::Message;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// Message types that carry user-written text; calls, membership changes
+/// and other events are skipped.
+const TEXT_TYPES: &[&str] = &["Text", "RichText"];
+
+/// `content` reduced to plain text, plus what the markup told us.
+#[derive(Debug, Default, PartialEq)]
+struct Markup {
+    text: String,
+    reply_to: Option<u64>,
+    edited_secs: Option<i64>,
+}
+
+/// Parse a Skype export into messages, oldest first.
+///
+/// All conversations in the file are combined into one list.
+pub(crate) fn parse(input: &str) -> std::result::Result<Vec<Message>, String> {
+    let root: Value = serde_json::from_str(input).map_err(|e| format!("Invalid JSON: {e}"))?;
+    let lists = message_lists(&root).ok_or_else(|| {
+        "Failed to parse Skype export: expected conversations[].MessageList or a messages array"
+            .to_string()
+    })?;
+
+    let mut messages: Vec<Message> =
+        lists.into_iter().flatten().filter_map(parse_message).collect();
+    messages.sort_by_key(|m| (m.timestamp.is_none(), m.timestamp));
+
+    Ok(messages)
+}
+
+fn message_lists(root: &Value) -> Option<Vec<&Vec<Value>>> {
+    if let Some(messages) = root.as_array().or_else(|| root["messages"].as_array()) {
+        return Some(vec![messages]);
+    }
+
+    let conversations = root["conversations"].as_array()?;
+    Some(conversations.iter().filter_map(|c| c["MessageList"].as_array()).collect())
+}
+
+fn parse_message(raw: &Value) -> Option<Message> {
+    let message_type = raw["messagetype"].as_str().unwrap_or("RichText");
+    if !TEXT_TYPES.contains(&message_type) {
+        return None;
+    }
+
+    let markup = strip_markup(raw["content"].as_str()?);
+    if markup.text.is_empty() {
+        return None;
+    }
+
+    let mut message = Message::new(sender(raw).unwrap_or_default(), markup.text);
+    if let Some(timestamp) = raw["originalarrivaltime"].as_str().and_then(parse_rfc3339) {
+        message = message.with_timestamp(timestamp);
+    }
+    if let Some(id) = number(&raw["id"]) {
+        message = message.with_id(id);
+    }
+    if let Some(reply_to) = markup.reply_to {
+        message = message.with_reply_to(reply_to);
+    }
+
+    let edited = number(&raw["properties"]["edittime"])
+        .and_then(|ms| DateTime::from_timestamp_millis(ms as i64))
+        .or_else(|| markup.edited_secs.and_then(|secs| DateTime::from_timestamp(secs, 0)));
+    if let Some(edited) = edited {
+        message = message.with_edited(edited);
+    }
+
+    Some(message)
+}
+
+/// Display name, falling back to the account id without its `8:` / `8:live:`
+/// prefix; messages with neither are left to the `unknown_sender` policy.
+fn sender(raw: &Value) -> Option<String> {
+    let display_name = raw["displayName"].as_str().map(str::trim).unwrap_or_default();
+    if !display_name.is_empty() {
+        return Some(display_name.to_string());
+    }
+
+    let from = raw["from"].as_str()?.trim();
+    let account = from.strip_prefix("8:").unwrap_or(from);
+    let account = account.strip_prefix("live:").unwrap_or(account);
+    (!account.is_empty()).then(|| account.to_string())
+}
+
+/// Skype writes ids and edit times as numbers in some versions and strings in others.
+fn number(value: &Value) -> Option<u64> {
+    value.as_u64().or_else(|| value.as_str()?.trim().parse().ok())
+}
+
+fn parse_rfc3339(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value).ok().map(|t| t.with_timezone(&Utc))
+}
+
+/// Drop tags and decode entities; quoted messages are removed from the text
+/// and their `messageid` kept as the reply target.
+fn strip_markup(content: &str) -> Markup {
+    let mut markup = Markup { text: String::with_capacity(content.len()), ..Markup::default() };
+    let mut rest = content;
+
+    while let Some(start) = rest.find('<') {
+        markup.text.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start + 1..start + len];
+        rest = &rest[start + len + 1..];
+
+        match tag_name(tag) {
+            "quote" => {
+                markup.reply_to = attribute(tag, "messageid").and_then(|id| id.parse().ok());
+                rest = rest.find("</quote>").map_or("", |end| &rest[end + "</quote>".len()..]);
+            }
+            "e_m" => {
+                markup.edited_secs = attribute(tag, "ts").and_then(|ts| ts.parse().ok());
+            }
+            _ => {}
+        }
+    }
+    markup.text.push_str(rest);
+    markup.text = decode_entities(markup.text.trim());
+
+    markup
+}
+
+fn tag_name(tag: &str) -> &str {
+    tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default()
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {name}=\""))? + name.len() + 3;
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
+}
+
+fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../fixtures/skype.json");
+
+    #[test]
+    fn test_parse_fixture() {
+        let messages = parse(FIXTURE).expect("fixture should parse");
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+
+        assert_eq!(contents, vec!["Lunch at 1?", "See the menu :)", "Works for me & Carol"]);
+        assert_eq!(messages[0].sender, "Alice");
+        assert_eq!(messages[2].reply_to, Some(1_705_314_600_000));
+        assert_eq!(messages[2].id, Some(1_705_314_900_000));
+        assert_eq!(messages[1].edited.unwrap().timestamp(), 1_705_314_680);
+        assert!(messages[0].edited.is_none());
+    }
+
+    #[test]
+    fn test_lenient_shapes() {
+        let input = r#"{"messages": [
+            {"from": "8:live:carol", "content": "a &lt;b&gt; c<e_m ts=\"1705314700\"/>", "originalarrivaltime": "bad"},
+            {"from": "8:dave", "content": "<ss type=\"wink\">;)</ss>", "messagetype": "Text"},
+            {"from": "8:erin", "content": "   "}
+        ]}"#;
+        let messages = parse(input).expect("lenient input should parse");
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].sender, "carol");
+        assert_eq!(messages[0].content, "a <b> c");
+        assert!(messages[0].timestamp.is_none());
+        assert_eq!(messages[0].edited.unwrap().timestamp(), 1_705_314_700);
+        assert_eq!(messages[1].sender, "dave");
+        assert!(parse(r#"{"chats": []}"#).unwrap_err().contains("MessageList"));
+    }
+}
@@ -0,0 +1,187 @@
+//! Microsoft Teams chat messages, as returned by Microsoft Graph
+//! (`/chats/{id}/messages`, `/teams/{id}/channels/{id}/messages`) or saved
+//! by the personal export tool.
+//!
+//! A Graph response is `{"value": [...]}`, newest first; a bare array or
+//! `{"messages": [...]}` is accepted as well. The sender is
+//! `from.user.displayName` (the user id for deleted accounts), the text
+//! `body.content`, which is HTML unless
+//! `body.contentType` is `"text"`. Ids and `replyToId` are numeric strings.
+//! System events (`messageType` other than `"message"`), deleted messages and
+//! messages from apps without a user are skipped.
+
+use chatpack::Message;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// Tags that end a line of text.
+const BLOCK_TAGS: &[&str] = &["br", "p", "div", "li", "tr", "h1", "h2", "h3", "blockquote"];
+
+/// Parse a Teams export into messages, oldest first.
+pub(crate) fn parse(input: &str) -> std::result::Result<Vec<Message>, String> {
+    let root: Value = serde_json::from_str(input).map_err(|e| format!("Invalid JSON: {e}"))?;
+    let items = root
+        .as_array()
+        .or_else(|| root["value"].as_array())
+        .or_else(|| root["messages"].as_array())
+        .ok_or_else(|| {
+            "Failed to parse Teams export: expected a value or messages array".to_string()
+        })?;
+
+    let mut messages: Vec<Message> = items.iter().filter_map(parse_message).collect();
+    messages.sort_by_key(|m| (m.timestamp.is_none(), m.timestamp));
+
+    Ok(messages)
+}
+
+fn parse_message(raw: &Value) -> Option<Message> {
+    if raw["messageType"].as_str().is_some_and(|kind| kind != "message")
+        || !raw["deletedDateTime"].is_null()
+    {
+        return None;
+    }
+
+    let user = raw["from"]["user"].as_object()?;
+    let field = |key: &str| {
+        user.get(key).and_then(Value::as_str).map(str::trim).filter(|value| !value.is_empty())
+    };
+    let sender = field("displayName").or_else(|| field("id")).unwrap_or_default();
+    let content = raw["body"]["content"].as_str().unwrap_or_default();
+    let text = if raw["body"]["contentType"].as_str() == Some("text") {
+        content.trim().to_string()
+    } else {
+        html_to_text(content)
+    };
+    if text.is_empty() {
+        return None;
+    }
+
+    let mut message = Message::new(sender, text);
+    if let Some(timestamp) = raw["createdDateTime"].as_str().and_then(parse_rfc3339) {
+        message = message.with_timestamp(timestamp);
+    }
+    if let Some(id) = number(&raw["id"]) {
+        message = message.with_id(id);
+    }
+    if let Some(reply_to) = number(&raw["replyToId"]) {
+        message = message.with_reply_to(reply_to);
+    }
+    if let Some(edited) = raw["lastEditedDateTime"].as_str().and_then(parse_rfc3339) {
+        message = message.with_edited(edited);
+    }
+
+    Some(message)
+}
+
+fn number(value: &Value) -> Option<u64> {
+    value.as_u64().or_else(|| value.as_str()?.trim().parse().ok())
+}
+
+fn parse_rfc3339(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value).ok().map(|t| t.with_timezone(&Utc))
+}
+
+/// Plain text of an HTML body: tags dropped, block elements on their own
+/// lines, entities decoded and blank lines removed.
+fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = rest[start + 1..start + len].trim_start_matches('/');
+        let name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or_default();
+        if BLOCK_TAGS.iter().any(|block| block.eq_ignore_ascii_case(name)) {
+            text.push('\n');
+        }
+        rest = &rest[start + len + 1..];
+    }
+    text.push_str(rest);
+
+    let text = decode_entities(&text);
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    lines.join("\n")
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..].find(';').filter(|&len| len <= 8).map(|len| &rest[1..=len]);
+        match entity.and_then(entity_char) {
+            Some(c) => {
+                decoded.push(c);
+                rest = &rest[entity.map_or(0, str::len) + 2..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    decoded
+}
+
+fn entity_char(entity: &str) -> Option<char> {
+    match entity {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code = entity.strip_prefix('#')?;
+            let code = match code.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => code.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../fixtures/teams.json");
+
+    #[test]
+    fn test_parse_fixture() {
+        let messages = parse(FIXTURE).expect("fixture should parse");
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+
+        assert_eq!(
+            contents,
+            [
+                "Sync on Friday at 10?",
+                "Agenda for Friday:\nBudget & hiring\nQ<1> review\nSee the doc first.",
+                "Works for me",
+            ]
+        );
+        assert_eq!(messages[0].sender, "Ada Moreno");
+        assert_eq!(messages[0].id, Some(1_705_314_600_000));
+        assert_eq!(messages[2].reply_to, Some(1_705_314_600_000));
+        assert!(messages[2].edited.is_some());
+        assert_eq!(
+            messages[1].timestamp.map(|t| t.to_rfc3339()).as_deref(),
+            Some("2024-01-15T10:35:00+00:00")
+        );
+    }
+
+    #[test]
+    fn test_entities_and_bad_input() {
+        assert_eq!(decode_entities("a &#233;&#x1F600; &bogus; & b"), "a é😀 &bogus; & b");
+        assert!(parse("{}").unwrap_err().starts_with("Failed to parse Teams export"));
+    }
+}
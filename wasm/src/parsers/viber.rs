@@ -0,0 +1,158 @@
+//! Viber chat history export (`.csv`, also shared as `.txt`).
+//!
+//! The export is a CSV table with a header row naming at least the sender,
+//! date and message columns (`Name,Date,Message`). Cells follow standard CSV
+//! quoting, so messages may contain delimiters, quotes and line breaks. Some
+//! app versions split the date into separate `Date` and `Time` columns or use
+//! `;` as the delimiter; both are accepted.
+
+use chatpack::Message;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+
+const SENDER_COLUMNS: &[&str] = &["name", "sender"];
+const DATE_COLUMNS: &[&str] = &["date", "datetime", "timestamp"];
+const TIME_COLUMNS: &[&str] = &["time"];
+const CONTENT_COLUMNS: &[&str] = &["message", "text"];
+
+/// Day-first formats are tried before month-first ones, matching the locales
+/// Viber is most used in.
+const DATE_TIME_FORMATS: &[&str] = &[
+    "%d/%m/%Y %H:%M:%S",
+    "%d/%m/%Y %H:%M",
+    "%d.%m.%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%m/%d/%Y %I:%M:%S %p",
+    "%m/%d/%Y %I:%M %p",
+];
+const DATE_FORMATS: &[&str] = &["%d/%m/%Y", "%d.%m.%Y", "%Y-%m-%d", "%m/%d/%Y"];
+const TIME_FORMATS: &[&str] = &["%H:%M:%S", "%H:%M", "%I:%M:%S %p", "%I:%M %p"];
+
+struct Columns {
+    sender: usize,
+    date: usize,
+    time: Option<usize>,
+    content: usize,
+}
+
+/// Parse a Viber export into messages, in file order.
+///
+/// Dates are read as UTC; a date that matches none of the known layouts
+/// leaves the message without a timestamp rather than failing the export.
+pub(crate) fn parse(input: &str) -> std::result::Result<Vec<Message>, String> {
+    let input = input.trim_start_matches('\u{feff}');
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(sniff_delimiter(input))
+        .flexible(true)
+        .from_reader(input.as_bytes());
+
+    let headers = reader.headers().map_err(|e| format!("Failed to parse Viber export: {e}"))?;
+    let columns = find_columns(headers).ok_or_else(|| {
+        "Failed to parse Viber export: expected a header row with Name, Date and Message columns"
+            .to_string()
+    })?;
+
+    let mut messages = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("Failed to parse Viber export: {e}"))?;
+        if record.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+
+        let field = |column: usize| record.get(column).unwrap_or_default();
+        // A blank sender is left to the `unknown_sender` policy.
+        let sender = field(columns.sender).trim();
+
+        let content = field(columns.content).replace("\r\n", "\n");
+        let mut message = Message::new(sender, content);
+        let time = columns.time.map(field);
+        if let Some(timestamp) = parse_timestamp(field(columns.date), time) {
+            message = message.with_timestamp(timestamp);
+        }
+        messages.push(message);
+    }
+
+    Ok(messages)
+}
+
+fn sniff_delimiter(input: &str) -> u8 {
+    let header = input.lines().next().unwrap_or_default();
+    if header.matches(';').count() > header.matches(',').count() {
+        b';'
+    } else {
+        b','
+    }
+}
+
+fn find_columns(headers: &csv::StringRecord) -> Option<Columns> {
+    let position = |names: &[&str]| {
+        headers
+            .iter()
+            .position(|header| names.iter().any(|name| header.trim().eq_ignore_ascii_case(name)))
+    };
+
+    Some(Columns {
+        sender: position(SENDER_COLUMNS)?,
+        date: position(DATE_COLUMNS)?,
+        time: position(TIME_COLUMNS),
+        content: position(CONTENT_COLUMNS)?,
+    })
+}
+
+fn parse_timestamp(date: &str, time: Option<&str>) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    let naive = match time.map(str::trim).filter(|time| !time.is_empty()) {
+        Some(time) => {
+            let date = DATE_FORMATS.iter().find_map(|f| NaiveDate::parse_from_str(date, f).ok())?;
+            let time = TIME_FORMATS.iter().find_map(|f| NaiveTime::parse_from_str(time, f).ok())?;
+            date.and_time(time)
+        }
+        None => DATE_TIME_FORMATS
+            .iter()
+            .find_map(|f| NaiveDateTime::parse_from_str(date, f).ok())
+            .or_else(|| {
+                DATE_FORMATS
+                    .iter()
+                    .find_map(|f| NaiveDate::parse_from_str(date, f).ok())
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+            })?,
+    };
+
+    Some(naive.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = include_str!("../../fixtures/viber.csv");
+
+    #[test]
+    fn test_parse_fixture() {
+        let messages = parse(FIXTURE).expect("fixture should parse");
+
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[0].sender, "Alice");
+        assert_eq!(messages[1].content, "Yes, 7pm works");
+        assert_eq!(messages[2].content, "Shopping list:\n- bread\n- \"good\" cheese");
+        assert_eq!(
+            messages[0].timestamp.unwrap().format("%Y-%m-%d %H:%M:%S").to_string(),
+            "2024-01-15 10:30:12"
+        );
+    }
+
+    #[test]
+    fn test_parse_split_date_time_and_semicolons() {
+        let input = "Date;Time;Sender;Text\r\n16.01.2024;09:02;Bob;Morning\r\n;;;\r\n";
+        let messages = parse(input).expect("variant should parse");
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].sender, "Bob");
+        assert_eq!(
+            messages[0].timestamp.unwrap().format("%Y-%m-%d %H:%M").to_string(),
+            "2024-01-16 09:02"
+        );
+        assert!(parse("From,When,Body\nAlice,today,hi\n").unwrap_err().contains("header row"));
+    }
+}
@@ -0,0 +1,86 @@
+//! Pinned messages, for `include_pinned` and `only_pinned`.
+//!
+//! Telegram does not flag a pinned message itself: pinning adds a service
+//! entry (`"action": "pin_message"`) whose `message_id` names the message,
+//! so the pinned ids are collected from those. No other supported export
+//! records pins or stars (WhatsApp's text export marks neither), so these
+//! options are Telegram-only; `supports_pins` in the registry says which
+//! platforms have the data, and elsewhere the options only warn.
+
+use std::collections::HashSet;
+
+use chatpack::Message;
+use serde_json::Value;
+
+use crate::enrich::ExtraColumn;
+#[cfg(feature = "telegram")]
+use crate::telegram::TelegramExport;
+
+/// Ids of the messages a Telegram export's `pin_message` entries name.
+#[cfg(feature = "telegram")]
+pub(crate) fn telegram_pinned(export: &TelegramExport) -> HashSet<u64> {
+    export
+        .messages
+        .iter()
+        .filter(|raw| raw.kind == "service" && raw.action.as_deref() == Some("pin_message"))
+        .filter_map(|raw| raw.message_id)
+        .collect()
+}
+
+/// `pinned` column for `messages`: whether each one is in `pinned`. A
+/// merged message counts as pinned when its first message was.
+pub(crate) fn pinned_column(messages: &[Message], pinned: &HashSet<u64>) -> ExtraColumn {
+    let values =
+        messages.iter().map(|m| Value::from(m.id.is_some_and(|id| pinned.contains(&id)))).collect();
+
+    ExtraColumn::new("pinned", values)
+}
+
+/// The messages of `messages` that are in `pinned`.
+pub(crate) fn only_pinned(messages: Vec<Message>, pinned: &HashSet<u64>) -> Vec<Message> {
+    messages.into_iter().filter(|m| m.id.is_some_and(|id| pinned.contains(&id))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_options, prepare_messages, run_conversion};
+
+    const FIXTURE: &str = include_str!("../fixtures/telegram_pinned.json");
+
+    #[test]
+    fn test_only_pinned_keeps_pinned_messages() {
+        let options = parse_options(r#"{"only_pinned":true}"#).unwrap();
+        let prepared = prepare_messages(FIXTURE, "telegram", &options).unwrap();
+        let contents: Vec<&str> = prepared.messages.iter().map(|m| m.content.as_str()).collect();
+
+        assert_eq!(contents, ["Bins go out on Tuesdays", "Rent is due on the 5th"]);
+        assert!(prepared.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_pinned_column() {
+        let options =
+            parse_options(r#"{"include_pinned":true,"merge_consecutive":false}"#).unwrap();
+        let report = run_conversion(FIXTURE, "telegram", "jsonl", &options).unwrap();
+        let pinned: Vec<serde_json::Value> = report
+            .output
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["pinned"].clone())
+            .collect();
+
+        assert_eq!(pinned, [false, true, false, true]);
+    }
+
+    #[test]
+    fn test_platforms_without_pins_warn() {
+        let input = "[15.01.24, 10:01:00] Alice: Welcome!\n";
+        let options = parse_options(r#"{"only_pinned":true,"source_timezone":"+00:00"}"#).unwrap();
+        let prepared = prepare_messages(input, "whatsapp", &options).unwrap();
+
+        assert!(prepared.messages.is_empty());
+        assert_eq!(
+            prepared.warnings,
+            ["only_pinned: WhatsApp exports do not record pinned messages"]
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! Heuristic question detection for the `only_questions` filter.
+//!
+//! A message is a question if any of its lines ends with a question mark
+//! (`?`, the full-width `？` or the Arabic `؟`), or starts with an
+//! interrogative word from [`INTERROGATIVES`] and does not end with `.` or
+//! `!`. This catches "when do we leave" typed without punctuation while
+//! leaving "How nice!" alone; it will miss questions phrased as statements
+//! and cannot tell a rhetorical question from a real one.
+
+use chatpack::Message;
+
+/// `(language, words)` that open a question. Compared case-insensitively
+/// against the first word of a line, ignoring a leading `¿`.
+const INTERROGATIVES: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "who", "whom", "whose", "what", "when", "where", "why", "how", "which", "is", "are",
+            "do", "does", "did", "can", "could", "would", "should", "will",
+        ],
+    ),
+    ("es", &["qué", "quién", "quiénes", "dónde", "cuándo", "cómo", "cuál", "cuánto", "por qué"]),
+    ("pt", &["quem", "onde", "quando", "como", "qual", "quanto", "porque", "por que"]),
+    ("de", &["wer", "wen", "wem", "was", "wo", "wann", "warum", "wieso", "wie", "welche"]),
+    ("fr", &["qui", "quoi", "où", "quand", "pourquoi", "comment", "quel", "quelle", "est-ce"]),
+    ("ru", &["кто", "что", "где", "когда", "почему", "зачем", "как", "какой", "какая", "сколько"]),
+];
+
+const QUESTION_MARKS: &[char] = &['?', '？', '؟'];
+
+/// Keep only messages that look like questions.
+pub(crate) fn only_questions(messages: Vec<Message>) -> Vec<Message> {
+    messages.into_iter().filter(|message| is_question(&message.content)).collect()
+}
+
+pub(crate) fn is_question(content: &str) -> bool {
+    content.lines().map(str::trim).filter(|line| !line.is_empty()).any(|line| {
+        line.ends_with(QUESTION_MARKS)
+            || (starts_with_interrogative(line) && !line.ends_with(['.', '!']))
+    })
+}
+
+fn starts_with_interrogative(line: &str) -> bool {
+    let line = line.trim_start_matches('¿').to_lowercase();
+
+    INTERROGATIVES.iter().flat_map(|(_, words)| words.iter()).any(|word| {
+        line.strip_prefix(word)
+            .is_some_and(|rest| rest.chars().next().is_none_or(|c| !c.is_alphanumeric()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_options, run_conversion};
+
+    #[test]
+    fn test_questions_in_english_and_russian() {
+        assert!(is_question("Are you coming tonight?"));
+        assert!(is_question("ok\nwhen do we leave"));
+        assert!(is_question("Где встречаемся"));
+        assert!(is_question("Ты придёшь?"));
+        assert!(!is_question("How nice!"));
+        assert!(!is_question("Whatever works"));
+        assert!(!is_question("Как скажешь."));
+        assert!(!is_question("See you at 8"));
+    }
+
+    #[test]
+    fn test_only_questions_option() {
+        let chat = "[15.01.24, 10:30:00] Ann: Lunch at noon?\n\
+[15.01.24, 10:31:00] Bob: sure\n\
+[15.01.24, 10:32:00] Ann: ¿Dónde nos vemos\n\
+[15.01.24, 10:33:00] Bob: En la plaza.\n";
+        let options =
+            parse_options(r#"{"only_questions":true,"merge_consecutive":false}"#).unwrap();
+        let report = run_conversion(chat, "whatsapp", "jsonl", &options).unwrap();
+
+        assert_eq!(report.output.lines().count(), 2);
+        assert!(report.output.contains("Dónde"));
+        assert_eq!(report.stats.filtered_count, 2);
+    }
+}
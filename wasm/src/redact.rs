@@ -0,0 +1,131 @@
+//! Replacing a user-supplied list of phrases with a mask.
+//!
+//! Phrases match case-insensitively and only as whole words: a phrase that
+//! starts (ends) with a letter or digit must not be preceded (followed) by
+//! one, so redacting "Ann" leaves "Annual" alone. Longer phrases are
+//! replaced first, so "Acme Corp" wins over "Acme" where both match.
+
+use std::collections::BTreeMap;
+
+use chatpack::Message;
+
+/// Mask used when `redact_mask` is unset.
+pub(crate) const DEFAULT_MASK: &str = "[REDACTED]";
+
+pub(crate) fn validate(words: &[String]) -> std::result::Result<(), String> {
+    if words.iter().any(|word| word.trim().is_empty()) {
+        return Err("Invalid redact_words: phrases must not be empty".to_string());
+    }
+
+    Ok(())
+}
+
+/// Replace every phrase in contents (and senders, if `senders`) with `mask`.
+///
+/// Returns the number of replacements per phrase, zero for phrases that
+/// never matched.
+pub(crate) fn redact(
+    messages: &mut [Message],
+    words: &[String],
+    mask: &str,
+    senders: bool,
+) -> BTreeMap<String, usize> {
+    let mut phrases: Vec<Vec<char>> = words.iter().map(|w| w.trim().chars().collect()).collect();
+    phrases.sort_by_key(|phrase| std::cmp::Reverse(phrase.len()));
+
+    let mut counts: BTreeMap<String, usize> =
+        words.iter().map(|word| (word.trim().to_string(), 0)).collect();
+    for message in messages.iter_mut() {
+        for phrase in &phrases {
+            let count = counts.entry(phrase.iter().collect()).or_default();
+            *count += replace(&mut message.content, phrase, mask);
+            if senders {
+                *count += replace(&mut message.sender, phrase, mask);
+            }
+        }
+    }
+
+    counts
+}
+
+/// Replace whole-word, case-insensitive matches of `phrase` in `text`.
+pub(crate) fn replace(text: &mut String, phrase: &[char], mask: &str) -> usize {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let word_char = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric());
+    let guard_start = word_char(phrase.first());
+    let guard_end = word_char(phrase.last());
+
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut count = 0;
+    let mut index = 0;
+    while index + phrase.len() <= chars.len() {
+        let end = index + phrase.len();
+        let matches = chars[index..end].iter().zip(phrase).all(|(&(_, a), &b)| same_letter(a, b))
+            && !(guard_start && index > 0 && chars[index - 1].1.is_alphanumeric())
+            && !(guard_end && chars.get(end).is_some_and(|&(_, c)| c.is_alphanumeric()));
+        if !matches {
+            index += 1;
+            continue;
+        }
+
+        let start_byte = chars[index].0;
+        let end_byte = chars.get(end).map_or(text.len(), |&(byte, _)| byte);
+        result.push_str(&text[copied..start_byte]);
+        result.push_str(mask);
+        copied = end_byte;
+        count += 1;
+        index = end;
+    }
+
+    if count > 0 {
+        result.push_str(&text[copied..]);
+        *text = result;
+    }
+    count
+}
+
+fn same_letter(a: char, b: char) -> bool {
+    a == b || a.to_lowercase().eq(b.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_options, run_conversion};
+
+    const WHATSAPP: &str = "[15.01.24, 10:30:00] Ann Lee: Meet at ACME corp, Main Street 5\n\
+[15.01.24, 10:31:00] Bob: Annual review at Acme Corporation? ask ann lee, not Ann Leeds\n";
+
+    #[test]
+    fn test_whole_phrases_replaced_case_insensitively() {
+        let mut messages = vec![Message::new("Ann Lee", "Meet at ACME corp, Main Street 5")];
+        let words = ["Acme Corp".to_string(), "Main Street 5".to_string(), "Acme".to_string()];
+
+        let counts = redact(&mut messages, &words, "███", false);
+
+        assert_eq!(messages[0].content, "Meet at ███, ███");
+        assert_eq!(counts["Acme Corp"], 1);
+        assert_eq!(counts["Acme"], 0);
+        assert_eq!(messages[0].sender, "Ann Lee");
+    }
+
+    #[test]
+    fn test_redaction_in_conversion_report() {
+        let options = parse_options(
+            r#"{"redact_words":["Ann Lee","Acme"],"redact_senders":true,"merge_consecutive":false}"#,
+        )
+        .unwrap();
+        let report = run_conversion(WHATSAPP, "whatsapp", "csv", &options).unwrap();
+        let stats = serde_json::to_value(&report.stats).unwrap();
+
+        assert!(!report.output.contains("Ann Lee;"));
+        assert!(report.output.contains("[REDACTED];Meet at [REDACTED] corp"));
+        assert!(report
+            .output
+            .contains("Annual review at [REDACTED] Corporation? ask [REDACTED], not Ann Leeds"));
+        assert_eq!(stats["redactions"]["Ann Lee"], 2);
+        assert_eq!(stats["redactions"]["Acme"], 2);
+        assert!(parse_options(r#"{"redact_words":[" "]}"#).is_err());
+    }
+}
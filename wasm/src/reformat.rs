@@ -0,0 +1,106 @@
+//! Switching existing converter output to another format.
+
+use chatpack::format::OutputFormat;
+use chatpack::Message;
+use wasm_bindgen::prelude::*;
+
+use crate::parsers::reimport;
+use crate::{
+    format_with_columns, js_error, parse_options, registry, resolve_format, ConvertOptions,
+};
+
+/// Re-emit chatpack output (`from`: csv, json, json-compact or jsonl) as `to`.
+///
+/// No platform parsing, filtering or merging happens: the messages are read
+/// as written and only the formatting options of `options_json` apply
+/// (`include_*`, `pretty`, `sentiment`). A field requested with `include_*`
+/// that the input does not carry, such as timestamps from a CSV exported
+/// without them, is left out instead of producing empty values.
+/// Fields options cannot recompute, such as an event's `action`, a Discord
+/// `thread` or `convert_with_enricher` fields, are kept as written.
+#[wasm_bindgen]
+pub fn convert_format(
+    input: &str,
+    from: &str,
+    to: &str,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    reformat(input, from, to, options_json).map_err(js_error)
+}
+
+fn reformat(
+    input: &str,
+    from: &str,
+    to: &str,
+    options_json: &str,
+) -> std::result::Result<String, String> {
+    let from = registry::find_format(from)?.format;
+    let parse = match from {
+        OutputFormat::Csv => reimport::parse_csv,
+        OutputFormat::Json => reimport::parse_json,
+        OutputFormat::Jsonl => reimport::parse_jsonl,
+        other => return Err(format!("Reading {other} output is not supported")),
+    };
+    let mut options = parse_options(options_json)?;
+    let output_format = resolve_format(to, &mut options)?;
+    let messages = parse(input)?;
+    let carried = reimport::carried_columns(input, from)?;
+    omit_missing_fields(&messages, &mut options);
+
+    format_with_columns(&messages, output_format, &options, carried)
+}
+
+fn omit_missing_fields(messages: &[Message], options: &mut ConvertOptions) {
+    let any = |has: fn(&Message) -> bool| messages.iter().any(has);
+
+    options.include_timestamps &= any(|m| m.timestamp.is_some());
+    options.include_ids &= any(|m| m.id.is_some());
+    options.include_replies &= any(|m| m.reply_to.is_some());
+    options.include_edited &= any(|m| m.edited.is_some());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_conversion;
+    use crate::test_support::TELEGRAM;
+
+    const ALL_FIELDS: &str =
+        r#"{"include_timestamps":true,"include_ids":true,"include_replies":true}"#;
+
+    #[test]
+    fn test_reformat_matches_direct_conversion() {
+        let options = parse_options(ALL_FIELDS).unwrap();
+        let convert = |format| run_conversion(TELEGRAM, "telegram", format, &options).unwrap();
+        let json = convert("json").output;
+
+        for to in ["csv", "jsonl", "json-compact"] {
+            let switched =
+                reformat(&json, "json", to, ALL_FIELDS).expect("reformat should succeed");
+            assert_eq!(switched, convert(to).output, "json -> {to} differs");
+        }
+    }
+
+    #[test]
+    fn test_reformat_keeps_event_actions() {
+        let events = include_str!("../fixtures/telegram_group_events.json");
+        let options = parse_options(r#"{"include_events":true}"#).unwrap();
+        let convert = |format| run_conversion(events, "telegram", format, &options).unwrap().output;
+
+        for (from, to) in [("csv", "jsonl"), ("jsonl", "csv"), ("json", "csv")] {
+            let switched = reformat(&convert(from), from, to, "").expect("reformat should succeed");
+            assert_eq!(switched, convert(to), "{from} -> {to} differs");
+        }
+    }
+
+    #[test]
+    fn test_reformat_omits_fields_missing_from_input() {
+        let csv = "Sender;Content\nAlice;Hello\nBob;Hi\n";
+        let jsonl = reformat(csv, "csv", "jsonl", ALL_FIELDS).expect("reformat should succeed");
+
+        assert_eq!(jsonl.lines().count(), 2);
+        assert!(!jsonl.contains("timestamp"));
+        assert!(!jsonl.contains("\"id\""));
+        assert!(reformat(csv, "xml", "json", "").unwrap_err().starts_with("Unknown format"));
+    }
+}
@@ -0,0 +1,466 @@
+//! Single source of truth for the sources and formats this crate accepts.
+//!
+//! `parse_platform`, `parse_format` and the introspection exports all read
+//! from these tables, so adding an entry here is enough to make it parseable
+//! and visible to the web UI.
+//!
+//! Each platform with its own parser sits behind a cargo feature of the same
+//! name (all on by default). A platform compiled out keeps its entry, so
+//! asking for it fails with [`PLATFORM_NOT_ENABLED`] instead of looking like
+//! a typo, but it is left out of the introspection exports.
+
+use chatpack::format::OutputFormat;
+use chatpack::parser::Platform;
+use chatpack::Message;
+use serde::Serialize;
+
+use crate::parsers;
+use crate::parsers::generic_csv::{ColumnMap, LocalFlagged};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum InputKind {
+    Text,
+    Json,
+}
+
+/// Which parser turns an export into messages.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Backend {
+    /// One of chatpack's parsers.
+    Core(Platform),
+    /// A parser in this crate, for platforms chatpack does not know.
+    Local(fn(&str) -> std::result::Result<Vec<Message>, String>),
+    /// A parser in this crate whose columns come from the `column_map` option;
+    /// it flags which messages have a local timestamp (see `local_time`).
+    Mapped(fn(&str, &ColumnMap) -> std::result::Result<LocalFlagged, String>),
+    /// Compiled out by the platform's cargo feature; unused in default builds.
+    #[allow(dead_code)]
+    Disabled,
+}
+
+/// Error code for a source whose parser is not part of this build.
+pub(crate) const PLATFORM_NOT_ENABLED: &str = "PLATFORM_NOT_ENABLED";
+
+/// `backend` if the cargo feature `feature` is on, [`Backend::Disabled`] otherwise.
+macro_rules! gated {
+    ($feature:literal, $backend:expr) => {{
+        #[cfg(feature = $feature)]
+        let backend = $backend;
+        #[cfg(not(feature = $feature))]
+        let backend = Backend::Disabled;
+        backend
+    }};
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct PlatformSpec {
+    pub id: &'static str,
+    pub aliases: &'static [&'static str],
+    pub display_name: &'static str,
+    pub input_kind: InputKind,
+    pub default_extension: &'static str,
+    pub supports_replies: bool,
+    pub supports_timestamps: bool,
+    pub supports_ids: bool,
+    pub supports_edits: bool,
+    /// Pinned messages are recorded (`include_pinned`, `only_pinned`).
+    pub supports_pins: bool,
+    /// Timestamps are the sender's wall-clock time, with no UTC offset; for
+    /// a `Mapped` backend, some may be, as flagged by its parser.
+    pub local_time: bool,
+    /// Longest text the app sends as one message; longer ones are split.
+    pub max_message_length: Option<usize>,
+    #[serde(skip)]
+    pub backend: Backend,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct FormatSpec {
+    pub id: &'static str,
+    pub aliases: &'static [&'static str],
+    pub display_name: &'static str,
+    pub binary: bool,
+    pub mime_type: &'static str,
+    pub extension: &'static str,
+    /// JSON without indentation or line breaks (sets the `pretty: false` option).
+    pub compact: bool,
+    #[serde(skip)]
+    pub format: OutputFormat,
+}
+
+pub(crate) const PLATFORMS: &[PlatformSpec] = &[
+    PlatformSpec {
+        id: "telegram",
+        aliases: &["tg"],
+        display_name: "Telegram",
+        input_kind: InputKind::Json,
+        default_extension: "json",
+        supports_replies: true,
+        supports_timestamps: true,
+        supports_ids: true,
+        supports_edits: true,
+        supports_pins: true,
+        local_time: false,
+        max_message_length: Some(4096),
+        backend: gated!("telegram", Backend::Core(Platform::Telegram)),
+    },
+    PlatformSpec {
+        id: "whatsapp",
+        aliases: &["wa"],
+        display_name: "WhatsApp",
+        input_kind: InputKind::Text,
+        default_extension: "txt",
+        supports_replies: false,
+        supports_timestamps: true,
+        supports_ids: false,
+        supports_edits: false,
+        supports_pins: false,
+        local_time: true,
+        max_message_length: None,
+        backend: gated!("whatsapp", Backend::Core(Platform::WhatsApp)),
+    },
+    PlatformSpec {
+        id: "instagram",
+        aliases: &["ig"],
+        display_name: "Instagram",
+        input_kind: InputKind::Json,
+        default_extension: "json",
+        supports_replies: false,
+        supports_timestamps: true,
+        supports_ids: false,
+        supports_edits: false,
+        supports_pins: false,
+        local_time: false,
+        max_message_length: None,
+        backend: gated!("instagram", Backend::Core(Platform::Instagram)),
+    },
+    PlatformSpec {
+        id: "discord",
+        aliases: &["dc"],
+        display_name: "Discord",
+        input_kind: InputKind::Json,
+        default_extension: "json",
+        supports_replies: true,
+        supports_timestamps: true,
+        supports_ids: true,
+        supports_edits: true,
+        supports_pins: false,
+        local_time: false,
+        max_message_length: Some(2000),
+        backend: gated!("discord", Backend::Core(Platform::Discord)),
+    },
+    PlatformSpec {
+        id: "viber",
+        aliases: &["vb"],
+        display_name: "Viber",
+        input_kind: InputKind::Text,
+        default_extension: "csv",
+        supports_replies: false,
+        supports_timestamps: true,
+        supports_ids: false,
+        supports_edits: false,
+        supports_pins: false,
+        local_time: true,
+        max_message_length: None,
+        backend: gated!("viber", Backend::Local(parsers::viber::parse)),
+    },
+    PlatformSpec {
+        id: "skype",
+        aliases: &["sk"],
+        display_name: "Skype",
+        input_kind: InputKind::Json,
+        default_extension: "json",
+        supports_replies: true,
+        supports_timestamps: true,
+        supports_ids: true,
+        supports_edits: true,
+        supports_pins: false,
+        local_time: false,
+        max_message_length: None,
+        backend: gated!("skype", Backend::Local(parsers::skype::parse)),
+    },
+    PlatformSpec {
+        id: "line",
+        aliases: &["ln"],
+        display_name: "LINE",
+        input_kind: InputKind::Text,
+        default_extension: "txt",
+        supports_replies: false,
+        supports_timestamps: true,
+        supports_ids: false,
+        supports_edits: false,
+        supports_pins: false,
+        local_time: true,
+        max_message_length: None,
+        backend: gated!("line", Backend::Local(parsers::line::parse)),
+    },
+    PlatformSpec {
+        id: "kakaotalk",
+        aliases: &["kakao", "kt"],
+        display_name: "KakaoTalk",
+        input_kind: InputKind::Text,
+        default_extension: "txt",
+        supports_replies: false,
+        supports_timestamps: true,
+        supports_ids: false,
+        supports_edits: false,
+        supports_pins: false,
+        local_time: true,
+        max_message_length: None,
+        backend: gated!("kakaotalk", Backend::Local(parsers::kakaotalk::parse)),
+    },
+    PlatformSpec {
+        id: "hangouts",
+        aliases: &["gh"],
+        display_name: "Google Hangouts",
+        input_kind: InputKind::Json,
+        default_extension: "json",
+        supports_replies: false,
+        supports_timestamps: true,
+        supports_ids: false,
+        supports_edits: false,
+        supports_pins: false,
+        local_time: false,
+        max_message_length: None,
+        backend: gated!("hangouts", Backend::Local(parsers::hangouts::parse)),
+    },
+    PlatformSpec {
+        id: "teams",
+        aliases: &["msteams"],
+        display_name: "Microsoft Teams",
+        input_kind: InputKind::Json,
+        default_extension: "json",
+        supports_replies: true,
+        supports_timestamps: true,
+        supports_ids: true,
+        supports_edits: true,
+        supports_pins: false,
+        local_time: false,
+        max_message_length: None,
+        backend: gated!("teams", Backend::Local(parsers::teams::parse)),
+    },
+    PlatformSpec {
+        id: "generic-csv",
+        aliases: &["tsv"],
+        display_name: "CSV/TSV (any columns)",
+        input_kind: InputKind::Text,
+        default_extension: "csv",
+        supports_replies: true,
+        supports_timestamps: true,
+        supports_ids: true,
+        supports_edits: false,
+        supports_pins: false,
+        local_time: true,
+        max_message_length: None,
+        backend: Backend::Mapped(parsers::generic_csv::parse_mapped),
+    },
+    PlatformSpec {
+        id: "chatpack-csv",
+        aliases: &[],
+        display_name: "Chatpack CSV",
+        input_kind: InputKind::Text,
+        default_extension: "csv",
+        supports_replies: true,
+        supports_timestamps: true,
+        supports_ids: true,
+        supports_edits: true,
+        supports_pins: false,
+        local_time: false,
+        max_message_length: None,
+        backend: Backend::Local(parsers::reimport::parse_csv),
+    },
+    PlatformSpec {
+        id: "chatpack-json",
+        aliases: &[],
+        display_name: "Chatpack JSON",
+        input_kind: InputKind::Json,
+        default_extension: "json",
+        supports_replies: true,
+        supports_timestamps: true,
+        supports_ids: true,
+        supports_edits: true,
+        supports_pins: false,
+        local_time: false,
+        max_message_length: None,
+        backend: Backend::Local(parsers::reimport::parse_json),
+    },
+    PlatformSpec {
+        id: "chatpack-jsonl",
+        aliases: &[],
+        display_name: "Chatpack JSONL",
+        input_kind: InputKind::Text,
+        default_extension: "jsonl",
+        supports_replies: true,
+        supports_timestamps: true,
+        supports_ids: true,
+        supports_edits: true,
+        supports_pins: false,
+        local_time: false,
+        max_message_length: None,
+        backend: Backend::Local(parsers::reimport::parse_jsonl),
+    },
+];
+
+pub(crate) const FORMATS: &[FormatSpec] = &[
+    FormatSpec {
+        id: "csv",
+        aliases: &[],
+        display_name: "CSV",
+        binary: false,
+        mime_type: "text/csv",
+        extension: "csv",
+        compact: false,
+        format: OutputFormat::Csv,
+    },
+    FormatSpec {
+        id: "json",
+        aliases: &[],
+        display_name: "JSON",
+        binary: false,
+        mime_type: "application/json",
+        extension: "json",
+        compact: false,
+        format: OutputFormat::Json,
+    },
+    FormatSpec {
+        id: "jsonl",
+        aliases: &["ndjson"],
+        display_name: "JSONL",
+        binary: false,
+        mime_type: "application/x-ndjson",
+        extension: "jsonl",
+        compact: false,
+        format: OutputFormat::Jsonl,
+    },
+    FormatSpec {
+        id: "json-compact",
+        aliases: &[],
+        display_name: "JSON (compact)",
+        binary: false,
+        mime_type: "application/json",
+        extension: "json",
+        compact: true,
+        format: OutputFormat::Json,
+    },
+];
+
+impl PlatformSpec {
+    /// Whether the platform's parser is part of this build.
+    pub(crate) fn enabled(&self) -> bool {
+        !matches!(self.backend, Backend::Disabled)
+    }
+}
+
+/// Platforms whose parser is part of this build.
+pub(crate) fn enabled_platforms() -> impl Iterator<Item = &'static PlatformSpec> {
+    PLATFORMS.iter().filter(|spec| spec.enabled())
+}
+
+pub(crate) fn find_platform(name: &str) -> std::result::Result<&'static PlatformSpec, String> {
+    find_in(PLATFORMS, name)
+}
+
+fn find_in(
+    platforms: &'static [PlatformSpec],
+    name: &str,
+) -> std::result::Result<&'static PlatformSpec, String> {
+    let name = name.trim();
+    let spec = platforms.iter().find(|spec| matches_name(spec.id, spec.aliases, name));
+    match spec {
+        Some(spec) if spec.enabled() => Ok(spec),
+        Some(spec) => Err(not_enabled(spec.id)),
+        None => Err(format!(
+            "Unknown source: '{name}'. Expected one of: {}",
+            ids(platforms.iter().filter(|s| s.enabled()).map(|s| s.id))
+        )),
+    }
+}
+
+/// Error for a platform compiled out of this build.
+pub(crate) fn not_enabled(id: &str) -> String {
+    format!(
+        "{PLATFORM_NOT_ENABLED}: '{id}' is not available in this build (enable the `{id}` feature)"
+    )
+}
+
+pub(crate) fn find_format(name: &str) -> std::result::Result<&'static FormatSpec, String> {
+    let name = name.trim();
+    FORMATS.iter().find(|spec| matches_name(spec.id, spec.aliases, name)).ok_or_else(|| {
+        format!("Unknown format: '{name}'. Expected one of: {}", ids(FORMATS.iter().map(|s| s.id)))
+    })
+}
+
+fn matches_name(id: &str, aliases: &[&str], name: &str) -> bool {
+    id.eq_ignore_ascii_case(name) || aliases.iter().any(|alias| alias.eq_ignore_ascii_case(name))
+}
+
+fn ids<'a>(ids: impl Iterator<Item = &'a str>) -> String {
+    ids.collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_alias_resolves_to_its_entry() {
+        for spec in PLATFORMS {
+            for name in spec.aliases.iter().chain([&spec.id]) {
+                assert_eq!(find_platform(&name.to_uppercase()).unwrap().id, spec.id);
+            }
+        }
+        for spec in FORMATS {
+            for name in spec.aliases.iter().chain([&spec.id]) {
+                assert_eq!(find_format(name).unwrap().id, spec.id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_tables_cover_core_enums() {
+        for platform in Platform::all() {
+            assert!(
+                PLATFORMS.iter().any(|spec| matches!(
+                    spec.backend,
+                    Backend::Core(core) if core.to_string() == platform.to_string()
+                )),
+                "{platform}"
+            );
+        }
+        for format in OutputFormat::all() {
+            assert!(
+                FORMATS.iter().any(|spec| spec.format.to_string() == format.to_string()),
+                "{format}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_names_list_valid_ids() {
+        let error = find_format("parquet").unwrap_err();
+
+        assert!(error.starts_with("Unknown format"));
+        assert!(error.contains("csv, json, jsonl"));
+        assert!(find_platform("myspace").unwrap_err().starts_with("Unknown source"));
+    }
+
+    #[test]
+    fn test_disabled_platform_is_not_unknown() {
+        const TABLE: &[PlatformSpec] = &[
+            PlatformSpec { id: "telegram", ..PLATFORMS[0] },
+            PlatformSpec {
+                id: "discord",
+                aliases: &["dc"],
+                backend: Backend::Disabled,
+                ..PLATFORMS[0]
+            },
+        ];
+
+        let error = find_in(TABLE, "DC").unwrap_err();
+        assert!(error.starts_with(PLATFORM_NOT_ENABLED), "{error}");
+        assert!(error.contains("`discord` feature"));
+        assert!(find_in(TABLE, "myspace").unwrap_err().ends_with("Expected one of: telegram"));
+        assert_eq!(find_in(TABLE, "telegram").unwrap().id, "telegram");
+    }
+}
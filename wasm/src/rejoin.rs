@@ -0,0 +1,121 @@
+//! Long texts the app split into several messages, joined back together.
+//!
+//! Telegram sends a pasted text over 4096 characters as consecutive
+//! messages of that length, Discord at 2000, a moment apart and often
+//! mid-word. A message is taken as the continuation of the one before it
+//! when both come from the same sender within [`MAX_GAP_SECONDS`] and the
+//! earlier piece is within [`LIMIT_SLACK`] characters of the platform's
+//! `max_message_length` (clients break at the last space or newline before
+//! the limit). Pieces are joined without a separator, keeping the first
+//! piece's id, timestamp and reply.
+
+use chatpack::Message;
+
+/// Longest pause between two pieces of one split text.
+const MAX_GAP_SECONDS: i64 = 5;
+
+/// How far below the limit a piece may end and still count as cut off.
+const LIMIT_SLACK: usize = 64;
+
+/// Join split texts in `messages`, returning how many pieces were joined
+/// into the message before them.
+pub(crate) fn rejoin(messages: &mut Vec<Message>, limit: usize) -> usize {
+    let mut joined = 0;
+    let mut kept: Vec<Message> = Vec::with_capacity(messages.len());
+    // The last piece appended to the last kept message, if it was split.
+    let mut last_piece: Option<Message> = None;
+
+    for message in messages.drain(..) {
+        let continues = kept.last().is_some_and(|previous| {
+            is_continuation(last_piece.as_ref().unwrap_or(previous), &message, limit)
+        });
+        if continues {
+            if let Some(previous) = kept.last_mut() {
+                previous.content.push_str(&message.content);
+            }
+            last_piece = Some(message);
+            joined += 1;
+        } else {
+            kept.push(message);
+            last_piece = None;
+        }
+    }
+
+    *messages = kept;
+    joined
+}
+
+fn is_continuation(piece: &Message, next: &Message, limit: usize) -> bool {
+    let (Some(sent), Some(next_sent)) = (piece.timestamp, next.timestamp) else {
+        return false;
+    };
+    let gap = (next_sent - sent).num_seconds();
+
+    piece.sender == next.sender
+        && (0..=MAX_GAP_SECONDS).contains(&gap)
+        && piece.content.chars().count() + LIMIT_SLACK >= limit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_options, prepare_messages};
+
+    const FIXTURE: &str = include_str!("../fixtures/telegram_split_message.json");
+
+    fn contents(options: &str) -> (Vec<String>, Vec<String>) {
+        let options = parse_options(options).unwrap();
+        let prepared = prepare_messages(FIXTURE, "telegram", &options).unwrap();
+        (prepared.messages.into_iter().map(|m| m.content).collect(), prepared.warnings)
+    }
+
+    #[test]
+    fn test_split_text_is_rejoined() {
+        let (plain, _) = contents(r#"{"merge_consecutive":false}"#);
+        let (rejoined, warnings) =
+            contents(r#"{"merge_consecutive":false,"rejoin_split_messages":true}"#);
+
+        assert_eq!(plain.len(), 6);
+        assert_eq!(rejoined.len(), 5);
+        assert_eq!(rejoined[0], format!("{}{}", plain[0], plain[1]));
+        assert!(rejoined[0].contains("quick brown fox"));
+        assert!(warnings.iter().any(|w| w.starts_with("1 message(s) split")));
+    }
+
+    #[test]
+    fn test_long_messages_minutes_apart_stay_separate() {
+        let (rejoined, _) = contents(r#"{"merge_consecutive":false,"rejoin_split_messages":true}"#);
+
+        // Bob's two long messages are a minute apart.
+        assert_eq!(rejoined[1], "Thanks, reading it now");
+        assert_eq!(rejoined[2].chars().count(), 4090);
+        assert!(rejoined[3].starts_with("Second essay"));
+        assert_eq!(rejoined[4], "Short reply");
+    }
+
+    #[test]
+    fn test_pieces_chain() {
+        let piece = |secs, content: &str| {
+            Message::new("Ann", content)
+                .with_timestamp(chrono::DateTime::from_timestamp(secs, 0).unwrap())
+        };
+        let long = "x".repeat(100);
+        let mut messages = vec![piece(0, &long), piece(1, &long), piece(2, "end"), piece(3, "new")];
+
+        assert_eq!(rejoin(&mut messages, 100), 2);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].content.len(), 203);
+    }
+
+    #[test]
+    fn test_runs_before_merge_and_needs_a_limit() {
+        let (merged, _) = contents(r#"{"rejoin_split_messages":true}"#);
+        assert!(merged[0].contains("quick brown fox"));
+        assert!(!merged[0].contains("bro\nwn"));
+
+        let whatsapp = "[15.01.24, 10:00:00] Ann: hi\n";
+        let options = parse_options(r#"{"rejoin_split_messages":true}"#).unwrap();
+        let prepared = prepare_messages(whatsapp, "whatsapp", &options).unwrap();
+        assert!(prepared.warnings.iter().any(|w| w.contains("no message length limit")));
+    }
+}
@@ -0,0 +1,258 @@
+//! Deterministic random sampling of messages or whole sessions, and
+//! systematic every-Nth sampling.
+
+use std::collections::HashMap;
+
+use chatpack::Message;
+use serde::{Deserialize, Serialize};
+
+use crate::pairs::{session_gap, session_starts};
+
+/// Silence that starts a new session when `session_gap_minutes` is unset.
+const DEFAULT_SESSION_GAP_MINUTES: u64 = 60;
+
+/// The `sample` conversion option.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub(crate) struct SampleOptions {
+    /// Number of messages or sessions to keep.
+    pub n: usize,
+    /// Same seed and input always give the same sample; random if unset.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub unit: SampleUnit,
+    /// Session splitting for `unit: "session"`, as in `extract_pairs`.
+    #[serde(default)]
+    pub session_gap_minutes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SampleUnit {
+    #[default]
+    Message,
+    Session,
+}
+
+pub(crate) fn validate(options: &SampleOptions) -> std::result::Result<(), String> {
+    if options.n == 0 {
+        return Err("Invalid sample: n must be at least 1".to_string());
+    }
+
+    Ok(())
+}
+
+/// Keep `options.n` messages (or whole sessions) drawn uniformly without
+/// replacement, in their original order. Asking for more than there are
+/// keeps everything and adds a warning.
+pub(crate) fn sample(
+    messages: Vec<Message>,
+    options: &SampleOptions,
+    warnings: &mut Vec<String>,
+) -> Vec<Message> {
+    let groups = match options.unit {
+        SampleUnit::Message => vec![true; messages.len()],
+        SampleUnit::Session => session_starts(
+            &messages,
+            session_gap(Some(options.session_gap_minutes.unwrap_or(DEFAULT_SESSION_GAP_MINUTES))),
+        ),
+    };
+    let group_count = groups.iter().filter(|&&start| start).count();
+    if options.n >= group_count {
+        if options.n > group_count {
+            let unit = match options.unit {
+                SampleUnit::Message => "messages",
+                SampleUnit::Session => "sessions",
+            };
+            warnings.push(format!(
+                "Sample of {} requested but only {group_count} {unit} available; kept all",
+                options.n
+            ));
+        }
+        return messages;
+    }
+
+    let mut rng = SplitMix64::new(options.seed.unwrap_or_else(random_seed));
+    let mut order: Vec<usize> = (0..group_count).collect();
+    for i in 0..options.n {
+        let j = i + rng.below(group_count - i);
+        order.swap(i, j);
+    }
+    let mut keep = vec![false; group_count];
+    for &group in &order[..options.n] {
+        keep[group] = true;
+    }
+
+    let mut group = 0;
+    messages
+        .into_iter()
+        .zip(groups)
+        .enumerate()
+        .filter_map(|(index, (message, start))| {
+            if start && index > 0 {
+                group += 1;
+            }
+            keep[group].then_some(message)
+        })
+        .collect()
+}
+
+/// Keep the first message and every `n`th after it, counting over all
+/// messages or, with `per_sender`, over each sender's own messages.
+pub(crate) fn every(messages: Vec<Message>, n: usize, per_sender: bool) -> Vec<Message> {
+    let n = n.max(1);
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut total = 0;
+
+    messages
+        .into_iter()
+        .filter(|message| {
+            let count = if per_sender {
+                seen.entry(message.sender.clone()).or_default()
+            } else {
+                &mut total
+            };
+            let keep = *count % n == 0;
+            *count += 1;
+            keep
+        })
+        .collect()
+}
+
+/// Keep at most `max` messages of each sender, their most recent by
+/// timestamp (undated messages count as oldest), in chat order.
+pub(crate) fn cap_per_sender(messages: Vec<Message>, max: usize) -> Vec<Message> {
+    let mut by_sender: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (index, message) in messages.iter().enumerate() {
+        by_sender.entry(&message.sender).or_default().push(index);
+    }
+    let mut keep = vec![false; messages.len()];
+    for indices in by_sender.values_mut() {
+        indices.sort_by_key(|&index| std::cmp::Reverse((messages[index].timestamp, index)));
+        for &index in indices.iter().take(max) {
+            keep[index] = true;
+        }
+    }
+
+    messages.into_iter().zip(keep).filter(|(_, keep)| *keep).map(|(message, _)| message).collect()
+}
+
+fn random_seed() -> u64 {
+    // 2^53: every integer below it is exactly representable as f64.
+    (js_sys::Math::random() * 9_007_199_254_740_992.0) as u64
+}
+
+/// SplitMix64: tiny, seedable and good enough for picking a sample.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..bound` (multiply-shift; bias is negligible for chat sizes).
+    fn below(&mut self, bound: usize) -> usize {
+        ((u128::from(self.next_u64()) * bound as u128) >> 64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn messages(minutes: &[i64]) -> Vec<Message> {
+        minutes
+            .iter()
+            .enumerate()
+            .map(|(index, &minute)| {
+                Message::new(if index % 2 == 0 { "Ann" } else { "Bob" }, index.to_string())
+                    .with_timestamp(Utc.timestamp_opt(1_705_312_800 + minute * 60, 0).unwrap())
+            })
+            .collect()
+    }
+
+    fn contents(messages: &[Message]) -> Vec<String> {
+        messages.iter().map(|m| m.content.clone()).collect()
+    }
+
+    #[test]
+    fn test_message_sample_is_deterministic_and_ordered() {
+        let options = SampleOptions {
+            n: 4,
+            seed: Some(42),
+            unit: SampleUnit::Message,
+            session_gap_minutes: None,
+        };
+        let input = messages(&(0..20).collect::<Vec<_>>());
+        let mut warnings = Vec::new();
+
+        let first = contents(&sample(input.clone(), &options, &mut warnings));
+        let second = contents(&sample(input.clone(), &options, &mut warnings));
+        let other_seed =
+            contents(&sample(input, &SampleOptions { seed: Some(7), ..options }, &mut warnings));
+
+        assert_eq!(first.len(), 4);
+        assert_eq!(first, second);
+        assert_ne!(first, other_seed);
+        let numbers: Vec<usize> = first.iter().map(|c| c.parse().unwrap()).collect();
+        assert!(numbers.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_session_sample_keeps_whole_sessions() {
+        let options = SampleOptions {
+            n: 1,
+            seed: Some(1),
+            unit: SampleUnit::Session,
+            session_gap_minutes: Some(30),
+        };
+        let sampled =
+            contents(&sample(messages(&[0, 1, 2, 100, 101, 300]), &options, &mut Vec::new()));
+
+        assert!([vec!["0", "1", "2"], vec!["3", "4"], vec!["5"]]
+            .contains(&sampled.iter().map(String::as_str).collect()));
+    }
+
+    #[test]
+    fn test_sample_every_third_message() {
+        let input = messages(&(0..10).collect::<Vec<_>>());
+
+        assert_eq!(contents(&every(input.clone(), 3, false)), ["0", "3", "6", "9"]);
+        // Ann sent 0, 2, 4, 6, 8 and Bob 1, 3, 5, 7, 9.
+        assert_eq!(contents(&every(input, 3, true)), ["0", "1", "6", "7"]);
+    }
+
+    #[test]
+    fn test_cap_per_sender_keeps_most_recent() {
+        // Ann sent 0, 2, 4 and 6; her message 2 is the latest of them.
+        let input = messages(&[0, 1, 50, 3, 4, 5, 6]);
+
+        assert_eq!(contents(&cap_per_sender(input.clone(), 2)), ["2", "3", "5", "6"]);
+        assert_eq!(contents(&cap_per_sender(input, 1)), ["2", "5"]);
+    }
+
+    #[test]
+    fn test_oversized_sample_keeps_everything_with_warning() {
+        let options = SampleOptions {
+            n: 10,
+            seed: Some(1),
+            unit: SampleUnit::Session,
+            session_gap_minutes: Some(30),
+        };
+        let mut warnings = Vec::new();
+        let sampled = sample(messages(&[0, 1, 100]), &options, &mut warnings);
+
+        assert_eq!(sampled.len(), 3);
+        assert_eq!(warnings, ["Sample of 10 requested but only 2 sessions available; kept all"]);
+    }
+}
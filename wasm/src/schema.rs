@@ -0,0 +1,306 @@
+use chatpack::format::OutputFormat;
+use chatpack::Message;
+use chrono::DateTime;
+use serde::Serialize;
+use serde_json::{json, Value};
+use wasm_bindgen::prelude::*;
+
+use crate::enrich::OrderedObject;
+use crate::{format_with_columns, js_error, parse_options, resolve_format};
+
+/// Timestamp layout used by chatpack's formatters.
+const TIMESTAMP_PATTERN: &str = r"^\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}$";
+
+/// Version of the output shape; bumped whenever a field or column is added,
+/// removed, renamed or reordered.
+pub(crate) const SCHEMA_VERSION: &str = "1";
+
+#[derive(Debug, Serialize)]
+struct OutputShape {
+    schema_version: &'static str,
+    format: &'static str,
+    /// CSV header, in order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    columns: Option<Vec<String>>,
+    /// JSON/JSONL object keys, in order.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fields: Option<Vec<Field>>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Field {
+    name: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    /// Left out of messages that have no value for it.
+    optional: bool,
+    /// `null` on messages that have no value for it.
+    nullable: bool,
+}
+
+/// Return the JSON Schema (draft 2020-12) of one output message as JSON.
+///
+/// JSON output is an array of these objects and JSONL output has one per
+/// line. Only `sender` and `content` are always present; every other field
+/// appears when its option is enabled and the source message has a value,
+/// as described in each property's `description`.
+#[wasm_bindgen]
+pub fn output_schema() -> String {
+    json_schema().to_string()
+}
+
+/// Return the exact shape `convert_with_options` produces for `format` and
+/// `options_json`, as JSON.
+///
+/// `{schema_version, format, columns}` for CSV, where `columns` is the
+/// header in order, or `{schema_version, format, fields}` for JSON/JSONL,
+/// with each field's `name`, `type` (`string`, `integer`, `number`,
+/// `boolean` or `array`), whether it is `optional` (left out when the
+/// message has no value) and `nullable`, in output order. The shape is read
+/// from the output of sample messages run through the real formatter, so it
+/// cannot drift from what a conversion writes. Fields that depend on the
+/// export rather than the options (`action`, `thread`) are not listed.
+/// `schema_version` changes whenever the shape of some output does.
+#[wasm_bindgen]
+pub fn schema(format: &str, options_json: &str) -> std::result::Result<String, JsValue> {
+    let shape = output_shape(format, options_json).map_err(js_error)?;
+
+    serde_json::to_string(&shape).map_err(|e| js_error(e.to_string()))
+}
+
+fn output_shape(format: &str, options_json: &str) -> std::result::Result<OutputShape, String> {
+    let mut options = parse_options(options_json)?;
+    let output_format = resolve_format(format, &mut options)?;
+    let render =
+        |message: Message| format_with_columns(&[message], output_format, &options, Vec::new());
+    let full = render(sample_message(true))?;
+    let bare = render(sample_message(false))?;
+
+    let mut shape = OutputShape {
+        schema_version: SCHEMA_VERSION,
+        format: crate::registry::find_format(format)?.id,
+        columns: None,
+        fields: None,
+    };
+    if matches!(output_format, OutputFormat::Csv) {
+        shape.columns =
+            Some(full.lines().next().unwrap_or_default().split(';').map(str::to_string).collect());
+        return Ok(shape);
+    }
+
+    let full = first_object(&full)?;
+    let bare = first_object(&bare)?;
+    let fields = full
+        .into_iter()
+        .map(|(name, value)| {
+            let bare_value = bare.iter().find(|(key, _)| *key == name).map(|(_, value)| value);
+            Field {
+                kind: kind(&value),
+                optional: bare_value.is_none(),
+                nullable: value.is_null() || bare_value.is_some_and(Value::is_null),
+                name,
+            }
+        })
+        .collect();
+    shape.fields = Some(fields);
+
+    Ok(shape)
+}
+
+/// A message with every field set (`full`) or only sender and content.
+fn sample_message(full: bool) -> Message {
+    let message = Message::new(
+        "Alice",
+        "Good morning everyone, the slides are at https://example.com/slides and I am happy",
+    );
+    if !full {
+        return message;
+    }
+    let time = DateTime::from_timestamp(1_705_314_600, 0).unwrap_or_default();
+    message.with_timestamp(time).with_id(2).with_reply_to(1).with_edited(time)
+}
+
+/// Keys and values of the first object in JSON or JSONL output, in order.
+fn first_object(output: &str) -> std::result::Result<Vec<(String, Value)>, String> {
+    let trimmed = output.trim_start();
+    let object = if trimmed.starts_with('[') {
+        let items: Vec<OrderedObject> = serde_json::from_str(trimmed).map_err(|e| e.to_string())?;
+        items.into_iter().next()
+    } else {
+        let line = trimmed.lines().next().unwrap_or_default();
+        Some(serde_json::from_str::<OrderedObject>(line).map_err(|e| e.to_string())?)
+    };
+
+    Ok(object.map(|object| object.0).unwrap_or_default())
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_u64() || n.is_i64() => "integer",
+        Value::Number(_) => "number",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+        Value::String(_) | Value::Null => "string",
+    }
+}
+
+fn json_schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "Chatpack output message",
+        "type": "object",
+        "required": ["sender", "content"],
+        "additionalProperties": false,
+        "properties": {
+            "id": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Platform message id. Present with `include_ids` when the platform provides ids."
+            },
+            "timestamp": {
+                "type": "string",
+                "pattern": TIMESTAMP_PATTERN,
+                "description": "Send time in UTC, `YYYY-MM-DD HH:MM:SS`. Present with `include_timestamps` when the message has one."
+            },
+            "sender": {
+                "type": "string",
+                "description": "Display name of the author."
+            },
+            "content": {
+                "type": "string",
+                "description": "Message text; merged messages are joined with newlines."
+            },
+            "reply_to": {
+                "type": "integer",
+                "minimum": 0,
+                "description": "Id of the message this one replies to. Present with `include_replies` when the message is a reply."
+            },
+            "edited": {
+                "type": "string",
+                "pattern": TIMESTAMP_PATTERN,
+                "description": "Last edit time in UTC, `YYYY-MM-DD HH:MM:SS`. Present with `include_edited` when the message was edited."
+            },
+            "sentiment": {
+                "type": "number",
+                "minimum": -1,
+                "maximum": 1,
+                "description": "Lexicon sentiment score. Present with `sentiment` (requires the `sentiment` build feature)."
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TELEGRAM;
+
+    /// Checks the subset of JSON Schema that `json_schema()` uses.
+    fn assert_matches(schema: &Value, item: &Value) {
+        let object = item.as_object().expect("message should be an object");
+        let properties = schema["properties"].as_object().unwrap();
+
+        for required in schema["required"].as_array().unwrap() {
+            assert!(object.contains_key(required.as_str().unwrap()), "missing {required}");
+        }
+        for (key, value) in object {
+            let property = properties.get(key).unwrap_or_else(|| panic!("unexpected {key}"));
+            let type_ok = match property["type"].as_str().unwrap() {
+                "integer" => value.is_u64(),
+                "number" => value.is_number(),
+                "string" => value.is_string(),
+                other => panic!("unsupported type {other}"),
+            };
+            assert!(type_ok, "{key} has the wrong type: {value}");
+            if property.get("pattern").is_some() {
+                let text = value.as_str().unwrap();
+                assert!(chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_output_validates_against_schema() {
+        let schema: Value = serde_json::from_str(&output_schema()).unwrap();
+        let all = r#"{"include_timestamps":true,"include_ids":true,"include_replies":true,"include_edited":true,"merge_consecutive":false}"#;
+
+        for options in ["", all] {
+            let output = crate::convert_with_options(TELEGRAM, "telegram", "json", options)
+                .map_err(|e| e.as_string().unwrap_or_default())
+                .unwrap();
+            let items: Vec<Value> = serde_json::from_str(&output).unwrap();
+
+            assert!(!items.is_empty());
+            for item in &items {
+                assert_matches(&schema, item);
+            }
+        }
+    }
+
+    #[test]
+    fn test_schema_covers_every_output_field() {
+        let schema = json_schema();
+        let all = r#"{"include_timestamps":true,"include_ids":true,"include_replies":true,"include_edited":true,"merge_consecutive":false}"#;
+        let output = crate::convert_with_options(TELEGRAM, "telegram", "jsonl", all)
+            .map_err(|e| e.as_string().unwrap_or_default())
+            .unwrap();
+        let reply: Value = serde_json::from_str(output.lines().nth(1).unwrap()).unwrap();
+
+        for key in ["id", "timestamp", "sender", "content", "reply_to", "edited"] {
+            assert!(reply.get(key).is_some(), "sample lacks {key}");
+            assert!(schema["properties"].get(key).is_some(), "schema lacks {key}");
+        }
+    }
+
+    #[test]
+    fn test_shape_matches_output_for_option_matrix() {
+        let matrix = [
+            "{}",
+            r#"{"merge_consecutive":false}"#,
+            r#"{"include_timestamps":true,"include_replies":true,"merge_consecutive":false}"#,
+            r#"{"include_ids":true,"include_edited":true,"self_sender":"Bob"}"#,
+            r#"{"include_timestamps":true,"self_sender":"Bob","self_roles":true,"urls":"extract"}"#,
+        ];
+
+        for format in ["csv", "json", "jsonl", "json-compact"] {
+            for options in matrix {
+                let shape = output_shape(format, options).unwrap();
+                let parsed = crate::parse_options(options).unwrap();
+                let output =
+                    crate::run_conversion(TELEGRAM, "telegram", format, &parsed).unwrap().output;
+                assert_eq!(shape.schema_version, SCHEMA_VERSION);
+
+                if let Some(columns) = shape.columns {
+                    assert_eq!(
+                        output.lines().next().unwrap().split(';').collect::<Vec<_>>(),
+                        columns
+                    );
+                    continue;
+                }
+                let fields = shape.fields.unwrap();
+                let items: Vec<Vec<(String, Value)>> = if format == "jsonl" {
+                    output.lines().map(|line| first_object(line).unwrap()).collect()
+                } else {
+                    let items: Vec<OrderedObject> = serde_json::from_str(&output).unwrap();
+                    items.into_iter().map(|item| item.0).collect()
+                };
+                for item in items {
+                    let expected: Vec<&str> = fields
+                        .iter()
+                        .filter(|field| {
+                            !field.optional || item.iter().any(|(key, _)| *key == field.name)
+                        })
+                        .map(|field| field.name.as_str())
+                        .collect();
+                    let keys: Vec<&str> = item.iter().map(|(key, _)| key.as_str()).collect();
+                    assert_eq!(keys, expected, "{format} {options}");
+                    for (key, value) in &item {
+                        let field = fields.iter().find(|field| field.name == *key).unwrap();
+                        assert!(kind(value) == field.kind || (value.is_null() && field.nullable));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//! Searching parsed messages for a phrase or pattern.
+//!
+//! Backs `ChatSession::search`: matches come back with their position in the
+//! session, a few messages of context on either side and the ranges to
+//! highlight, so the UI never needs the whole chat on the JS side.
+
+use chatpack::Message;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+/// Matches returned when `limit` is unset.
+pub(crate) const DEFAULT_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SearchMode {
+    /// The query as literal text.
+    #[default]
+    Substring,
+    /// The query as a regular expression (Rust `regex` syntax).
+    Regex,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub(crate) struct SearchOptions {
+    mode: SearchMode,
+    case_sensitive: bool,
+    /// Messages of context on each side of a match.
+    context: usize,
+    limit: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            mode: SearchMode::Substring,
+            case_sensitive: false,
+            context: 2,
+            limit: DEFAULT_LIMIT,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct SearchResults<'a> {
+    matches: Vec<SearchMatch<'a>>,
+    /// Messages that matched, including those past `limit`.
+    total: usize,
+    truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct SearchMatch<'a> {
+    index: usize,
+    timestamp: Option<String>,
+    sender: &'a str,
+    snippet: &'a str,
+    /// `[start, end)` of each match in `snippet`, in UTF-16 code units so
+    /// they can be passed to `String.prototype.slice`.
+    match_ranges: Vec<[usize; 2]>,
+    before: Vec<ContextMessage<'a>>,
+    after: Vec<ContextMessage<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct ContextMessage<'a> {
+    index: usize,
+    timestamp: Option<String>,
+    sender: &'a str,
+    content: &'a str,
+}
+
+pub(crate) fn parse_options(options_json: &str) -> std::result::Result<SearchOptions, String> {
+    if options_json.trim().is_empty() {
+        return Ok(SearchOptions::default());
+    }
+
+    serde_json::from_str(options_json).map_err(|e| format!("Invalid search options: {e}"))
+}
+
+/// Messages whose content matches `query`, in chat order.
+pub(crate) fn search<'a>(
+    messages: &'a [Message],
+    query: &str,
+    options: &SearchOptions,
+) -> std::result::Result<SearchResults<'a>, String> {
+    let pattern = matcher(query, options)?;
+
+    let mut matches = Vec::new();
+    let mut total = 0;
+    for (index, message) in messages.iter().enumerate() {
+        let ranges: Vec<[usize; 2]> = pattern
+            .find_iter(&message.content)
+            .filter(|found| !found.is_empty())
+            .map(|found| {
+                let start = utf16_len(&message.content[..found.start()]);
+                [start, start + utf16_len(found.as_str())]
+            })
+            .collect();
+        if ranges.is_empty() {
+            continue;
+        }
+        total += 1;
+        if matches.len() == options.limit {
+            continue;
+        }
+
+        let context = |range: std::ops::Range<usize>| {
+            messages[range.clone()]
+                .iter()
+                .zip(range)
+                .map(|(message, index)| ContextMessage {
+                    index,
+                    timestamp: timestamp(message),
+                    sender: &message.sender,
+                    content: &message.content,
+                })
+                .collect()
+        };
+        matches.push(SearchMatch {
+            index,
+            timestamp: timestamp(message),
+            sender: &message.sender,
+            snippet: &message.content,
+            match_ranges: ranges,
+            before: context(index.saturating_sub(options.context)..index),
+            after: context(index + 1..(index + 1 + options.context).min(messages.len())),
+        });
+    }
+
+    Ok(SearchResults { truncated: total > matches.len(), matches, total })
+}
+
+fn matcher(query: &str, options: &SearchOptions) -> std::result::Result<Regex, String> {
+    if query.is_empty() {
+        return Err("Search query must not be empty".to_string());
+    }
+    let pattern = match options.mode {
+        SearchMode::Substring => regex::escape(query),
+        SearchMode::Regex => query.to_string(),
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|e| format!("Invalid search pattern: {e}"))
+}
+
+fn timestamp(message: &Message) -> Option<String> {
+    message.timestamp.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages() -> Vec<Message> {
+        ["Lunch at noon?", "Sure", "Café at NOON works too", "ok", "see you"]
+            .into_iter()
+            .enumerate()
+            .map(|(i, content)| Message::new(["Ann", "Bob"][i % 2], content))
+            .collect()
+    }
+
+    fn results(query: &str, options: &str) -> serde_json::Value {
+        let messages = messages();
+        let options = parse_options(options).unwrap();
+        serde_json::to_value(search(&messages, query, &options).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_substring_search_with_context() {
+        let results = results("noon", r#"{"context":1}"#);
+
+        assert_eq!(results["total"], 2);
+        assert_eq!(results["truncated"], false);
+        assert_eq!(results["matches"][0]["match_ranges"], serde_json::json!([[9, 13]]));
+        assert_eq!(results["matches"][1]["index"], 2);
+        assert_eq!(results["matches"][1]["match_ranges"], serde_json::json!([[8, 12]]));
+        assert_eq!(results["matches"][1]["before"][0]["content"], "Sure");
+        assert_eq!(results["matches"][1]["after"][0]["index"], 3);
+        assert_eq!(results["matches"][0]["before"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_regex_search_and_limit() {
+        let results = results(r"^(ok|sure)$", r#"{"mode":"regex","limit":1}"#);
+
+        assert_eq!(results["total"], 2);
+        assert_eq!(results["truncated"], true);
+        assert_eq!(results["matches"][0]["snippet"], "Sure");
+        assert!(search(&messages(), "(", &parse_options(r#"{"mode":"regex"}"#).unwrap()).is_err());
+        assert!(search(&messages(), "", &SearchOptions::default()).is_err());
+    }
+}
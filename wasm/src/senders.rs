@@ -0,0 +1,221 @@
+//! Grouping senders by account id instead of display name.
+//!
+//! A display name change mid-chat makes one person show up as two senders,
+//! which splits stats and stops `merge_consecutive` from joining their
+//! messages. With `sender_merge_by: "id"`, every message from an account is
+//! attributed to the name that account used last. chatpack's messages do
+//! not carry the sender's account id, so it is read from the raw export;
+//! only Telegram (`from_id`) is supported so far, other platforms keep
+//! grouping by name with a warning.
+//!
+//! Messages without a resolvable sender (deleted accounts, anonymous channel
+//! posts) are handled here too, per the `unknown_sender` policy, so no
+//! parser ever produces an empty sender.
+
+use std::collections::HashMap;
+
+use chatpack::Message;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "telegram")]
+use crate::telegram::TelegramExport;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SenderMergeBy {
+    #[default]
+    Name,
+    Id,
+}
+
+/// What happens to messages whose sender cannot be resolved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum UnknownSender {
+    /// Attribute them to the account id, if the export has one, or to
+    /// [`UNKNOWN_SENDER`].
+    #[default]
+    Placeholder,
+    Skip,
+    Error,
+}
+
+/// Sender of messages without a name or account id.
+pub(crate) const UNKNOWN_SENDER: &str = "Unknown";
+
+/// Rename senders per `merge_by`; returns a warning if the platform has no
+/// sender ids to group by. `accounts` is the latest name of each message's
+/// account by message id, `None` on platforms without account ids.
+pub(crate) fn group_senders(
+    messages: &mut [Message],
+    accounts: Option<&HashMap<u64, String>>,
+    merge_by: SenderMergeBy,
+) -> Option<String> {
+    if merge_by == SenderMergeBy::Name || messages.is_empty() {
+        return None;
+    }
+    let Some(accounts) = accounts else {
+        return Some(
+            "sender_merge_by \"id\" is only supported for Telegram; senders were grouped by name"
+                .to_string(),
+        );
+    };
+
+    for message in messages {
+        if let Some(name) = message.id.and_then(|id| accounts.get(&id)) {
+            message.sender.clone_from(name);
+        }
+    }
+
+    None
+}
+
+/// The name each Telegram account used last, by the ids of its messages.
+#[cfg(feature = "telegram")]
+pub(crate) fn telegram_accounts(export: &TelegramExport) -> HashMap<u64, String> {
+    let mut latest_name: HashMap<&str, &str> = HashMap::new();
+    let mut account_of: HashMap<u64, &str> = HashMap::new();
+    for raw in &export.messages {
+        if let (Some(from), Some(from_id)) = (raw.from.as_deref(), raw.from_id.as_deref()) {
+            latest_name.insert(from_id, from);
+            account_of.insert(raw.id, from_id);
+        }
+    }
+
+    account_of.into_iter().map(|(id, account)| (id, latest_name[account].to_string())).collect()
+}
+
+/// Apply `policy` to messages with a blank sender and to the ones in
+/// `unnamed`, whose sender the export does not name (deleted accounts), with
+/// the raw sender id where it has one.
+pub(crate) fn resolve_unknown(
+    messages: &mut Vec<Message>,
+    unnamed: &HashMap<u64, Option<String>>,
+    policy: UnknownSender,
+) -> std::result::Result<(), String> {
+    let unknown = |message: &Message| {
+        message.sender.trim().is_empty() || message.id.is_some_and(|id| unnamed.contains_key(&id))
+    };
+    if !messages.iter().any(unknown) {
+        return Ok(());
+    }
+
+    match policy {
+        UnknownSender::Placeholder => {
+            for message in messages.iter_mut().filter(|m| unknown(m)) {
+                let raw_id = message.id.and_then(|id| unnamed.get(&id).cloned().flatten());
+                message.sender = raw_id.unwrap_or_else(|| UNKNOWN_SENDER.to_string());
+            }
+        }
+        UnknownSender::Skip => messages.retain(|message| !unknown(message)),
+        UnknownSender::Error => {
+            let index = messages.iter().position(unknown).unwrap_or_default();
+            return Err(format!(
+                "Message {} has no sender; set unknown_sender to \"placeholder\" or \"skip\"",
+                index + 1
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Telegram messages and service entries without a sender name, with their
+/// `from_id` (or `actor_id`) where the export has one.
+#[cfg(feature = "telegram")]
+pub(crate) fn telegram_unnamed(export: &TelegramExport) -> HashMap<u64, Option<String>> {
+    export
+        .messages
+        .iter()
+        .filter(|raw| {
+            raw.from.as_deref().or(raw.actor.as_deref()).is_none_or(|n| n.trim().is_empty())
+        })
+        .map(|raw| {
+            let raw_id = raw.from_id.as_ref().or(raw.actor_id.as_ref());
+            (raw.id, raw_id.filter(|id| !id.trim().is_empty()).cloned())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_options, prepare_messages};
+
+    const FIXTURE: &str = include_str!("../fixtures/telegram_name_change.json");
+
+    fn senders(options: &str) -> Vec<String> {
+        let options = parse_options(options).unwrap();
+        let prepared = prepare_messages(FIXTURE, "telegram", &options).unwrap();
+        prepared.messages.into_iter().map(|m| m.sender).collect()
+    }
+
+    #[test]
+    fn test_group_by_id_uses_latest_name() {
+        assert_eq!(senders("{}"), ["Alice", "Bob", "Bobby", "Bob", "Alice"]);
+        assert_eq!(
+            senders(r#"{"sender_merge_by":"id","merge_consecutive":false}"#),
+            ["Alice", "Bobby", "Bobby", "Bob", "Alice"]
+        );
+        assert_eq!(senders(r#"{"sender_merge_by":"id"}"#), ["Alice", "Bobby", "Bob", "Alice"]);
+    }
+
+    #[test]
+    fn test_unknown_sender_policies() {
+        let telegram = r#"{"messages": [
+  {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": null, "from_id": "user42", "text": "ghost"},
+  {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Ann", "from_id": "user1", "text": "hi"}
+]}"#;
+        let convert = |policy: &str, source: &str, input: &str| {
+            let options = parse_options(&format!(
+                r#"{{"unknown_sender":"{policy}","merge_consecutive":false}}"#
+            ))
+            .unwrap();
+            prepare_messages(input, source, &options)
+                .map(|prepared| prepared.messages.into_iter().map(|m| m.sender).collect::<Vec<_>>())
+        };
+        let viber =
+            "Name,Date,Message\n,2024-01-15 10:30:00,who am I\nAnn,2024-01-15 10:31:00,hi\n";
+
+        assert_eq!(convert("placeholder", "telegram", telegram).unwrap(), ["user42", "Ann"]);
+        assert_eq!(convert("placeholder", "viber", viber).unwrap(), ["Unknown", "Ann"]);
+        assert_eq!(convert("skip", "telegram", telegram).unwrap(), ["Ann"]);
+        assert!(convert("error", "viber", viber).unwrap_err().contains("has no sender"));
+    }
+
+    #[test]
+    fn test_no_fixture_yields_empty_sender() {
+        // Every single-chat fixture; the full Telegram export goes through `convert_chat`.
+        let fixtures = [
+            ("generic-csv", include_str!("../fixtures/generic.csv")),
+            ("hangouts", include_str!("../fixtures/hangouts.json")),
+            ("kakaotalk", include_str!("../fixtures/kakaotalk.txt")),
+            ("line", include_str!("../fixtures/line.txt")),
+            ("skype", include_str!("../fixtures/skype.json")),
+            ("teams", include_str!("../fixtures/teams.json")),
+            ("telegram", include_str!("../fixtures/telegram_entities.json")),
+            ("telegram", include_str!("../fixtures/telegram_group_events.json")),
+            ("telegram", include_str!("../fixtures/telegram_name_change.json")),
+            ("telegram", include_str!("../fixtures/telegram_shared_items.json")),
+            ("viber", include_str!("../fixtures/viber.csv")),
+            ("whatsapp", include_str!("../fixtures/whatsapp_es.txt")),
+            ("whatsapp", include_str!("../fixtures/whatsapp_ru.txt")),
+        ];
+        let options =
+            parse_options(r#"{"include_events":true,"merge_consecutive":false}"#).unwrap();
+
+        for (source, input) in fixtures {
+            let prepared = prepare_messages(input, source, &options).unwrap();
+            assert!(!prepared.messages.is_empty(), "{source}");
+            assert!(prepared.messages.iter().all(|m| !m.sender.trim().is_empty()), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_group_by_id_warns_without_ids() {
+        let options = parse_options(r#"{"sender_merge_by":"id"}"#).unwrap();
+        let prepared =
+            prepare_messages("[15.01.24, 10:30:00] Alice: hi\n", "whatsapp", &options).unwrap();
+
+        assert!(prepared.warnings[0].starts_with("sender_merge_by \"id\" is only supported"));
+    }
+}
@@ -0,0 +1,204 @@
+//! Coarse lexicon-based sentiment scoring (`sentiment` feature).
+//!
+//! Each known word carries a weight on the AFINN scale (-5..=5); a message
+//! scores the mean weight of the words it contains, scaled to -1..=1. A
+//! negation directly before a word flips its weight. Messages without any
+//! known word score 0. This is meant for rough per-sender trends, not for
+//! judging individual messages.
+
+use std::collections::BTreeMap;
+
+use chatpack::Message;
+
+/// Sorted by word so lookups can binary search.
+const LEXICON: &[(&str, i8)] = &[
+    ("afraid", -2),
+    ("amazing", 4),
+    ("angry", -3),
+    ("annoyed", -2),
+    ("annoying", -2),
+    ("awesome", 4),
+    ("awful", -3),
+    ("bad", -3),
+    ("beautiful", 3),
+    ("best", 3),
+    ("better", 2),
+    ("boring", -3),
+    ("brilliant", 4),
+    ("broken", -1),
+    ("calm", 2),
+    ("congrats", 3),
+    ("congratulations", 3),
+    ("cool", 1),
+    ("cry", -1),
+    ("crying", -2),
+    ("cute", 2),
+    ("damn", -4),
+    ("dead", -3),
+    ("delighted", 3),
+    ("disappointed", -2),
+    ("disappointing", -2),
+    ("disgusting", -3),
+    ("dumb", -3),
+    ("enjoy", 2),
+    ("enjoyed", 2),
+    ("excellent", 3),
+    ("excited", 3),
+    ("fail", -2),
+    ("failed", -2),
+    ("fantastic", 4),
+    ("fear", -2),
+    ("fine", 2),
+    ("fun", 4),
+    ("furious", -3),
+    ("glad", 3),
+    ("good", 3),
+    ("gorgeous", 3),
+    ("grateful", 3),
+    ("great", 3),
+    ("haha", 3),
+    ("happy", 3),
+    ("hate", -3),
+    ("hated", -3),
+    ("helpful", 2),
+    ("hooray", 2),
+    ("hope", 2),
+    ("horrible", -3),
+    ("hurt", -2),
+    ("idiot", -3),
+    ("ill", -2),
+    ("impressive", 3),
+    ("incredible", 4),
+    ("interesting", 2),
+    ("joy", 3),
+    ("kind", 2),
+    ("laugh", 1),
+    ("like", 2),
+    ("liked", 2),
+    ("lol", 3),
+    ("lonely", -2),
+    ("lost", -3),
+    ("love", 3),
+    ("loved", 3),
+    ("lovely", 3),
+    ("lucky", 3),
+    ("mad", -3),
+    ("miss", -2),
+    ("nasty", -3),
+    ("nervous", -2),
+    ("nice", 3),
+    ("pain", -2),
+    ("perfect", 3),
+    ("pleased", 3),
+    ("poor", -2),
+    ("problem", -2),
+    ("proud", 2),
+    ("recommend", 2),
+    ("relieved", 2),
+    ("sad", -2),
+    ("safe", 1),
+    ("scared", -2),
+    ("shame", -2),
+    ("sick", -2),
+    ("smile", 2),
+    ("sorry", -1),
+    ("stupid", -2),
+    ("success", 2),
+    ("sucks", -3),
+    ("super", 3),
+    ("sweet", 2),
+    ("terrible", -3),
+    ("thank", 2),
+    ("thanks", 2),
+    ("thx", 2),
+    ("tired", -2),
+    ("ugly", -3),
+    ("unfair", -2),
+    ("upset", -2),
+    ("useless", -2),
+    ("win", 4),
+    ("wonderful", 4),
+    ("worried", -3),
+    ("worse", -3),
+    ("worst", -3),
+    ("wow", 4),
+    ("wrong", -2),
+    ("yay", 3),
+    ("yes", 1),
+];
+
+const NEGATIONS: &[&str] = &["don't", "dont", "isn't", "never", "no", "not", "wasn't"];
+
+/// Polarity of `content` in -1..=1, rounded to two decimals.
+pub(crate) fn score(content: &str) -> f64 {
+    let mut total = 0i32;
+    let mut matched = 0i32;
+    let mut negated = false;
+
+    for word in content
+        .split(|c: char| !(c.is_alphanumeric() || c == '\''))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+    {
+        if let Ok(index) = LEXICON.binary_search_by(|(entry, _)| (*entry).cmp(word.as_str())) {
+            let weight = i32::from(LEXICON[index].1);
+            total += if negated { -weight } else { weight };
+            matched += 1;
+        }
+        negated = NEGATIONS.contains(&word.as_str());
+    }
+
+    if matched == 0 {
+        return 0.0;
+    }
+
+    let polarity = (f64::from(total) / f64::from(matched) / 5.0).clamp(-1.0, 1.0);
+    (polarity * 100.0).round() / 100.0
+}
+
+/// Average score per sender, rounded to two decimals.
+pub(crate) fn by_sender(messages: &[Message]) -> BTreeMap<String, f64> {
+    let mut sums: BTreeMap<&str, (f64, usize)> = BTreeMap::new();
+    for message in messages {
+        let entry = sums.entry(&message.sender).or_default();
+        entry.0 += score(&message.content);
+        entry.1 += 1;
+    }
+
+    sums.into_iter()
+        .map(|(sender, (sum, count))| {
+            (sender.to_string(), (sum / count as f64 * 100.0).round() / 100.0)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lexicon_is_sorted() {
+        assert!(LEXICON.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    }
+
+    #[test]
+    fn test_score_polarity() {
+        assert!(score("I love this, it's wonderful!") > 0.5);
+        assert!(score("This is terrible and I hate it") < -0.5);
+        assert_eq!(score("The meeting is at 5pm"), 0.0);
+        assert!(score("not good") < 0.0);
+    }
+
+    #[test]
+    fn test_by_sender_averages() {
+        let messages = vec![
+            Message::new("Alice", "great"),
+            Message::new("Alice", "ok then"),
+            Message::new("Bob", "awful"),
+        ];
+        let averages = by_sender(&messages);
+
+        assert_eq!(averages["Alice"], 0.3);
+        assert_eq!(averages["Bob"], -0.6);
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::registry::PlatformSpec;
+use crate::{
+    build_stats, format_report, js_error, parse_options, process_export, read_export, registry,
+    resolve_format, search, ConvertOptions, PreparedMessages, ReadExport, ReadOptions,
+};
+
+#[derive(Serialize)]
+struct Participant<'a> {
+    name: &'a str,
+    message_count: usize,
+}
+
+/// A chat export parsed once and exported any number of times.
+///
+/// The session keeps the messages read with the options given to `parse`
+/// (see `read_export`) in WASM memory until it is freed (call `free()` from
+/// JS, or let the generated finalizer do it); the raw export is not kept.
+/// Options that change how the export is read (`column_map`,
+/// `source_timezone`, `unknown_sender`, `include_events`, `entities`,
+/// `threads`, ...) are
+/// therefore fixed by `parse`, and an `export` or `stats` call that sets
+/// them differently fails. Otherwise every call gives exactly what
+/// `convert_with_options` gives for the same options. Each call works on a
+/// copy of the messages, so filters and merging never alter what the
+/// session holds and repeated exports with different options always start
+/// from the same data.
+#[wasm_bindgen]
+pub struct ChatSession {
+    export: ReadExport,
+    read_options: ReadOptions,
+    input_bytes: usize,
+    platform: &'static PlatformSpec,
+}
+
+#[wasm_bindgen]
+impl ChatSession {
+    /// Parse `input` from `source`; this is the only expensive step.
+    ///
+    /// `options_json` takes the `convert_with_options` keys; only those that
+    /// change how the export is read are used here, and every later call
+    /// must leave them as they are. `undefined` reads with the defaults.
+    pub fn parse(
+        input: &str,
+        source: &str,
+        options_json: Option<String>,
+    ) -> std::result::Result<ChatSession, JsValue> {
+        let options =
+            parse_options(options_json.as_deref().unwrap_or_default()).map_err(js_error)?;
+        let export = read_export(input, source, &options).map_err(js_error)?;
+        let platform = registry::find_platform(source).map_err(js_error)?;
+
+        Ok(Self {
+            export,
+            read_options: ReadOptions::of(&options),
+            input_bytes: input.len(),
+            platform,
+        })
+    }
+
+    /// Export the parsed messages in `format`.
+    ///
+    /// `options_json` accepts the same keys as `convert_with_options`.
+    pub fn export(&self, format: &str, options_json: &str) -> std::result::Result<String, JsValue> {
+        let mut options = parse_options(options_json).map_err(js_error)?;
+        let output_format = resolve_format(format, &mut options).map_err(js_error)?;
+        let prepared = self.prepare(&options).map_err(js_error)?;
+        let report =
+            format_report(prepared, self.input_bytes, output_format, &options).map_err(js_error)?;
+
+        Ok(report.output)
+    }
+
+    /// Return processing stats for `options_json` as JSON, without formatting output.
+    pub fn stats(&self, options_json: &str) -> std::result::Result<String, JsValue> {
+        let options = parse_options(options_json).map_err(js_error)?;
+        let prepared = self.prepare(&options).map_err(js_error)?;
+        let stats = build_stats(&prepared, &options, self.input_bytes, 0);
+
+        serde_json::to_string(&stats).map_err(|e| js_error(e.to_string()))
+    }
+
+    /// Return senders with their message counts as JSON, in order of first appearance.
+    pub fn participants(&self) -> std::result::Result<String, JsValue> {
+        let mut participants: Vec<Participant> = Vec::new();
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        for message in &self.export.messages {
+            let position = *index.entry(&message.sender).or_insert_with(|| {
+                participants.push(Participant { name: &message.sender, message_count: 0 });
+                participants.len() - 1
+            });
+            participants[position].message_count += 1;
+        }
+
+        serde_json::to_string(&participants).map_err(|e| js_error(e.to_string()))
+    }
+
+    /// Find messages whose content contains `query`; returns JSON.
+    ///
+    /// `options_json` may set `mode` (`"substring"`, the default, or
+    /// `"regex"`), `case_sensitive` (default `false`), `context` (messages
+    /// before and after each match, default 2) and `limit` (default 500).
+    /// The result has `matches` (`index`, `timestamp`, `sender`, `snippet`,
+    /// `match_ranges` as UTF-16 offsets, `before`, `after`), the `total`
+    /// number of matching messages and `truncated` when it exceeds `limit`.
+    /// Indexes refer to the parsed messages, before filtering or merging.
+    pub fn search(&self, query: &str, options_json: &str) -> std::result::Result<String, JsValue> {
+        let options = search::parse_options(options_json).map_err(js_error)?;
+        let results = search::search(&self.export.messages, query, &options).map_err(js_error)?;
+
+        serde_json::to_string(&results).map_err(|e| js_error(e.to_string()))
+    }
+
+    /// Number of parsed messages, before any filtering or merging.
+    pub fn message_count(&self) -> usize {
+        self.export.messages.len()
+    }
+}
+
+impl ChatSession {
+    /// The session's messages processed for `options`, which must read the
+    /// export as `parse` did.
+    fn prepare(&self, options: &ConvertOptions) -> std::result::Result<PreparedMessages, String> {
+        if ReadOptions::of(options) != self.read_options {
+            return Err(
+                "Options that change how the export is read must be given to ChatSession.parse"
+                    .to_string(),
+            );
+        }
+
+        process_export(self.export.clone(), self.platform, options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TELEGRAM: &str = r#"{
+  "messages": [
+    {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Alice", "text": "Hello"},
+    {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Alice", "text": "Again"},
+    {"id": 3, "type": "message", "date_unixtime": "1705314720", "from": "Bob", "text": "Hi"}
+  ]
+}"#;
+
+    fn session() -> ChatSession {
+        ChatSession::parse(TELEGRAM, "telegram", None)
+            .map_err(|e| e.as_string().unwrap_or_default())
+            .expect("parse should succeed")
+    }
+
+    #[test]
+    fn test_session_exports_repeatedly_without_mutation() {
+        let session = session();
+        let export = |format: &str, options: &str| {
+            session
+                .export(format, options)
+                .map_err(|e| e.as_string().unwrap_or_default())
+                .expect("export should succeed")
+        };
+
+        let merged = export("jsonl", r#"{"merge_consecutive":true}"#);
+        let unmerged = export("jsonl", r#"{"merge_consecutive":false}"#);
+        let filtered = export("csv", r#"{"filter_sender":"Bob"}"#);
+
+        assert_eq!(merged.lines().count(), 2);
+        assert_eq!(unmerged.lines().count(), 3);
+        assert!(!filtered.contains("Alice"));
+        assert_eq!(session.message_count(), 3);
+    }
+
+    #[test]
+    fn test_session_export_matches_one_shot_conversion() {
+        let input = include_str!("../fixtures/whatsapp_group_events.txt")
+            .replace("Alice: Welcome!", "Alice: <Media omitted>");
+        let parse = |options: &str| {
+            ChatSession::parse(&input, "whatsapp", Some(options.to_string()))
+                .map_err(|e| e.as_string().unwrap_or_default())
+                .unwrap()
+        };
+        let session = parse("{}");
+
+        for options in [r#"{"merge_consecutive":false}"#, r#"{"include_events":true}"#, "{}"] {
+            let exported = parse(options).export("csv", options).ok().unwrap();
+            let expected =
+                crate::run_conversion(&input, "whatsapp", "csv", &parse_options(options).unwrap());
+
+            assert_eq!(exported, expected.unwrap().output, "{options}");
+        }
+        assert!(session.export("csv", "{}").ok().unwrap().contains("Alice;[media]"));
+        let rereading = parse_options(r#"{"include_events":true}"#).unwrap();
+        assert!(session.prepare(&rereading).is_err());
+    }
+
+    #[test]
+    fn test_session_reads_with_parse_options() {
+        let input = include_str!("../fixtures/generic.csv");
+        let options = r#"{"column_map":{"timestamp":"Date","sender":"From","content":"Body"}}"#;
+        let session = ChatSession::parse(input, "generic-csv", Some(options.to_string()))
+            .map_err(|e| e.as_string().unwrap_or_default())
+            .expect("parse should succeed");
+        let unmerged = options.replace("}}", r#"},"merge_consecutive":false}"#);
+
+        assert_eq!(session.message_count(), 3);
+        assert_eq!(session.export("jsonl", &unmerged).ok().unwrap().lines().count(), 3);
+        assert!(session.prepare(&ConvertOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_session_participants_and_stats() {
+        let session = session();
+        let participants: serde_json::Value =
+            serde_json::from_str(&session.participants().ok().unwrap()).unwrap();
+        let stats: serde_json::Value =
+            serde_json::from_str(&session.stats("{}").ok().unwrap()).unwrap();
+
+        assert_eq!(participants[0]["name"], "Alice");
+        assert_eq!(participants[0]["message_count"], 2);
+        assert_eq!(participants[1]["name"], "Bob");
+        assert_eq!(stats["original_count"], 3);
+        assert_eq!(stats["merged_count"], 2);
+    }
+}
@@ -0,0 +1,144 @@
+//! Telegram polls, locations, venues and shared contacts as text.
+//!
+//! These messages carry their payload in dedicated fields (`poll`,
+//! `location_information`, `place_name`, `contact_information`) and an empty
+//! `text`, so chatpack's parser has nothing to show for them. From the raw
+//! export (`telegram`) each one gets a content of its own: a poll's question
+//! with its answers and votes, `[location: lat,lon]` (or the venue's name) and
+//! `[contact: name, number]`. `exclude_kinds` leaves out whole kinds.
+
+use std::collections::HashMap;
+
+use chatpack::Message;
+use chrono::DateTime;
+
+use crate::kinds::MessageKind;
+use crate::telegram::{RawMessage, TelegramExport};
+
+/// The kind and content of a shared item, if `raw` is one.
+fn render(raw: &RawMessage) -> Option<(MessageKind, String)> {
+    if let Some(poll) = &raw.poll {
+        let mut content = format!("[poll: {}]", poll.question.trim());
+        for answer in &poll.answers {
+            let votes = if answer.voters == 1 { "vote" } else { "votes" };
+            content.push_str(&format!("\n- {} ({} {votes})", answer.text.trim(), answer.voters));
+        }
+        return Some((MessageKind::Poll, content));
+    }
+    if let Some(place) = raw.place_name.as_deref().filter(|p| !p.trim().is_empty()) {
+        let content = match raw.address.as_deref().filter(|a| !a.trim().is_empty()) {
+            Some(address) => format!("[location: {}, {}]", place.trim(), address.trim()),
+            None => format!("[location: {}]", place.trim()),
+        };
+        return Some((MessageKind::Venue, content));
+    }
+    if let Some(location) = &raw.location_information {
+        let label =
+            if raw.live_location_period_seconds.is_some() { "live location" } else { "location" };
+        let content = format!("[{label}: {},{}]", location.latitude, location.longitude);
+        return Some((MessageKind::Location, content));
+    }
+    if let Some(contact) = &raw.contact_information {
+        let name = format!("{} {}", contact.first_name.trim(), contact.last_name.trim());
+        let content = [name.trim(), contact.phone_number.trim()]
+            .into_iter()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ");
+        return Some((MessageKind::Contact, format!("[contact: {content}]")));
+    }
+
+    None
+}
+
+/// Give polls, locations, venues and contacts their content, adding the
+/// ones chatpack skipped and dropping kinds listed in `exclude`.
+pub(crate) fn render_shares(
+    messages: &mut Vec<Message>,
+    export: &TelegramExport,
+    exclude: &[MessageKind],
+) {
+    let rendered: HashMap<u64, (MessageKind, String, &RawMessage)> = export
+        .messages
+        .iter()
+        .filter(|raw| raw.kind == "message")
+        .filter_map(|raw| render(raw).map(|(kind, content)| (raw.id, (kind, content, raw))))
+        .collect();
+    if rendered.is_empty() {
+        return;
+    }
+
+    let mut seen = Vec::new();
+    messages.retain_mut(|message| {
+        let Some((kind, content, _)) = message.id.and_then(|id| rendered.get(&id)) else {
+            return true;
+        };
+        seen.extend(message.id);
+        message.content.clone_from(content);
+        !exclude.contains(kind)
+    });
+
+    let mut added = false;
+    for (&id, (kind, content, raw)) in &rendered {
+        if seen.contains(&id) || exclude.contains(kind) {
+            continue;
+        }
+        let mut message =
+            Message::new(raw.from.as_deref().unwrap_or_default(), content.clone()).with_id(id);
+        if let Some(time) = raw
+            .date_unixtime
+            .as_deref()
+            .and_then(|secs| secs.trim().parse().ok())
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        {
+            message = message.with_timestamp(time);
+        }
+        messages.push(message);
+        added = true;
+    }
+    if added {
+        messages.sort_by_key(|m| (m.timestamp, m.id));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_options, prepare_messages};
+
+    const FIXTURE: &str = include_str!("../fixtures/telegram_shared_items.json");
+
+    fn contents(options: &str) -> Vec<String> {
+        let options = parse_options(options).unwrap();
+        let prepared = prepare_messages(FIXTURE, "telegram", &options).unwrap();
+        prepared.messages.into_iter().map(|m| m.content).collect()
+    }
+
+    #[test]
+    fn test_shared_items_rendered() {
+        let contents = contents(r#"{"merge_consecutive":false}"#);
+
+        assert_eq!(contents.len(), 7);
+        assert!(contents.iter().all(|c| !c.trim().is_empty()));
+        assert_eq!(contents[1], "[poll: Meeting point]\n- Station (2 votes)\n- Old town (1 vote)");
+        assert_eq!(contents[2], "[location: 48.858844,2.294351]");
+        assert_eq!(contents[3], "[live location: 48.8606,2.3376]");
+        assert_eq!(contents[4], "[location: Café de Flore, 172 Bd Saint-Germain, Paris]");
+        assert_eq!(contents[5], "[contact: Dan Guide, +33 6 12 34 56 78]");
+    }
+
+    #[test]
+    fn test_exclude_kinds() {
+        let contents = contents(
+            r#"{"merge_consecutive":false,"exclude_kinds":["poll","contact","location"]}"#,
+        );
+
+        assert_eq!(
+            contents,
+            [
+                "Where do we meet?",
+                "[location: Café de Flore, 172 Bd Saint-Germain, Paris]",
+                "See you there"
+            ]
+        );
+    }
+}
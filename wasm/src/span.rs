@@ -0,0 +1,95 @@
+//! The first and last timestamp of an export, for a "chat from X to Y" label.
+
+use chatpack::parser::{create_parser, Platform};
+use chatpack::Message;
+use chrono::{DateTime, Utc};
+use wasm_bindgen::prelude::*;
+
+use crate::registry::{self, Backend};
+use crate::{date_span_of, js_error, parse_messages, DateSpan};
+
+/// Lines read from each end of a text export before falling back to a full parse.
+const SCAN_LINES: usize = 50;
+
+/// Earliest and latest timestamp of `input`, as JSON `{first, last}`
+/// (`YYYY-MM-DD HH:MM:SS`, `null` when no message has one).
+///
+/// WhatsApp exports start every message on a new line in time order, so
+/// only the first and last dated lines are read. Other sources (JSON
+/// exports, formats with date headers) are parsed in full.
+#[wasm_bindgen]
+pub fn date_span(input: &str, source: &str) -> std::result::Result<String, JsValue> {
+    let span = span(input, source).map_err(js_error)?;
+
+    serde_json::to_string(&span).map_err(|e| js_error(e.to_string()))
+}
+
+fn span(input: &str, source: &str) -> std::result::Result<DateSpan, String> {
+    let backend = registry::find_platform(source)?.backend;
+    if let Some(span) = line_span(input, backend) {
+        return Ok(span);
+    }
+
+    Ok(date_span_of(&parse_messages(input, source)?))
+}
+
+/// The span from the first and last dated lines, for line-based exports.
+fn line_span(input: &str, backend: Backend) -> Option<DateSpan> {
+    if !matches!(backend, Backend::Core(Platform::WhatsApp)) {
+        return None;
+    }
+    let first = input.lines().take(SCAN_LINES).find_map(line_time)?;
+    let last = input.lines().rev().take(SCAN_LINES).find_map(line_time)?;
+    let messages = [first, last].map(|time| Message::new("", "").with_timestamp(time));
+
+    Some(date_span_of(&messages))
+}
+
+fn line_time(line: &str) -> Option<DateTime<Utc>> {
+    create_parser(Platform::WhatsApp).parse_str(line).ok()?.first()?.timestamp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHATSAPP: &str = "[15.01.24, 10:30:00] Alice: Hello\n\
+[15.01.24, 10:31:00] Bob: Two lines,\n\
+the second one\n\
+[17.01.24, 08:05:00] Alice: Bye\n\
+and a trailing line\n";
+
+    fn json(input: &str, source: &str) -> serde_json::Value {
+        serde_json::to_value(span(input, source).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_whatsapp_reads_only_the_ends() {
+        let backend = registry::find_platform("whatsapp").unwrap().backend;
+
+        assert!(line_span(WHATSAPP, backend).is_some());
+        assert_eq!(
+            json(WHATSAPP, "whatsapp"),
+            serde_json::json!({"first": "2024-01-15 10:30:00", "last": "2024-01-17 08:05:00"})
+        );
+        assert_eq!(
+            serde_json::to_value(line_span(WHATSAPP, backend)).unwrap(),
+            serde_json::to_value(date_span_of(&parse_messages(WHATSAPP, "whatsapp").unwrap()))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_json_exports_fall_back_to_a_full_parse() {
+        let telegram = include_str!("../fixtures/telegram_out_of_order.json");
+        let backend = registry::find_platform("telegram").unwrap().backend;
+        let parsed = parse_messages(telegram, "telegram").unwrap();
+        let times: Vec<_> = parsed.iter().filter_map(|m| m.timestamp).collect();
+        let format = |t: &DateTime<Utc>| t.format("%Y-%m-%d %H:%M:%S").to_string();
+
+        assert!(line_span(telegram, backend).is_none());
+        assert_eq!(json(telegram, "telegram")["first"], format(times.iter().min().unwrap()));
+        assert_eq!(json(telegram, "telegram")["last"], format(times.iter().max().unwrap()));
+        assert_eq!(json("", "whatsapp"), serde_json::json!({"first": null, "last": null}));
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::BTreeMap;
+
+use chatpack::Message;
+use chrono::FixedOffset;
+use serde::Deserialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{js_error, non_empty, parse_options, prepare_messages, resolve_format, timezone};
+
+/// Key of the part holding messages without a timestamp.
+const UNDATED: &str = "undated";
+
+#[derive(Debug, Clone, Copy)]
+enum Period {
+    Day,
+    Month,
+    Year,
+}
+
+impl Period {
+    fn parse(by: &str) -> std::result::Result<Self, String> {
+        match by.trim().to_lowercase().as_str() {
+            "day" => Ok(Period::Day),
+            "month" => Ok(Period::Month),
+            "year" => Ok(Period::Year),
+            _ => Err(format!("Invalid split period: '{by}'. Expected one of: day, month, year")),
+        }
+    }
+
+    fn key_format(self) -> &'static str {
+        match self {
+            Period::Day => "%Y-%m-%d",
+            Period::Month => "%Y-%m",
+            Period::Year => "%Y",
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SplitOptions {
+    split_timezone: Option<String>,
+}
+
+/// Convert chat export into one part per day, month or year.
+///
+/// `by` is `"day"`, `"month"` or `"year"`. Filters and merging are applied
+/// first; a merged message belongs to the period of its first part. Periods
+/// follow the calendar in `split_timezone` (a UTC offset such as `+02:00`;
+/// defaults to `source_timezone`, then UTC), and each part is formatted on
+/// its own (own CSV header / JSON array).
+///
+/// Returns a JSON object from period key (`"2024-03-05"`, `"2024-03"` or
+/// `"2024"`) to output string, in chronological order; messages without a
+/// timestamp go into an `"undated"` part.
+#[wasm_bindgen]
+pub fn convert_split(
+    input: &str,
+    source: &str,
+    by: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    let parts = convert_split_inner(input, source, by, format, options_json).map_err(js_error)?;
+
+    serde_json::to_string(&parts).map_err(|e| js_error(e.to_string()))
+}
+
+fn convert_split_inner(
+    input: &str,
+    source: &str,
+    by: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<BTreeMap<String, String>, String> {
+    let period = Period::parse(by)?;
+    let mut options = parse_options(options_json)?;
+    let split_options: SplitOptions = if options_json.trim().is_empty() {
+        SplitOptions::default()
+    } else {
+        serde_json::from_str(options_json).map_err(|e| format!("Invalid options JSON: {e}"))?
+    };
+    let offset = match non_empty(split_options.split_timezone.as_deref())
+        .or(non_empty(options.source_timezone.as_deref()))
+    {
+        Some(timezone) => timezone::parse_offset(timezone)?,
+        None => FixedOffset::east_opt(0).expect("zero offset is valid"),
+    };
+    let output_format = resolve_format(format, &mut options)?;
+    let prepared = prepare_messages(input, source, &options)?;
+
+    split(&prepared.messages, period, offset)
+        .into_iter()
+        .map(|(key, indices)| {
+            Ok((key, prepared.format_selection(&indices, output_format, &options)?))
+        })
+        .collect()
+}
+
+/// Indices of `messages` in each part, by part key.
+fn split(
+    messages: &[Message],
+    period: Period,
+    offset: FixedOffset,
+) -> BTreeMap<String, Vec<usize>> {
+    let mut parts: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (index, message) in messages.iter().enumerate() {
+        let key = message.timestamp.map_or_else(
+            || UNDATED.to_string(),
+            |t| t.with_timezone(&offset).format(period.key_format()).to_string(),
+        );
+        parts.entry(key).or_default().push(index);
+    }
+
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TELEGRAM: &str = r#"{
+  "messages": [
+    {"id": 1, "type": "message", "date_unixtime": "1709157600", "from": "Ann", "text": "Feb, late"},
+    {"id": 2, "type": "message", "date_unixtime": "1709280000", "from": "Bob", "text": "March 1st"},
+    {"id": 3, "type": "message", "date_unixtime": "1711900800", "from": "Ann", "text": "March 31st"}
+  ]
+}"#;
+
+    fn parts(by: &str, options: &str) -> BTreeMap<String, String> {
+        convert_split_inner(TELEGRAM, "telegram", by, "jsonl", options).unwrap()
+    }
+
+    #[test]
+    fn test_split_by_month_and_timezone() {
+        let utc = parts("month", r#"{"merge_consecutive":false}"#);
+        assert_eq!(utc.keys().collect::<Vec<_>>(), ["2024-02", "2024-03"]);
+        assert_eq!(utc["2024-03"].lines().count(), 2);
+
+        // 2024-03-31 16:00 UTC is already April in UTC+9.
+        let tokyo = parts("month", r#"{"merge_consecutive":false,"split_timezone":"+09:00"}"#);
+        assert_eq!(tokyo.keys().collect::<Vec<_>>(), ["2024-02", "2024-03", "2024-04"]);
+        assert_eq!(parts("year", "{}").len(), 1);
+    }
+
+    #[test]
+    fn test_undated_and_independent_parts() {
+        let mut messages = vec![Message::new("Ann", "no date")];
+        messages.extend(
+            parse_options("{}")
+                .and_then(|options| prepare_messages(TELEGRAM, "telegram", &options))
+                .unwrap()
+                .messages,
+        );
+        let split = split(&messages, Period::Day, FixedOffset::east_opt(0).unwrap());
+        assert_eq!(split.keys().last().map(String::as_str), Some(UNDATED));
+
+        let csv = convert_split_inner(TELEGRAM, "telegram", "day", "csv", "{}").unwrap();
+        assert!(csv.values().all(|part| part.starts_with("Sender;Content")));
+        assert!(convert_split_inner(TELEGRAM, "telegram", "week", "csv", "{}").is_err());
+    }
+}
@@ -0,0 +1,397 @@
+use std::ops::Range;
+
+use chatpack::format::OutputFormat;
+use chatpack::Message;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    build_stats, describe_js_error, js_error, limit_output, parse_options, prepare_messages,
+    resolve_format, ConversionStats,
+};
+
+/// Default number of messages formatted into a single chunk.
+pub(crate) const DEFAULT_CHUNK_MESSAGES: usize = 10_000;
+
+/// Default soft size limit of a single chunk (4 MiB).
+pub(crate) const DEFAULT_CHUNK_BYTES: usize = 4 * 1024 * 1024;
+
+/// Most `on_progress` calls in one conversion, the final one included.
+const MAX_PROGRESS_UPDATES: usize = 100;
+
+#[derive(Serialize)]
+struct StreamSummary {
+    chunks: usize,
+    stats: ConversionStats,
+}
+
+/// Formatted messages out of all of them, and the ratio of the two.
+#[derive(Debug, Serialize)]
+struct StreamProgress {
+    done: usize,
+    total: usize,
+    progress: f64,
+}
+
+impl StreamProgress {
+    fn new(done: usize, total: usize) -> Self {
+        // Both counts are exact, so the ratio is too and the last one is 1.0.
+        let progress = if done >= total { 1.0 } else { done as f64 / total as f64 };
+        Self { done, total, progress }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct StreamError {
+    message: String,
+    chunks_emitted: usize,
+}
+
+/// Convert chat export and deliver the output to `on_chunk` in pieces.
+///
+/// A chunk is flushed every `chunk_messages` messages or once roughly
+/// `chunk_bytes` of output is pending, whichever comes first. Concatenating
+/// all chunks yields exactly the output of `convert_with_options`.
+///
+/// `on_progress`, if given, is called after chunks with a JSON string
+/// `{done, total, progress}`: `done` of the `total` messages left after
+/// filters and merging are formatted, and `progress` is their ratio. It is
+/// called at most 100 times, with strictly increasing `done`, and always
+/// last with `done == total` and `progress` exactly `1`, after the final
+/// chunk (also when there are no messages). The input is parsed before
+/// anything is formatted, so the total is always known.
+///
+/// `max_output_bytes` stops the stream at the same message as it does
+/// `convert_with_options` and sets `stats.truncated`; with `dry_run` no
+/// chunk is emitted.
+///
+/// Returns a JSON summary `{chunks, stats}`. On failure the error is a JSON
+/// object `{message, chunks_emitted}` and neither callback is called again.
+#[wasm_bindgen]
+pub fn convert_streaming(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+    on_chunk: &js_sys::Function,
+    on_progress: Option<js_sys::Function>,
+) -> std::result::Result<String, JsValue> {
+    let summary = stream_conversion(
+        input,
+        source,
+        format,
+        options_json,
+        |chunk| {
+            on_chunk
+                .call1(&JsValue::NULL, &JsValue::from_str(chunk))
+                .map(drop)
+                .map_err(describe_js_error)
+        },
+        |progress| {
+            let Some(on_progress) = &on_progress else {
+                return Ok(());
+            };
+            let progress = serde_json::to_string(progress).map_err(|e| e.to_string())?;
+            on_progress
+                .call1(&JsValue::NULL, &JsValue::from_str(&progress))
+                .map(drop)
+                .map_err(describe_js_error)
+        },
+    )
+    .map_err(|e| js_error(serde_json::to_string(&e).unwrap_or(e.message)))?;
+
+    serde_json::to_string(&summary).map_err(|e| js_error(e.to_string()))
+}
+
+fn stream_conversion<F, P>(
+    input: &str,
+    source: &str,
+    format: &str,
+    options_json: &str,
+    mut emit: F,
+    mut report: P,
+) -> std::result::Result<StreamSummary, StreamError>
+where
+    F: FnMut(&str) -> std::result::Result<(), String>,
+    P: FnMut(&StreamProgress) -> std::result::Result<(), String>,
+{
+    let mut chunks = 0;
+    let fail = |message: String, chunks_emitted: usize| StreamError { message, chunks_emitted };
+
+    let mut options = parse_options(options_json).map_err(|e| fail(e, chunks))?;
+    let output_format = resolve_format(format, &mut options).map_err(|e| fail(e, chunks))?;
+    let mut prepared = prepare_messages(input, source, &options).map_err(|e| fail(e, chunks))?;
+    let truncated = !options.dry_run
+        && limit_output(&mut prepared, output_format, &options)
+            .map_err(|e| fail(e, chunks))?
+            .is_some_and(|(_, truncated)| truncated);
+    let mut stitcher = ChunkStitcher::new(output_format, options.pretty);
+    let mut output_bytes = 0;
+    let total = prepared.messages.len();
+    let (mut done, mut reported_step) = (0, 0);
+    let batches = match options.dry_run {
+        true => Vec::new(),
+        false => batches(&prepared.messages, options.chunk_messages, options.chunk_bytes),
+    };
+
+    for batch in batches {
+        done += batch.len();
+        let formatted =
+            prepared.format_range(batch, output_format, &options).map_err(|e| fail(e, chunks))?;
+        let chunk = stitcher.push(formatted);
+        emit(&chunk).map_err(|e| fail(e, chunks))?;
+        chunks += 1;
+        output_bytes += chunk.len();
+
+        // Updates before the final one, at most one per hundredth of the total.
+        let step = done * MAX_PROGRESS_UPDATES / total.max(1);
+        if step > reported_step && step < MAX_PROGRESS_UPDATES {
+            reported_step = step;
+            report(&StreamProgress::new(done, total)).map_err(|e| fail(e, chunks))?;
+        }
+    }
+
+    if let Some(tail) = stitcher.finish() {
+        emit(&tail).map_err(|e| fail(e, chunks))?;
+        chunks += 1;
+        output_bytes += tail.len();
+    }
+    report(&StreamProgress::new(total, total)).map_err(|e| fail(e, chunks))?;
+
+    let mut stats = build_stats(&prepared, &options, input.len(), output_bytes);
+    stats.truncated = truncated;
+
+    Ok(StreamSummary { chunks, stats })
+}
+
+/// Splits messages into index ranges bounded by count and estimated output size.
+///
+/// An empty message list still yields one (empty) batch so the format's
+/// header or empty document is emitted.
+pub(crate) fn batches(
+    messages: &[Message],
+    max_messages: usize,
+    max_bytes: usize,
+) -> Vec<Range<usize>> {
+    let max_messages = max_messages.max(1);
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut pending = 0;
+
+    for (index, message) in messages.iter().enumerate() {
+        pending += estimated_size(message);
+        if index + 1 - start >= max_messages || pending >= max_bytes {
+            batches.push(start..index + 1);
+            start = index + 1;
+            pending = 0;
+        }
+    }
+    if start < messages.len() || batches.is_empty() {
+        batches.push(start..messages.len());
+    }
+
+    batches
+}
+
+fn estimated_size(message: &Message) -> usize {
+    message.sender.len() + message.content.len() + 32
+}
+
+/// Joins independently formatted batches into one valid document.
+///
+/// CSV keeps only the first header, JSONL is concatenated as-is, and JSON
+/// arrays are unwrapped so the items land in a single array that is closed
+/// by [`ChunkStitcher::finish`].
+pub(crate) struct ChunkStitcher {
+    format: OutputFormat,
+    pretty: bool,
+    started: bool,
+}
+
+impl ChunkStitcher {
+    pub(crate) fn new(format: OutputFormat, pretty: bool) -> Self {
+        Self { format, pretty, started: false }
+    }
+
+    pub(crate) fn push(&mut self, formatted: String) -> String {
+        let first = !self.started;
+
+        if matches!(self.format, OutputFormat::Json) {
+            let Some(items) = json_items(&formatted, self.pretty) else {
+                return if first { formatted } else { String::new() };
+            };
+            self.started = true;
+            let separator = match (first, self.pretty) {
+                (true, true) => "[\n",
+                (true, false) => "[",
+                (false, true) => ",\n",
+                (false, false) => ",",
+            };
+            return format!("{separator}{items}");
+        }
+
+        self.started = true;
+        match self.format {
+            OutputFormat::Csv if !first => {
+                formatted.split_once('\n').map(|(_, rows)| rows.to_string()).unwrap_or_default()
+            }
+            _ => formatted,
+        }
+    }
+
+    pub(crate) fn finish(self) -> Option<String> {
+        match self.format {
+            OutputFormat::Json if self.started => {
+                Some(if self.pretty { "\n]" } else { "]" }.to_string())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns the items of a JSON array, or `None` if it is empty.
+fn json_items(formatted: &str, pretty: bool) -> Option<&str> {
+    let (open, close) = if pretty { ("[\n", "\n]") } else { ("[", "]") };
+    formatted.strip_prefix(open).and_then(|rest| rest.strip_suffix(close)).filter(|i| !i.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_conversion;
+
+    const TELEGRAM: &str = r#"{
+  "messages": [
+    {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Alice", "text": "Hello"},
+    {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Bob", "text": "Hi"},
+    {"id": 3, "type": "message", "date_unixtime": "1705314720", "from": "Alice", "text": "Bye"}
+  ]
+}"#;
+
+    const OPTIONS: &str =
+        r#"{"merge_consecutive":false,"include_timestamps":true,"chunk_messages":1}"#;
+
+    #[test]
+    fn test_chunks_concatenate_to_full_output() {
+        let events = include_str!("../fixtures/telegram_group_events.json");
+        let events_options = OPTIONS.replace('}', r#","include_events":true}"#);
+        let cases = [(TELEGRAM, OPTIONS), (events, events_options.as_str())];
+
+        for (input, options_json) in cases {
+            for format in ["csv", "json", "jsonl", "json-compact"] {
+                let mut chunks = Vec::new();
+                let summary = stream_conversion(
+                    input,
+                    "telegram",
+                    format,
+                    options_json,
+                    |chunk| {
+                        chunks.push(chunk.to_string());
+                        Ok(())
+                    },
+                    |_| Ok(()),
+                )
+                .expect("streaming should succeed");
+                let options = parse_options(options_json).unwrap();
+                let expected = run_conversion(input, "telegram", format, &options).unwrap();
+
+                assert!(summary.chunks >= 3, "{format} should be split");
+                assert_eq!(summary.chunks, chunks.len());
+                assert_eq!(chunks.concat(), expected.output, "{format} output differs");
+                assert_eq!(summary.stats.output_bytes, expected.output.len());
+            }
+        }
+        let options = parse_options(&events_options).unwrap();
+        let csv = run_conversion(events, "telegram", "csv", &options).unwrap().output;
+        assert!(csv.lines().next().unwrap().ends_with(";Action"), "events should add a column");
+    }
+
+    #[test]
+    fn test_stream_applies_output_limit_and_dry_run() {
+        let full = run_conversion(TELEGRAM, "telegram", "csv", &parse_options(OPTIONS).unwrap());
+        let limit = full.unwrap().output.len() - 1;
+        let limited = OPTIONS.replace('}', &format!(r#","max_output_bytes":{limit}}}"#));
+        let dry = OPTIONS.replace('}', r#","dry_run":true}"#);
+
+        for options_json in [limited, dry] {
+            let mut chunks = Vec::new();
+            let summary = stream_conversion(
+                TELEGRAM,
+                "telegram",
+                "csv",
+                &options_json,
+                |chunk| {
+                    chunks.push(chunk.to_string());
+                    Ok(())
+                },
+                |_| Ok(()),
+            )
+            .expect("streaming should succeed");
+            let options = parse_options(&options_json).unwrap();
+            let expected = run_conversion(TELEGRAM, "telegram", "csv", &options).unwrap();
+
+            assert_eq!(chunks.concat(), expected.output, "{options_json}");
+            assert_eq!(summary.stats.truncated, expected.stats.truncated);
+            assert_eq!(summary.stats.merged_count, expected.stats.merged_count);
+        }
+    }
+
+    #[test]
+    fn test_callback_error_stops_stream() {
+        let mut calls = 0;
+        let error = stream_conversion(
+            TELEGRAM,
+            "telegram",
+            "jsonl",
+            OPTIONS,
+            |_| {
+                calls += 1;
+                if calls == 2 {
+                    Err("disk full".to_string())
+                } else {
+                    Ok(())
+                }
+            },
+            |_| Ok(()),
+        )
+        .err()
+        .expect("streaming should fail");
+
+        assert_eq!(calls, 2);
+        assert_eq!(error.chunks_emitted, 1);
+        assert_eq!(error.message, "disk full");
+    }
+
+    #[test]
+    fn test_progress_is_monotonic_bounded_and_ends_at_one() {
+        let lines: Vec<String> = (0..250)
+            .map(|i| format!("[15.01.24, 10:00:00] User{}: message {i}\n", i % 2))
+            .collect();
+        let input = lines.concat();
+        let progress = |input: &str| {
+            let mut updates: Vec<(usize, usize, f64)> = Vec::new();
+            stream_conversion(
+                input,
+                "whatsapp",
+                "csv",
+                OPTIONS,
+                |_| Ok(()),
+                |p| {
+                    updates.push((p.done, p.total, p.progress));
+                    Ok(())
+                },
+            )
+            .expect("streaming should succeed");
+            updates
+        };
+
+        let updates = progress(&input);
+        assert!(updates.len() <= MAX_PROGRESS_UPDATES);
+        assert!(updates.len() > 50);
+        assert!(updates.windows(2).all(|w| w[0].0 < w[1].0 && w[0].2 < w[1].2));
+        assert!(updates.iter().all(|&(done, total, p)| done <= total && (0.0..=1.0).contains(&p)));
+        assert_eq!(updates.last(), Some(&(250, 250, 1.0)));
+
+        assert_eq!(progress(""), [(0, 0, 1.0)]);
+    }
+}
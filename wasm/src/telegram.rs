@@ -0,0 +1,87 @@
+//! The raw Telegram export, read once for every step that needs more than
+//! chatpack's messages.
+//!
+//! chatpack keeps the sender, text and ids of `"type": "message"` entries;
+//! entities, shared items, service events and sender ids live in fields it
+//! drops. `read_export` deserializes the export into [`TelegramExport`] once
+//! and hands it to each of those steps, instead of each step parsing the
+//! whole JSON again. Every field any of them reads is here; the rest of an
+//! entry is skipped while parsing.
+
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct TelegramExport {
+    #[serde(default)]
+    pub messages: Vec<RawMessage>,
+}
+
+/// One entry of `messages`, message or service.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct RawMessage {
+    pub id: u64,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub date_unixtime: Option<String>,
+    pub from: Option<String>,
+    pub from_id: Option<String>,
+    /// Sender of service messages.
+    pub actor: Option<String>,
+    pub actor_id: Option<String>,
+    /// A plain string, or an array of strings and entity objects.
+    pub text: Value,
+    pub poll: Option<Poll>,
+    pub location_information: Option<Location>,
+    pub live_location_period_seconds: Option<u64>,
+    pub place_name: Option<String>,
+    pub address: Option<String>,
+    pub contact_information: Option<Contact>,
+    /// Service action (`pin_message`, `phone_call`, ...).
+    pub action: Option<String>,
+    pub title: Option<String>,
+    pub members: Vec<Option<String>>,
+    /// Message a service entry refers to, on `pin_message`.
+    pub message_id: Option<u64>,
+    /// Seconds, on `phone_call`.
+    pub duration_seconds: Option<u64>,
+    /// Seconds, on `group_call`.
+    pub duration: Option<u64>,
+    pub discard_reason: Option<String>,
+}
+
+/// A poll's question and answers with their vote counts.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct Poll {
+    pub question: String,
+    pub answers: Vec<Answer>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct Answer {
+    pub text: String,
+    pub voters: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct Contact {
+    pub first_name: String,
+    pub last_name: String,
+    pub phone_number: String,
+}
+
+/// `input` as a Telegram export, or why it is not one.
+pub(crate) fn parse(input: &str) -> std::result::Result<TelegramExport, String> {
+    serde_json::from_str(input).map_err(|e| format!("Failed to parse Telegram export: {e}"))
+}
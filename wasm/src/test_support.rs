@@ -0,0 +1,20 @@
+//! Inputs and helpers shared by the test modules.
+
+use serde_json::Value;
+
+use crate::{parse_options, run_conversion};
+
+/// Two-message Telegram chat: Alice's greeting and Bob's edited reply to it.
+pub(crate) const TELEGRAM: &str = include_str!("../fixtures/telegram_chat.json");
+
+/// Output of converting `input` with the `options` JSON.
+pub(crate) fn convert(input: &str, source: &str, format: &str, options: &str) -> String {
+    let options = parse_options(options).unwrap();
+    run_conversion(input, source, format, &options).unwrap().output
+}
+
+/// The messages of a JSONL conversion of `input`, one value each.
+pub(crate) fn convert_rows(input: &str, source: &str, options: &str) -> Vec<Value> {
+    let output = convert(input, source, "jsonl", options);
+    output.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+}
@@ -0,0 +1,155 @@
+//! Early rejection of inputs that are not text.
+//!
+//! Chat exports are JSON or plain text. A zip archive, image or database
+//! picked by mistake otherwise reaches the parsers and fails with an error
+//! about JSON syntax or date formats that says nothing about the real problem.
+//!
+//! Text exports can still carry stray control characters (BEL, vertical
+//! tab, ...) in message content; `strip_control_chars` removes them.
+
+use chatpack::Message;
+use wasm_bindgen::prelude::*;
+
+use crate::{parse_options, run_conversion, CodedError, CONVERSION_FAILED};
+
+/// Machine-readable code for inputs that look binary.
+pub(crate) const NOT_TEXT: &str = "NOT_TEXT";
+
+/// Only this many leading bytes are inspected.
+const SAMPLE_BYTES: usize = 8 * 1024;
+/// Share of suspicious characters above which the sample counts as binary.
+const MAX_SUSPICIOUS_RATIO: f64 = 0.1;
+
+/// Convert chat export given as raw bytes (e.g. `new Uint8Array(await file.arrayBuffer())`).
+///
+/// Same arguments and output as `convert_with_options`, but the input is
+/// checked before parsing: invalid UTF-8, NUL bytes or a high share of
+/// control characters are rejected. Errors are JSON `{code, message}` where
+/// `code` is `NOT_TEXT` for binary input and `CONVERSION_FAILED` otherwise.
+#[wasm_bindgen]
+pub fn convert_bytes(
+    input: &[u8],
+    source: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    convert_checked(input, source, format, options_json).map_err(CodedError::into_js)
+}
+
+fn convert_checked(
+    input: &[u8],
+    source: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<String, CodedError> {
+    let text = decode(input).map_err(|message| CodedError::new(NOT_TEXT, message))?;
+    let failed = |message| CodedError::new(CONVERSION_FAILED, message);
+    let options = parse_options(options_json).map_err(failed)?;
+
+    run_conversion(text, source, format, &options).map(|report| report.output).map_err(failed)
+}
+
+/// Borrow `input` as text, or explain why it does not look like text.
+fn decode(input: &[u8]) -> std::result::Result<&str, String> {
+    let text = std::str::from_utf8(input).map_err(|e| {
+        format!("{NOT_TEXT}: input is not valid UTF-8 (at byte {})", e.valid_up_to())
+    })?;
+    check_text(text)?;
+
+    Ok(text)
+}
+
+/// Reject text that decoded fine but is mostly control characters.
+///
+/// Catches binary data that went through a lossy decode on the JS side,
+/// where invalid bytes become U+FFFD.
+pub(crate) fn check_text(text: &str) -> std::result::Result<(), String> {
+    let mut end = text.len().min(SAMPLE_BYTES);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    let mut total = 0usize;
+    let mut suspicious = 0usize;
+    for c in text[..end].chars() {
+        if c == '\0' {
+            return Err(format!("{NOT_TEXT}: input contains NUL bytes"));
+        }
+        total += 1;
+        if c == char::REPLACEMENT_CHARACTER || (c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+        {
+            suspicious += 1;
+        }
+    }
+
+    if total > 0 && suspicious as f64 / total as f64 > MAX_SUSPICIOUS_RATIO {
+        return Err(format!(
+            "{NOT_TEXT}: input looks like binary data ({suspicious} of the first {total} \
+             characters are not printable)"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Remove C0 control characters other than `\n` and `\t` from contents.
+pub(crate) fn strip_control_chars(messages: &mut [Message]) {
+    let is_stray = |c: char| c.is_ascii_control() && c != '\n' && c != '\t' && c != '\x7f';
+    for message in messages.iter_mut().filter(|m| m.content.contains(is_stray)) {
+        message.content.retain(|c| !is_stray(c));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TELEGRAM: &str = r#"{"messages": [{"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Alice", "text": "Привет 👋"}]}"#;
+
+    fn random_bytes(len: usize) -> Vec<u8> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 24) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_random_bytes_are_not_text() {
+        let error = convert_checked(&random_bytes(4096), "telegram", "csv", "").unwrap_err();
+        let lossy = String::from_utf8_lossy(&random_bytes(4096)).into_owned();
+
+        assert_eq!(error.code, NOT_TEXT);
+        assert!(error.message.contains("UTF-8"));
+        assert!(check_text(&lossy).unwrap_err().starts_with(NOT_TEXT));
+        assert!(check_text("ab\0cd").unwrap_err().contains("NUL"));
+    }
+
+    #[test]
+    fn test_text_passes_through_to_conversion() {
+        let output = convert_checked(TELEGRAM.as_bytes(), "telegram", "csv", "")
+            .map_err(|e| e.message)
+            .expect("text input should convert");
+        let error = convert_checked(TELEGRAM.as_bytes(), "telegram", "xml", "").unwrap_err();
+
+        assert!(output.contains("Привет 👋"));
+        assert!(check_text("[15.01.24, 10:30:00] Alice: tab\there\r\n").is_ok());
+        assert_eq!(error.code, CONVERSION_FAILED);
+    }
+
+    #[test]
+    fn test_control_characters_stripped() {
+        let input = "[15.01.24, 10:30:00] Alice: ding\u{0007}\u{000b}dong\tand\r\n";
+        let output = |options: &str| {
+            let options = parse_options(options).unwrap();
+            run_conversion(input, "whatsapp", "json", &options).unwrap().output
+        };
+
+        assert!(output("{}").contains("dingdong\\tand"));
+        assert!(output(r#"{"strip_control_chars":false}"#).contains("ding\\u0007\\u000bdong"));
+    }
+}
@@ -0,0 +1,224 @@
+//! Reply threads: chains of messages linked by `reply_to`.
+//!
+//! A thread is a message that was replied to (its root) and every message
+//! that replies to it, directly or down a chain, in chat order. Replies to
+//! messages outside the export start no thread. Platforms without reply
+//! metadata (see `supports_replies` in the registry) have no threads.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chatpack::Message;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::{js_error, parse_options, prepare_messages, registry};
+
+/// Shortest thread kept when `thread_min_length` is unset: a message and one reply.
+pub(crate) const DEFAULT_MIN_LENGTH: usize = 2;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ThreadOptions {
+    /// Also return the messages posted between a thread's first and last
+    /// message that are not part of it.
+    include_intervening: bool,
+}
+
+#[derive(Serialize)]
+struct Threads<'a> {
+    threads: Vec<Thread<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Thread<'a> {
+    root_id: Option<u64>,
+    /// Messages in the thread, not counting intervening ones.
+    length: usize,
+    messages: Vec<ThreadMessage<'a>>,
+}
+
+#[derive(Serialize)]
+struct ThreadMessage<'a> {
+    #[serde(flatten)]
+    message: &'a Message,
+    in_thread: bool,
+}
+
+/// Reply threads of an export as JSON.
+///
+/// `options_json` takes the `convert_with_options` keys (filters;
+/// `merge_consecutive` is ignored, as merging loses reply ids) plus
+/// `thread_min_length` (default 2) and `include_intervening`, which adds the
+/// other messages posted while a thread ran, with `in_thread: false`.
+/// Returns `{threads: [{root_id, length, messages}]}`, root first; for
+/// platforms without reply metadata `threads` is empty and a `note` says why.
+#[wasm_bindgen]
+pub fn extract_threads(
+    input: &str,
+    source: &str,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    let mut options = parse_options(options_json).map_err(js_error)?;
+    options.merge_consecutive = false;
+    let thread_options: ThreadOptions = if options_json.trim().is_empty() {
+        ThreadOptions::default()
+    } else {
+        serde_json::from_str(options_json)
+            .map_err(|e| js_error(format!("Invalid options JSON: {e}")))?
+    };
+    let platform = registry::find_platform(source).map_err(js_error)?;
+    let prepared = prepare_messages(input, source, &options).map_err(js_error)?;
+
+    let threads = if platform.supports_replies {
+        let messages = &prepared.messages;
+        let threads = threads(messages, options.thread_min_length)
+            .into_iter()
+            .map(|thread| {
+                let members: HashSet<usize> = thread.iter().copied().collect();
+                let indices = if thread_options.include_intervening {
+                    let first = thread.iter().min().copied().unwrap_or_default();
+                    let last = thread.iter().max().copied().unwrap_or_default();
+                    std::iter::once(thread[0])
+                        .chain((first..=last).filter(|index| *index != thread[0]))
+                        .collect()
+                } else {
+                    thread.clone()
+                };
+                Thread {
+                    root_id: messages[thread[0]].id,
+                    length: thread.len(),
+                    messages: indices
+                        .into_iter()
+                        .map(|index| ThreadMessage {
+                            message: &messages[index],
+                            in_thread: members.contains(&index),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+        Threads { threads, note: None }
+    } else {
+        Threads {
+            threads: Vec::new(),
+            note: Some(format!("{} exports have no reply metadata", platform.display_name)),
+        }
+    };
+
+    serde_json::to_string(&threads).map_err(|e| js_error(e.to_string()))
+}
+
+/// Indices of each thread with at least `min_length` messages, root first,
+/// then replies in chat order; threads are ordered by their root.
+pub(crate) fn threads(messages: &[Message], min_length: usize) -> Vec<Vec<usize>> {
+    let by_id: HashMap<u64, usize> =
+        messages.iter().enumerate().filter_map(|(index, m)| m.id.map(|id| (id, index))).collect();
+    let parent = |index: usize| {
+        messages[index].reply_to.and_then(|id| by_id.get(&id).copied()).filter(|p| *p != index)
+    };
+
+    let mut threads: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for index in 0..messages.len() {
+        let mut root = index;
+        // Bounded walk, so a reply cycle cannot loop forever.
+        for _ in 0..messages.len() {
+            match parent(root) {
+                Some(next) => root = next,
+                None => break,
+            }
+        }
+        if root != index {
+            threads.entry(root).or_insert_with(|| vec![root]).push(index);
+        }
+    }
+
+    threads.into_values().filter(|thread| thread.len() >= min_length).collect()
+}
+
+/// Keep only messages in a thread of at least `min_length` messages.
+pub(crate) fn threads_only(messages: Vec<Message>, min_length: usize) -> Vec<Message> {
+    let keep: HashSet<usize> = threads(&messages, min_length).into_iter().flatten().collect();
+
+    messages
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| keep.contains(index))
+        .map(|(_, m)| m)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_conversion;
+
+    const TELEGRAM: &str = r#"{
+  "messages": [
+    {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Ann", "text": "Lunch?"},
+    {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Cid", "text": "Unrelated"},
+    {"id": 3, "type": "message", "date_unixtime": "1705314720", "from": "Bob", "text": "Yes", "reply_to_message_id": 1},
+    {"id": 4, "type": "message", "date_unixtime": "1705314780", "from": "Ann", "text": "Noon?", "reply_to_message_id": 3},
+    {"id": 5, "type": "message", "date_unixtime": "1705314840", "from": "Cid", "text": "Re: old", "reply_to_message_id": 99},
+    {"id": 6, "type": "message", "date_unixtime": "1705314900", "from": "Dan", "text": "Build?"},
+    {"id": 7, "type": "message", "date_unixtime": "1705314960", "from": "Eve", "text": "Green", "reply_to_message_id": 6}
+  ]
+}"#;
+
+    fn extract(source: &str, input: &str, options: &str) -> serde_json::Value {
+        let json = extract_threads(input, source, options)
+            .map_err(|e| e.as_string().unwrap_or_default())
+            .unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_threads_follow_reply_chains() {
+        let result = extract("telegram", TELEGRAM, "{}");
+        let threads = result["threads"].as_array().unwrap();
+        let contents = |thread: &serde_json::Value| -> Vec<String> {
+            thread["messages"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|m| m["content"].as_str().unwrap().to_string())
+                .collect()
+        };
+
+        assert_eq!(threads.len(), 2);
+        assert_eq!(contents(&threads[0]), ["Lunch?", "Yes", "Noon?"]);
+        assert_eq!(threads[0]["root_id"], 1);
+        assert_eq!(contents(&threads[1]), ["Build?", "Green"]);
+
+        let long = extract("telegram", TELEGRAM, r#"{"thread_min_length":3}"#);
+        assert_eq!(long["threads"].as_array().unwrap().len(), 1);
+
+        let intervening = extract("telegram", TELEGRAM, r#"{"include_intervening":true}"#);
+        assert_eq!(contents(&intervening["threads"][0]), ["Lunch?", "Unrelated", "Yes", "Noon?"]);
+        assert_eq!(intervening["threads"][0]["messages"][1]["in_thread"], false);
+        assert_eq!(intervening["threads"][0]["length"], 3);
+    }
+
+    #[test]
+    fn test_platform_without_replies_has_note() {
+        let result = extract("whatsapp", "[15.01.24, 10:30:00] Ann: hi\n", "{}");
+
+        assert_eq!(result["threads"], serde_json::json!([]));
+        assert!(result["note"].as_str().unwrap().contains("no reply metadata"));
+    }
+
+    #[test]
+    fn test_threads_only_option() {
+        let options = parse_options(
+            r#"{"threads_only":true,"thread_min_length":3,"merge_consecutive":false}"#,
+        )
+        .unwrap();
+        let report = run_conversion(TELEGRAM, "telegram", "jsonl", &options).unwrap();
+
+        assert_eq!(report.output.lines().count(), 3);
+        assert!(!report.output.contains("Unrelated"));
+        assert!(report.stats.filters_active);
+        assert!(parse_options(r#"{"thread_min_length":1}"#).is_err());
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::{HashMap, HashSet};
+
+use chatpack::Message;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::discord::{self, ThreadMode};
+use crate::edits::{self, EditPolicy};
+use crate::enrich::ExtraColumn;
+use crate::pinned;
+use crate::{
+    events, format_with_columns, js_error, parse_options, process_export, read_export, registry,
+    resolve_format, ConvertOptions, ReadExport,
+};
+
+#[derive(Debug, Deserialize)]
+struct SourcedInput {
+    input: String,
+    source: String,
+    /// `convert_merged` only: value of the `chat` column.
+    #[serde(default)]
+    label: Option<String>,
+    /// `convert_merged` only: overrides the `source_timezone` option for this input.
+    #[serde(default, alias = "assume_input_timezone")]
+    source_timezone: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct TimelineMessage {
+    platform: &'static str,
+    #[serde(flatten)]
+    message: Message,
+}
+
+#[derive(Serialize)]
+struct TimelineReport {
+    messages: Vec<TimelineMessage>,
+    duplicates_removed: usize,
+}
+
+/// Parse several exports, possibly from different platforms, into one timeline.
+///
+/// `inputs_json` is an array of `{input, source}` objects. Messages are
+/// ordered by timestamp (stable, so same-second messages keep their export
+/// order; messages without a timestamp go last), each tagged with the
+/// canonical `platform` id it came from. Messages with identical timestamp,
+/// sender and content are kept once, as is a message repeated under one id
+/// in exports of the same platform (its most recently edited version).
+///
+/// Returns JSON `{messages, duplicates_removed}`.
+#[wasm_bindgen]
+pub fn merge_conversations(inputs_json: &str) -> std::result::Result<String, JsValue> {
+    let inputs: Vec<SourcedInput> = serde_json::from_str(inputs_json)
+        .map_err(|e| js_error(format!("Invalid inputs JSON: {e}")))?;
+    let (messages, duplicates_removed) = merge_timeline(inputs).map_err(js_error)?;
+
+    serde_json::to_string(&TimelineReport { messages, duplicates_removed })
+        .map_err(|e| js_error(e.to_string()))
+}
+
+/// Convert several exports, possibly from different platforms, into one output.
+///
+/// `inputs_json` is an array of `{input, source, label, source_timezone}`
+/// objects; `label` defaults to the canonical platform id and
+/// `source_timezone` (alias `assume_input_timezone`) overrides the option of
+/// the same name for that input, so local times from different exports can
+/// be reconciled. Each input is filtered and merged on its own (merging
+/// never joins messages of different chats), then all messages are ordered
+/// by timestamp like `merge_conversations`. A message repeated under one id
+/// in exports of the same platform is resolved per the `edits` option before
+/// anything else. Every message carries its label in a `chat` column (CSV) or
+/// field (JSON/JSONL) after the usual ones. Discord thread exports given next
+/// to their channel's are handled per the `threads` option, with
+/// `"separate"` putting each thread after all channel messages.
+///
+/// Takes the same `format` and `options_json` as `convert_with_options`.
+#[wasm_bindgen]
+pub fn convert_merged(
+    inputs_json: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    merged_output(inputs_json, format, options_json).map_err(js_error)
+}
+
+fn merged_output(
+    inputs_json: &str,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<String, String> {
+    let inputs: Vec<SourcedInput> =
+        serde_json::from_str(inputs_json).map_err(|e| format!("Invalid inputs JSON: {e}"))?;
+    let mut options = parse_options(options_json)?;
+    let output_format = resolve_format(format, &mut options)?;
+
+    let mut parsed = parse_all(&inputs, &options)?;
+    dedupe_by_platform(&mut parsed, options.edits);
+
+    let mut labelled = Vec::new();
+    let mut thread_names = HashMap::new();
+    let mut actions = HashMap::new();
+    let mut pinned = HashSet::new();
+    for (index, (sourced, (spec, export))) in inputs.into_iter().zip(parsed).enumerate() {
+        let context = |e: String| format!("Input {index} ({}): {e}", spec.id);
+        let mut input_options = options.clone();
+        if sourced.source_timezone.is_some() {
+            input_options.source_timezone = sourced.source_timezone;
+        }
+
+        let prepared = process_export(export, spec, &input_options).map_err(context)?;
+        thread_names.extend(prepared.threads);
+        actions.extend(prepared.actions);
+        pinned.extend(prepared.pinned);
+        let label = sourced.label.unwrap_or_else(|| spec.id.to_string());
+        labelled.extend(prepared.messages.into_iter().map(|message| (label.clone(), message)));
+    }
+
+    let separate = options.threads == ThreadMode::Separate;
+    labelled.sort_by_cached_key(|(_, message)| {
+        let thread = message.id.and_then(|id| thread_names.get(&id)).filter(|_| separate);
+        (thread.cloned(), message.timestamp.is_none(), message.timestamp)
+    });
+    let (labels, messages): (Vec<String>, Vec<Message>) = labelled.into_iter().unzip();
+    let chat = ExtraColumn::new("chat", labels.into_iter().map(serde_json::Value::from).collect());
+    let actions = events::action_column(&messages, &actions);
+    let threads = discord::thread_column(&messages, &thread_names);
+    let pinned = options.include_pinned.then(|| pinned::pinned_column(&messages, &pinned));
+
+    format_with_columns(
+        &messages,
+        output_format,
+        &options,
+        [chat].into_iter().chain(actions).chain(threads).chain(pinned).collect(),
+    )
+}
+
+fn merge_timeline(
+    inputs: Vec<SourcedInput>,
+) -> std::result::Result<(Vec<TimelineMessage>, usize), String> {
+    let mut parsed = parse_all(&inputs, &ConvertOptions::default())?;
+    let repeated = dedupe_by_platform(&mut parsed, EditPolicy::Latest);
+
+    let mut timeline = Vec::new();
+    for (spec, export) in parsed {
+        timeline.extend(
+            export
+                .messages
+                .into_iter()
+                .map(|message| TimelineMessage { platform: spec.id, message }),
+        );
+    }
+
+    timeline.sort_by_key(|entry| (entry.message.timestamp.is_none(), entry.message.timestamp));
+
+    let mut seen = HashSet::new();
+    let keep: Vec<bool> = timeline
+        .iter()
+        .map(|entry| {
+            let m = &entry.message;
+            seen.insert((m.timestamp, m.sender.as_str(), m.content.as_str()))
+        })
+        .collect();
+    let duplicates = keep.iter().filter(|keep| !**keep).count();
+    let mut keep = keep.into_iter();
+    timeline.retain(|_| keep.next().unwrap_or(true));
+
+    Ok((timeline, duplicates + repeated))
+}
+
+fn parse_all(
+    inputs: &[SourcedInput],
+    options: &ConvertOptions,
+) -> std::result::Result<Vec<(&'static registry::PlatformSpec, ReadExport)>, String> {
+    inputs
+        .iter()
+        .enumerate()
+        .map(|(index, sourced)| {
+            let spec = registry::find_platform(&sourced.source)
+                .map_err(|e| format!("Input {index}: {e}"))?;
+            let export = read_export(&sourced.input, spec.id, options)
+                .map_err(|e| format!("Input {index} ({}): {e}", spec.id))?;
+            Ok((spec, export))
+        })
+        .collect()
+}
+
+/// Resolve ids repeated across the inputs of each platform; returns the
+/// number of messages removed.
+fn dedupe_by_platform(
+    parsed: &mut [(&'static registry::PlatformSpec, ReadExport)],
+    policy: EditPolicy,
+) -> usize {
+    let mut platforms: Vec<&str> = parsed.iter().map(|(spec, _)| spec.id).collect();
+    platforms.dedup();
+    platforms.sort_unstable();
+    platforms.dedup();
+
+    platforms
+        .into_iter()
+        .map(|platform| {
+            let (mut lists, slots): (Vec<Vec<Message>>, Vec<usize>) = parsed
+                .iter_mut()
+                .enumerate()
+                .filter(|(_, (spec, _))| spec.id == platform)
+                .map(|(slot, (_, export))| (std::mem::take(&mut export.messages), slot))
+                .unzip();
+            let removed = edits::dedupe(&mut lists, policy);
+            for (slot, messages) in slots.into_iter().zip(lists) {
+                parsed[slot].1.messages = messages;
+            }
+            removed
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TELEGRAM: &str = r#"{
+  "messages": [
+    {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Alice", "text": "Morning on Telegram"},
+    {"id": 2, "type": "message", "date_unixtime": "1705314720", "from": "Bob", "text": "Replying on Telegram"}
+  ]
+}"#;
+
+    const WHATSAPP: &str = "[15.01.24, 10:31:00] Alice: Also on WhatsApp\n\
+[15.01.24, 10:33:00] Bob: Last one on WhatsApp\n";
+
+    fn merge(inputs: serde_json::Value) -> serde_json::Value {
+        let json = merge_conversations(&inputs.to_string())
+            .map_err(|e| e.as_string().unwrap_or_default())
+            .expect("merge should succeed");
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn test_merge_interleaves_platforms_by_time() {
+        let report = merge(serde_json::json!([
+            {"input": TELEGRAM, "source": "tg"},
+            {"input": WHATSAPP, "source": "whatsapp"},
+        ]));
+        let messages = report["messages"].as_array().unwrap();
+        let order: Vec<(&str, &str)> = messages
+            .iter()
+            .map(|m| (m["platform"].as_str().unwrap(), m["content"].as_str().unwrap()))
+            .collect();
+
+        assert_eq!(
+            order,
+            vec![
+                ("telegram", "Morning on Telegram"),
+                ("whatsapp", "Also on WhatsApp"),
+                ("telegram", "Replying on Telegram"),
+                ("whatsapp", "Last one on WhatsApp"),
+            ]
+        );
+        assert_eq!(report["duplicates_removed"], 0);
+    }
+
+    #[test]
+    fn test_merge_drops_identical_messages() {
+        let report = merge(serde_json::json!([
+            {"input": TELEGRAM, "source": "telegram"},
+            {"input": TELEGRAM, "source": "telegram"},
+        ]));
+
+        assert_eq!(report["messages"].as_array().unwrap().len(), 2);
+        assert_eq!(report["duplicates_removed"], 2);
+    }
+
+    #[test]
+    fn test_merge_keeps_latest_edit_of_repeated_id() {
+        let edited = TELEGRAM.replace(
+            r#""text": "Replying on Telegram""#,
+            r#""text": "Replying on Telegram (edited)", "edited_unixtime": "1705314800""#,
+        );
+        let report = merge(serde_json::json!([
+            {"input": edited, "source": "telegram"},
+            {"input": TELEGRAM, "source": "telegram"},
+        ]));
+        let messages = report["messages"].as_array().unwrap();
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1]["content"], "Replying on Telegram (edited)");
+        assert_eq!(report["duplicates_removed"], 2);
+    }
+
+    #[test]
+    fn test_convert_merged_labels_and_reconciles_timezones() {
+        let inputs = serde_json::json!([
+            {"input": TELEGRAM, "source": "telegram", "label": "tg-chat"},
+            {"input": WHATSAPP, "source": "whatsapp", "label": "wa-chat",
+             "assume_input_timezone": "+01:00"},
+        ])
+        .to_string();
+        let options = r#"{"include_timestamps":true}"#;
+
+        let csv = merged_output(&inputs, "csv", options).expect("merge should convert");
+        let jsonl = merged_output(&inputs, "jsonl", options).unwrap();
+        let labels: Vec<String> = jsonl
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<serde_json::Value>(line).unwrap()["chat"].to_string()
+            })
+            .collect();
+
+        assert!(csv.starts_with("Timestamp;Sender;Content;Chat\n"));
+        assert!(csv.contains(";Also on WhatsApp;wa-chat\n"));
+        // WhatsApp's 10:31 local is 09:31 UTC, before Telegram's 10:30 UTC.
+        assert_eq!(labels, [r#""wa-chat""#, r#""wa-chat""#, r#""tg-chat""#, r#""tg-chat""#]);
+    }
+
+    #[test]
+    fn test_single_input_matches_sync_conversion() {
+        let input = include_str!("../fixtures/whatsapp_group_events.txt")
+            .replace("Alice: Welcome!", "Alice: <Media omitted>");
+        let inputs = serde_json::json!([{"input": input, "source": "whatsapp"}]).to_string();
+
+        for options in [r#"{"merge_consecutive":false}"#, r#"{"include_events":true}"#] {
+            let merged = merged_output(&inputs, "jsonl", options).expect("merge should convert");
+            let expected = crate::run_conversion(
+                &input,
+                "whatsapp",
+                "jsonl",
+                &parse_options(options).unwrap(),
+            );
+            let without_chat: Vec<serde_json::Value> = merged
+                .lines()
+                .map(|line| {
+                    let mut message: serde_json::Value = serde_json::from_str(line).unwrap();
+                    message.as_object_mut().unwrap().remove("chat");
+                    message
+                })
+                .collect();
+            let expected: Vec<serde_json::Value> = expected
+                .unwrap()
+                .output
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+
+            assert_eq!(without_chat, expected, "{options}");
+        }
+    }
+}
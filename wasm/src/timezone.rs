@@ -0,0 +1,125 @@
+//! Wall-clock timestamps from exports that carry no UTC offset.
+//!
+//! WhatsApp, Viber, LINE and KakaoTalk write the sender's local time, which
+//! the parsers store as if it were UTC. The `source_timezone` option names
+//! the export's UTC offset so those timestamps can be shifted to real UTC;
+//! without it the conversion reports a warning instead of guessing. A
+//! generic CSV export can mix both kinds, so only its timestamps read
+//! without an offset are shifted or counted ([`resolve_local`]).
+
+use chatpack::Message;
+use chrono::{Duration, FixedOffset};
+
+/// Parse a `source_timezone` value: `UTC`, `Z`, `+05:30`, `-0800` or `+9`.
+pub(crate) fn parse_offset(value: &str) -> std::result::Result<FixedOffset, String> {
+    let value = value.trim();
+    let invalid = || {
+        format!("Invalid source_timezone: '{value}'. Expected a UTC offset like +05:30 or -08:00")
+    };
+
+    if value.eq_ignore_ascii_case("utc") || value.eq_ignore_ascii_case("z") {
+        return Ok(FixedOffset::east_opt(0).expect("zero offset is valid"));
+    }
+
+    let (sign, rest) = match value.as_bytes().first() {
+        Some(b'+') => (1, &value[1..]),
+        Some(b'-') => (-1, &value[1..]),
+        _ => return Err(invalid()),
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some(parts) => parts,
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    let digits = |s: &str| !s.is_empty() && s.len() <= 2 && s.bytes().all(|b| b.is_ascii_digit());
+    if !digits(hours) || !digits(minutes) {
+        return Err(invalid());
+    }
+
+    let hours: i32 = hours.parse().map_err(|_| invalid())?;
+    let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 14 || minutes > 59 {
+        return Err(invalid());
+    }
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+}
+
+/// Reinterpret local timestamps (stored as UTC) as times at `offset`.
+pub(crate) fn shift_to_utc(messages: &mut [Message], offset: FixedOffset) {
+    shift(messages.iter_mut(), offset);
+}
+
+fn shift<'a>(messages: impl Iterator<Item = &'a mut Message>, offset: FixedOffset) {
+    let shift = Duration::seconds(offset.local_minus_utc().into());
+
+    for message in messages {
+        if let Some(timestamp) = message.timestamp.as_mut() {
+            *timestamp -= shift;
+        }
+        if let Some(edited) = message.edited.as_mut() {
+            *edited -= shift;
+        }
+    }
+}
+
+/// Warning for local timestamps that were kept as UTC, if there are any.
+pub(crate) fn ambiguity_warning(messages: &[Message]) -> Option<String> {
+    warning(messages.iter().filter(|m| m.timestamp.is_some()).count())
+}
+
+/// [`shift_to_utc`] with `source_timezone`, or else [`ambiguity_warning`],
+/// for only the messages whose `local` flag is set: those a parser read
+/// without a UTC offset.
+pub(crate) fn resolve_local(
+    messages: &mut [Message],
+    local: &[bool],
+    source_timezone: Option<&str>,
+) -> std::result::Result<Option<String>, String> {
+    let local_messages = messages.iter_mut().zip(local).filter(|(_, &local)| local);
+    match source_timezone {
+        Some(timezone) => {
+            shift(local_messages.map(|(message, _)| message), parse_offset(timezone)?);
+            Ok(None)
+        }
+        None => Ok(warning(local_messages.filter(|(m, _)| m.timestamp.is_some()).count())),
+    }
+}
+
+fn warning(affected: usize) -> Option<String> {
+    (affected > 0).then(|| {
+        format!(
+            "{affected} message(s) have local timestamps without a UTC offset and were treated \
+             as UTC; set source_timezone to convert them"
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_offset() {
+        let seconds = |value| parse_offset(value).map(|offset| offset.local_minus_utc());
+
+        assert_eq!(seconds("+05:30"), Ok(19_800));
+        assert_eq!(seconds("-0800"), Ok(-28_800));
+        assert_eq!(seconds("+9"), Ok(32_400));
+        assert_eq!(seconds("UTC"), Ok(0));
+        assert!(parse_offset("Europe/Berlin").unwrap_err().starts_with("Invalid source_timezone"));
+        assert!(parse_offset("+25:00").is_err());
+    }
+
+    #[test]
+    fn test_shift_to_utc() {
+        let local = Utc.with_ymd_and_hms(2024, 1, 15, 10, 30, 0).unwrap();
+        let mut messages = vec![Message::new("Alice", "Hi").with_timestamp(local)];
+
+        shift_to_utc(&mut messages, parse_offset("+05:00").unwrap());
+
+        assert_eq!(messages[0].timestamp, Utc.with_ymd_and_hms(2024, 1, 15, 5, 30, 0).single());
+    }
+}
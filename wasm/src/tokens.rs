@@ -0,0 +1,33 @@
+//! Rough token counts for sizing output against LLM context limits.
+
+/// Estimate how many tokens `text` takes in a GPT-style BPE tokenizer.
+///
+/// English text averages about four characters per token, while scripts
+/// outside ASCII (Cyrillic, CJK, emoji) split into far more tokens per
+/// character; this counts ASCII at four characters and everything else at
+/// two characters per token. Good for budgeting, not for billing: real
+/// tokenizers can differ by 20% or more either way.
+pub(crate) fn estimate_tokens(text: &str) -> usize {
+    let (ascii, other) = text.chars().fold((0usize, 0usize), |(ascii, other), c| {
+        if c.is_ascii() {
+            (ascii + 1, other)
+        } else {
+            (ascii, other + 1)
+        }
+    });
+
+    ascii.div_ceil(4) + other.div_ceil(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("Hello world!"), 3);
+        assert_eq!(estimate_tokens("Привет"), 3);
+        assert_eq!(estimate_tokens("hi 👋"), 2);
+    }
+}
@@ -0,0 +1,201 @@
+//! Links in message content, kept, dropped or pulled out into a field.
+//!
+//! A URL starts with `http://`, `https://` or `www.` and runs to the next
+//! whitespace. Punctuation that ends the sentence around it (`.`, `,`, `!`,
+//! a closing quote) is not part of it, and neither is a closing bracket
+//! without a matching opening one inside the URL, so `(see
+//! https://en.wikipedia.org/wiki/Rust_(language))` keeps the inner pair.
+
+use std::sync::OnceLock;
+
+use chatpack::Message;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::enrich::ExtraColumn;
+
+/// Content left where `urls: "extract"` took a URL out.
+pub(crate) const PLACEHOLDER: &str = "[url]";
+
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', '\'', '"', '…'];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum UrlMode {
+    #[default]
+    Keep,
+    Drop,
+    Extract,
+}
+
+fn candidates() -> &'static Regex {
+    static CANDIDATES: OnceLock<Regex> = OnceLock::new();
+    CANDIDATES.get_or_init(|| {
+        Regex::new(r#"(?i)\b(?:https?://|www\.)[^\s<>"]+"#).expect("URL pattern should compile")
+    })
+}
+
+/// Byte ranges of the URLs in `text`.
+fn find(text: &str) -> Vec<(usize, usize)> {
+    candidates()
+        .find_iter(text)
+        .filter_map(|found| {
+            let url = trim_end(found.as_str());
+            // A scheme or `www.` with nothing left after trimming is not a link.
+            let prefix = url.find("://").map_or("www.".len(), |scheme| scheme + "://".len());
+            (url.len() > prefix).then_some((found.start(), found.start() + url.len()))
+        })
+        .collect()
+}
+
+/// `url` without trailing punctuation and unbalanced closing brackets.
+fn trim_end(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let trimmed = url.trim_end_matches(TRAILING_PUNCTUATION);
+        let trimmed = match trimmed.chars().last() {
+            Some(close @ (')' | ']' | '}')) => {
+                let open = match close {
+                    ')' => '(',
+                    ']' => '[',
+                    _ => '{',
+                };
+                if trimmed.matches(open).count() < trimmed.matches(close).count() {
+                    &trimmed[..trimmed.len() - 1]
+                } else {
+                    trimmed
+                }
+            }
+            _ => trimmed,
+        };
+        if trimmed.len() == url.len() {
+            return url;
+        }
+        url = trimmed;
+    }
+}
+
+/// Remove the URLs from every message's content, with the spaces before
+/// each one (after it, at the start of a line), so `see https://x.io now`
+/// becomes `see now`.
+pub(crate) fn drop_urls(messages: &mut [Message]) {
+    for message in messages {
+        let found = find(&message.content);
+        if found.is_empty() {
+            continue;
+        }
+        let mut content = String::with_capacity(message.content.len());
+        let mut copied = 0;
+        for (start, end) in found {
+            let before = &message.content[copied..start];
+            let line_start = (content.is_empty() && before.trim().is_empty())
+                || before.trim_end_matches([' ', '\t']).ends_with('\n');
+            content.push_str(before.trim_end_matches([' ', '\t']));
+            copied = end;
+            if line_start {
+                let rest = &message.content[end..];
+                copied += rest.len() - rest.trim_start_matches([' ', '\t']).len();
+            }
+        }
+        content.push_str(&message.content[copied..]);
+        message.content = content.trim_end().to_string();
+    }
+}
+
+/// Copies of `messages` with each URL replaced by [`PLACEHOLDER`], and the
+/// `urls` column listing what was taken out of each (in order).
+pub(crate) fn extract(messages: &[Message]) -> (Vec<Message>, ExtraColumn) {
+    let mut values = Vec::with_capacity(messages.len());
+    let messages = messages
+        .iter()
+        .map(|message| {
+            let mut message = message.clone();
+            let mut urls = Vec::new();
+            let mut content = String::with_capacity(message.content.len());
+            let mut copied = 0;
+            for (start, end) in find(&message.content) {
+                content.push_str(&message.content[copied..start]);
+                content.push_str(PLACEHOLDER);
+                urls.push(Value::from(&message.content[start..end]));
+                copied = end;
+            }
+            content.push_str(&message.content[copied..]);
+            message.content = content;
+            values.push(Value::Array(urls));
+            message
+        })
+        .collect();
+
+    (messages, ExtraColumn::new("urls", values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{convert, convert_rows};
+
+    const TELEGRAM: &str = r#"{"messages": [
+  {"id": 1, "type": "message", "date_unixtime": "1705314600", "from": "Ann", "text": "Docs: https://example.com/docs. Also see www.rust-lang.org!"},
+  {"id": 2, "type": "message", "date_unixtime": "1705314660", "from": "Bob", "text": "(the wiki: https://en.wikipedia.org/wiki/Rust_(language))"},
+  {"id": 3, "type": "message", "date_unixtime": "1705314720", "from": "Ann", "text": "https://example.com/a?b=1&c=2"},
+  {"id": 4, "type": "message", "date_unixtime": "1705314780", "from": "Bob", "text": "no links here"}
+]}"#;
+
+    fn urls(text: &str) -> Vec<&str> {
+        find(text).into_iter().map(|(start, end)| &text[start..end]).collect()
+    }
+
+    #[test]
+    fn test_trailing_punctuation_is_not_part_of_the_url() {
+        assert_eq!(urls("see https://example.com/docs."), ["https://example.com/docs"]);
+        assert_eq!(urls("\"https://x.io/a\", or www.y.org?"), ["https://x.io/a", "www.y.org"]);
+        assert_eq!(urls("(at https://x.io/a_(b))"), ["https://x.io/a_(b)"]);
+        assert_eq!(urls("[https://x.io/page]"), ["https://x.io/page"]);
+        assert_eq!(urls("HTTPS://X.IO/?q=a,b;c"), ["HTTPS://X.IO/?q=a,b;c"]);
+        assert!(urls("https:// and www. and example.com").is_empty());
+    }
+
+    #[test]
+    fn test_keep_is_the_default() {
+        let kept = convert_rows(TELEGRAM, "telegram", r#"{"merge_consecutive":false}"#);
+
+        assert_eq!(kept[2]["content"], "https://example.com/a?b=1&c=2");
+        assert_eq!(kept[0].get("urls"), None);
+        assert_eq!(
+            convert_rows(TELEGRAM, "telegram", r#"{"merge_consecutive":false,"urls":"keep"}"#),
+            kept
+        );
+    }
+
+    #[test]
+    fn test_drop_removes_urls_from_content() {
+        let dropped =
+            convert_rows(TELEGRAM, "telegram", r#"{"merge_consecutive":false,"urls":"drop"}"#);
+
+        assert_eq!(dropped[0]["content"], "Docs:. Also see!");
+        assert_eq!(dropped[1]["content"], "(the wiki:)");
+        assert_eq!(dropped[2]["content"], "");
+        assert_eq!(dropped[3]["content"], "no links here");
+        assert_eq!(dropped[0].get("urls"), None);
+    }
+
+    #[test]
+    fn test_extract_moves_urls_into_a_field() {
+        let extracted =
+            convert_rows(TELEGRAM, "telegram", r#"{"merge_consecutive":false,"urls":"extract"}"#);
+
+        assert_eq!(extracted[0]["content"], "Docs: [url]. Also see [url]!");
+        assert_eq!(
+            extracted[0]["urls"],
+            serde_json::json!(["https://example.com/docs", "www.rust-lang.org"])
+        );
+        assert_eq!(extracted[1]["urls"][0], "https://en.wikipedia.org/wiki/Rust_(language)");
+        assert_eq!(extracted[3]["urls"], serde_json::json!([]));
+
+        let csv =
+            convert(TELEGRAM, "telegram", "csv", r#"{"merge_consecutive":false,"urls":"extract"}"#);
+        assert!(csv.starts_with("Sender;Content;Urls\n"));
+        assert!(csv.contains("Ann;[url];https://example.com/a?b=1&c=2\n"));
+    }
+}
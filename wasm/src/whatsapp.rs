@@ -0,0 +1,511 @@
+//! Localized WhatsApp system lines and media tokens.
+//!
+//! WhatsApp writes placeholders and notices into the export in the phone's
+//! language: `<Media omitted>`, the end-to-end encryption banner (attributed
+//! to the chat itself), deleted-message notices and the `<This message was
+//! edited>` suffix. They are matched against [`MARKERS`], so a new locale is
+//! a few more rows there: media tokens (and the `null` of view-once media)
+//! become the `[media]` placeholder, deleted notices `[deleted]`, the edit
+//! suffix is stripped, and banner lines are dropped.
+//!
+//! Group notices ("Alice added Bob", "Bob left") are lines with a date but
+//! no sender. The parser appends them to the message before them; they are
+//! matched against [`EVENT_PATTERNS`] and moved to the events stream (see
+//! `events`), and notices no pattern knows stay where they were.
+//!
+//! Calls are messages from the caller whose content is one of
+//! [`CALL_LINES`], optionally followed by the call's length ("Voice call,
+//! 23 min"); see `calls`.
+
+use chatpack::parser::{create_parser, Platform};
+use chatpack::Message;
+use chrono::{DateTime, Utc};
+
+use crate::calls::{CallKind, CallStatus};
+use crate::events::Event;
+use crate::kinds::MEDIA_PLACEHOLDER;
+use crate::registry::Backend;
+
+/// Content of a message that was deleted.
+pub(crate) const DELETED_PLACEHOLDER: &str = "[deleted]";
+
+/// Left-to-right mark WhatsApp puts before system text.
+const LRM: char = '\u{200e}';
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Marker {
+    /// Whole content of a message whose media was not exported.
+    MediaOmitted,
+    /// Start of the encryption notice.
+    EncryptionBanner,
+    /// Whole content of a deleted message.
+    Deleted,
+    /// Suffix of an edited message.
+    Edited,
+}
+
+/// `(locale, marker, text)`; texts are compared without a trailing period.
+const MARKERS: &[(&str, Marker, &str)] = &[
+    ("en", Marker::MediaOmitted, "<Media omitted>"),
+    ("en", Marker::EncryptionBanner, "Messages and calls are end-to-end encrypted"),
+    ("en", Marker::Deleted, "This message was deleted"),
+    ("en", Marker::Deleted, "You deleted this message"),
+    ("en", Marker::Edited, "<This message was edited>"),
+    ("es", Marker::MediaOmitted, "<Multimedia omitido>"),
+    (
+        "es",
+        Marker::EncryptionBanner,
+        "Los mensajes y las llamadas están cifrados de extremo a extremo",
+    ),
+    ("es", Marker::Deleted, "Se eliminó este mensaje"),
+    ("es", Marker::Deleted, "Eliminaste este mensaje"),
+    ("es", Marker::Edited, "<Se editó este mensaje.>"),
+    ("pt", Marker::MediaOmitted, "<Mídia oculta>"),
+    ("pt", Marker::MediaOmitted, "<Multimédia omitida>"),
+    (
+        "pt",
+        Marker::EncryptionBanner,
+        "As mensagens e as chamadas são protegidas com a criptografia de ponta a ponta",
+    ),
+    ("pt", Marker::Deleted, "Mensagem apagada"),
+    ("pt", Marker::Deleted, "Você apagou esta mensagem"),
+    ("pt", Marker::Edited, "<Mensagem editada>"),
+    ("de", Marker::MediaOmitted, "<Medien ausgeschlossen>"),
+    ("de", Marker::EncryptionBanner, "Nachrichten und Anrufe sind Ende-zu-Ende-verschlüsselt"),
+    ("de", Marker::Deleted, "Diese Nachricht wurde gelöscht"),
+    ("de", Marker::Deleted, "Du hast diese Nachricht gelöscht"),
+    ("de", Marker::Edited, "<Diese Nachricht wurde bearbeitet>"),
+    ("fr", Marker::MediaOmitted, "<Médias omis>"),
+    ("fr", Marker::EncryptionBanner, "Les messages et les appels sont chiffrés de bout en bout"),
+    ("fr", Marker::Deleted, "Ce message a été supprimé"),
+    ("fr", Marker::Deleted, "Vous avez supprimé ce message"),
+    ("fr", Marker::Edited, "<Ce message a été modifié>"),
+    ("ru", Marker::MediaOmitted, "<Без медиафайлов>"),
+    ("ru", Marker::EncryptionBanner, "Сообщения и звонки защищены сквозным шифрованием"),
+    ("ru", Marker::Deleted, "Данное сообщение удалено"),
+    ("ru", Marker::Deleted, "Вы удалили данное сообщение"),
+    ("ru", Marker::Edited, "<Сообщение изменено>"),
+    ("tr", Marker::MediaOmitted, "<Medya dahil edilmedi>"),
+    ("tr", Marker::EncryptionBanner, "Mesajlar ve aramalar uçtan uca şifrelidir"),
+    ("tr", Marker::Deleted, "Bu mesaj silindi"),
+    ("tr", Marker::Deleted, "Bu mesajı sildiniz"),
+    ("tr", Marker::Edited, "<Bu mesaj düzenlendi>"),
+    ("id", Marker::MediaOmitted, "<Media tidak disertakan>"),
+    ("id", Marker::EncryptionBanner, "Pesan dan panggilan terenkripsi secara end-to-end"),
+    ("id", Marker::Deleted, "Pesan ini telah dihapus"),
+    ("id", Marker::Deleted, "Anda menghapus pesan ini"),
+    ("id", Marker::Edited, "<Pesan ini telah diedit>"),
+];
+
+/// `(locale, kind, pattern)` of group notices. `{actor}` and `{target}`
+/// capture the people involved, `{detail}` the new subject or name, and
+/// `{old}` is matched but dropped. The first matching pattern wins.
+const EVENT_PATTERNS: &[(&str, &str, &str)] = &[
+    ("en", "group_created", "{actor} created group \"{detail}\""),
+    ("en", "subject_changed", "{actor} changed the subject from \"{old}\" to \"{detail}\""),
+    ("en", "subject_changed", "{actor} changed the subject to \"{detail}\""),
+    ("en", "icon_changed", "{actor} changed this group's icon"),
+    ("en", "icon_deleted", "{actor} deleted this group's icon"),
+    ("en", "description_changed", "{actor} changed the group description"),
+    ("en", "member_joined", "{actor} joined using this group's invite link"),
+    ("en", "member_added", "{actor} added {target}"),
+    ("en", "member_removed", "{actor} removed {target}"),
+    ("en", "member_left", "{actor} left"),
+    ("es", "group_created", "{actor} creó el grupo \"{detail}\""),
+    ("es", "subject_changed", "{actor} cambió el asunto de \"{old}\" a \"{detail}\""),
+    ("es", "subject_changed", "{actor} cambió el asunto a \"{detail}\""),
+    ("es", "icon_changed", "{actor} cambió el ícono de este grupo"),
+    ("es", "icon_deleted", "{actor} eliminó el ícono de este grupo"),
+    ("es", "description_changed", "{actor} cambió la descripción del grupo"),
+    ("es", "member_joined", "{actor} se unió usando el enlace de invitación de este grupo"),
+    ("es", "member_added", "{actor} añadió a {target}"),
+    ("es", "member_removed", "{actor} eliminó a {target}"),
+    ("es", "member_left", "{actor} salió del grupo"),
+    ("pt", "group_created", "{actor} criou o grupo \"{detail}\""),
+    ("pt", "subject_changed", "{actor} mudou o assunto de \"{old}\" para \"{detail}\""),
+    ("pt", "subject_changed", "{actor} mudou o assunto para \"{detail}\""),
+    ("pt", "icon_changed", "{actor} mudou a imagem deste grupo"),
+    ("pt", "member_joined", "{actor} entrou usando o link de convite deste grupo"),
+    ("pt", "member_added", "{actor} adicionou {target}"),
+    ("pt", "member_removed", "{actor} removeu {target}"),
+    ("pt", "member_left", "{actor} saiu"),
+    ("de", "group_created", "{actor} hat die Gruppe \"{detail}\" erstellt"),
+    ("de", "subject_changed", "{actor} hat den Betreff zu \"{detail}\" geändert"),
+    ("de", "icon_changed", "{actor} hat das Gruppenbild geändert"),
+    ("de", "member_joined", "{actor} ist über den Einladungslink dieser Gruppe beigetreten"),
+    ("de", "member_left", "{actor} hat die Gruppe verlassen"),
+    ("de", "member_added", "{actor} hat {target} hinzugefügt"),
+    ("de", "member_removed", "{actor} hat {target} entfernt"),
+    ("fr", "group_created", "{actor} a créé le groupe \"{detail}\""),
+    ("fr", "subject_changed", "{actor} a modifié le sujet en \"{detail}\""),
+    ("fr", "icon_changed", "{actor} a changé l'icône de ce groupe"),
+    ("fr", "member_added", "{actor} a ajouté {target}"),
+    ("fr", "member_removed", "{actor} a retiré {target}"),
+    ("fr", "member_left", "{actor} est parti"),
+    ("fr", "member_left", "{actor} est partie"),
+    ("ru", "group_created", "{actor} создал(а) группу «{detail}»"),
+    ("ru", "subject_changed", "{actor} изменил(а) тему на «{detail}»"),
+    ("ru", "icon_changed", "{actor} изменил(а) изображение этой группы"),
+    ("ru", "member_added", "{actor} добавил(а) {target}"),
+    ("ru", "member_removed", "{actor} удалил(а) {target}"),
+    ("ru", "member_left", "{actor} вышел(-а)"),
+];
+
+/// `(locale, kind, status, text)` of call messages.
+const CALL_LINES: &[(&str, CallKind, CallStatus, &str)] = &[
+    ("en", CallKind::Voice, CallStatus::Missed, "Missed voice call"),
+    ("en", CallKind::Video, CallStatus::Missed, "Missed video call"),
+    ("en", CallKind::Voice, CallStatus::Completed, "Voice call"),
+    ("en", CallKind::Video, CallStatus::Completed, "Video call"),
+    ("es", CallKind::Voice, CallStatus::Missed, "Llamada de voz perdida"),
+    ("es", CallKind::Video, CallStatus::Missed, "Videollamada perdida"),
+    ("es", CallKind::Voice, CallStatus::Completed, "Llamada de voz"),
+    ("es", CallKind::Video, CallStatus::Completed, "Videollamada"),
+    ("pt", CallKind::Voice, CallStatus::Missed, "Chamada de voz perdida"),
+    ("pt", CallKind::Video, CallStatus::Missed, "Chamada de vídeo perdida"),
+    ("pt", CallKind::Voice, CallStatus::Completed, "Chamada de voz"),
+    ("pt", CallKind::Video, CallStatus::Completed, "Chamada de vídeo"),
+    ("de", CallKind::Voice, CallStatus::Missed, "Verpasster Sprachanruf"),
+    ("de", CallKind::Video, CallStatus::Missed, "Verpasster Videoanruf"),
+    ("de", CallKind::Voice, CallStatus::Completed, "Sprachanruf"),
+    ("de", CallKind::Video, CallStatus::Completed, "Videoanruf"),
+    ("fr", CallKind::Voice, CallStatus::Missed, "Appel vocal manqué"),
+    ("fr", CallKind::Video, CallStatus::Missed, "Appel vidéo manqué"),
+    ("fr", CallKind::Voice, CallStatus::Completed, "Appel vocal"),
+    ("fr", CallKind::Video, CallStatus::Completed, "Appel vidéo"),
+    ("ru", CallKind::Voice, CallStatus::Missed, "Пропущенный аудиозвонок"),
+    ("ru", CallKind::Video, CallStatus::Missed, "Пропущенный видеозвонок"),
+    ("ru", CallKind::Voice, CallStatus::Completed, "Аудиозвонок"),
+    ("ru", CallKind::Video, CallStatus::Completed, "Видеозвонок"),
+];
+
+/// A group notice matched by one of [`EVENT_PATTERNS`].
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Notice<'a> {
+    kind: &'a str,
+    actor: Option<&'a str>,
+    target: Option<&'a str>,
+    detail: Option<&'a str>,
+}
+
+/// Normalize placeholders and drop system lines in WhatsApp messages.
+///
+/// Returns a warning counting the notices that matched no event pattern and
+/// were left in the message before them.
+pub(crate) fn normalize(messages: &mut Vec<Message>, backend: Backend) -> Option<String> {
+    if !matches!(backend, Backend::Core(Platform::WhatsApp)) {
+        return None;
+    }
+
+    let mut unknown = 0;
+    messages.retain_mut(|message| match normalized(&message.content, &mut unknown) {
+        Some(content) => {
+            message.content = content;
+            true
+        }
+        None => false,
+    });
+
+    (unknown > 0).then(|| {
+        format!(
+            "{unknown} WhatsApp system line(s) matched no known event and were kept in the \
+             message before them"
+        )
+    })
+}
+
+/// Group notices of a WhatsApp export, in the order they appear.
+pub(crate) fn events(input: &str) -> Vec<Event> {
+    input
+        .lines()
+        .filter_map(|line| {
+            let (header, text) = system_line(line)?;
+            let notice = notice(text)?;
+            let actor = notice.actor.map(str::to_string);
+            let description = describe(&notice);
+            Some(Event::new(
+                notice.kind,
+                actor,
+                notice.target.map(str::to_string),
+                notice.detail.map(str::to_string),
+                header_time(header),
+                description,
+            ))
+        })
+        .collect()
+}
+
+/// Kind, status and length in seconds (if written) of a call message.
+pub(crate) fn call(content: &str) -> Option<(CallKind, CallStatus, Option<u64>)> {
+    let content = content.replace(LRM, "");
+    let content = clean(&content);
+    CALL_LINES.iter().find_map(|&(_, kind, status, text)| {
+        let rest = content.strip_prefix(text)?;
+        if rest.is_empty() {
+            return Some((kind, status, None));
+        }
+        let length = rest.strip_prefix(", ").or_else(|| rest.strip_prefix(". "))?;
+        Some((kind, status, Some(call_length(length)?)))
+    })
+}
+
+/// Seconds in a call length such as "23 min", "1 hr 5 min" or "45 sec", in
+/// any of the locales' abbreviations.
+fn call_length(text: &str) -> Option<u64> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut pairs = words.chunks_exact(2);
+    if words.is_empty() || !pairs.remainder().is_empty() {
+        return None;
+    }
+    pairs.try_fold(0, |total, pair| {
+        let count: u64 = pair[0].parse().ok()?;
+        let unit = pair[1].trim_end_matches('.').to_lowercase();
+        let seconds = if unit.starts_with("min") || unit.starts_with("мин") {
+            60
+        } else if unit.starts_with('h') || unit.starts_with("std") || unit.starts_with('ч') {
+            3600
+        } else if unit.starts_with('s') || unit.starts_with('с') {
+            1
+        } else {
+            return None;
+        };
+        Some(total + count * seconds)
+    })
+}
+
+/// Normalized content, or `None` if nothing but system text is left.
+///
+/// Lines after the first that carry a date of their own are notices the
+/// parser appended: banners and known events are dropped, other notices
+/// are kept and counted in `unknown`.
+fn normalized(content: &str, unknown: &mut usize) -> Option<String> {
+    let mut lines: Vec<&str> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let system = system_line(line).filter(|_| index > 0).map(|(_, text)| text);
+        let text = system.unwrap_or(line);
+        if texts(Marker::EncryptionBanner).any(|banner| clean(text).starts_with(banner)) {
+            continue;
+        }
+        if system.is_some() {
+            if notice(text).is_some() {
+                continue;
+            }
+            *unknown += 1;
+        }
+        lines.push(line);
+    }
+    if lines.is_empty() {
+        return None;
+    }
+
+    let content = lines.join("\n");
+    let text = strip_edited(&content);
+    let bare = clean(text);
+    if bare == "null" || texts(Marker::MediaOmitted).any(|token| same(bare, token)) {
+        Some(MEDIA_PLACEHOLDER.to_string())
+    } else if texts(Marker::Deleted).any(|notice| same(bare, notice)) {
+        Some(DELETED_PLACEHOLDER.to_string())
+    } else {
+        Some(text.to_string())
+    }
+}
+
+fn strip_edited(content: &str) -> &str {
+    let trimmed = content.trim_end();
+    texts(Marker::Edited)
+        .find_map(|suffix| trimmed.strip_suffix(suffix))
+        .map_or(content, |text| text.trim_end_matches([' ', LRM]))
+}
+
+/// Split a line with a date but no sender (`[15.01.24, 10:06:00] Bob left`,
+/// `15/01/2024, 10:06 - Bob left`) into its date header and its text.
+fn system_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start_matches(LRM);
+    let end = if line.starts_with('[') {
+        line.find("] ")? + 2
+    } else if line.starts_with(|c: char| c.is_ascii_digit()) {
+        line.find(" - ")? + 3
+    } else {
+        return None;
+    };
+    let (header, text) = line.split_at(end);
+    (!text.contains(": ") && header_time(header).is_some()).then_some((header, text))
+}
+
+/// Timestamp of a date header, read by chatpack's own parser so every date
+/// layout it knows is understood here too.
+fn header_time(header: &str) -> Option<DateTime<Utc>> {
+    let probe = format!("{header}x: x");
+    create_parser(Platform::WhatsApp).parse_str(&probe).ok()?.first()?.timestamp
+}
+
+fn notice(text: &str) -> Option<Notice<'_>> {
+    let text = clean(text);
+    EVENT_PATTERNS.iter().find_map(|&(_, kind, pattern)| {
+        let mut notice = Notice { kind, ..Notice::default() };
+        for (name, value) in captures(pattern, text)? {
+            match name {
+                "actor" => notice.actor = Some(value),
+                "target" => notice.target = Some(value),
+                "detail" => notice.detail = Some(value),
+                _ => {}
+            }
+        }
+        Some(notice)
+    })
+}
+
+/// Values of the `{name}` placeholders of `pattern` in `text`, if it
+/// matches. Each value runs up to the first occurrence of the literal text
+/// after it.
+fn captures<'a, 'p>(pattern: &'p str, text: &'a str) -> Option<Vec<(&'p str, &'a str)>> {
+    let parts: Vec<&str> = pattern.split(['{', '}']).collect();
+    let mut rest = text.strip_prefix(parts[0])?;
+    let mut values = Vec::new();
+    for pair in parts[1..].chunks(2) {
+        let [name, literal] = pair else {
+            return None;
+        };
+        let value = if values.len() * 2 + 3 == parts.len() {
+            std::mem::take(&mut rest).strip_suffix(literal)?
+        } else {
+            let end = rest.find(literal)?;
+            let value = &rest[..end];
+            rest = &rest[end + literal.len()..];
+            value
+        };
+        if value.is_empty() {
+            return None;
+        }
+        values.push((*name, value));
+    }
+
+    rest.is_empty().then_some(values)
+}
+
+/// Readable content of a notice converted as a message from its actor.
+fn describe(notice: &Notice) -> String {
+    let target = notice.target.unwrap_or_default();
+    let detail = notice.detail.unwrap_or_default();
+    match notice.kind {
+        "group_created" => format!("created the group \"{detail}\""),
+        "subject_changed" => format!("changed the subject to \"{detail}\""),
+        "icon_changed" => "changed the group icon".to_string(),
+        "icon_deleted" => "deleted the group icon".to_string(),
+        "description_changed" => "changed the group description".to_string(),
+        "member_joined" => "joined via invite link".to_string(),
+        "member_added" => format!("added {target}"),
+        "member_removed" => format!("removed {target}"),
+        "member_left" => "left".to_string(),
+        other => other.replace('_', " "),
+    }
+}
+
+fn texts(marker: Marker) -> impl Iterator<Item = &'static str> {
+    MARKERS.iter().filter(move |(_, m, _)| *m == marker).map(|(_, _, text)| *text)
+}
+
+fn clean(text: &str) -> &str {
+    text.trim_matches(|c: char| c == LRM || c.is_whitespace())
+}
+
+fn same(text: &str, marker: &str) -> bool {
+    text.strip_suffix('.').unwrap_or(text) == marker.strip_suffix('.').unwrap_or(marker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_options, prepare_messages};
+
+    const GROUP: &str = include_str!("../fixtures/whatsapp_group_events.txt");
+
+    fn messages(input: &str) -> Vec<(String, String)> {
+        let options = parse_options(r#"{"merge_consecutive":false}"#).unwrap();
+        let prepared = prepare_messages(input, "whatsapp", &options).unwrap();
+        prepared.messages.into_iter().map(|m| (m.sender, m.content)).collect()
+    }
+
+    #[test]
+    fn test_spanish_export() {
+        let messages = messages(include_str!("../fixtures/whatsapp_es.txt"));
+
+        assert_eq!(
+            messages,
+            [
+                ("Lucía".into(), "¡Hola a todos!".into()),
+                ("Mateo".into(), "[media]".into()),
+                ("Lucía".into(), "[deleted]".into()),
+                ("Mateo".into(), "Nos vemos a las ocho".into()),
+                ("Lucía".into(), "[media]".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_russian_export() {
+        let contents: Vec<String> = messages(include_str!("../fixtures/whatsapp_ru.txt"))
+            .into_iter()
+            .map(|(_, content)| content)
+            .collect();
+
+        assert_eq!(contents, ["Доброе утро", "[media]", "[deleted]", "Буду в семь"]);
+    }
+
+    #[test]
+    fn test_call_lines() {
+        assert_eq!(
+            call("\u{200e}Missed voice call"),
+            Some((CallKind::Voice, CallStatus::Missed, None))
+        );
+        assert_eq!(
+            call("Video call, \u{200e}23 min"),
+            Some((CallKind::Video, CallStatus::Completed, Some(1380)))
+        );
+        assert_eq!(call("Voice call, 1 hr 5 min").unwrap().2, Some(3900));
+        assert_eq!(call("Llamada de voz, 45 seg.").unwrap().2, Some(45));
+        assert_eq!(call("Видеозвонок, 2 ч 1 мин").unwrap().2, Some(7260));
+        assert_eq!(call("Video call, let's do it"), None);
+        assert_eq!(call("Voice calls are fine"), None);
+    }
+
+    #[test]
+    fn test_group_events() {
+        let events = serde_json::to_value(events(GROUP)).unwrap();
+
+        assert_eq!(events.as_array().unwrap().len(), 5);
+        assert_eq!(events[0]["kind"], "group_created");
+        assert_eq!(events[0]["detail"], "Trip 2024");
+        assert_eq!(events[1]["kind"], "member_added");
+        assert_eq!((&events[1]["actor"], &events[1]["target"]), (&"Alice".into(), &"Bob".into()));
+        assert_eq!(events[2]["kind"], "subject_changed");
+        assert_eq!(events[2]["detail"], "Lisbon trip");
+        assert_eq!(events[3]["kind"], "icon_changed");
+        assert_eq!(events[4]["kind"], "member_left");
+        assert_eq!(events[4]["timestamp"], "2024-01-15 10:06:00");
+    }
+
+    #[test]
+    fn test_group_events_in_conversion() {
+        let options = parse_options(r#"{"merge_consecutive":false}"#).unwrap();
+        let prepared = prepare_messages(GROUP, "whatsapp", &options).unwrap();
+        let contents: Vec<&str> = prepared.messages.iter().map(|m| m.content.as_str()).collect();
+
+        // The unknown "pinned a message" notice stays in the message before it.
+        assert_eq!(
+            contents,
+            [
+                "Welcome!",
+                "Thanks, looking forward to it\n[15.01.24, 10:05:00] \u{200e}Bob pinned a message",
+                "Bye Bob"
+            ]
+        );
+        assert!(prepared.warnings.iter().any(|w| w.starts_with("1 WhatsApp system line")));
+
+        let options = parse_options(r#"{"merge_consecutive":false,"include_events":true}"#);
+        let included = prepare_messages(GROUP, "whatsapp", &options.unwrap()).unwrap().messages;
+        assert_eq!(included.len(), 8);
+        assert_eq!(included[1].content, "added Bob");
+        assert_eq!((included[6].sender.as_str(), included[6].content.as_str()), ("Bob", "left"));
+    }
+}
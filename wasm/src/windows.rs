@@ -0,0 +1,119 @@
+use std::ops::Range;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{js_error, parse_options, prepare_messages, resolve_format};
+
+/// Convert chat export into fixed-size windows of consecutive messages.
+///
+/// Filters and merging are applied first, then a window of `window_size`
+/// messages starts every `stride` messages: `stride < window_size` gives
+/// overlapping windows, `stride == window_size` splits the chat into
+/// consecutive parts. The last window may be shorter, and no window starts
+/// after one has reached the end. Each window is formatted on its own (own
+/// CSV header / JSON array).
+///
+/// Returns a JSON array of output strings.
+#[wasm_bindgen]
+pub fn convert_windows(
+    input: &str,
+    source: &str,
+    window_size: usize,
+    stride: usize,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<String, JsValue> {
+    let windows = convert_windows_inner(input, source, window_size, stride, format, options_json)
+        .map_err(js_error)?;
+
+    serde_json::to_string(&windows).map_err(|e| js_error(e.to_string()))
+}
+
+fn convert_windows_inner(
+    input: &str,
+    source: &str,
+    window_size: usize,
+    stride: usize,
+    format: &str,
+    options_json: &str,
+) -> std::result::Result<Vec<String>, String> {
+    if window_size == 0 || stride == 0 {
+        return Err("Invalid window: window_size and stride must be at least 1".to_string());
+    }
+
+    let mut options = parse_options(options_json)?;
+    let output_format = resolve_format(format, &mut options)?;
+    let prepared = prepare_messages(input, source, &options)?;
+
+    windows(prepared.messages.len(), window_size, stride)
+        .map(|window| prepared.format_range(window, output_format, &options))
+        .collect()
+}
+
+/// Index ranges of the windows over `len` messages.
+fn windows(len: usize, window_size: usize, stride: usize) -> impl Iterator<Item = Range<usize>> {
+    let starts = (0..len).step_by(stride);
+    let mut done = false;
+
+    starts.map_while(move |start| {
+        if done {
+            return None;
+        }
+        let end = (start + window_size).min(len);
+        done = end == len;
+        Some(start..end)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chatpack::Message;
+
+    use super::*;
+
+    fn messages(count: usize) -> Vec<Message> {
+        (0..count).map(|i| Message::new("Alice", format!("m{i}"))).collect()
+    }
+
+    fn contents(
+        messages: &[Message],
+        windows: impl Iterator<Item = Range<usize>>,
+    ) -> Vec<Vec<&str>> {
+        windows.map(|w| messages[w].iter().map(|m| m.content.as_str()).collect()).collect()
+    }
+
+    #[test]
+    fn test_overlapping_windows() {
+        let messages = messages(5);
+        let windows = contents(&messages, windows(messages.len(), 3, 1));
+
+        assert_eq!(
+            windows,
+            vec![vec!["m0", "m1", "m2"], vec!["m1", "m2", "m3"], vec!["m2", "m3", "m4"]]
+        );
+    }
+
+    #[test]
+    fn test_adjacent_windows() {
+        let messages = messages(5);
+        let windows = contents(&messages, windows(messages.len(), 2, 2));
+
+        assert_eq!(windows, vec![vec!["m0", "m1"], vec!["m2", "m3"], vec!["m4"]]);
+    }
+
+    #[test]
+    fn test_windows_apply_merging_and_format_each_part() {
+        let input = "[15.01.24, 10:30:00] Alice: one\n\
+[15.01.24, 10:30:05] Alice: two\n\
+[15.01.24, 10:31:00] Bob: three\n\
+[15.01.24, 10:32:00] Alice: four\n";
+        let parts = convert_windows_inner(input, "whatsapp", 2, 2, "csv", "")
+            .expect("windows should convert");
+
+        assert_eq!(parts.len(), 2);
+        assert!(parts.iter().all(|part| part.starts_with("Sender;Content")));
+        assert!(parts[0].contains("two") && parts[0].contains("three"));
+        assert!(parts[1].contains("four") && !parts[1].contains("three"));
+        assert!(convert_windows_inner(input, "whatsapp", 0, 1, "csv", "").is_err());
+    }
+}
@@ -0,0 +1,244 @@
+//! The one tokenizer behind every word-level feature.
+//!
+//! Word counts, language detection and anything else that looks at words
+//! split text here, so they agree on what a word is. Links, `@mentions` and
+//! `#hashtags` stay whole tokens; everything else is split into words (runs
+//! of letters and digits, keeping inner apostrophes and hyphens as in
+//! "don't" or "e-mail"), emoji and punctuation.
+
+use std::borrow::Cow;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::js_error;
+
+/// Characters removed from the end of a link, mention or hashtag as sentence punctuation.
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '"', '\''];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TokenKind {
+    Word,
+    Url,
+    Mention,
+    Hashtag,
+    Emoji,
+    Punctuation,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Token<'a> {
+    pub text: Cow<'a, str>,
+    pub kind: TokenKind,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum EmojiHandling {
+    /// One token per emoji (ZWJ sequences and modifiers stay together).
+    #[default]
+    Keep,
+    Drop,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub(crate) struct TokenizeOptions {
+    /// Lowercase words, mentions and hashtags (links keep their case).
+    pub lowercase: bool,
+    /// Drop punctuation instead of returning it as tokens.
+    pub strip_punctuation: bool,
+    pub emoji: EmojiHandling,
+}
+
+impl Default for TokenizeOptions {
+    fn default() -> Self {
+        Self { lowercase: false, strip_punctuation: true, emoji: EmojiHandling::Keep }
+    }
+}
+
+/// Split `content` into tokens.
+///
+/// `options_json` (may be empty) takes `lowercase` (default `false`),
+/// `strip_punctuation` (default `true`) and `emoji` (`"keep"`, the default,
+/// or `"drop"`). Returns a JSON array of `{text, kind}`, where `kind` is
+/// `word`, `url`, `mention`, `hashtag`, `emoji` or `punctuation`.
+#[wasm_bindgen]
+pub fn tokenize(content: &str, options_json: &str) -> std::result::Result<String, JsValue> {
+    let options: TokenizeOptions = if options_json.trim().is_empty() {
+        TokenizeOptions::default()
+    } else {
+        serde_json::from_str(options_json)
+            .map_err(|e| js_error(format!("Invalid options JSON: {e}")))?
+    };
+
+    serde_json::to_string(&tokens(content, &options)).map_err(|e| js_error(e.to_string()))
+}
+
+pub(crate) fn tokens<'a>(content: &'a str, options: &TokenizeOptions) -> Vec<Token<'a>> {
+    let mut tokens = Vec::new();
+    for chunk in content.split_whitespace() {
+        match whole_token_kind(chunk) {
+            Some(kind) => {
+                let trimmed = chunk.trim_end_matches(TRAILING_PUNCTUATION);
+                push(&mut tokens, trimmed, kind, options);
+                push_punctuation(&mut tokens, &chunk[trimmed.len()..], options);
+            }
+            None => split_chunk(&mut tokens, chunk, options),
+        }
+    }
+
+    tokens
+}
+
+/// Number of words, links, mentions and hashtags in `content`.
+pub(crate) fn word_count(content: &str) -> usize {
+    tokens(content, &TokenizeOptions::default())
+        .iter()
+        .filter(|token| !matches!(token.kind, TokenKind::Emoji | TokenKind::Punctuation))
+        .count()
+}
+
+/// Links, mentions and hashtags, recognized on a whitespace-separated chunk.
+fn whole_token_kind(chunk: &str) -> Option<TokenKind> {
+    let lower = chunk.get(..8).unwrap_or(chunk).to_ascii_lowercase();
+    if lower.starts_with("http://") || lower.starts_with("https://") || lower.starts_with("www.") {
+        return Some(TokenKind::Url);
+    }
+
+    let name = |rest: &str| {
+        let rest = rest.trim_end_matches(TRAILING_PUNCTUATION);
+        !rest.is_empty() && rest.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+    match chunk.chars().next()? {
+        '@' if name(&chunk[1..]) => Some(TokenKind::Mention),
+        '#' if name(&chunk[1..]) => Some(TokenKind::Hashtag),
+        _ => None,
+    }
+}
+
+fn split_chunk<'a>(tokens: &mut Vec<Token<'a>>, chunk: &'a str, options: &TokenizeOptions) {
+    let mut chars = chunk.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let mut end = start + c.len_utf8();
+        let kind = if c.is_alphanumeric() {
+            while let Some(&(i, next)) = chars.peek() {
+                let joiner = matches!(next, '\'' | '’' | '-')
+                    && chunk[i + next.len_utf8()..].starts_with(char::is_alphanumeric);
+                if !next.is_alphanumeric() && !joiner {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+            TokenKind::Word
+        } else if is_emoji(c) {
+            while let Some((i, next)) = chars.next_if(|&(_, next)| is_emoji_modifier(next)) {
+                end = i + next.len_utf8();
+                if next == '\u{200D}' {
+                    if let Some((i, joined)) = chars.next_if(|&(_, joined)| is_emoji(joined)) {
+                        end = i + joined.len_utf8();
+                    }
+                }
+            }
+            TokenKind::Emoji
+        } else if is_emoji_modifier(c) {
+            continue;
+        } else {
+            TokenKind::Punctuation
+        };
+        push(tokens, &chunk[start..end], kind, options);
+    }
+}
+
+fn push_punctuation<'a>(tokens: &mut Vec<Token<'a>>, text: &'a str, options: &TokenizeOptions) {
+    for (start, c) in text.char_indices() {
+        push(tokens, &text[start..start + c.len_utf8()], TokenKind::Punctuation, options);
+    }
+}
+
+fn push<'a>(
+    tokens: &mut Vec<Token<'a>>,
+    text: &'a str,
+    kind: TokenKind,
+    options: &TokenizeOptions,
+) {
+    let keep = match kind {
+        TokenKind::Punctuation => !options.strip_punctuation,
+        TokenKind::Emoji => options.emoji == EmojiHandling::Keep,
+        _ => true,
+    };
+    if !keep || text.is_empty() {
+        return;
+    }
+
+    let lowercase = options.lowercase
+        && matches!(kind, TokenKind::Word | TokenKind::Mention | TokenKind::Hashtag)
+        && text.chars().any(char::is_uppercase);
+    let text = if lowercase { Cow::Owned(text.to_lowercase()) } else { Cow::Borrowed(text) };
+    tokens.push(Token { text, kind });
+}
+
+/// Pictographs, symbols and flags that render as emoji.
+fn is_emoji(c: char) -> bool {
+    matches!(
+        u32::from(c),
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0x2190..=0x21FF | 0x3030 | 0x303D
+    ) && !is_emoji_modifier(c)
+}
+
+/// Characters that only modify the emoji before them: skin tones, variation
+/// selectors, zero-width joiners and tag sequences.
+fn is_emoji_modifier(c: char) -> bool {
+    matches!(u32::from(c), 0x1F3FB..=0x1F3FF | 0xFE0E | 0xFE0F | 0x200D | 0xE0020..=0xE007F)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(content: &str, options: TokenizeOptions) -> Vec<(String, TokenKind)> {
+        tokens(content, &options).into_iter().map(|t| (t.text.into_owned(), t.kind)).collect()
+    }
+
+    #[test]
+    fn test_links_mentions_and_hashtags_stay_whole() {
+        let tokens = texts(
+            "Ask @Ann_B about https://example.com/a?b=1, then post #RustLang!",
+            TokenizeOptions { lowercase: true, ..TokenizeOptions::default() },
+        );
+
+        assert_eq!(
+            tokens,
+            vec![
+                ("ask".into(), TokenKind::Word),
+                ("@ann_b".into(), TokenKind::Mention),
+                ("about".into(), TokenKind::Word),
+                ("https://example.com/a?b=1".into(), TokenKind::Url),
+                ("then".into(), TokenKind::Word),
+                ("post".into(), TokenKind::Word),
+                ("#rustlang".into(), TokenKind::Hashtag),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_words_punctuation_and_emoji() {
+        let content = "Don't e-mail me... 👍🏽👨‍👩‍👧 ok?";
+        let kept = texts(
+            content,
+            TokenizeOptions { strip_punctuation: false, ..TokenizeOptions::default() },
+        );
+        let dropped = texts(
+            content,
+            TokenizeOptions { emoji: EmojiHandling::Drop, ..TokenizeOptions::default() },
+        );
+
+        let kept_texts: Vec<&str> = kept.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(kept_texts, ["Don't", "e-mail", "me", ".", ".", ".", "👍🏽", "👨‍👩‍👧", "ok", "?"]);
+        let dropped_texts: Vec<&str> = dropped.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(dropped_texts, ["Don't", "e-mail", "me", "ok"]);
+        assert_eq!(word_count("Hi, @bob — see www.example.com 😀"), 4);
+    }
+}